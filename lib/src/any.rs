@@ -0,0 +1,706 @@
+//! `any` is a module for applications that select the database backend at runtime, e.g. SQLite in development and
+//! MySQL in production. `AnyORM` wraps the ORM of the backend given by the connection URL and implements `ORMTrait`,
+//! so the application code is written once:
+//!
+//! ```ignore
+//! let conn = AnyORM::connect(std::env::var("DATABASE_URL")?).await?;
+//! let user: Option<User> = conn.find_one(1).run().await?;
+//! ```
+//!
+//! A `mysql://` URL opens a MySQL database, any other URL a SQLite database file, with an optional `sqlite://`
+//! prefix. The query builders of `AnyORM` are executed by the builders of the backend, so the statements are the
+//! same as with the ORM of the backend. Backend specific methods are reached by matching on the variants, e.g.
+//! `if let AnyORM::Sqlite(orm) = &*conn { ... }`.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use futures::stream::LocalBoxStream;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use crate::queue::Job;
+use crate::stats::{IndexUsage, QueryStats, RowCount, TableSize};
+use crate::{AddMany, AddOrUpdate, AlterTable, Batch, BlobReader, Cond, ConnectOptions, DbManager, DdlStatement, Dialect, DynamicEntity, FromRow, HealthReport, ImportAll, ImportReport, in_transaction, InsertMeta, IsolationLevel, MergeResult, MergeStrategy, Migration, MigrationPlan, Order, ORMError, ORMTrait, Page, Persisted, Policy, PoolStatus, QueryBuilder, Queue, ResultSet, Row, row_stream, Scope, Scoped, TableDeserialize, TableSerialize, TableWrite, ToSqlLiteral, UpsertAll, Value};
+#[cfg(feature = "mysql")]
+use crate::mysql;
+#[cfg(feature = "sqlite")]
+use crate::sqlite;
+
+/// The ORM the associated functions of `ORMTrait` without a connection, e.g. `escape`, are delegated to.
+/// They are the same for both backends.
+#[cfg(feature = "sqlite")]
+type Backend = sqlite::ORM;
+#[cfg(not(feature = "sqlite"))]
+type Backend = mysql::ORM;
+
+/// The URL prefix of a MySQL database.
+const MYSQL_PREFIX: &str = "mysql://";
+
+/// The optional URL prefix of a SQLite database file.
+const SQLITE_PREFIX: &str = "sqlite://";
+
+// Evaluates `$body` with `$orm` bound to the ORM of the backend of `$any`.
+macro_rules! dispatch {
+    ($any:expr, $orm:ident => $body:expr) => {
+        match $any {
+            #[cfg(feature = "sqlite")]
+            AnyORM::Sqlite($orm) => $body,
+            #[cfg(feature = "mysql")]
+            AnyORM::MySql($orm) => $body,
+        }
+    };
+}
+
+/// `AnyORM` is a connection to a SQLite or MySQL database selected at runtime, see the module documentation.
+#[derive(Debug, Clone)]
+pub enum AnyORM {
+    /// A SQLite database.
+    #[cfg(feature = "sqlite")]
+    Sqlite(Arc<sqlite::ORM>),
+
+    /// A MySQL database.
+    #[cfg(feature = "mysql")]
+    MySql(Arc<mysql::ORM>),
+}
+
+impl AnyORM {
+    /// Opens the database of `url`: a MySQL database for a `mysql://` URL, otherwise a SQLite database file.
+    /// Returns `ORMError::Config` if the backend of the URL is not enabled by a feature of the crate.
+    pub async fn connect(url: String) -> Result<Arc<AnyORM>, ORMError> {
+        AnyORM::connect_with(ConnectOptions::new(url.as_str())).await
+    }
+
+    /// Opens the database described by `options`, whose `url` selects the backend like in `connect`.
+    pub async fn connect_with(mut options: ConnectOptions) -> Result<Arc<AnyORM>, ORMError> {
+        if options.url.starts_with(MYSQL_PREFIX) {
+            #[cfg(feature = "mysql")]
+            return Ok(Arc::new(AnyORM::MySql(mysql::ORM::connect_with(options).await?)));
+            #[cfg(not(feature = "mysql"))]
+            return Err(ORMError::Config(format!("the mysql feature is required for {}", options.url)));
+        }
+        if let Some(path) = options.url.strip_prefix(SQLITE_PREFIX) {
+            options.url = path.to_string();
+        } else if options.url.contains("://") {
+            return Err(ORMError::Config(format!("unsupported database URL {}", options.url)));
+        }
+        #[cfg(feature = "sqlite")]
+        return Ok(Arc::new(AnyORM::Sqlite(sqlite::ORM::connect_with(options)?)));
+        #[cfg(not(feature = "sqlite"))]
+        return Err(ORMError::Config(format!("the sqlite feature is required for {}", options.url)));
+    }
+
+    /// Opens the database configured by the `DATABASE_*` environment variables, see `ConnectOptions::from_env`.
+    pub async fn from_env() -> Result<Arc<AnyORM>, ORMError> {
+        AnyORM::connect_with(ConnectOptions::from_env()?).await
+    }
+
+    /// Opens the database configured in the `[database]` section of the TOML file `path`, see `ConnectOptions::from_config`.
+    pub async fn from_config<P: AsRef<std::path::Path>>(path: P) -> Result<Arc<AnyORM>, ORMError> {
+        AnyORM::connect_with(ConnectOptions::from_config(path)?).await
+    }
+
+    /// Returns the SQL dialect of the backend, e.g. to choose between backend specific statements.
+    pub fn dialect(&self) -> Dialect {
+        match self {
+            #[cfg(feature = "sqlite")]
+            AnyORM::Sqlite(_) => Dialect::Sqlite,
+            #[cfg(feature = "mysql")]
+            AnyORM::MySql(_) => Dialect::MySql,
+        }
+    }
+
+    /// Executes `f` in a transaction, see `sqlite::ORM::transaction` and `mysql::ORM::transaction`.
+    pub async fn transaction<F, Fut, T, E>(self: &Arc<Self>, f: F) -> Result<T, E>
+        where F: FnOnce(Arc<AnyORM>) -> Fut, Fut: std::future::Future<Output = Result<T, E>>, E: From<ORMError>
+    {
+        in_transaction(self.as_ref(), IsolationLevel::Default, f(self.clone())).await
+    }
+
+    /// Executes `f` while holding the advisory lock `name` of the backend, see `sqlite::ORM::with_advisory_lock`
+    /// and `mysql::ORM::with_advisory_lock`.
+    pub async fn with_advisory_lock<F, Fut, T, E>(self: &Arc<Self>, name: &str, f: F) -> Result<T, E>
+        where F: FnOnce(Arc<AnyORM>) -> Fut, Fut: std::future::Future<Output = Result<T, E>>, E: From<ORMError>
+    {
+        let any = self.clone();
+        dispatch!(self.as_ref(), orm => orm.with_advisory_lock(name, move |_| f(any)).await)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<Arc<sqlite::ORM>> for AnyORM {
+    fn from(orm: Arc<sqlite::ORM>) -> Self {
+        AnyORM::Sqlite(orm)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl From<Arc<mysql::ORM>> for AnyORM {
+    fn from(orm: Arc<mysql::ORM>) -> Self {
+        AnyORM::MySql(orm)
+    }
+}
+
+impl DbManager<AnyORM> {
+    /// Opens the databases configured in the `[databases.<name>]` sections of the TOML file `path`,
+    /// see `ConnectOptions::databases_from_config`. The databases may use different backends.
+    pub async fn from_config<P: AsRef<std::path::Path>>(path: P) -> Result<DbManager<AnyORM>, ORMError> {
+        let mut manager = DbManager::new();
+        for (name, options) in ConnectOptions::databases_from_config(path)? {
+            manager.insert(&name, AnyORM::connect_with(options).await?);
+        }
+        Ok(manager)
+    }
+}
+
+#[async_trait]
+impl ORMTrait<AnyORM> for AnyORM {
+
+    fn add<T>(&self, data: T) -> QueryBuilder<'_, T, T, AnyORM>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static
+    {
+        dispatch!(self, orm => orm.add(data).with_orm(self))
+    }
+
+    async fn add_all<T>(&self, data: Vec<T>) -> Result<Vec<u64>, ORMError>
+        where T: TableDeserialize + TableSerialize + Serialize + Send + 'static
+    {
+        dispatch!(self, orm => orm.add_all(data).await)
+    }
+
+    fn add_many<T>(&self, data: Vec<T>) -> AddMany<'_, T, AnyORM>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static
+    {
+        AddMany::new(self, data)
+    }
+
+    fn add_or_update<T>(&self, data: T) -> AddOrUpdate<'_, T, AnyORM>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static
+    {
+        AddOrUpdate::new(self, data)
+    }
+
+    async fn last_insert_rowid(&self) -> Result<i64, ORMError> {
+        dispatch!(self, orm => orm.last_insert_rowid().await)
+    }
+
+    async fn last_insert_id(&self) -> Result<u64, ORMError> {
+        dispatch!(self, orm => orm.last_insert_id().await)
+    }
+
+    async fn close(&self) -> Result<(), ORMError> {
+        dispatch!(self, orm => orm.close().await)
+    }
+
+    fn find_one<T>(&self, id: u64) -> QueryBuilder<'_, Option<T>, T, AnyORM>
+        where T: TableDeserialize + TableSerialize + for<'a> Deserialize<'a> + 'static
+    {
+        dispatch!(self, orm => orm.find_one(id).with_orm(self))
+    }
+
+    async fn exists_by_id<T>(&self, id: u64) -> Result<bool, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        dispatch!(self, orm => orm.exists_by_id::<T>(id).await)
+    }
+
+    async fn find_by_ids<T>(&self, ids: &[u64]) -> Result<Vec<Option<T>>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + Clone + Send + Sync + 'static
+    {
+        dispatch!(self, orm => orm.find_by_ids(ids).await)
+    }
+
+    async fn descendants_of<T>(&self, id: u64) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + Send + Sync + 'static
+    {
+        dispatch!(self, orm => orm.descendants_of(id).await)
+    }
+
+    async fn ancestors_of<T>(&self, id: u64) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + Send + Sync + 'static
+    {
+        dispatch!(self, orm => orm.ancestors_of(id).await)
+    }
+
+    async fn refresh<T>(&self, data: &mut T) -> Result<(), ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + Send + Sync + 'static
+    {
+        dispatch!(self, orm => orm.refresh(data).await)
+    }
+
+    async fn save<T>(&self, data: T) -> Result<T, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Send + Sync + 'static
+    {
+        dispatch!(self, orm => orm.save(data).await)
+    }
+
+    async fn persist<T>(&self, entity: &mut Persisted<T>) -> Result<(), ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + Send + Sync + 'static
+    {
+        dispatch!(self, orm => orm.persist(entity).await)
+    }
+
+    fn find_one_where<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, Option<T>, T, AnyORM>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+    {
+        dispatch!(self, orm => orm.find_one_where(query_where).with_orm(self))
+    }
+
+    fn find_many<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, Vec<T>, T, AnyORM>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+    {
+        dispatch!(self, orm => orm.find_many(query_where).with_orm(self))
+    }
+
+    fn find_all<T>(&self) -> QueryBuilder<'_, Vec<T>, T, AnyORM>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+    {
+        dispatch!(self, orm => orm.find_all().with_orm(self))
+    }
+
+    fn count<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, u64, T, AnyORM>
+        where T: TableDeserialize + 'static
+    {
+        dispatch!(self, orm => orm.count(query_where).with_orm(self))
+    }
+
+    fn exists<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, bool, T, AnyORM>
+        where T: TableDeserialize + 'static
+    {
+        dispatch!(self, orm => orm.exists(query_where).with_orm(self))
+    }
+
+    fn find_as<P>(&self, table: &str, query_where: &str) -> QueryBuilder<'_, Vec<P>, P, AnyORM>
+        where P: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+    {
+        dispatch!(self, orm => orm.find_as(table, query_where).with_orm(self))
+    }
+
+    async fn fetch<T>(&self, query: QueryBuilder<'_, Vec<T>, T, AnyORM>) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + Send + Sync + 'static
+    {
+        query.run().await
+    }
+
+    fn modify<T>(&self, data: T) -> QueryBuilder<'_, usize, (), AnyORM>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        dispatch!(self, orm => orm.modify(data).with_orm(self))
+    }
+
+    fn modify_fields<T>(&self, data: &T, fields: &[&str]) -> QueryBuilder<'_, usize, (), AnyORM>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        dispatch!(self, orm => orm.modify_fields(data, fields).with_orm(self))
+    }
+
+    fn increment<T>(&self, id: u64, column: &str, by: i64) -> QueryBuilder<'_, usize, (), AnyORM>
+        where T: TableDeserialize + 'static
+    {
+        dispatch!(self, orm => orm.increment::<T>(id, column, by).with_orm(self))
+    }
+
+    fn upsert_all<T>(&self, rows: Vec<T>) -> UpsertAll<'_, T, AnyORM>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        UpsertAll {
+            rows,
+            conflict_columns: Vec::new(),
+            chunk_size: UpsertAll::<T, AnyORM>::DEFAULT_CHUNK_SIZE,
+            orm: self,
+        }
+    }
+
+    fn import_all<T>(&self, rows: Vec<T>) -> ImportAll<'_, T, AnyORM>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        ImportAll {
+            rows,
+            chunk_size: ImportAll::<T, AnyORM>::DEFAULT_CHUNK_SIZE,
+            orm: self,
+        }
+    }
+
+    fn queue(&self, name: &str) -> Queue<'_, AnyORM> {
+        Queue::new(self, name)
+    }
+
+    fn create_table<T>(&self) -> QueryBuilder<'_, usize, (), AnyORM>
+        where T: TableDeserialize + 'static
+    {
+        dispatch!(self, orm => orm.create_table::<T>().with_orm(self))
+    }
+
+    fn ddl<D: DdlStatement>(&self, ddl: &D) -> QueryBuilder<'_, usize, (), AnyORM> {
+        dispatch!(self, orm => orm.ddl(ddl).with_orm(self))
+    }
+
+    fn alter_table<T>(&self) -> AlterTable<'_, T, AnyORM>
+        where T: TableDeserialize + 'static
+    {
+        dispatch!(self, orm => orm.alter_table::<T>().with_orm(self))
+    }
+
+    async fn store_comments<T>(&self) -> Result<usize, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        dispatch!(self, orm => orm.store_comments::<T>().await)
+    }
+
+    fn remove<T>(&self, data: T) -> QueryBuilder<'_, usize, (), AnyORM>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        dispatch!(self, orm => orm.remove(data).with_orm(self))
+    }
+
+    fn remove_by_id<T>(&self, id: u64) -> QueryBuilder<'_, usize, (), AnyORM>
+        where T: TableDeserialize + 'static
+    {
+        dispatch!(self, orm => orm.remove_by_id::<T>(id).with_orm(self))
+    }
+
+    async fn remove_by_ids<T>(&self, ids: &[u64]) -> Result<usize, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        dispatch!(self, orm => orm.remove_by_ids::<T>(ids).await)
+    }
+
+    fn read_blob<T>(&self, id: u64, column: &str) -> BlobReader<'_, T, AnyORM>
+        where T: TableDeserialize + 'static
+    {
+        BlobReader::new(self, id, column)
+    }
+
+    async fn blob_length<T>(&self, id: u64, column: &str) -> Result<u64, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        dispatch!(self, orm => orm.blob_length::<T>(id, column).await)
+    }
+
+    async fn read_blob_chunk<T>(&self, id: u64, column: &str, offset: u64, len: usize) -> Result<Vec<u8>, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        dispatch!(self, orm => orm.read_blob_chunk::<T>(id, column, offset, len).await)
+    }
+
+    fn scoped(&self, scope: Scope) -> Scoped<'_, AnyORM> {
+        Scoped::new(self, scope)
+    }
+
+    async fn add_dynamic(&self, entity: &DynamicEntity) -> Result<DynamicEntity, ORMError> {
+        dispatch!(self, orm => orm.add_dynamic(entity).await)
+    }
+
+    async fn find_dynamic(&self, table: &str, id: u64) -> Result<Option<DynamicEntity>, ORMError> {
+        dispatch!(self, orm => orm.find_dynamic(table, id).await)
+    }
+
+    async fn find_many_dynamic<W: Into<Cond> + Send>(&self, table: &str, query_where: W) -> Result<Vec<DynamicEntity>, ORMError> {
+        let query_where = query_where.into();
+        dispatch!(self, orm => orm.find_many_dynamic(table, query_where).await)
+    }
+
+    async fn modify_dynamic(&self, entity: &DynamicEntity) -> Result<usize, ORMError> {
+        dispatch!(self, orm => orm.modify_dynamic(entity).await)
+    }
+
+    async fn remove_dynamic(&self, entity: &DynamicEntity) -> Result<usize, ORMError> {
+        dispatch!(self, orm => orm.remove_dynamic(entity).await)
+    }
+
+    fn query<T>(&self, query: &str) -> QueryBuilder<'_, Vec<T>, T, AnyORM> {
+        dispatch!(self, orm => orm.query(query).with_orm(self))
+    }
+
+    fn query_update(&self, query: &str) -> QueryBuilder<'_, usize, (), AnyORM> {
+        dispatch!(self, orm => orm.query_update(query).with_orm(self))
+    }
+
+    async fn query_as<T: FromRow + Send>(&self, query: &str) -> Result<Vec<T>, ORMError> {
+        dispatch!(self, orm => orm.query_as(query).await)
+    }
+
+    async fn query_map(&self, query: &str) -> Result<Vec<HashMap<String, Value>>, ORMError> {
+        dispatch!(self, orm => orm.query_map(query).await)
+    }
+
+    async fn join_all(&self, queries: Vec<QueryBuilder<'_, Vec<Row>, Row, AnyORM>>) -> Result<Vec<Vec<Row>>, ORMError> {
+        dispatch!(self, orm => {
+            let queries: Vec<_> = queries.iter().map(|query| query.with_orm(&**orm)).collect();
+            orm.join_all(queries).await
+        })
+    }
+
+    async fn merge<T>(&self, entities: Vec<T>, strategy: MergeStrategy<T>) -> Result<MergeResult, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + Send + Sync + 'static
+    {
+        dispatch!(self, orm => orm.merge(entities, strategy).await)
+    }
+
+    fn pool_status(&self) -> PoolStatus {
+        dispatch!(self, orm => orm.pool_status())
+    }
+
+    fn query_stats(&self) -> Vec<QueryStats> {
+        dispatch!(self, orm => orm.query_stats())
+    }
+
+    fn reset_query_stats(&self) {
+        dispatch!(self, orm => orm.reset_query_stats())
+    }
+
+    fn on_table_write(&self, table: &str, hook: impl Fn(&TableWrite) + Send + Sync + 'static) {
+        dispatch!(self, orm => orm.on_table_write(table, hook))
+    }
+
+    fn set_policy(&self, policy: Policy) {
+        dispatch!(self, orm => orm.set_policy(policy))
+    }
+
+    async fn health(&self) -> HealthReport {
+        dispatch!(self, orm => orm.health().await)
+    }
+
+    async fn plan_migrations(&self, migrations: &[Migration]) -> Result<MigrationPlan, ORMError> {
+        dispatch!(self, orm => orm.plan_migrations(migrations).await)
+    }
+
+    async fn table_sizes(&self) -> Result<Vec<TableSize>, ORMError> {
+        dispatch!(self, orm => orm.table_sizes().await)
+    }
+
+    async fn row_counts(&self) -> Result<Vec<RowCount>, ORMError> {
+        dispatch!(self, orm => orm.row_counts().await)
+    }
+
+    async fn index_usage(&self) -> Result<Vec<IndexUsage>, ORMError> {
+        dispatch!(self, orm => orm.index_usage().await)
+    }
+
+    async fn begin(&self, isolation: IsolationLevel) -> Result<(), ORMError> {
+        dispatch!(self, orm => orm.begin(isolation).await)
+    }
+
+    async fn commit(&self) -> Result<(), ORMError> {
+        dispatch!(self, orm => orm.commit().await)
+    }
+
+    async fn rollback(&self) -> Result<(), ORMError> {
+        dispatch!(self, orm => orm.rollback().await)
+    }
+
+    fn batch(&self) -> Batch<'_, AnyORM> {
+        Batch {
+            queries: Vec::new(),
+            orm: self,
+        }
+    }
+
+    async fn exec_all(&self, queries: &[QueryBuilder<'_, usize, (), AnyORM>]) -> Result<usize, ORMError> {
+        dispatch!(self, orm => {
+            let queries: Vec<_> = queries.iter().map(|query| query.with_orm(&**orm)).collect();
+            orm.exec_all(&queries).await
+        })
+    }
+
+    fn protect(&self, value: &str) -> String {
+        dispatch!(self, orm => orm.protect(value))
+    }
+
+    fn protect_value<V: ToSqlLiteral>(&self, value: V) -> Result<String, ORMError> {
+        dispatch!(self, orm => orm.protect_value(value))
+    }
+
+    fn escape(str: &str) -> String {
+        Backend::escape(str)
+    }
+
+    fn escape_json(input: &str) -> String {
+        Backend::escape_json(input)
+    }
+
+    async fn init(&self, script: &str) -> Result<(), ORMError> {
+        dispatch!(self, orm => orm.init(script).await)
+    }
+
+    async fn change(&self, update_query: &str) -> Result<(), ORMError> {
+        dispatch!(self, orm => orm.change(update_query).await)
+    }
+}
+
+impl<T> QueryBuilder<'_, usize, T, AnyORM> {
+    pub async fn exec(&self) -> Result<usize, ORMError> {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).exec().await)
+    }
+}
+
+impl Batch<'_, AnyORM> {
+    pub async fn execute(&self) -> Result<Vec<usize>, ORMError> {
+        dispatch!(self.orm, orm => Batch { queries: self.queries.clone(), orm: &**orm }.execute().await)
+    }
+}
+
+// The rows are copied into the writer of the backend, whose statements depend on the options of the backend ORM.
+impl<T: TableDeserialize + TableSerialize + Serialize + Clone> ImportAll<'_, T, AnyORM> {
+    pub async fn run(&self) -> Result<ImportReport, ORMError> {
+        dispatch!(self.orm, orm => ImportAll { rows: self.rows.clone(), chunk_size: self.chunk_size, orm: &**orm }.run().await)
+    }
+}
+
+impl Queue<'_, AnyORM> {
+    pub async fn create_table(&self) -> Result<(), ORMError> {
+        dispatch!(self.orm, orm => Queue::new(&**orm, &self.name).create_table().await)
+    }
+
+    pub async fn push(&self, payload: &str) -> Result<u64, ORMError> {
+        dispatch!(self.orm, orm => Queue::new(&**orm, &self.name).push(payload).await)
+    }
+
+    pub async fn claim(&self, n: usize, visibility_timeout: Duration) -> Result<Vec<Job>, ORMError> {
+        dispatch!(self.orm, orm => Queue::new(&**orm, &self.name).claim(n, visibility_timeout).await)
+    }
+}
+
+impl<T: TableDeserialize + TableSerialize + Serialize + Clone> UpsertAll<'_, T, AnyORM> {
+    pub async fn run(&self) -> Result<usize, ORMError> {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).run().await)
+    }
+
+    // Returns a copy of the rows that is written with `orm`.
+    fn with_orm<'b, P: ORMTrait<P>>(&self, orm: &'b P) -> UpsertAll<'b, T, P> {
+        UpsertAll {
+            rows: self.rows.clone(),
+            conflict_columns: self.conflict_columns.clone(),
+            chunk_size: self.chunk_size,
+            orm,
+        }
+    }
+}
+
+impl<T> AddOrUpdate<'_, T, AnyORM>
+    where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + 'static
+{
+    pub async fn apply(&self) -> Result<T, ORMError> {
+        dispatch!(self.upsert.orm, orm => AddOrUpdate { upsert: self.upsert.with_orm(&**orm) }.apply().await)
+    }
+}
+
+impl<T: TableDeserialize> AlterTable<'_, T, AnyORM> {
+    pub async fn run(&self) -> Result<(), ORMError> {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).run().await)
+    }
+}
+
+impl<T> QueryBuilder<'_, T, T, AnyORM> {
+    pub async fn apply(&self) -> Result<T, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + 'static
+    {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).apply().await)
+    }
+
+    pub async fn apply_with_meta(&self) -> Result<(T, InsertMeta), ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + 'static
+    {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).apply_with_meta().await)
+    }
+}
+
+impl<T> QueryBuilder<'_, usize, T, AnyORM> {
+    pub async fn run(&self) -> Result<usize, ORMError> {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).run().await)
+    }
+}
+
+impl<T> QueryBuilder<'_, u64, T, AnyORM> {
+    pub async fn run(&self) -> Result<u64, ORMError> {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).run().await)
+    }
+}
+
+impl<T> QueryBuilder<'_, bool, T, AnyORM> {
+    pub async fn run(&self) -> Result<bool, ORMError> {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).run().await)
+    }
+}
+
+impl<T> QueryBuilder<'_, Option<T>, T, AnyORM>
+    where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+{
+    pub async fn run(&self) -> Result<Option<T>, ORMError> {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).run().await)
+    }
+}
+
+impl<R> QueryBuilder<'_, Vec<Row>, R, AnyORM> {
+    pub async fn exec(&self) -> Result<Vec<Row>, ORMError> {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).exec().await)
+    }
+
+    // The rows of the stream of the backend are forwarded, since the streams of the backends have different types.
+    pub fn stream(&self) -> LocalBoxStream<'_, Result<Row, ORMError>>
+        where R: Sync
+    {
+        row_stream(move |mut tx| async move {
+            dispatch!(self.orm, orm => {
+                let query = self.with_orm(&**orm);
+                let mut rows = query.stream();
+                while let Some(row) = rows.next().await {
+                    if tx.send(row).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            Ok(())
+        }).boxed_local()
+    }
+
+    pub async fn exec_result_set(&self) -> Result<ResultSet, ORMError> {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).exec_result_set().await)
+    }
+}
+
+impl<T> QueryBuilder<'_, Vec<T>, T, AnyORM> {
+    pub async fn run(&self) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+    {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).run().await)
+    }
+
+    pub async fn paginate(&self, page: u64, per_page: u64) -> Result<Page<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+    {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).paginate(page, per_page).await)
+    }
+
+    pub async fn run_into_map<K, F>(&self, key: F) -> Result<HashMap<K, T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static, K: Eq + std::hash::Hash, F: Fn(&T) -> K
+    {
+        Ok(self.run().await?.into_iter().map(|data| (key(&data), data)).collect())
+    }
+
+    pub async fn run_grouped<K, F>(&self, key: F) -> Result<HashMap<K, Vec<T>>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static, K: Eq + std::hash::Hash, F: Fn(&T) -> K
+    {
+        let mut groups: HashMap<K, Vec<T>> = HashMap::new();
+        for data in self.run().await? {
+            groups.entry(key(&data)).or_default().push(data);
+        }
+        Ok(groups)
+    }
+}
+
+// The modifiers are rendered by the backend, e.g. SQLite and MySQL use different LIMITs for an OFFSET alone.
+impl<'a, R, E> QueryBuilder<'a, R, E, AnyORM> {
+    pub fn limit(&self, limit: i32) -> QueryBuilder<'a, R, E, AnyORM> {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).limit(limit).with_orm(self.orm))
+    }
+
+    pub fn offset(&self, offset: u64) -> QueryBuilder<'a, R, E, AnyORM> {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).offset(offset).with_orm(self.orm))
+    }
+
+    pub fn order_by(&self, column: &str, order: Order) -> QueryBuilder<'a, R, E, AnyORM> {
+        dispatch!(self.orm, orm => self.with_orm(&**orm).order_by(column, order).with_orm(self.orm))
+    }
+}
@@ -0,0 +1,227 @@
+//! `backfill` is a module for updating all records of a table in batches while the application keeps running,
+//! e.g. to fill a new column after a migration:
+//!
+//! ```ignore
+//! let progress = Backfill::<User, _>::new(&conn, "user_email_lower")
+//!     .batch_size(500)
+//!     .filter("email_lower is null")
+//!     .pause(Duration::from_millis(50))
+//!     .run(|mut user| {
+//!         user.email_lower = Some(user.email.to_lowercase());
+//!         Some(user)
+//!     })
+//!     .await?;
+//! ```
+//!
+//! The records are read in keyset batches ordered by ID, so every batch is an indexed range scan however large
+//! the table is. The changes of a batch and the checkpoint of the backfill are written in one transaction,
+//! so a backfill that was interrupted resumes after the last committed batch when it is run again.
+
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::schema::quote;
+use crate::{Cond, ORMError, ORMTrait, QueryBuilder, TableDeserialize, TableSerialize, Value};
+
+/// The table of the checkpoints of all backfills.
+pub(crate) const TABLE: &str = "parvati_backfill";
+
+/// `BackfillProgress` is the checkpoint of a `Backfill`, as it is stored after the last committed batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackfillProgress {
+    /// `last_id` is the ID of the last processed record; the next batch starts after it.
+    pub last_id: u64,
+
+    /// `processed` is the number of records passed to the closure.
+    pub processed: u64,
+
+    /// `modified` is the number of records the closure returned to be written.
+    pub modified: u64,
+
+    /// `completed_at` is the time the last batch was committed in seconds since the Unix epoch,
+    /// or `None` if there are records left.
+    pub completed_at: Option<u64>,
+}
+
+impl BackfillProgress {
+    /// Returns `true` if all records were processed.
+    pub fn is_completed(&self) -> bool {
+        self.completed_at.is_some()
+    }
+}
+
+/// `Backfill` passes every record of `T` to a closure and writes the records it returns, see the module documentation.
+/// The progress is stored under `name` in the table `parvati_backfill`, which is created on the first run.
+/// Only one process should run a backfill with the same name at a time.
+pub struct Backfill<'a, T, O> {
+    /// `orm` is a reference to an ORM object that implements the `ORMTrait`.
+    orm: &'a O,
+
+    /// `name` identifies the checkpoint of the backfill.
+    name: String,
+
+    /// `batch_size` is the maximum number of records of one batch.
+    batch_size: usize,
+
+    /// `filter` restricts the records that are processed.
+    filter: Option<Cond>,
+
+    /// `pause` is the time to wait between batches, to leave room for the other queries of the database.
+    pause: Option<Duration>,
+
+    /// `max_batches` is the maximum number of batches of one `run`.
+    max_batches: Option<usize>,
+
+    entity: PhantomData<T>,
+}
+
+impl<'a, T, O> Backfill<'a, T, O>
+    where T: for<'de> Deserialize<'de> + TableDeserialize + TableSerialize + Serialize + Debug + Send + Sync + 'static,
+          O: ORMTrait<O> + Sync
+{
+    /// The default maximum number of records of one batch.
+    pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+    /// Constructs the backfill `name` of the records of `T`.
+    pub fn new(orm: &'a O, name: &str) -> Self {
+        Backfill {
+            orm,
+            name: name.to_string(),
+            batch_size: Self::DEFAULT_BATCH_SIZE,
+            filter: None,
+            pause: None,
+            max_batches: None,
+            entity: PhantomData,
+        }
+    }
+
+    /// Sets the maximum number of records of one batch.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Processes only the records that match the provided WHERE clause, a raw SQL fragment or a `Cond`,
+    /// e.g. the records whose new column is still `NULL`.
+    pub fn filter(mut self, filter: impl Into<Cond>) -> Self {
+        self.filter = Some(filter.into());
+        self
+    }
+
+    /// Sets the time to wait after every batch but the last one.
+    pub fn pause(mut self, pause: Duration) -> Self {
+        self.pause = Some(pause);
+        self
+    }
+
+    /// Stops `run` after `max_batches` batches, e.g. to spread a backfill over several runs of a scheduled job.
+    pub fn max_batches(mut self, max_batches: usize) -> Self {
+        self.max_batches = Some(max_batches);
+        self
+    }
+
+    /// Returns the name of the backfill.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the stored progress, or `None` if the backfill was never run.
+    pub async fn progress(&self) -> Result<Option<BackfillProgress>, ORMError> {
+        self.create_table().await?;
+        let rows = self.orm.query_map(&format!("select last_id, processed, modified, completed_at from {} where name = {}",
+                                               TABLE, quote(&self.name))).await?;
+        Ok(rows.first().map(|row| BackfillProgress {
+            last_id: row.get("last_id").and_then(Value::as_i64).unwrap_or_default() as u64,
+            processed: row.get("processed").and_then(Value::as_i64).unwrap_or_default() as u64,
+            modified: row.get("modified").and_then(Value::as_i64).unwrap_or_default() as u64,
+            completed_at: row.get("completed_at").and_then(Value::as_i64).map(|completed_at| completed_at as u64),
+        }))
+    }
+
+    /// Removes the stored progress, so that the next `run` starts again with the first record.
+    /// Returns `false` if the backfill was never run.
+    pub async fn reset(&self) -> Result<bool, ORMError> {
+        self.create_table().await?;
+        let query = self.orm.query_update(&format!("delete from {} where name = ?", TABLE))
+            .bind(vec![Value::Text(self.name.clone())]);
+        Ok(self.orm.exec_all(&[query]).await? > 0)
+    }
+
+    /// Passes the records after the stored checkpoint to `f` in batches ordered by ID and writes the records
+    /// it returns with `modify`; `None` leaves a record as it is. Every batch is committed together with the new
+    /// checkpoint. Returns the progress after the last batch of this run. A completed backfill processes nothing
+    /// until it is `reset`.
+    pub async fn run<F>(&self, mut f: F) -> Result<BackfillProgress, ORMError>
+        where F: FnMut(T) -> Option<T> + Send
+    {
+        let mut progress = match self.progress().await? {
+            Some(progress) => progress,
+            None => {
+                let query = self.orm.query_update(&format!("insert into {} (name, last_id, processed, modified) values (?, 0, 0, 0)", TABLE))
+                    .bind(vec![Value::Text(self.name.clone())]);
+                self.orm.exec_all(&[query]).await?;
+                BackfillProgress { last_id: 0, processed: 0, modified: 0, completed_at: None }
+            }
+        };
+        let mut batches = 0;
+        while !progress.is_completed() && self.max_batches.is_none_or(|max_batches| batches < max_batches) {
+            if batches > 0 {
+                if let Some(pause) = self.pause {
+                    tokio::time::sleep(pause).await;
+                }
+            }
+            let records = self.orm.fetch(self.batch(progress.last_id)).await?;
+            if records.len() < self.batch_size {
+                progress.completed_at = Some(now());
+            }
+            let mut queries = Vec::with_capacity(records.len() + 1);
+            for record in records {
+                progress.last_id = progress.last_id.max(record.get_id().parse().unwrap_or_default());
+                progress.processed += 1;
+                if let Some(record) = f(record) {
+                    queries.push(self.orm.modify(record));
+                    progress.modified += 1;
+                }
+            }
+            queries.push(self.checkpoint(&progress));
+            self.orm.exec_all(&queries).await?;
+            batches += 1;
+        }
+        Ok(progress)
+    }
+
+    // Returns the query of the records of the batch after `last_id`.
+    fn batch(&self, last_id: u64) -> QueryBuilder<'a, Vec<T>, T, O> {
+        let cond = match &self.filter {
+            Some(filter) => Cond::field("id").gt(last_id).and(filter.clone()),
+            None => Cond::field("id").gt(last_id),
+        };
+        let batch_size = self.batch_size as i64;
+        self.orm.find_many::<T>(cond).with_modifiers("", |m| {
+            m.order_by.push("id asc".to_string());
+            m.limit = Some(batch_size);
+        })
+    }
+
+    // Returns the update of the stored checkpoint to `progress`.
+    fn checkpoint(&self, progress: &BackfillProgress) -> QueryBuilder<'a, usize, (), O> {
+        let completed_at = progress.completed_at.map(|completed_at| Value::Int(completed_at as i64)).unwrap_or(Value::Null);
+        self.orm.query_update(&format!("update {} set last_id = ?, processed = ?, modified = ?, completed_at = ? where name = ?", TABLE))
+            .bind(vec![Value::Int(progress.last_id as i64), Value::Int(progress.processed as i64), Value::Int(progress.modified as i64),
+                       completed_at, Value::Text(self.name.clone())])
+    }
+
+    // Creates the table of the checkpoints if it does not exist.
+    async fn create_table(&self) -> Result<(), ORMError> {
+        let query = self.orm.query_update(&format!("create table if not exists {} (name VARCHAR(255) NOT NULL PRIMARY KEY, \
+            last_id BIGINT NOT NULL, processed BIGINT NOT NULL, modified BIGINT NOT NULL, completed_at BIGINT)", TABLE));
+        self.orm.exec_all(&[query]).await?;
+        Ok(())
+    }
+}
+
+// Returns the current time in seconds since the Unix epoch, the unit of `completed_at`.
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or_default()
+}
@@ -0,0 +1,498 @@
+//! `cassette` is a module that records the statements executed by an ORM together with their responses,
+//! and replays them later without a database, e.g. to run MySQL integration tests in CI without a live server.
+//!
+//! A test is run once against the real database with `Cassette::record` and the cassette is saved to a file.
+//! Later runs connect with `Cassette::replay`: no connection is opened, and every statement must be the next
+//! recorded one, whose response is returned instead of executing it.
+//!
+//! `Cassette::capture` collects the statements an operation generates without a database, e.g. for snapshot tests
+//! of the generated SQL with `assert_sql_snapshot`.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use crate::{Column, Dialect, FloatPolicy, ORMError, ResultSet, Row, ToSqlLiteral, Value};
+
+const HEADER: &str = "parvati-cassette 1";
+
+/// `CassetteMode` is an enumeration of the modes of a `Cassette`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Statements are executed and recorded with their responses.
+    Record,
+    /// Statements are not executed; the recorded responses are returned.
+    Replay,
+    /// Statements are not executed but collected; they get empty responses (no rows, no affected rows, no ID).
+    Capture,
+}
+
+/// `Recorded` is an enumeration of the recorded responses of a statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recorded {
+    /// The number of affected rows and the generated ID of an update or insert.
+    Affected { rows: u64, last_insert_id: Option<u64> },
+    /// The numbers of affected rows of the statements of a MySQL multi-statement batch.
+    Batch(Vec<u64>),
+    /// The columns and rows returned by a select.
+    Rows { columns: Vec<Column>, rows: Vec<Vec<Value>> },
+    /// The error of a failed statement.
+    Error(String),
+}
+
+/// `Interaction` is a statement together with its recorded response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interaction {
+    /// `statement` is the executed SQL.
+    pub statement: String,
+
+    /// `response` is the response of the database.
+    pub response: Recorded,
+}
+
+/// `Cassette` is a struct that records or replays the statements of an ORM, see `ConnectOptions::cassette`.
+/// Transaction statements (`BEGIN`, `COMMIT`, savepoints) are not recorded; in replay mode they always succeed.
+#[derive(Debug)]
+pub struct Cassette {
+    mode: CassetteMode,
+    path: PathBuf,
+    interactions: Mutex<Vec<Interaction>>,
+    position: Mutex<usize>,
+}
+
+impl Cassette {
+    /// Constructs an empty cassette that records statements and is written to `path` by `save`.
+    pub fn record<P: AsRef<Path>>(path: P) -> Arc<Cassette> {
+        Arc::new(Cassette {
+            mode: CassetteMode::Record,
+            path: path.as_ref().to_path_buf(),
+            interactions: Mutex::new(Vec::new()),
+            position: Mutex::new(0),
+        })
+    }
+
+    /// Loads a recorded cassette from `path` for replaying.
+    pub fn replay<P: AsRef<Path>>(path: P) -> Result<Arc<Cassette>, ORMError> {
+        let interactions = parse(&std::fs::read_to_string(path.as_ref())?)?;
+        Ok(Arc::new(Cassette {
+            mode: CassetteMode::Replay,
+            path: path.as_ref().to_path_buf(),
+            interactions: Mutex::new(interactions),
+            position: Mutex::new(0),
+        }))
+    }
+
+    /// Constructs an empty cassette that collects the statements without executing them.
+    /// Operations that depend on the response of a statement, e.g. `apply` that reads the inserted row,
+    /// fail after their first statement, which is still collected.
+    pub fn capture() -> Arc<Cassette> {
+        Arc::new(Cassette {
+            mode: CassetteMode::Capture,
+            path: PathBuf::new(),
+            interactions: Mutex::new(Vec::new()),
+            position: Mutex::new(0),
+        })
+    }
+
+    /// Records to `path` if the environment variable `PARVATI_RECORD` is set to `1`, and replays `path` otherwise.
+    pub fn from_env<P: AsRef<Path>>(path: P) -> Result<Arc<Cassette>, ORMError> {
+        match std::env::var("PARVATI_RECORD").as_deref() {
+            Ok("1") => Ok(Cassette::record(path)),
+            _ => Cassette::replay(path),
+        }
+    }
+
+    /// Returns the mode of the cassette.
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// Returns a copy of the recorded interactions.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.interactions.lock().unwrap().clone()
+    }
+
+    /// Returns the recorded or captured statements in the order they were executed.
+    pub fn statements(&self) -> Vec<String> {
+        self.interactions.lock().unwrap().iter().map(|interaction| interaction.statement.clone()).collect()
+    }
+
+    /// Removes the recorded or captured interactions, e.g. the statements of the test setup.
+    /// Does nothing in replay mode.
+    pub fn clear(&self) {
+        if self.mode != CassetteMode::Replay {
+            self.interactions.lock().unwrap().clear();
+        }
+    }
+
+    /// Returns the number of recorded interactions that have not been replayed yet.
+    pub fn remaining(&self) -> usize {
+        match self.mode {
+            CassetteMode::Record | CassetteMode::Capture => 0,
+            CassetteMode::Replay => self.interactions.lock().unwrap().len() - *self.position.lock().unwrap(),
+        }
+    }
+
+    /// Writes the recorded interactions to the file of the cassette. Does nothing in replay mode.
+    pub fn save(&self) -> Result<(), ORMError> {
+        if self.mode == CassetteMode::Record {
+            std::fs::write(&self.path, format(&self.interactions.lock().unwrap()))?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if statements are answered by the cassette instead of the database.
+    pub(crate) fn answers(&self) -> bool {
+        self.mode != CassetteMode::Record
+    }
+
+    pub(crate) fn record_interaction(&self, statement: &str, response: Recorded) {
+        self.interactions.lock().unwrap().push(Interaction { statement: statement.to_string(), response });
+    }
+
+    // Returns the response of the next interaction, which must be `statement`.
+    // In capture mode the statement is collected and `empty` is returned.
+    fn next(&self, statement: &str, empty: Recorded) -> Result<Recorded, ORMError> {
+        if self.mode == CassetteMode::Capture {
+            self.record_interaction(statement, empty.clone());
+            return Ok(empty);
+        }
+        let interactions = self.interactions.lock().unwrap();
+        let mut position = self.position.lock().unwrap();
+        let interaction = interactions.get(*position)
+            .ok_or_else(|| ORMError::Replay(format!("no recorded response for {:?}", statement)))?;
+        if interaction.statement != statement {
+            return Err(ORMError::Replay(format!("expected {:?}, got {:?}", interaction.statement, statement)));
+        }
+        *position += 1;
+        match &interaction.response {
+            Recorded::Error(e) => Err(ORMError::Replay(e.clone())),
+            response => Ok(response.clone()),
+        }
+    }
+
+    /// Replays an update and returns the number of affected rows and the generated ID.
+    pub(crate) fn replay_affected(&self, statement: &str) -> Result<(u64, Option<u64>), ORMError> {
+        match self.next(statement, Recorded::Affected { rows: 0, last_insert_id: None })? {
+            Recorded::Affected { rows, last_insert_id } => Ok((rows, last_insert_id)),
+            response => Err(mismatch(statement, &response)),
+        }
+    }
+
+    /// Replays a MySQL multi-statement batch.
+    #[cfg_attr(not(feature = "mysql"), allow(dead_code))]
+    pub(crate) fn replay_batch(&self, statement: &str) -> Result<Vec<usize>, ORMError> {
+        match self.next(statement, Recorded::Batch(Vec::new()))? {
+            Recorded::Batch(rows) => Ok(rows.into_iter().map(|rows| rows as usize).collect()),
+            response => Err(mismatch(statement, &response)),
+        }
+    }
+
+    /// Replays a select and returns its rows as `Value`s by column name, like `query_map`.
+    pub(crate) fn replay_values(&self, statement: &str) -> Result<Vec<HashMap<String, Value>>, ORMError> {
+        match self.next(statement, Recorded::Rows { columns: Vec::new(), rows: Vec::new() })? {
+            Recorded::Rows { columns, rows } => Ok(rows.into_iter().map(|row| {
+                columns.iter().map(|column| column.name.clone()).zip(row).collect()
+            }).collect()),
+            response => Err(mismatch(statement, &response)),
+        }
+    }
+
+    /// Replays a select and returns its rows as a `ResultSet`.
+    pub(crate) fn replay_result_set(&self, statement: &str) -> Result<ResultSet, ORMError> {
+        match self.next(statement, Recorded::Rows { columns: Vec::new(), rows: Vec::new() })? {
+            Recorded::Rows { columns, rows } => {
+                let names = Arc::new(columns.iter().map(|column| column.name.clone()).collect());
+                let rows = rows.into_iter().map(|values| {
+                    let mut row = Row::with_names(Arc::clone(&names));
+                    for (i, value) in values.into_iter().enumerate() {
+                        row.set(i as i32, text(value));
+                    }
+                    row
+                }).collect();
+                Ok(ResultSet { columns, rows })
+            }
+            response => Err(mismatch(statement, &response)),
+        }
+    }
+}
+
+impl Recorded {
+    /// Converts a `ResultSet` into a `Rows` response; the column values are recorded as text.
+    pub(crate) fn result_set(result_set: &ResultSet) -> Recorded {
+        let rows = result_set.rows.iter().map(|row| {
+            (0..result_set.columns.len() as i32).map(|i| match row.columns.get(&i) {
+                Some(Some(value)) => Value::Text(value.clone()),
+                _ => Value::Null,
+            }).collect()
+        }).collect();
+        Recorded::Rows { columns: result_set.columns.clone(), rows }
+    }
+
+    /// Converts the rows of `query_map` into a `Rows` response. The columns are taken from the first row.
+    pub(crate) fn values(rows: &[HashMap<String, Value>]) -> Recorded {
+        let mut names: Vec<String> = rows.first().map(|row| row.keys().cloned().collect()).unwrap_or_default();
+        names.sort();
+        let columns = names.iter().map(|name| Column { name: name.clone(), declared_type: None, nullable: None }).collect();
+        let rows = rows.iter().map(|row| names.iter().map(|name| row.get(name).cloned().unwrap_or(Value::Null)).collect()).collect();
+        Recorded::Rows { columns, rows }
+    }
+}
+
+/// Compares `statements` with the snapshot file at `path`, which contains every statement followed by `;` and a line break.
+/// The snapshot is written if it does not exist or the environment variable `PARVATI_UPDATE_SNAPSHOTS` is set to `1`.
+/// Panics with both versions if the statements differ from the snapshot.
+pub fn assert_sql_snapshot<P: AsRef<Path>>(path: P, statements: &[String]) {
+    let path = path.as_ref();
+    let actual: String = statements.iter().map(|statement| format!("{};\n", statement)).collect();
+    if !path.exists() || std::env::var("PARVATI_UPDATE_SNAPSHOTS").as_deref() == Ok("1") {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        std::fs::write(path, actual).unwrap_or_else(|e| panic!("cannot write snapshot {}: {}", path.display(), e));
+        return;
+    }
+    let expected = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("cannot read snapshot {}: {}", path.display(), e));
+    if expected != actual {
+        panic!("SQL differs from snapshot {} (set PARVATI_UPDATE_SNAPSHOTS=1 to update it)\nexpected:\n{}\nactual:\n{}",
+               path.display(), expected, actual);
+    }
+}
+
+/// Returns the statement under which a query with bound `params` is recorded: the SQL followed by the params
+/// as SQL literals of `dialect` in a comment, so that the same SQL with other values is a different statement.
+pub(crate) fn statement_key<'a>(statement: &'a str, params: &[Value], dialect: Dialect) -> Cow<'a, str> {
+    if params.is_empty() {
+        return Cow::Borrowed(statement);
+    }
+    // The key is never executed, so NaN and infinite floats are keyed as NULL whatever the float policy.
+    let params: Vec<String> = params.iter()
+        .map(|param| param.to_sql_literal(dialect, FloatPolicy::Null).unwrap_or_default())
+        .collect();
+    Cow::Owned(format!("{} /* {} */", statement, params.join(", ")))
+}
+
+/// Encodes `params` as one line of text for a journal, see `parse_params`.
+pub(crate) fn format_params(params: &[Value]) -> String {
+    params.iter().map(format_value).collect::<Vec<String>>().join("\t")
+}
+
+/// Decodes params encoded with `format_params`.
+pub(crate) fn parse_params(params: &str) -> Option<Vec<Value>> {
+    if params.is_empty() {
+        return Some(Vec::new());
+    }
+    params.split('\t').map(parse_value).collect()
+}
+
+fn mismatch(statement: &str, response: &Recorded) -> ORMError {
+    ORMError::Replay(format!("unexpected recorded response for {:?}: {:?}", statement, response))
+}
+
+fn text(value: Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Int(v) => Some(v.to_string()),
+        Value::Float(v) => Some(v.to_string()),
+        Value::Text(v) => Some(v),
+        Value::Bytes(v) => Some(String::from_utf8_lossy(&v).to_string()),
+    }
+}
+
+// The cassette file is a text file with one tab-separated record per line:
+// `statement`, followed by `affected`, `batch`, `error` or `rows` with `column` and `row` lines.
+fn format(interactions: &[Interaction]) -> String {
+    let mut lines = vec![HEADER.to_string()];
+    for interaction in interactions.iter() {
+        lines.push(format!("statement\t{}", escape(&interaction.statement)));
+        match &interaction.response {
+            Recorded::Affected { rows, last_insert_id } => {
+                let id = last_insert_id.map(|id| id.to_string()).unwrap_or_else(|| "\\N".to_string());
+                lines.push(format!("affected\t{}\t{}", rows, id));
+            }
+            Recorded::Batch(rows) => {
+                let rows: Vec<String> = rows.iter().map(|rows| rows.to_string()).collect();
+                lines.push(format!("batch\t{}", rows.join("\t")).trim_end().to_string());
+            }
+            Recorded::Error(e) => lines.push(format!("error\t{}", escape(e))),
+            Recorded::Rows { columns, rows } => {
+                lines.push("rows".to_string());
+                for column in columns.iter() {
+                    let declared_type = column.declared_type.as_deref().map(escape).unwrap_or_else(|| "\\N".to_string());
+                    let nullable = column.nullable.map(|nullable| nullable.to_string()).unwrap_or_else(|| "\\N".to_string());
+                    lines.push(format!("column\t{}\t{}\t{}", escape(&column.name), declared_type, nullable));
+                }
+                for row in rows.iter() {
+                    let values: Vec<String> = row.iter().map(format_value).collect();
+                    lines.push(format!("row\t{}", values.join("\t")));
+                }
+            }
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+fn parse(content: &str) -> Result<Vec<Interaction>, ORMError> {
+    let mut lines = content.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(ORMError::Replay("not a cassette file".to_string()));
+    }
+    let invalid = |line: &str| ORMError::Replay(format!("invalid cassette line {:?}", line));
+    let mut interactions: Vec<Interaction> = Vec::new();
+    let mut answered = true;
+    for line in lines {
+        let mut fields = line.split('\t');
+        let response = interactions.last_mut().map(|interaction| &mut interaction.response);
+        match (fields.next().unwrap_or(""), response) {
+            ("statement", _) if answered => {
+                answered = false;
+                let statement = unescape(fields.next().ok_or_else(|| invalid(line))?);
+                // the placeholder is replaced by the response on the following line
+                interactions.push(Interaction { statement, response: Recorded::Batch(Vec::new()) });
+            }
+            ("affected", Some(response)) => {
+                let rows = fields.next().and_then(|rows| rows.parse().ok()).ok_or_else(|| invalid(line))?;
+                let last_insert_id = match fields.next() {
+                    Some("\\N") => None,
+                    Some(id) => Some(id.parse().map_err(|_| invalid(line))?),
+                    None => return Err(invalid(line)),
+                };
+                *response = Recorded::Affected { rows, last_insert_id };
+                answered = true;
+            }
+            ("batch", Some(response)) => {
+                *response = Recorded::Batch(fields.map(|rows| rows.parse().map_err(|_| invalid(line))).collect::<Result<_, _>>()?);
+                answered = true;
+            }
+            ("error", Some(response)) => {
+                *response = Recorded::Error(unescape(fields.next().unwrap_or("")));
+                answered = true;
+            }
+            ("rows", Some(response)) => {
+                *response = Recorded::Rows { columns: Vec::new(), rows: Vec::new() };
+                answered = true;
+            }
+            ("column", Some(Recorded::Rows { columns, .. })) => {
+                let fields: Vec<&str> = fields.collect();
+                if fields.len() != 3 {
+                    return Err(invalid(line));
+                }
+                columns.push(Column {
+                    name: unescape(fields[0]),
+                    declared_type: if fields[1] == "\\N" { None } else { Some(unescape(fields[1])) },
+                    nullable: fields[2].parse().ok(),
+                });
+            }
+            ("row", Some(Recorded::Rows { rows, .. })) => {
+                rows.push(fields.map(|value| parse_value(value).ok_or_else(|| invalid(line))).collect::<Result<_, _>>()?);
+            }
+            _ => return Err(invalid(line)),
+        }
+    }
+    if !answered {
+        return Err(ORMError::Replay("the last statement has no response".to_string()));
+    }
+    Ok(interactions)
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Null => "\\N".to_string(),
+        Value::Int(v) => format!("i:{}", v),
+        Value::Float(v) => format!("f:{}", v),
+        Value::Text(v) => format!("t:{}", escape(v)),
+        Value::Bytes(v) => format!("b:{}", v.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+    }
+}
+
+fn parse_value(value: &str) -> Option<Value> {
+    if value == "\\N" {
+        return Some(Value::Null);
+    }
+    match value.split_once(':')? {
+        ("i", v) => v.parse().ok().map(Value::Int),
+        ("f", v) => v.parse().ok().map(Value::Float),
+        ("t", v) => Some(Value::Text(unescape(v))),
+        ("b", v) if v.len() % 2 == 0 => (0..v.len()).step_by(2)
+            .map(|i| u8::from_str_radix(v.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>().map(Value::Bytes),
+        _ => None,
+    }
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+pub(crate) fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('t') => unescaped.push('\t'),
+                Some('n') => unescaped.push('\n'),
+                Some('r') => unescaped.push('\r'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            },
+            (c, false) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Column, Value};
+    use super::{assert_sql_snapshot, format, parse, Cassette, Interaction, Recorded};
+
+    #[test]
+    fn test_format_and_parse() {
+        let interactions = vec![
+            Interaction {
+                statement: "insert into user (name) values (\"a\tb\")".to_string(),
+                response: Recorded::Affected { rows: 1, last_insert_id: Some(7) },
+            },
+            Interaction {
+                statement: "select id, name, data from user\nwhere id = 7".to_string(),
+                response: Recorded::Rows {
+                    columns: vec![
+                        Column { name: "id".to_string(), declared_type: Some("INTEGER".to_string()), nullable: Some(false) },
+                        Column { name: "name".to_string(), declared_type: None, nullable: None },
+                        Column { name: "data".to_string(), declared_type: None, nullable: None },
+                    ],
+                    rows: vec![
+                        vec![Value::Int(7), Value::Text("back\\slash".to_string()), Value::Bytes(vec![0, 255])],
+                        vec![Value::Float(1.5), Value::Null, Value::Text("".to_string())],
+                    ],
+                },
+            },
+            Interaction { statement: "select 1; select 2".to_string(), response: Recorded::Batch(vec![0, 3]) },
+            Interaction { statement: "drop table user".to_string(), response: Recorded::Error("no such table".to_string()) },
+            Interaction { statement: "select 1".to_string(), response: Recorded::Rows { columns: Vec::new(), rows: Vec::new() } },
+        ];
+        assert_eq!(parse(&format(&interactions)).unwrap(), interactions);
+        assert!(parse("garbage").is_err());
+        assert!(parse("parvati-cassette 1\naffected\t1\t\\N").is_err());
+        assert!(parse("parvati-cassette 1\nstatement\tselect 1").is_err());
+    }
+
+    #[test]
+    fn test_capture_and_snapshot() {
+        let cassette = Cassette::capture();
+        assert_eq!(cassette.replay_affected("delete from user").unwrap(), (0, None));
+        assert!(cassette.replay_values("select * from user").unwrap().is_empty());
+        assert_eq!(cassette.statements(), vec!["delete from user", "select * from user"]);
+        assert_eq!(cassette.remaining(), 0);
+
+        let path = std::env::temp_dir().join(format!("parvati_snapshot_{}.sql", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert_sql_snapshot(&path, &cassette.statements());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "delete from user;\nselect * from user;\n");
+        assert_sql_snapshot(&path, &cassette.statements());
+        let changed = std::panic::catch_unwind(|| assert_sql_snapshot(&path, &["delete from team".to_string()]));
+        std::fs::remove_file(&path).unwrap();
+        assert!(changed.is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! `cond` is a module for conditions of WHERE clauses whose values are bound as params, so that values supplied
+//! by users are never concatenated into the SQL:
+//!
+//! ```ignore
+//! let adults: Vec<User> = conn.find_many(Cond::field("age").ge(18).and(Cond::field("name").like(pattern))).run().await?;
+//! // select * from user where age >= ? and name like ?
+//! ```
+//!
+//! A raw SQL fragment, e.g. `find_many("id > 0")`, is converted into a condition without params.
+#![cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+
+use crate::{is_table_name, Value};
+
+/// `Cond` is a condition of a WHERE clause with `?` placeholders and the params bound to them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cond {
+    sql: String,
+    params: Vec<Value>,
+    error: Option<String>,
+    /// `compound` is `true` for the conditions that need parentheses when they are combined, e.g. `a or b`.
+    compound: bool,
+}
+
+impl Cond {
+    /// Returns the column `name` to compare, e.g. `Cond::field("age").gt(18)`.
+    pub fn field(name: &str) -> CondField {
+        CondField { name: name.to_string() }
+    }
+
+    /// Returns the raw SQL fragment `sql` as a condition without params.
+    pub fn raw(sql: &str) -> Cond {
+        Cond { sql: sql.to_string(), params: Vec::new(), error: None, compound: true }
+    }
+
+    /// Returns the SQL fragment `sql` with a `?` placeholder per value of `params`, e.g. the expansion of `r#where!`.
+    pub fn with_params(sql: &str, params: Vec<Value>) -> Cond {
+        Cond { sql: sql.to_string(), params, error: None, compound: true }
+    }
+
+    /// Returns the condition that both this condition and `other` hold.
+    pub fn and<C: Into<Cond>>(self, other: C) -> Cond {
+        self.combine("and", other.into())
+    }
+
+    /// Returns the condition that this condition or `other` holds.
+    pub fn or<C: Into<Cond>>(self, other: C) -> Cond {
+        self.combine("or", other.into())
+    }
+
+    /// Returns the negation of the condition.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Cond {
+        Cond { sql: format!("not ({})", self.sql), compound: false, ..self }
+    }
+
+    /// Returns the SQL of the condition with a `?` placeholder per param.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Returns the values bound to the placeholders of the SQL.
+    pub fn params(&self) -> &[Value] {
+        &self.params
+    }
+
+    /// Returns the SQL, the params and the error of a condition with an invalid column name.
+    pub(crate) fn into_parts(self) -> (String, Vec<Value>, Option<String>) {
+        (self.sql, self.params, self.error)
+    }
+
+    fn combine(mut self, operator: &str, other: Cond) -> Cond {
+        self.sql = format!("{} {} {}", self.grouped(), operator, other.grouped());
+        self.params.extend(other.params);
+        self.error = self.error.or(other.error);
+        self.compound = true;
+        self
+    }
+
+    // Returns the SQL in parentheses if it has to be grouped to be combined with another condition.
+    fn grouped(&self) -> String {
+        if self.compound { format!("({})", self.sql) } else { self.sql.clone() }
+    }
+}
+
+impl From<&str> for Cond {
+    fn from(sql: &str) -> Self {
+        Cond::raw(sql)
+    }
+}
+
+impl From<&String> for Cond {
+    fn from(sql: &String) -> Self {
+        Cond::raw(sql)
+    }
+}
+
+impl From<String> for Cond {
+    fn from(sql: String) -> Self {
+        Cond::raw(&sql)
+    }
+}
+
+/// `CondField` is the column of a `Cond`, see `Cond::field`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CondField {
+    name: String,
+}
+
+impl CondField {
+    /// Returns the condition that the column is equal to `value`, or `is null` for `Value::Null`.
+    pub fn eq<V: Into<Value>>(self, value: V) -> Cond {
+        match value.into() {
+            Value::Null => self.is_null(),
+            value => self.compare("=", value),
+        }
+    }
+
+    /// Returns the condition that the column is not equal to `value`, or `is not null` for `Value::Null`.
+    pub fn ne<V: Into<Value>>(self, value: V) -> Cond {
+        match value.into() {
+            Value::Null => self.is_not_null(),
+            value => self.compare("<>", value),
+        }
+    }
+
+    /// Returns the condition that the column is greater than `value`.
+    pub fn gt<V: Into<Value>>(self, value: V) -> Cond {
+        self.compare(">", value.into())
+    }
+
+    /// Returns the condition that the column is greater than or equal to `value`.
+    pub fn ge<V: Into<Value>>(self, value: V) -> Cond {
+        self.compare(">=", value.into())
+    }
+
+    /// Returns the condition that the column is less than `value`.
+    pub fn lt<V: Into<Value>>(self, value: V) -> Cond {
+        self.compare("<", value.into())
+    }
+
+    /// Returns the condition that the column is less than or equal to `value`.
+    pub fn le<V: Into<Value>>(self, value: V) -> Cond {
+        self.compare("<=", value.into())
+    }
+
+    /// Returns the condition that the column matches the LIKE pattern `pattern`, e.g. `"M%"`.
+    pub fn like<V: Into<Value>>(self, pattern: V) -> Cond {
+        self.compare("like", pattern.into())
+    }
+
+    /// Returns the condition that the column is between `low` and `high`, both included.
+    pub fn between<V: Into<Value>>(self, low: V, high: V) -> Cond {
+        let sql = format!("{} between ? and ?", self.name);
+        self.cond(sql, vec![low.into(), high.into()])
+    }
+
+    /// Returns the condition that the column is equal to one of `values`. It never holds if `values` is empty.
+    pub fn is_in<V: Into<Value>, I: IntoIterator<Item = V>>(self, values: I) -> Cond {
+        let params: Vec<Value> = values.into_iter().map(Into::into).collect();
+        let sql = match params.len() {
+            0 => "1 = 0".to_string(),
+            len => format!("{} in ({})", self.name, vec!["?"; len].join(", ")),
+        };
+        self.cond(sql, params)
+    }
+
+    /// Returns the condition that the column is NULL.
+    pub fn is_null(self) -> Cond {
+        let sql = format!("{} is null", self.name);
+        self.cond(sql, Vec::new())
+    }
+
+    /// Returns the condition that the column is not NULL.
+    pub fn is_not_null(self) -> Cond {
+        let sql = format!("{} is not null", self.name);
+        self.cond(sql, Vec::new())
+    }
+
+    fn compare(self, operator: &str, value: Value) -> Cond {
+        let sql = format!("{} {} ?", self.name, operator);
+        self.cond(sql, vec![value])
+    }
+
+    // Returns the condition `sql`, or an error if the column is not a plain, optionally qualified column name.
+    fn cond(self, sql: String, params: Vec<Value>) -> Cond {
+        let error = (!is_table_name(&self.name)).then(|| format!("invalid column name {:?}", self.name));
+        Cond { sql, params, error, compound: false }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cond;
+    use crate::Value;
+
+    #[test]
+    fn test_cond() {
+        let cond = Cond::field("age").gt(18).and(Cond::field("name").like("M%"));
+        assert_eq!(cond.sql(), "age > ? and name like ?");
+        assert_eq!(cond.params(), &[Value::Int(18), Value::Text("M%".to_string())]);
+
+        let cond = Cond::field("age").ge(18).or(Cond::field("guardian_id").is_not_null()).and("deleted = 0");
+        assert_eq!(cond.sql(), "(age >= ? or guardian_id is not null) and (deleted = 0)");
+        let cond = Cond::field("id").is_in([3, 5]).and(Cond::field("name").eq(None::<String>).not());
+        assert_eq!((cond.sql(), cond.params().len()), ("id in (?, ?) and not (name is null)", 2));
+        assert_eq!(Cond::field("id").is_in(Vec::<i64>::new()).sql(), "1 = 0");
+        assert_eq!(Cond::field("user.age").between(1, 9).params(), &[Value::Int(1), Value::Int(9)]);
+
+        let (_, _, error) = Cond::field("age; drop table user").eq(1).and(Cond::field("id").eq(2)).into_parts();
+        assert_eq!(error.as_deref(), Some("invalid column name \"age; drop table user\""));
+    }
+}
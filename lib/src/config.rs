@@ -0,0 +1,276 @@
+//! `config` is a module that reads `ConnectOptions` from environment variables or from a section of a TOML
+//! configuration file, so that services are configured the same way:
+//!
+//! ```toml
+//! [database]
+//! url = "mysql://app@db:3306/app"
+//! time_zone = "+00:00"
+//! wait_timeout = 600
+//! sql_mode = ["STRICT_TRANS_TABLES"]
+//! pool_status_interval = 60
+//! pool_min = 1
+//! pool_max = 10
+//! ```
+//!
+//! The same settings are read from the variables `DATABASE_URL`, `DATABASE_TIME_ZONE`, `DATABASE_WAIT_TIMEOUT`,
+//! and so on. In variables the modes of `DATABASE_SQL_MODE` are separated by commas and the statements of
+//! `DATABASE_ON_CONNECT` by semicolons. Only the flat `key = value` form of TOML is supported: strings, integers, booleans and arrays
+//! of strings.
+//!
+//! Applications with several databases configure each one in a `[databases.<name>]` section, which
+//! `ConnectOptions::databases_from_config` and `DbManager::from_config` read.
+//!
+//! The password is best kept out of the URL and the file: `password_file` reads it from a file and `password_env`
+//! from the named variable; `DATABASE_PASSWORD` sets it directly.
+
+use std::path::Path;
+use std::time::Duration;
+use crate::{ConnectOptions, ORMError, Secret};
+
+/// The prefix of the environment variables read by `ConnectOptions::from_env`.
+const ENV_PREFIX: &str = "DATABASE_";
+
+/// The prefix of the sections read by `ConnectOptions::databases_from_config`.
+const DATABASES_PREFIX: &str = "databases.";
+
+impl ConnectOptions {
+    /// Reads the options from the `DATABASE_*` environment variables. `DATABASE_URL` is required.
+    pub fn from_env() -> Result<Self, ORMError> {
+        ConnectOptions::from_vars(std::env::vars())
+    }
+
+    /// Reads the options from `DATABASE_*` variables, e.g. of a `.env` file. Other variables are ignored.
+    pub fn from_vars<K: AsRef<str>, V: AsRef<str>>(vars: impl IntoIterator<Item = (K, V)>) -> Result<Self, ORMError> {
+        let mut settings = Vec::new();
+        for (name, value) in vars {
+            if let Some(key) = name.as_ref().strip_prefix(ENV_PREFIX) {
+                let value = value.as_ref().trim();
+                let values = match key {
+                    "SQL_MODE" => value.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect(),
+                    "ON_CONNECT" => value.split(';').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect(),
+                    _ if value.is_empty() => Vec::new(),
+                    _ => vec![value.to_string()],
+                };
+                settings.push((key.to_lowercase(), values));
+            }
+        }
+        ConnectOptions::from_settings(settings, "DATABASE_URL")
+    }
+
+    /// Reads the options from the `[database]` section of the TOML file `path`.
+    pub fn from_config<P: AsRef<Path>>(path: P) -> Result<Self, ORMError> {
+        ConnectOptions::from_config_section(path, "database")
+    }
+
+    /// Reads the options from the section `section` of the TOML file `path`, e.g. `replica` for `[replica]`.
+    pub fn from_config_section<P: AsRef<Path>>(path: P, section: &str) -> Result<Self, ORMError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let settings = parse_section(&content, section)
+            .map_err(|e| ORMError::Config(format!("{}: {}", path.display(), e)))?;
+        ConnectOptions::from_settings(settings, &format!("url in [{}] of {}", section, path.display()))
+    }
+
+    /// Reads the options of every `[databases.<name>]` section of the TOML file `path`, in the order of the file,
+    /// e.g. `[databases.main]` and `[databases.analytics]`.
+    pub fn databases_from_config<P: AsRef<Path>>(path: P) -> Result<Vec<(String, Self)>, ORMError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let mut databases = Vec::new();
+        for section in section_names(&content) {
+            if let Some(name) = section.strip_prefix(DATABASES_PREFIX) {
+                let settings = parse_section(&content, &section)
+                    .map_err(|e| ORMError::Config(format!("{}: {}", path.display(), e)))?;
+                let options = ConnectOptions::from_settings(settings, &format!("url in [{}] of {}", section, path.display()))?;
+                databases.push((name.to_string(), options));
+            }
+        }
+        Ok(databases)
+    }
+
+    fn from_settings(settings: Vec<(String, Vec<String>)>, url_name: &str) -> Result<Self, ORMError> {
+        let url = settings.iter().find(|(key, _)| key == "url").and_then(|(_, values)| values.first());
+        let mut options = match url {
+            Some(url) => ConnectOptions::new(url),
+            None => return Err(ORMError::Config(format!("{} is not set", url_name))),
+        };
+        for (key, values) in settings {
+            let value = values.first().cloned().unwrap_or_default();
+            match key.as_str() {
+                "url" => {}
+                "schema" => options.schema = Some(value),
+                "password" => options.password = Some(Secret::Value(value)),
+                "password_env" => options.password = Some(Secret::Env(value)),
+                "password_file" => options.password = Some(Secret::File(value.into())),
+                "time_zone" => options.time_zone = Some(value),
+                "charset" => options.charset = Some(value),
+                "collation" => options.collation = Some(value),
+                "sql_mode" => options.sql_mode = Some(values),
+                "on_connect" => options.on_connect.extend(values),
+                "wait_timeout" => options.wait_timeout = Some(parse(&key, &value)?),
+                "pool_status_interval" => options.pool_status_interval = Some(Duration::from_secs(parse(&key, &value)?)),
+                "pool_min" => options.pool_min = Some(parse(&key, &value)?),
+                "pool_max" => options.pool_max = Some(parse(&key, &value)?),
+                "readers" => options.readers = parse(&key, &value)?,
+                "strict_decode" => options.strict_decode = parse(&key, &value)?,
+                "n_plus_one_threshold" => options.n_plus_one_threshold = Some(parse(&key, &value)?),
+                _ => log::warn!("unknown database setting {}", key),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, ORMError> {
+    value.parse().map_err(|_| ORMError::Config(format!("invalid {} {:?}", key, value)))
+}
+
+// Returns the settings of `[section]` with their values; a scalar value is a list of one value.
+fn parse_section(content: &str, section: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut settings = Vec::new();
+    let mut current: Option<String> = None;
+    let mut found = false;
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            current = Some(name.trim().to_string());
+            found |= name.trim() == section;
+            continue;
+        }
+        if current.as_deref() != Some(section) {
+            continue;
+        }
+        let error = || format!("line {}: unsupported setting {:?}", number + 1, line);
+        let (key, value) = line.split_once('=').ok_or_else(error)?;
+        settings.push((key.trim().to_string(), parse_value(value.trim()).ok_or_else(error)?));
+    }
+    if !found {
+        return Err(format!("section [{}] not found", section));
+    }
+    Ok(settings)
+}
+
+// Returns the names of the sections of the file, without duplicates.
+fn section_names(content: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for line in content.lines() {
+        if let Some(name) = line.trim().strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            if !names.iter().any(|existing| existing == name.trim()) {
+                names.push(name.trim().to_string());
+            }
+        }
+    }
+    names
+}
+
+// Parses a scalar or an array of scalars, followed by an optional comment.
+fn parse_value(value: &str) -> Option<Vec<String>> {
+    let Some(mut rest) = value.strip_prefix('[') else {
+        let (scalar, rest) = parse_scalar(value)?;
+        return is_comment(rest).then(|| vec![scalar]);
+    };
+    let mut values = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some(end) = rest.strip_prefix(']') {
+            return is_comment(end).then_some(values);
+        }
+        let (scalar, after) = parse_scalar(rest)?;
+        values.push(scalar);
+        rest = after.trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after;
+        } else if !rest.starts_with(']') {
+            return None;
+        }
+    }
+}
+
+// Parses a string, integer or boolean at the start of `value` and returns it with the rest of `value`.
+fn parse_scalar(value: &str) -> Option<(String, &str)> {
+    if let Some(rest) = value.strip_prefix('"') {
+        let mut parsed = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Some((parsed, &rest[i + 1..])),
+                '\\' => match chars.next()?.1 {
+                    'n' => parsed.push('\n'),
+                    't' => parsed.push('\t'),
+                    c @ ('"' | '\\') => parsed.push(c),
+                    _ => return None,
+                },
+                c => parsed.push(c),
+            }
+        }
+        return None;
+    }
+    if let Some(rest) = value.strip_prefix('\'') {
+        let (literal, rest) = rest.split_once('\'')?;
+        return Some((literal.to_string(), rest));
+    }
+    let end = value.find(|c: char| c == ',' || c == ']' || c == '#' || c.is_whitespace()).unwrap_or(value.len());
+    let scalar = value[..end].replace('_', "");
+    let valid = scalar == "true" || scalar == "false" || scalar.parse::<i64>().is_ok();
+    valid.then_some((scalar, &value[end..]))
+}
+
+fn is_comment(rest: &str) -> bool {
+    let rest = rest.trim();
+    rest.is_empty() || rest.starts_with('#')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{ConnectOptions, ORMError};
+    use super::{parse_section, section_names};
+
+    #[test]
+    fn test_parse_section() {
+        let content = r#"
+            # service configuration
+            [server]
+            url = "http://localhost"
+
+            [database]
+            url = "mysql://app@db:3306/app" # primary
+            sql_mode = ["STRICT_TRANS_TABLES", 'NO_ZERO_DATE']
+            wait_timeout = 1_200
+            strict_decode = true
+            on_connect = ["SET @app = \"billing\", @tier = 'gold'", "SET @b = 2"]
+        "#;
+        let settings = parse_section(content, "database").unwrap();
+        assert_eq!(settings[0], ("url".to_string(), vec!["mysql://app@db:3306/app".to_string()]));
+        assert_eq!(settings[1].1, vec!["STRICT_TRANS_TABLES", "NO_ZERO_DATE"]);
+        assert_eq!(settings[2].1, vec!["1200"]);
+        assert_eq!(settings[4].1, vec!["SET @app = \"billing\", @tier = 'gold'", "SET @b = 2"]);
+        assert!(parse_section(content, "replica").is_err());
+        assert!(parse_section("[database]\nurl = mysql://app", "database").is_err());
+        assert_eq!(section_names(content), vec!["server", "database"]);
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let options = ConnectOptions::from_vars([
+            ("DATABASE_URL", "mysql://app@db:3306/app"),
+            ("DATABASE_SQL_MODE", "STRICT_TRANS_TABLES, NO_ZERO_DATE"),
+            ("DATABASE_ON_CONNECT", "SET @a = 1; SET @b = 2"),
+            ("DATABASE_POOL_STATUS_INTERVAL", "60"),
+            ("DATABASE_N_PLUS_ONE_THRESHOLD", "20"),
+            ("HOME", "/root"),
+        ]).unwrap();
+        assert_eq!(options.url, "mysql://app@db:3306/app");
+        assert_eq!(options.sql_mode, Some(vec!["STRICT_TRANS_TABLES".to_string(), "NO_ZERO_DATE".to_string()]));
+        assert_eq!(options.on_connect, vec!["SET @a = 1", "SET @b = 2"]);
+        assert_eq!(options.pool_status_interval, Some(Duration::from_secs(60)));
+        assert_eq!(options.n_plus_one_threshold, Some(20));
+        let options = ConnectOptions::from_vars([("DATABASE_URL", "mysql://app@db/app"), ("DATABASE_PASSWORD_FILE", "/run/secrets/db")]).unwrap();
+        assert_eq!(format!("{:?}", options.password), "Some(File(\"/run/secrets/db\"))");
+        assert!(matches!(ConnectOptions::from_vars([("DATABASE_SCHEMA", "app")]), Err(ORMError::Config(_))));
+        assert!(matches!(ConnectOptions::from_vars([("DATABASE_URL", "x"), ("DATABASE_WAIT_TIMEOUT", "soon")]), Err(ORMError::Config(_))));
+    }
+}
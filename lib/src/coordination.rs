@@ -0,0 +1,232 @@
+//! `coordination` is a module for clustered services that share a database, e.g. to run a scheduler on one
+//! instance only. `LeaderElector` elects one leader among the instances that use the same election name:
+//!
+//! ```ignore
+//! let elector = LeaderElector::new(&conn, "scheduler").lease(Duration::from_secs(15)).start();
+//! let mut changes = elector.changes();
+//! while let Some(leader) = changes.next().await {
+//!     if leader { scheduler.resume() } else { scheduler.pause() }
+//! }
+//! ```
+//!
+//! The leader holds a lease stored in a heartbeat row of the `parvati_leader` table, which is created if it does
+//! not exist. The leader renews the lease on every heartbeat; when it stops, e.g. because the instance crashed,
+//! another candidate takes the lease once it has expired. The expiry is compared with the clock of each candidate,
+//! so the clocks must be roughly synchronized and the lease should span several heartbeats.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures::channel::mpsc;
+use futures::Stream;
+use crate::schema::quote;
+use crate::{ORMError, ORMTrait, Value};
+
+/// The table of the heartbeat rows of all elections.
+pub(crate) const TABLE: &str = "parvati_leader";
+
+/// `LeaderElector` takes part in the election `name` as one candidate, see the module documentation.
+pub struct LeaderElector<O> {
+    /// `orm` is the connection the heartbeats are written with.
+    orm: Arc<O>,
+
+    /// `name` is the name of the election.
+    name: String,
+
+    /// `candidate` identifies this candidate in the heartbeat row.
+    candidate: String,
+
+    /// `lease` is how long a heartbeat keeps the leadership.
+    lease: Duration,
+
+    /// `interval` is the time between two heartbeats of `start`.
+    interval: Duration,
+
+    /// `leader` is `true` while this candidate holds the lease.
+    leader: AtomicBool,
+
+    /// `created` is `true` once the table was created.
+    created: AtomicBool,
+
+    /// `subscribers` receive the changes of `leader`.
+    subscribers: Mutex<Vec<mpsc::UnboundedSender<bool>>>,
+}
+
+impl<O: ORMTrait<O> + Send + Sync + 'static> LeaderElector<O> {
+    /// The default lease of a heartbeat.
+    pub const DEFAULT_LEASE: Duration = Duration::from_secs(30);
+
+    /// Constructs a candidate of the election `name` with the default lease and a heartbeat every third of it.
+    /// The candidate is identified by the process ID and the time it was constructed.
+    pub fn new(orm: &Arc<O>, name: &str) -> Self {
+        LeaderElector {
+            orm: orm.clone(),
+            name: name.to_string(),
+            candidate: format!("{}-{}", std::process::id(), now()),
+            lease: Self::DEFAULT_LEASE,
+            interval: Self::DEFAULT_LEASE / 3,
+            leader: AtomicBool::new(false),
+            created: AtomicBool::new(false),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Sets how long a heartbeat keeps the leadership, and the heartbeat interval to a third of it.
+    pub fn lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self.interval = lease / 3;
+        self
+    }
+
+    /// Sets the time between two heartbeats of `start`, which must be shorter than the lease.
+    pub fn heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the identity of the candidate, e.g. the host name. It must be unique among the candidates.
+    pub fn candidate(mut self, candidate: &str) -> Self {
+        self.candidate = candidate.to_string();
+        self
+    }
+
+    /// Returns the name of the election.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns `true` if this candidate holds the lease, as of the last heartbeat.
+    pub fn is_leader(&self) -> bool {
+        self.leader.load(Ordering::Acquire)
+    }
+
+    /// Returns a stream of the changes of `is_leader`: `true` when this candidate is elected and `false` when it
+    /// loses the lease or resigns.
+    pub fn changes(&self) -> impl Stream<Item = bool> + Unpin {
+        let (tx, rx) = mpsc::unbounded();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Spawns a task that sends a heartbeat every heartbeat interval until the elector is dropped.
+    /// A heartbeat that fails is logged, and the candidate steps down until a heartbeat succeeds again.
+    /// Does nothing but return the elector if it is called outside of a Tokio runtime.
+    pub fn start(self) -> Arc<Self> {
+        let elector = Arc::new(self);
+        let handle = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                log::warn!("leader election requires a Tokio runtime");
+                return elector;
+            }
+        };
+        let weak = Arc::downgrade(&elector);
+        let interval = elector.interval;
+        handle.spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(elector) = weak.upgrade() else {
+                    break;
+                };
+                if let Err(e) = elector.heartbeat().await {
+                    log::warn!("leader election {}: heartbeat failed: {}", elector.name, e);
+                    elector.set_leader(false);
+                }
+            }
+        });
+        elector
+    }
+
+    /// Sends one heartbeat: renews the lease if this candidate holds it, or takes it if it has expired.
+    /// Returns whether this candidate is the leader afterwards.
+    pub async fn heartbeat(&self) -> Result<bool, ORMError> {
+        self.create_table().await?;
+        let now = now();
+        let expires_at = now + self.lease.as_millis() as i64;
+        let query = self.orm.query_update(&format!("update {} set holder = ?, expires_at = ? where name = ? and (holder = ? or expires_at <= ?)", TABLE))
+            .bind(vec![self.candidate_param(), Value::Int(expires_at), self.name_param(), self.candidate_param(), Value::Int(now)]);
+        self.orm.exec_all(&[query]).await?;
+        // the row is read back, since MySQL does not count a renewal that does not change the row
+        let leader = match self.lease_holder().await? {
+            Some((holder, expires_at)) => holder == self.candidate && expires_at > now,
+            None => {
+                let query = self.orm.query_update(&format!("insert into {} (name, holder, expires_at) values (?, ?, ?)", TABLE))
+                    .bind(vec![self.name_param(), self.candidate_param(), Value::Int(expires_at)]);
+                match self.orm.exec_all(&[query]).await {
+                    Ok(_) => true,
+                    // another candidate inserted the row first
+                    Err(e) => match self.lease_holder().await? {
+                        Some(_) => false,
+                        None => return Err(e),
+                    },
+                }
+            }
+        };
+        self.set_leader(leader);
+        Ok(leader)
+    }
+
+    /// Gives up the lease, so that another candidate can take it with its next heartbeat.
+    /// The elector keeps taking part in the election if it was started; drop it to stop.
+    pub async fn resign(&self) -> Result<(), ORMError> {
+        self.create_table().await?;
+        let query = self.orm.query_update(&format!("update {} set expires_at = 0 where name = ? and holder = ?", TABLE))
+            .bind(vec![self.name_param(), self.candidate_param()]);
+        self.orm.exec_all(&[query]).await?;
+        self.set_leader(false);
+        Ok(())
+    }
+
+    // Creates the table of the heartbeat rows on first use.
+    async fn create_table(&self) -> Result<(), ORMError> {
+        if self.created.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        let query = self.orm.query_update(&format!("create table if not exists {} \
+            (name VARCHAR(255) NOT NULL PRIMARY KEY, holder VARCHAR(255) NOT NULL, expires_at BIGINT NOT NULL)", TABLE));
+        self.orm.exec_all(&[query]).await?;
+        self.created.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    // Returns the holder of the lease and its expiry, or `None` if the heartbeat row of the election does not exist.
+    async fn lease_holder(&self) -> Result<Option<(String, i64)>, ORMError> {
+        let rows = self.orm.query_map(&format!("select holder, expires_at from {} where name = {}", TABLE, quote(&self.name))).await?;
+        Ok(rows.first().map(|row| (
+            row.get("holder").and_then(Value::as_str).unwrap_or_default().to_string(),
+            row.get("expires_at").and_then(Value::as_i64).unwrap_or_default(),
+        )))
+    }
+
+    // Stores whether this candidate is the leader and notifies the subscribers if it changed.
+    fn set_leader(&self, leader: bool) {
+        if self.leader.swap(leader, Ordering::AcqRel) != leader {
+            log::info!("leader election {}: {} {}", self.name, self.candidate, if leader { "was elected" } else { "stepped down" });
+            self.subscribers.lock().unwrap().retain(|subscriber| subscriber.unbounded_send(leader).is_ok());
+        }
+    }
+
+    fn name_param(&self) -> Value {
+        Value::Text(self.name.clone())
+    }
+
+    fn candidate_param(&self) -> Value {
+        Value::Text(self.candidate.clone())
+    }
+}
+
+impl<O> std::fmt::Debug for LeaderElector<O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LeaderElector")
+            .field("name", &self.name)
+            .field("candidate", &self.candidate)
+            .field("leader", &self.leader.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+// Returns the current time in milliseconds since the Unix epoch, the unit of `expires_at`.
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_millis() as i64).unwrap_or_default()
+}
@@ -0,0 +1,162 @@
+//! `datetime` is a module that converts datetime values according to a `DateTimePolicy`.
+//!
+//! Only the values of declared datetime columns (`ColumnType::DateTime`, i.e. chrono datetime fields and fields
+//! with `#[column(datetime)]`) are converted, so that other text that looks like a datetime is kept as it is.
+//! With `DateTimePolicy::Utc` datetimes with an offset (RFC 3339, e.g. `2023-09-08T15:33:22+02:00`) are stored
+//! as UTC in the `YYYY-MM-DD HH:MM:SS` form understood by both SQLite and MySQL DATETIME/TIMESTAMP columns,
+//! and stored datetimes are read back as RFC 3339 UTC values (`2023-09-08T13:33:22Z`).
+
+use crate::{ColumnDef, ColumnType, DateTimePolicy};
+
+/// A datetime split into its parts. `fraction` keeps the digits after the decimal point, if any.
+struct Parts<'a> {
+    seconds: i64,
+    fraction: &'a str,
+}
+
+/// Converts a value of `column` before it is written to the database. Returns the value unchanged if the column
+/// is not a declared datetime column, the value is not a datetime with an offset or the policy is `AsIs`.
+pub(crate) fn to_storage(value: &str, column: Option<&ColumnDef>, policy: DateTimePolicy) -> String {
+    if policy == DateTimePolicy::AsIs || !is_datetime(column) {
+        return value.to_string();
+    }
+    match parse(value, true) {
+        Some(parts) => format(&parts, ' ', ""),
+        None => value.to_string(),
+    }
+}
+
+/// Converts a value of `column` after it is read from the database. Returns the value unchanged if the column
+/// is not a declared datetime column, the value is not a stored datetime without an offset or the policy is `AsIs`.
+pub(crate) fn from_storage(value: &str, column: Option<&ColumnDef>, policy: DateTimePolicy) -> String {
+    if policy == DateTimePolicy::AsIs || !is_datetime(column) {
+        return value.to_string();
+    }
+    match parse(value, false) {
+        Some(parts) => format(&parts, 'T', "Z"),
+        None => value.to_string(),
+    }
+}
+
+fn is_datetime(column: Option<&ColumnDef>) -> bool {
+    column.is_some_and(|column| column.column_type == ColumnType::DateTime)
+}
+
+/// Parses `YYYY-MM-DD[T ]HH:MM:SS[.fff]` followed by an offset (`Z`, `+HH:MM`, `-HH:MM`) if `with_offset` is set,
+/// or by nothing otherwise. The result is normalized to UTC.
+fn parse(value: &str, with_offset: bool) -> Option<Parts<'_>> {
+    let bytes = value.as_bytes();
+    if bytes.len() < 19 || !value.is_ascii() {
+        return None;
+    }
+    let number = |from: usize, to: usize| -> Option<i64> {
+        let s = &value[from..to];
+        if s.bytes().all(|b| b.is_ascii_digit()) { s.parse().ok() } else { None }
+    };
+    if bytes[4] != b'-' || bytes[7] != b'-' || (bytes[10] != b'T' && bytes[10] != b' ') || bytes[13] != b':' || bytes[16] != b':' {
+        return None;
+    }
+    let (year, month, day) = (number(0, 4)?, number(5, 7)?, number(8, 10)?);
+    let (hour, minute, second) = (number(11, 13)?, number(14, 16)?, number(17, 19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut rest = &value[19..];
+    let mut fraction = "";
+    if let Some(stripped) = rest.strip_prefix('.') {
+        let len = stripped.bytes().take_while(|b| b.is_ascii_digit()).count();
+        if len == 0 {
+            return None;
+        }
+        fraction = &stripped[..len];
+        rest = &stripped[len..];
+    }
+
+    let offset = if with_offset {
+        match rest {
+            "Z" | "z" => 0,
+            _ if rest.len() == 6 && (rest.starts_with('+') || rest.starts_with('-')) && &rest[3..4] == ":" => {
+                let hours: i64 = rest[1..3].parse().ok()?;
+                let minutes: i64 = rest[4..6].parse().ok()?;
+                let offset = hours * 3600 + minutes * 60;
+                if rest.starts_with('-') { -offset } else { offset }
+            }
+            _ => return None,
+        }
+    } else {
+        if !rest.is_empty() {
+            return None;
+        }
+        0
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+    Some(Parts { seconds, fraction })
+}
+
+fn format(parts: &Parts, separator: char, suffix: &str) -> String {
+    let days = parts.seconds.div_euclid(86400);
+    let secs = parts.seconds.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    let fraction = if parts.fraction.is_empty() { "".to_string() } else { format!(".{}", parts.fraction) };
+    format!("{:04}-{:02}-{:02}{}{:02}:{:02}:{:02}{}{}",
+            year, month, day, separator, secs / 3600, secs % 3600 / 60, secs % 60, fraction, suffix)
+}
+
+// Days since 1970-01-01 for a proleptic Gregorian date (H. Hinnant's algorithm).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_storage, to_storage};
+    use crate::{ColumnDef, ColumnType, DateTimePolicy};
+
+    fn created() -> ColumnDef {
+        ColumnDef::new("created", ColumnType::DateTime)
+    }
+
+    #[test]
+    fn test_to_storage() {
+        assert_eq!(to_storage("2023-09-08T15:33:22+02:00", Some(&created()), DateTimePolicy::Utc), "2023-09-08 13:33:22");
+        assert_eq!(to_storage("2023-12-31T23:30:00.250-01:00", Some(&created()), DateTimePolicy::Utc), "2024-01-01 00:30:00.250");
+        assert_eq!(to_storage("2023-09-08T13:33:22Z", Some(&created()), DateTimePolicy::Utc), "2023-09-08 13:33:22");
+        assert_eq!(to_storage("2023-09-08T15:33:22+02:00", Some(&created()), DateTimePolicy::AsIs), "2023-09-08T15:33:22+02:00");
+        assert_eq!(to_storage("2023-09-08 13:33:22", Some(&created()), DateTimePolicy::Utc), "2023-09-08 13:33:22");
+        assert_eq!(to_storage("John", Some(&created()), DateTimePolicy::Utc), "John");
+        let note = ColumnDef::new("note", ColumnType::Text);
+        assert_eq!(to_storage("2023-09-08T15:33:22+02:00", Some(&note), DateTimePolicy::Utc), "2023-09-08T15:33:22+02:00");
+        assert_eq!(to_storage("2023-09-08T15:33:22+02:00", None, DateTimePolicy::Utc), "2023-09-08T15:33:22+02:00");
+    }
+
+    #[test]
+    fn test_from_storage() {
+        assert_eq!(from_storage("2023-09-08 13:33:22", Some(&created()), DateTimePolicy::Utc), "2023-09-08T13:33:22Z");
+        assert_eq!(from_storage("2024-02-29 00:00:00.5", Some(&created()), DateTimePolicy::Utc), "2024-02-29T00:00:00.5Z");
+        assert_eq!(from_storage("2023-09-08 13:33:22", Some(&created()), DateTimePolicy::AsIs), "2023-09-08 13:33:22");
+        assert_eq!(from_storage("2023-09-08T13:33:22Z", Some(&created()), DateTimePolicy::Utc), "2023-09-08T13:33:22Z");
+        assert_eq!(from_storage("30", Some(&created()), DateTimePolicy::Utc), "30");
+        let note = ColumnDef::new("note", ColumnType::Text);
+        assert_eq!(from_storage("2023-09-08 13:33:22", Some(&note), DateTimePolicy::Utc), "2023-09-08 13:33:22");
+    }
+}
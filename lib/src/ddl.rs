@@ -0,0 +1,503 @@
+//! `ddl` is a module that builds schema statements from typed descriptions instead of raw SQL strings and renders
+//! them for the dialect of the connection, e.g. in migrations:
+//!
+//! ```ignore
+//! let table = Ddl::create_table("note")
+//!     .column("id", ColumnType::Integer, primary_key().auto_increment())
+//!     .column("title", ColumnType::Text, not_null())
+//!     .column("user_id", ColumnType::Integer, nullable().references(ForeignKey::new("user", "id")));
+//! conn.ddl(&table).run().await?;
+//! conn.ddl(&Ddl::create_index("note_title", "note", &["title"]).unique()).run().await?;
+//! ```
+
+use std::marker::PhantomData;
+use crate::schema::{column_sql, create_table_sql, foreign_key_sql, quote, type_sql};
+use crate::{ColumnDef, ColumnType, Dialect, ForeignKey, ORMError, ORMTrait, TableDeserialize};
+
+/// `DdlStatement` is a trait for schema statements that are rendered per dialect and executed with `ORMTrait::ddl`.
+pub trait DdlStatement {
+    /// Returns the statement in the SQL of `dialect`.
+    fn to_sql(&self, dialect: Dialect) -> String;
+}
+
+/// `Ddl` constructs the builders of the schema statements.
+pub struct Ddl;
+
+impl Ddl {
+    /// Starts a `CREATE TABLE` statement.
+    pub fn create_table(name: &str) -> CreateTable {
+        CreateTable { name: name.to_string(), if_not_exists: false, columns: Vec::new(), foreign_keys: Vec::new() }
+    }
+
+    /// Starts a `DROP TABLE` statement.
+    pub fn drop_table(name: &str) -> DropTable {
+        DropTable { name: name.to_string(), if_exists: false }
+    }
+
+    /// Starts a `CREATE INDEX` statement on `columns` of `table`.
+    pub fn create_index(name: &str, table: &str, columns: &[&str]) -> CreateIndex {
+        CreateIndex {
+            name: name.to_string(),
+            table: table.to_string(),
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            unique: false,
+            if_not_exists: false,
+        }
+    }
+
+    /// Starts a `DROP INDEX` statement. MySQL needs the `table` of the index.
+    pub fn drop_index(name: &str, table: &str) -> DropIndex {
+        DropIndex { name: name.to_string(), table: table.to_string(), if_exists: false }
+    }
+}
+
+/// `ColumnOptions` are the constraints of a column of `CreateTable`, started with `primary_key`, `not_null` or `nullable`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnOptions {
+    primary_key: bool,
+    auto_increment: bool,
+    nullable: bool,
+    unique: bool,
+    default: Option<String>,
+    foreign_key: Option<ForeignKey>,
+}
+
+/// Returns the options of a primary key column.
+pub fn primary_key() -> ColumnOptions {
+    ColumnOptions { primary_key: true, ..Default::default() }
+}
+
+/// Returns the options of a `NOT NULL` column.
+pub fn not_null() -> ColumnOptions {
+    Default::default()
+}
+
+/// Returns the options of a column that accepts NULL.
+pub fn nullable() -> ColumnOptions {
+    ColumnOptions { nullable: true, ..Default::default() }
+}
+
+impl ColumnOptions {
+    /// Generates the values of an integer primary key: `AUTOINCREMENT` on SQLite, `AUTO_INCREMENT` on MySQL.
+    pub fn auto_increment(mut self) -> Self {
+        self.auto_increment = true;
+        self
+    }
+
+    /// Adds a `UNIQUE` constraint.
+    pub fn unique(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    /// Sets the default value, given as SQL, e.g. `"0"`, `"'new'"` or `"CURRENT_TIMESTAMP"`.
+    pub fn default(mut self, value: &str) -> Self {
+        self.default = Some(value.to_string());
+        self
+    }
+
+    /// Adds a foreign key, rendered as a table constraint.
+    pub fn references(mut self, foreign_key: ForeignKey) -> Self {
+        self.foreign_key = Some(foreign_key);
+        self
+    }
+}
+
+/// `CreateTable` is the builder of a `CREATE TABLE` statement, see `Ddl::create_table`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateTable {
+    name: String,
+    if_not_exists: bool,
+    columns: Vec<(String, ColumnType, ColumnOptions)>,
+    foreign_keys: Vec<(String, ForeignKey)>,
+}
+
+impl CreateTable {
+    /// Adds a column.
+    pub fn column(mut self, name: &str, column_type: ColumnType, options: ColumnOptions) -> Self {
+        if let Some(foreign_key) = &options.foreign_key {
+            self.foreign_keys.push((name.to_string(), foreign_key.clone()));
+        }
+        self.columns.push((name.to_string(), column_type, options));
+        self
+    }
+
+    /// Does nothing if the table exists.
+    pub fn if_not_exists(mut self) -> Self {
+        self.if_not_exists = true;
+        self
+    }
+}
+
+impl DdlStatement for CreateTable {
+    fn to_sql(&self, dialect: Dialect) -> String {
+        let mut definitions: Vec<String> = self.columns.iter().map(|(name, column_type, options)| {
+            let mut sql = format!("{} {}", name, type_sql(*column_type, dialect));
+            if options.primary_key {
+                sql += match (options.auto_increment, dialect) {
+                    (true, Dialect::Sqlite) => " PRIMARY KEY AUTOINCREMENT",
+                    (true, Dialect::MySql) => " AUTO_INCREMENT PRIMARY KEY",
+                    (false, _) => " PRIMARY KEY",
+                };
+            } else if !options.nullable {
+                sql += " NOT NULL";
+            }
+            if options.unique {
+                sql += " UNIQUE";
+            }
+            if let Some(default) = &options.default {
+                sql += &format!(" DEFAULT {}", default);
+            }
+            sql
+        }).collect();
+        definitions.extend(self.foreign_keys.iter().map(|(column, foreign_key)| foreign_key_sql(column, foreign_key)));
+        let if_not_exists = if self.if_not_exists { "IF NOT EXISTS " } else { "" };
+        format!("CREATE TABLE {}{} ({})", if_not_exists, self.name, definitions.join(", "))
+    }
+}
+
+/// `DropTable` is the builder of a `DROP TABLE` statement, see `Ddl::drop_table`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropTable {
+    name: String,
+    if_exists: bool,
+}
+
+impl DropTable {
+    /// Does nothing if the table does not exist.
+    pub fn if_exists(mut self) -> Self {
+        self.if_exists = true;
+        self
+    }
+}
+
+impl DdlStatement for DropTable {
+    fn to_sql(&self, _dialect: Dialect) -> String {
+        let if_exists = if self.if_exists { "IF EXISTS " } else { "" };
+        format!("DROP TABLE {}{}", if_exists, self.name)
+    }
+}
+
+/// `CreateIndex` is the builder of a `CREATE INDEX` statement, see `Ddl::create_index`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateIndex {
+    name: String,
+    table: String,
+    columns: Vec<String>,
+    unique: bool,
+    if_not_exists: bool,
+}
+
+impl CreateIndex {
+    /// Creates a `UNIQUE` index.
+    pub fn unique(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    /// Does nothing if the index exists. Only SQLite supports it; it is ignored on MySQL.
+    pub fn if_not_exists(mut self) -> Self {
+        self.if_not_exists = true;
+        self
+    }
+}
+
+impl DdlStatement for CreateIndex {
+    fn to_sql(&self, dialect: Dialect) -> String {
+        let unique = if self.unique { "UNIQUE " } else { "" };
+        let if_not_exists = if self.if_not_exists && dialect == Dialect::Sqlite { "IF NOT EXISTS " } else { "" };
+        format!("CREATE {}INDEX {}{} ON {} ({})", unique, if_not_exists, self.name, self.table, self.columns.join(", "))
+    }
+}
+
+/// `DropIndex` is the builder of a `DROP INDEX` statement, see `Ddl::drop_index`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropIndex {
+    name: String,
+    table: String,
+    if_exists: bool,
+}
+
+impl DropIndex {
+    /// Does nothing if the index does not exist. Only SQLite supports it; it is ignored on MySQL.
+    pub fn if_exists(mut self) -> Self {
+        self.if_exists = true;
+        self
+    }
+}
+
+impl DdlStatement for DropIndex {
+    fn to_sql(&self, dialect: Dialect) -> String {
+        match dialect {
+            Dialect::Sqlite if self.if_exists => format!("DROP INDEX IF EXISTS {}", self.name),
+            Dialect::Sqlite => format!("DROP INDEX {}", self.name),
+            Dialect::MySql => format!("DROP INDEX {} ON {}", self.name, self.table),
+        }
+    }
+}
+
+/// Renders a column default given as text, e.g. for a `ColumnOptions::default` built from a Rust string.
+pub fn text_default(value: &str) -> String {
+    quote(value)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Alteration {
+    Add { column: String, default: Option<String> },
+    Rename { from: String, to: String },
+    Drop(String),
+    Modify(String),
+}
+
+/// `AlterTable` changes the existing table of `T` to the column definitions of `T`, see `ORMTrait::alter_table`.
+/// The columns are named by their new names, which must be columns of `T`, except for dropped columns.
+///
+/// MySQL executes one `ALTER TABLE` statement. SQLite executes `ALTER TABLE` for added columns that it
+/// supports and for renamed columns; otherwise it rebuilds the table as documented for SQLite: it creates
+/// the new table, copies the rows, drops the old table, renames the new one and recreates the indexes and
+/// triggers of the table, all inside one transaction with the foreign keys disabled.
+/// Indexes and triggers on dropped or renamed columns must be dropped before a rebuild.
+pub struct AlterTable<'a, T, O: ORMTrait<O>> {
+    table: String,
+    changes: Vec<Alteration>,
+    pub(crate) orm: &'a O,
+    entity: PhantomData<T>,
+}
+
+impl<'a, T, O: ORMTrait<O>> AlterTable<'a, T, O> {
+    pub(crate) fn new(orm: &'a O, table: String) -> Self {
+        AlterTable { table, changes: Vec::new(), orm, entity: PhantomData }
+    }
+
+    /// Returns a copy of the changes that is executed with `orm`, e.g. the backend of an `AnyORM`.
+    pub(crate) fn with_orm<'b, P: ORMTrait<P>>(&self, orm: &'b P) -> AlterTable<'b, T, P> {
+        AlterTable { table: self.table.clone(), changes: self.changes.clone(), orm, entity: PhantomData }
+    }
+
+    /// Returns the schema prefix of the table, e.g. `billing.`, and its unqualified name.
+    pub(crate) fn schema_and_name(&self) -> (&str, &str) {
+        match self.table.rfind('.') {
+            Some(dot) => self.table.split_at(dot + 1),
+            None => ("", &self.table),
+        }
+    }
+
+    /// Adds the column `column` of `T`. Existing rows get NULL, so a `NOT NULL` column needs
+    /// `add_column_with_default`.
+    pub fn add_column(mut self, column: &str) -> Self {
+        self.changes.push(Alteration::Add { column: column.to_string(), default: None });
+        self
+    }
+
+    /// Adds the column `column` of `T` with a default value given as SQL, e.g. `"0"` or `text_default("new")`.
+    pub fn add_column_with_default(mut self, column: &str, default: &str) -> Self {
+        self.changes.push(Alteration::Add { column: column.to_string(), default: Some(default.to_string()) });
+        self
+    }
+
+    /// Renames the column `from` to the column `to` of `T`, keeping its values.
+    pub fn rename_column(mut self, from: &str, to: &str) -> Self {
+        self.changes.push(Alteration::Rename { from: from.to_string(), to: to.to_string() });
+        self
+    }
+
+    /// Drops the column `column`, which is no longer a column of `T`.
+    pub fn drop_column(mut self, column: &str) -> Self {
+        self.changes.push(Alteration::Drop(column.to_string()));
+        self
+    }
+
+    /// Changes the type and the nullability of the column `column` to its definition in `T`.
+    pub fn modify_column(mut self, column: &str) -> Self {
+        self.changes.push(Alteration::Modify(column.to_string()));
+        self
+    }
+
+    /// Returns `true` if there are no changes.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl<T: TableDeserialize, O: ORMTrait<O>> AlterTable<'_, T, O> {
+    // Returns the definitions of `T` after checking the column names of the changes against them.
+    fn definitions(&self) -> Result<Vec<ColumnDef>, ORMError> {
+        let columns = T::columns();
+        let defined = |column: &str| columns.iter().any(|definition| definition.name == column);
+        for change in self.changes.iter() {
+            let (column, expected) = match change {
+                Alteration::Add { column, .. } | Alteration::Modify(column) => (column, true),
+                Alteration::Rename { to, .. } => (to, true),
+                Alteration::Drop(column) => (column, false),
+            };
+            if defined(column) != expected {
+                let state = if expected { "is not" } else { "is still" };
+                return Err(ORMError::InvalidValue(format!("{} {} a column of {}", column, state, T::same_name())));
+            }
+        }
+        Ok(columns)
+    }
+
+    fn definition<'c>(columns: &'c [ColumnDef], column: &str) -> &'c ColumnDef {
+        columns.iter().find(|definition| definition.name == column).unwrap()
+    }
+
+    /// Returns `true` if SQLite cannot execute the changes with `ALTER TABLE` and rebuilds the table.
+    pub(crate) fn needs_rebuild(&self) -> bool {
+        let columns = T::columns();
+        self.changes.iter().any(|change| match change {
+            Alteration::Add { column, default } => columns.iter().find(|definition| &definition.name == column)
+                .is_some_and(|definition| definition.is_primary_key() || definition.foreign_key.is_some()
+                    || (!definition.nullable && default.is_none())),
+            Alteration::Rename { .. } => false,
+            Alteration::Drop(_) | Alteration::Modify(_) => true,
+        })
+    }
+
+    /// Renders the `ALTER TABLE` statements: one for all changes on MySQL, one per change on SQLite.
+    pub(crate) fn alter_statements(&self, dialect: Dialect) -> Result<Vec<String>, ORMError> {
+        let columns = self.definitions()?;
+        let mut clauses = Vec::new();
+        for change in self.changes.iter() {
+            match change {
+                Alteration::Add { column, default } => {
+                    let definition = Self::definition(&columns, column);
+                    let mut clause = format!("ADD COLUMN {}", column_sql(definition, dialect, true));
+                    if let Some(default) = default {
+                        clause += &format!(" DEFAULT {}", default);
+                    }
+                    clauses.push(clause);
+                    if let (Some(foreign_key), Dialect::MySql) = (&definition.foreign_key, dialect) {
+                        clauses.push(format!("ADD {}", foreign_key_sql(column, foreign_key)));
+                    }
+                }
+                Alteration::Rename { from, to } => clauses.push(format!("RENAME COLUMN {} TO {}", from, to)),
+                Alteration::Drop(column) => clauses.push(format!("DROP COLUMN {}", column)),
+                Alteration::Modify(column) => {
+                    clauses.push(format!("MODIFY COLUMN {}", column_sql(Self::definition(&columns, column), dialect, false)));
+                }
+            }
+        }
+        let table = &self.table;
+        Ok(match dialect {
+            _ if clauses.is_empty() => Vec::new(),
+            Dialect::MySql => vec![format!("ALTER TABLE {} {}", table, clauses.join(", "))],
+            Dialect::Sqlite => clauses.iter().map(|clause| format!("ALTER TABLE {} {}", table, clause)).collect(),
+        })
+    }
+
+    /// Renders the statements that rebuild the table on SQLite from its `existing` column names and the SQL
+    /// of its indexes and triggers.
+    pub(crate) fn rebuild_statements(&self, existing: &[String], indexes: &[String]) -> Result<Vec<String>, ORMError> {
+        let columns = self.definitions()?;
+        let table = &self.table;
+        let (schema, name) = self.schema_and_name();
+        let new_table = format!("{}parvati_new_{}", schema, name);
+        let mut targets = Vec::new();
+        let mut sources = Vec::new();
+        for column in columns.iter() {
+            let renamed = self.changes.iter().find_map(|change| match change {
+                Alteration::Rename { from, to } if *to == column.name => Some(from.clone()),
+                _ => None,
+            });
+            let default = self.changes.iter().find_map(|change| match change {
+                Alteration::Add { column: added, default } if *added == column.name => Some(default.clone()),
+                _ => None,
+            });
+            let source = match (renamed, default) {
+                (Some(from), _) => Some(from),
+                (None, Some(default)) => default,
+                (None, None) if existing.contains(&column.name) => Some(column.name.clone()),
+                (None, None) => return Err(ORMError::InvalidValue(format!("{} is not a column of the table {}", column.name, table))),
+            };
+            if let Some(source) = source {
+                targets.push(column.name.clone());
+                sources.push(source);
+            }
+        }
+        let mut statements = vec![
+            create_table_sql(&new_table, None, &columns, Dialect::Sqlite),
+            format!("INSERT INTO {} ({}) SELECT {} FROM {}", new_table, targets.join(", "), sources.join(", "), table),
+            format!("DROP TABLE {}", table),
+            format!("ALTER TABLE {} RENAME TO {}", new_table, name),
+        ];
+        statements.extend(indexes.iter().cloned());
+        Ok(statements)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ColumnType, Dialect, ForeignKey, ReferentialAction};
+    use super::{not_null, nullable, primary_key, text_default, Ddl, DdlStatement};
+
+    #[test]
+    fn test_create_table() {
+        let table = Ddl::create_table("note").if_not_exists()
+            .column("id", ColumnType::Integer, primary_key().auto_increment())
+            .column("title", ColumnType::Text, not_null().unique())
+            .column("state", ColumnType::Text, not_null().default(&text_default("new")))
+            .column("user_id", ColumnType::BigInt, nullable().references(ForeignKey::new("user", "id").on_delete(ReferentialAction::SetNull)));
+        assert_eq!(table.to_sql(Dialect::Sqlite),
+                   "CREATE TABLE IF NOT EXISTS note (id INTEGER PRIMARY KEY AUTOINCREMENT, title TEXT NOT NULL UNIQUE, \
+                   state TEXT NOT NULL DEFAULT 'new', user_id INTEGER, FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE SET NULL)");
+        assert_eq!(table.to_sql(Dialect::MySql),
+                   "CREATE TABLE IF NOT EXISTS note (id INT AUTO_INCREMENT PRIMARY KEY, title VARCHAR(255) NOT NULL UNIQUE, \
+                   state VARCHAR(255) NOT NULL DEFAULT 'new', user_id BIGINT, FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE SET NULL)");
+        let keyed = Ddl::create_table("setting").column("key", ColumnType::Text, primary_key());
+        assert_eq!(keyed.to_sql(Dialect::Sqlite), "CREATE TABLE setting (key TEXT PRIMARY KEY)");
+    }
+
+    #[test]
+    fn test_indexes_and_drops() {
+        let index = Ddl::create_index("note_user", "note", &["user_id", "title"]).unique().if_not_exists();
+        assert_eq!(index.to_sql(Dialect::Sqlite), "CREATE UNIQUE INDEX IF NOT EXISTS note_user ON note (user_id, title)");
+        assert_eq!(index.to_sql(Dialect::MySql), "CREATE UNIQUE INDEX note_user ON note (user_id, title)");
+        assert_eq!(Ddl::drop_index("note_user", "note").if_exists().to_sql(Dialect::Sqlite), "DROP INDEX IF EXISTS note_user");
+        assert_eq!(Ddl::drop_index("note_user", "note").to_sql(Dialect::MySql), "DROP INDEX note_user ON note");
+        assert_eq!(Ddl::drop_table("note").if_exists().to_sql(Dialect::MySql), "DROP TABLE IF EXISTS note");
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_alter_statements() {
+        use crate::{ColumnDef, ORMTrait, TableDeserialize};
+        use crate::sqlite::ORM;
+
+        struct Account;
+
+        impl TableDeserialize for Account {
+            fn same_name() -> String {
+                "account".to_string()
+            }
+
+            fn columns() -> Vec<ColumnDef> {
+                vec![
+                    ColumnDef::new("id", ColumnType::Integer),
+                    ColumnDef::new("full_name", ColumnType::Text),
+                    ColumnDef::new("user_id", ColumnType::Integer).references(ForeignKey::new("user", "id")),
+                    ColumnDef::new("score", ColumnType::Real).nullable(true),
+                ]
+            }
+        }
+
+        let orm = ORM::connect(":memory:".to_string()).unwrap();
+        let alter = orm.alter_table::<Account>().rename_column("name", "full_name").add_column_with_default("user_id", "0")
+            .drop_column("legacy").modify_column("score");
+        assert_eq!(alter.alter_statements(Dialect::MySql).unwrap(), vec![
+            "ALTER TABLE account RENAME COLUMN name TO full_name, ADD COLUMN user_id INT NOT NULL DEFAULT 0, \
+            ADD FOREIGN KEY (user_id) REFERENCES user(id), DROP COLUMN legacy, MODIFY COLUMN score DOUBLE".to_string()]);
+        assert!(alter.needs_rebuild());
+        assert_eq!(alter.rebuild_statements(&["id".to_string(), "name".to_string(), "legacy".to_string(), "score".to_string()], &[]).unwrap(), vec![
+            "CREATE TABLE parvati_new_account (id INTEGER PRIMARY KEY AUTOINCREMENT, full_name TEXT NOT NULL, user_id INTEGER NOT NULL, \
+            score REAL, FOREIGN KEY (user_id) REFERENCES user(id))",
+            "INSERT INTO parvati_new_account (id, full_name, user_id, score) SELECT id, name, 0, score FROM account",
+            "DROP TABLE account",
+            "ALTER TABLE parvati_new_account RENAME TO account",
+        ]);
+        let rename = orm.alter_table::<Account>().rename_column("name", "full_name");
+        assert!(!rename.needs_rebuild());
+        assert_eq!(rename.alter_statements(Dialect::Sqlite).unwrap(), vec!["ALTER TABLE account RENAME COLUMN name TO full_name"]);
+        assert!(orm.alter_table::<Account>().modify_column("name").alter_statements(Dialect::MySql).is_err());
+    }
+}
@@ -11,7 +11,7 @@ use serde::de::{
     self, Deserialize, DeserializeSeed, EnumAccess, IntoDeserializer,
     MapAccess, SeqAccess, VariantAccess, Visitor,
 };
-use std::ops::{AddAssign, MulAssign};
+use std::str::FromStr;
 
 pub struct Deserializer<'de> {
     // This string starts with the input data and characters are truncated off
@@ -77,68 +77,55 @@ impl<'de> Deserializer<'de> {
         }
     }
 
-    // Parse a group of decimal digits as an unsigned integer of type T.
+    // Parse a quoted group of decimal digits as an unsigned integer of type T.
     //
-    // This implementation is a bit too lenient, for example `001` is not
-    // allowed in JSON. Also the various arithmetic operations can overflow and
-    // panic or return bogus data. But it is good enough for example code!
+    // The digits are parsed with `FromStr`, so values that do not fit into T
+    // (e.g. an id over 2^31 read into an i32) are rejected instead of wrapping.
     fn parse_unsigned<T>(&mut self) -> Result<T>
         where
-            T: AddAssign<T> + MulAssign<T> + From<u8>,
+            T: FromStr,
     {
-            if self.next_char()? != '"' {
-                return Err(Error::ExpectedString);
-            }
-            match self.input.find('"') {
-                Some(len) => {
-                    let s = &self.input[..len];
-                    self.input = &self.input[len + 1..];
-                    // let mut int = T::from(s[0] as u8 - b'0');
-                    let mut int = T::from(0);
-                    for ch in s[0..].chars() {
-                        int *= T::from(10);
-                        int += T::from(ch as u8 - b'0');
-                    }
-                    Ok(int)
-                }
-                None => Err(Error::Eof),
-            }
+        let s = self.parse_number()?;
+        if s.is_empty() || !s.chars().all(|ch| ch.is_ascii_digit()) {
+            return Err(Error::ExpectedInteger);
+        }
+        s.parse().map_err(|_| Error::ExpectedInteger)
     }
 
-    // Parse a possible minus sign followed by a group of decimal digits as a
-    // signed integer of type T.
-
-
+    // Parse a quoted, possibly negative group of decimal digits as a signed
+    // integer of type T.
     fn parse_signed<T>(&mut self) -> Result<T>
         where
-            T: AddAssign<T> + MulAssign<T> + From<i8>,
+            T: FromStr,
+    {
+        let s = self.parse_number()?;
+        let digits = s.strip_prefix('-').unwrap_or(s);
+        if digits.is_empty() || !digits.chars().all(|ch| ch.is_ascii_digit()) {
+            return Err(Error::ExpectedInteger);
+        }
+        s.parse().map_err(|_| Error::ExpectedInteger)
+    }
+
+    // Parse a quoted decimal number, e.g. `"-1.5"` or `"2.5e-3"`, as a float
+    // of type T. Float parsing is stupidly hard, so it is left to `FromStr`.
+    fn parse_float<T>(&mut self) -> Result<T>
+        where
+            T: FromStr,
     {
+        let s = self.parse_number()?;
+        s.parse().map_err(|_| Error::ExpectedFloat)
+    }
+
+    // Return the content of a quoted number and consume it.
+    fn parse_number(&mut self) -> Result<&'de str> {
         if self.next_char()? != '"' {
             return Err(Error::ExpectedString);
         }
         match self.input.find('"') {
             Some(len) => {
-                let s_src = &self.input[..len];
-                let s = if s_src.starts_with("-") {
-                    &s_src[1..]
-                } else {
-                    s_src
-                };
-                let sign = if s_src.starts_with("-") {
-                    -1
-                } else {
-                    1
-                };
+                let s = &self.input[..len];
                 self.input = &self.input[len + 1..];
-                let mut int = T::from(0);
-                for ch in s[0..].chars() {
-                    int *= T::from(10);
-                    let rrr = ch as u8 - b'0';
-                    int += T::from(rrr as i8);
-                }
-
-                int *= T::from(sign);
-                Ok(int)
+                Ok(s)
             }
             None => Err(Error::Eof),
         }
@@ -262,6 +249,13 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_i64(self.parse_signed()?)
     }
 
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: Visitor<'de>,
+    {
+        visitor.visit_i128(self.parse_signed()?)
+    }
+
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
         where
             V: Visitor<'de>,
@@ -290,24 +284,27 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_u64(self.parse_unsigned()?)
     }
 
-    // Float parsing is stupidly hard.
-    fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
         where
             V: Visitor<'de>,
     {
-        unimplemented!()
+        visitor.visit_u128(self.parse_unsigned()?)
     }
 
-    // Float parsing is stupidly hard.
-    fn deserialize_f64<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
         where
             V: Visitor<'de>,
     {
-        unimplemented!()
+        visitor.visit_f32(self.parse_float()?)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: Visitor<'de>,
+    {
+        visitor.visit_f64(self.parse_float()?)
     }
 
-    // The `Serializer` implementation on the previous page serialized chars as
-    // single-character strings so handle that representation here.
     fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value>
         where
             V: Visitor<'de>,
@@ -778,6 +775,50 @@ mod tests {
         assert_eq!(expected, from_str(j).unwrap());
     }
 
+    #[test]
+    fn test_big_integers() {
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Test {
+            id: i64,
+            ud: u64,
+            big: i128,
+            small: i32,
+        }
+
+        let j = r#"{"id":"-9223372036854775808","ud":"18446744073709551615","big":"-170141183460469231731687303715884105728","small":"2147483647"}"#;
+        let expected = Test {
+            id: i64::MIN,
+            ud: u64::MAX,
+            big: i128::MIN,
+            small: i32::MAX,
+        };
+        assert_eq!(expected, from_str(j).unwrap());
+
+        let j = r#"{"id":"1","ud":"1","big":"1","small":"2147483648"}"#;
+        assert!(from_str::<Test>(j).is_err());
+        let j = r#"{"id":"1","ud":"-1","big":"1","small":"1"}"#;
+        assert!(from_str::<Test>(j).is_err());
+    }
+
+    #[test]
+    fn test_optional_numbers() {
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Test {
+            count: Option<i32>,
+            value: Option<f64>,
+            ratio: f32,
+        }
+
+        let j = r#"{"count":null,"value":"-1.5e2","ratio":"0.25"}"#;
+        assert_eq!(Test { count: None, value: Some(-150.0), ratio: 0.25 }, from_str(j).unwrap());
+        let j = r#"{"count":"7","value":null,"ratio":"1"}"#;
+        assert_eq!(Test { count: Some(7), value: None, ratio: 1.0 }, from_str(j).unwrap());
+        let j = r#"{"count":"7","value":"abc","ratio":"1"}"#;
+        assert!(from_str::<Test>(j).is_err());
+    }
+
     // #[test]
     fn test_more() {
         let str = "{\"id\":\"15\",\"path\":\"C:\\$SysReset\\Logs\\diagwrn.xml\",\"internal\":null,\"mime_type\":\"application/xml\",\"disk\":\"C\",\"size\":\"47278\",\"modified\":\"1679648060\",\"content\":\"<xml xmlns:s=\\\"uuid:BDC6E3F0-6DA3-11d1-A2A3-00AA00C14882\\\"
@@ -0,0 +1,266 @@
+//! `dual_write` is a module that applies the same entity changes to two ORMs,
+//! e.g. a local SQLite cache and a remote MySQL database of an offline-first application.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::cassette::{escape, format_params, parse_params, unescape};
+use crate::{CountedStatement, IsolationLevel, ORMError, ORMTrait, QueryBuilder, Statement, TableDeserialize, TableSerialize, Value};
+
+/// The table of the journal, on the primary ORM.
+pub(crate) const TABLE: &str = "parvati_journal";
+
+/// `JournalEntry` describes changes that were committed to the primary ORM but not to the secondary one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    /// `id` orders the entries in the order they were journaled.
+    pub id: u64,
+
+    /// `queries` are the statements that still have to be executed on the secondary ORM, with their bound params.
+    pub queries: Vec<Statement>,
+
+    /// `error` is the error of the secondary ORM, or empty if the process stopped before the secondary transaction ended.
+    pub error: String,
+}
+
+/// `Journal` reads the changes that have to be reconciled with the secondary ORM of a `DualWrite`.
+/// The entries are stored in the table `parvati_journal` of the primary ORM, which is created on the first write.
+pub struct Journal<'a, P> {
+    primary: &'a P,
+}
+
+impl<'a, P: ORMTrait<P> + Sync> Journal<'a, P> {
+    /// Constructs the `Journal` of the `DualWrite`s with the primary ORM `primary`.
+    pub fn new(primary: &'a P) -> Self {
+        Journal { primary }
+    }
+
+    /// Returns the journaled entries in the order they were recorded.
+    pub async fn entries(&self) -> Result<Vec<JournalEntry>, ORMError> {
+        self.create_table().await?;
+        let rows = self.primary.query_map(&format!("select id, statements, error from {} order by id", TABLE)).await?;
+        rows.iter()
+            .map(|row| {
+                let id = row.get("id").and_then(Value::as_i64).unwrap_or_default() as u64;
+                let queries = row.get("statements").and_then(Value::as_str).and_then(parse_statements)
+                    .ok_or_else(|| ORMError::InvalidValue(format!("invalid statements of journal entry {}", id)))?;
+                Ok(JournalEntry {
+                    id,
+                    queries,
+                    error: row.get("error").and_then(Value::as_str).unwrap_or_default().to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the number of journaled entries.
+    pub async fn len(&self) -> Result<usize, ORMError> {
+        self.create_table().await?;
+        let rows = self.primary.query_map(&format!("select count(*) as entries from {}", TABLE)).await?;
+        Ok(rows.first().and_then(|row| row.get("entries")).and_then(Value::as_i64).unwrap_or_default() as usize)
+    }
+
+    /// Returns `true` if there is nothing to reconcile.
+    pub async fn is_empty(&self) -> Result<bool, ORMError> {
+        Ok(self.len().await? == 0)
+    }
+
+    /// Replays the journaled entries on `secondary` in order, each one in its own transaction.
+    /// Applied entries are removed from the journal. Replaying stops at the first entry that fails;
+    /// it stays in the journal together with the following ones, with the new error, and the error is returned.
+    /// Returns the number of applied entries.
+    pub async fn reconcile<S: ORMTrait<S> + Sync>(&self, secondary: &S) -> Result<usize, ORMError> {
+        let mut applied = 0;
+        for entry in self.entries().await? {
+            let queries: Vec<_> = entry.queries.into_iter().map(|(query, params)| secondary.query_update(&query).bind(params)).collect();
+            if let Err(e) = secondary.exec_all(&queries).await {
+                self.primary.exec_all(&[self.set_error(entry.id as i64, &e)]).await?;
+                return Err(e);
+            }
+            self.primary.exec_all(&[self.remove(entry.id as i64)]).await?;
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    // Returns the insert of the entry `id` with the statements `queries`.
+    fn insert(&self, id: i64, queries: &[Statement]) -> QueryBuilder<'a, usize, (), P> {
+        self.primary.query_update(&format!("insert into {} (id, statements, error) values (?, ?, ?)", TABLE))
+            .bind(vec![Value::Int(id), Value::Text(format_statements(queries)), Value::Text(String::new())])
+    }
+
+    // Returns the update of the error of the entry `id`.
+    fn set_error(&self, id: i64, error: &ORMError) -> QueryBuilder<'a, usize, (), P> {
+        self.primary.query_update(&format!("update {} set error = ? where id = ?", TABLE))
+            .bind(vec![Value::Text(format!("{:?}", error)), Value::Int(id)])
+    }
+
+    // Returns the removal of the entry `id`.
+    fn remove(&self, id: i64) -> QueryBuilder<'a, usize, (), P> {
+        self.primary.query_update(&format!("delete from {} where id = ?", TABLE)).bind(vec![Value::Int(id)])
+    }
+
+    // Creates the table of the journal if it does not exist.
+    async fn create_table(&self) -> Result<(), ORMError> {
+        let query = self.primary.query_update(&format!("create table if not exists {} (id BIGINT NOT NULL PRIMARY KEY, \
+            statements TEXT NOT NULL, error TEXT NOT NULL)", TABLE));
+        self.primary.exec_all(&[query]).await?;
+        Ok(())
+    }
+}
+
+/// `DualWrite` is a struct that collects entity changes and applies them to a primary and a secondary ORM.
+///
+/// Both ORMs run the changes inside a transaction. The secondary statements are written to the `Journal` of the primary
+/// ORM in the primary transaction and removed once the secondary transaction is committed. If anything fails before
+/// the primary transaction is committed, both transactions are rolled back. If the secondary transaction cannot be
+/// started or committed, the primary changes are committed and the entry stays in the journal, to be replayed later
+/// with `Journal::reconcile`; so does it if the process stops in between. If the entry cannot be removed after the
+/// secondary commit, it is replayed again.
+pub struct DualWrite<'a, P, S> {
+    primary: &'a P,
+    secondary: &'a S,
+    primary_queries: Vec<CountedStatement>,
+    secondary_queries: Vec<Statement>,
+    error: Option<String>,
+}
+
+impl<'a, P: ORMTrait<P> + Sync, S: ORMTrait<S> + Sync> DualWrite<'a, P, S> {
+    /// Constructs a `DualWrite` that mirrors the changes of `primary` to `secondary`.
+    pub fn new(primary: &'a P, secondary: &'a S) -> Self {
+        DualWrite {
+            primary,
+            secondary,
+            primary_queries: Vec::new(),
+            secondary_queries: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn push<R1, E1, R2, E2>(&mut self, primary: QueryBuilder<'_, R1, E1, P>, secondary: QueryBuilder<'_, R2, E2, S>) {
+        if self.error.is_none() {
+            self.error = primary.error.or(secondary.error);
+        }
+        self.primary_queries.push((primary.query, primary.params, primary.counters));
+        // the journal replays plain statements, so the counter cache updates precede the statement they belong to
+        self.secondary_queries.extend(secondary.counters);
+        self.secondary_queries.push((secondary.query, secondary.params));
+    }
+
+    /// Adds an insert of `data` (see `ORMTrait::add`) to both ORMs.
+    pub fn insert<T>(mut self, data: T) -> Self
+        where T: for<'b> Deserialize<'b> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + 'static
+    {
+        let (primary, secondary) = (self.primary.add(data.clone()), self.secondary.add(data));
+        self.push(primary, secondary);
+        self
+    }
+
+    /// Adds an update of `data` to both ORMs.
+    pub fn modify<T>(mut self, data: T) -> Self
+        where T: TableDeserialize + TableSerialize + Serialize + Clone + 'static
+    {
+        let (primary, secondary) = (self.primary.modify(data.clone()), self.secondary.modify(data));
+        self.push(primary, secondary);
+        self
+    }
+
+    /// Adds a removal of `data` to both ORMs.
+    pub fn remove<T>(mut self, data: T) -> Self
+        where T: TableDeserialize + TableSerialize + Serialize + Clone + 'static
+    {
+        let (primary, secondary) = (self.primary.remove(data.clone()), self.secondary.remove(data));
+        self.push(primary, secondary);
+        self
+    }
+
+    /// Applies the collected changes to both ORMs and returns the number of rows affected on the primary ORM.
+    /// Returns `ORMError::InvalidValue` without touching either ORM if one of the changes cannot be rendered.
+    pub async fn apply(&self) -> Result<usize, ORMError> {
+        if let Some(e) = &self.error {
+            return Err(ORMError::InvalidValue(e.clone()));
+        }
+        let primary_queries: Vec<_> = self.primary_queries.iter()
+            .map(|(query, params, counters)| {
+                let mut qb = self.primary.query_update(query).bind(params.clone());
+                qb.counters = counters.clone();
+                qb
+            })
+            .collect();
+        let secondary_queries: Vec<_> = self.secondary_queries.iter()
+            .map(|(query, params)| self.secondary.query_update(query).bind(params.clone()))
+            .collect();
+        let journal = Journal::new(self.primary);
+        // the table is created outside the transaction, MySQL commits it implicitly before a CREATE TABLE
+        journal.create_table().await?;
+        let id = next_id();
+
+        self.primary.begin(IsolationLevel::Default).await?;
+        let updated_rows = match self.primary.exec_all(&primary_queries).await {
+            Ok(updated_rows) => updated_rows,
+            Err(e) => {
+                let _ = self.primary.rollback().await;
+                return Err(e);
+            }
+        };
+        // the entry is committed together with the primary changes and removed once the secondary ORM committed them
+        if let Err(e) = self.primary.exec_all(&[journal.insert(id, &self.secondary_queries)]).await {
+            let _ = self.primary.rollback().await;
+            return Err(e);
+        }
+        if let Err(e) = self.secondary.begin(IsolationLevel::Default).await {
+            log::warn!("secondary transaction failed to start, the changes are journaled: {:?}", e);
+            if let Err(e) = self.primary.exec_all(&[journal.set_error(id, &e)]).await {
+                let _ = self.primary.rollback().await;
+                return Err(e);
+            }
+            self.primary.commit().await?;
+            return Ok(updated_rows);
+        }
+        if let Err(e) = self.secondary.exec_all(&secondary_queries).await {
+            let _ = self.secondary.rollback().await;
+            let _ = self.primary.rollback().await;
+            return Err(e);
+        }
+        if let Err(e) = self.primary.commit().await {
+            let _ = self.secondary.rollback().await;
+            return Err(e);
+        }
+        let journaled = match self.secondary.commit().await {
+            Ok(()) => self.primary.exec_all(&[journal.remove(id)]).await,
+            Err(e) => {
+                log::warn!("secondary commit failed, the changes are journaled: {:?}", e);
+                self.primary.exec_all(&[journal.set_error(id, &e)]).await
+            }
+        };
+        if let Err(e) = journaled {
+            log::warn!("journal entry {} could not be updated: {:?}", id, e);
+        }
+        Ok(updated_rows)
+    }
+}
+
+// Returns a new ID of a journal entry: the current time in nanoseconds since the Unix epoch,
+// increased if needed so that the IDs of this process are unique and ordered.
+fn next_id() -> i64 {
+    static LAST: AtomicI64 = AtomicI64::new(0);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_nanos() as i64).unwrap_or_default();
+    let last = LAST.fetch_update(Ordering::AcqRel, Ordering::Acquire, |last| Some(now.max(last + 1))).unwrap_or_default();
+    now.max(last + 1)
+}
+
+// Encodes the statements of an entry, one line per statement with its params, see `parse_statements`.
+fn format_statements(queries: &[Statement]) -> String {
+    queries.iter().map(|(query, params)| format!("{}\t{}", escape(query), format_params(params))).collect::<Vec<_>>().join("\n")
+}
+
+// Decodes statements encoded with `format_statements`.
+fn parse_statements(statements: &str) -> Option<Vec<Statement>> {
+    statements.lines()
+        .map(|line| {
+            let (query, params) = line.split_once('\t')?;
+            Some((unescape(query), parse_params(params)?))
+        })
+        .collect()
+}
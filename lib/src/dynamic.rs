@@ -0,0 +1,138 @@
+//! `dynamic` is a module for records of tables whose schema is only known at runtime, e.g. tables defined by plugins.
+//! A `DynamicEntity` is a table name and a map of column values that is written and read with the `*_dynamic`
+//! methods of `ORMTrait` instead of a derived struct.
+
+use std::collections::BTreeMap;
+use crate::{is_table_name, ORMError, Statement, Value};
+
+/// `DynamicEntity` is a record of the table `table` with its column values.
+/// The column `id` is the auto-incremented primary key, like for derived entities.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicEntity {
+    table: String,
+    values: BTreeMap<String, Value>,
+}
+
+impl DynamicEntity {
+    /// Constructs an empty record of `table`.
+    pub fn new(table: &str) -> Self {
+        DynamicEntity { table: table.to_string(), values: BTreeMap::new() }
+    }
+
+    /// Sets the value of `column` and returns the record, e.g. `DynamicEntity::new("user").with("name", "John")`.
+    pub fn with(mut self, column: &str, value: impl Into<Value>) -> Self {
+        self.set(column, value);
+        self
+    }
+
+    /// Sets the value of `column`.
+    pub fn set(&mut self, column: &str, value: impl Into<Value>) {
+        self.values.insert(column.to_string(), value.into());
+    }
+
+    /// Returns the value of `column`, if it is set.
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        self.values.get(column)
+    }
+
+    /// Removes the value of `column` and returns it.
+    pub fn remove(&mut self, column: &str) -> Option<Value> {
+        self.values.remove(column)
+    }
+
+    /// Returns the name of the table.
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// Returns the column values ordered by column name.
+    pub fn values(&self) -> &BTreeMap<String, Value> {
+        &self.values
+    }
+
+    /// Returns the ID of the record, if it has a positive integer `id`.
+    pub fn id(&self) -> Option<u64> {
+        self.get("id").and_then(Value::as_i64).filter(|id| *id > 0).map(|id| id as u64)
+    }
+}
+
+#[cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+impl DynamicEntity {
+    pub(crate) fn from_map(table: &str, values: impl IntoIterator<Item = (String, Value)>) -> Self {
+        DynamicEntity { table: table.to_string(), values: values.into_iter().collect() }
+    }
+
+    // Returns the INSERT statement of the values other than `id` with a `?` placeholder per value and its params.
+    pub(crate) fn insert_statement(&self) -> Result<Statement, ORMError> {
+        let values = self.values_without_id()?;
+        if values.is_empty() {
+            return Err(ORMError::InvalidValue(format!("{} record has no values to insert", self.table)));
+        }
+        let columns: Vec<&str> = values.iter().map(|(column, _)| column.as_str()).collect();
+        let placeholders = vec!["?"; values.len()];
+        let query = format!("insert into {} ({}) values ({})", checked_table(&self.table)?, columns.join(", "), placeholders.join(", "));
+        Ok((query, values.into_iter().map(|(_, value)| value.clone()).collect()))
+    }
+
+    // Returns the UPDATE statement of the values other than `id` for the record with the ID of the entity and its params.
+    pub(crate) fn update_statement(&self) -> Result<Statement, ORMError> {
+        let id = self.required_id()?;
+        let values = self.values_without_id()?;
+        if values.is_empty() {
+            return Err(ORMError::InvalidValue(format!("{} record has no values to modify", self.table)));
+        }
+        let assignments: Vec<String> = values.iter().map(|(column, _)| format!("{} = ?", column)).collect();
+        let query = format!("update {} set {} where id = ?", checked_table(&self.table)?, assignments.join(", "));
+        let mut params: Vec<Value> = values.into_iter().map(|(_, value)| value.clone()).collect();
+        params.push(Value::Int(id as i64));
+        Ok((query, params))
+    }
+
+    // Returns the DELETE statement for the record with the ID of the entity and its params.
+    pub(crate) fn delete_statement(&self) -> Result<Statement, ORMError> {
+        let id = self.required_id()?;
+        Ok((format!("delete from {} where id = ?", checked_table(&self.table)?), vec![Value::Int(id as i64)]))
+    }
+
+    // Returns the values other than `id`, or an error if a column is not a plain column name.
+    fn values_without_id(&self) -> Result<Vec<(&String, &Value)>, ORMError> {
+        let values: Vec<(&String, &Value)> = self.values.iter().filter(|(column, _)| *column != "id").collect();
+        match values.iter().find(|(column, _)| !is_table_name(column)) {
+            Some((column, _)) => Err(ORMError::InvalidValue(format!("invalid column name {:?} of {}", column, self.table))),
+            None => Ok(values),
+        }
+    }
+
+    fn required_id(&self) -> Result<u64, ORMError> {
+        self.id().ok_or_else(|| ORMError::InvalidValue(format!("{} record has no id", self.table)))
+    }
+}
+
+/// Returns `table`, or `ORMError::InvalidValue` if it is not a plain table name, e.g. a name read from plugin input
+/// that would inject SQL.
+pub(crate) fn checked_table(table: &str) -> Result<&str, ORMError> {
+    if is_table_name(table) { Ok(table) } else { Err(ORMError::InvalidValue(format!("invalid table name {:?}", table))) }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Value;
+    use super::DynamicEntity;
+
+    #[test]
+    fn test_statements() {
+        let mut entity = DynamicEntity::new("plugin_note").with("title", "it's \"new\"").with("score", 1.5).with("tag", Value::Null);
+        let title = Value::Text("it's \"new\"".to_string());
+        assert_eq!(entity.insert_statement().unwrap(), ("insert into plugin_note (score, tag, title) values (?, ?, ?)".to_string(),
+                                                        vec![Value::Float(1.5), Value::Null, title.clone()]));
+        assert!(entity.update_statement().is_err());
+        entity.set("id", 7);
+        assert_eq!(entity.id(), Some(7));
+        assert_eq!(entity.update_statement().unwrap(), ("update plugin_note set score = ?, tag = ?, title = ? where id = ?".to_string(),
+                                                        vec![Value::Float(1.5), Value::Null, title, Value::Int(7)]));
+        assert_eq!(entity.delete_statement().unwrap(), ("delete from plugin_note where id = ?".to_string(), vec![Value::Int(7)]));
+        assert!(DynamicEntity::new("plugin_note").insert_statement().is_err());
+        assert!(DynamicEntity::new("note; drop table user").with("title", "x").insert_statement().is_err());
+        assert!(DynamicEntity::new("plugin_note").with("title = 'x' --", "x").insert_statement().is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! `entity_cache` is a module for the second-level cache of entities registered with `ConnectOptions::entity_cache`,
+//! e.g. a `RedisCache` shared by all instances of a service. `find_one` of an entity with a `cache_ttl` reads its row
+//! from the cache and stores the row it read from the database for the TTL:
+//!
+//! ```ignore
+//! #[derive(TableDeserialize, TableSerialize, Serialize, Deserialize, Debug, Clone)]
+//! #[table(name = "user", cache_ttl = "60s")]
+//! pub struct User { pub id: i32, pub name: Option<String> }
+//!
+//! let options = ConnectOptions::new(url).entity_cache(Arc::new(RedisCache::open("redis://127.0.0.1/")?));
+//! ```
+//!
+//! The entries are keyed by the table, its generation and the ID. Every successful write of a table changes the
+//! generation of the table, so its entries are missed afterwards and expire with their TTL; inside a transaction the
+//! generation changes on commit. Lookups inside a transaction bypass the cache.
+#![cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+
+use std::fmt::{self, Debug};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::cassette::{escape, unescape};
+use crate::{ORMError, Row};
+
+/// `EntityCache` is a trait for the second-level cache of entities, see the module documentation.
+/// A cache that cannot be reached should log the error and miss, so that the queries keep working.
+pub trait EntityCache: Send + Sync {
+    /// Returns the generation of `table`, which is part of the keys of its entries.
+    fn generation(&self, table: &str) -> u64;
+
+    /// Returns the value of `key`, or `None` if it is missing or expired.
+    fn get(&self, key: &str) -> Option<String>;
+
+    /// Stores `value` under `key` for `ttl`.
+    fn set(&self, key: &str, value: &str, ttl: Duration);
+
+    /// Changes the generation of `table`, so that all of its entries are missed.
+    fn invalidate(&self, table: &str);
+}
+
+impl Debug for dyn EntityCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EntityCache")
+    }
+}
+
+/// `CachedLookup` is a lookup by ID of an entity whose rows are cached for `ttl`.
+pub(crate) struct CachedLookup<'a> {
+    pub(crate) cache: &'a Arc<dyn EntityCache>,
+    pub(crate) ttl: Duration,
+    pub(crate) table: &'a str,
+    pub(crate) id: u64,
+}
+
+/// Returns the ID looked up by `query` if it is the query of `find_one` on `table`, e.g. `select * from user where id = 5`.
+pub(crate) fn looked_up_id(query: &str, table: &str) -> Option<u64> {
+    query.strip_prefix("select * from ")?
+        .strip_prefix(table)?
+        .strip_prefix(" where id = ")?
+        .parse()
+        .ok()
+}
+
+/// Returns the rows of the lookup from the cache, or executes `read` and caches the row it returned.
+/// The generation is taken before `read`, so a row read while the table is written is cached under the old generation.
+pub(crate) async fn read_through(lookup: Option<CachedLookup<'_>>, read: impl Future<Output = Result<Vec<Row>, ORMError>>) -> Result<Vec<Row>, ORMError> {
+    let Some(lookup) = lookup else {
+        return read.await;
+    };
+    let table = lookup.table.to_lowercase();
+    let key = format!("parvati:{}:{}:{}", table, lookup.cache.generation(&table), lookup.id);
+    if let Some(row) = lookup.cache.get(&key).and_then(|value| decode_row(&value)) {
+        return Ok(vec![row]);
+    }
+    let rows = read.await?;
+    if let Some(row) = rows.first() {
+        lookup.cache.set(&key, &encode_row(row), lookup.ttl);
+    }
+    Ok(rows)
+}
+
+// Encodes the columns of `row` as lines of the column name and the value, separated by a tab.
+fn encode_row(row: &Row) -> String {
+    row.names.iter().enumerate()
+        .map(|(i, name)| match row.columns.get(&(i as i32)) {
+            Some(Some(value)) => format!("{}\t{}", escape(name), escape(value)),
+            _ => format!("{}\t\\N", escape(name)),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Decodes a row encoded with `encode_row`, or returns `None` if the value is malformed.
+fn decode_row(value: &str) -> Option<Row> {
+    let columns = value.lines()
+        .map(|line| line.split_once('\t').map(|(name, value)| (unescape(name), (value != "\\N").then(|| unescape(value)))))
+        .collect::<Option<Vec<(String, Option<String>)>>>()?;
+    let mut row = Row::with_names(Arc::new(columns.iter().map(|(name, _)| name.clone()).collect()));
+    for (i, (_, value)) in columns.into_iter().enumerate() {
+        row.set(i as i32, value);
+    }
+    Some(row)
+}
+
+/// `RedisCache` is an `EntityCache` stored in Redis, so that it is shared by all instances of a service.
+/// The generation of a table is the counter `parvati:<table>:generation`.
+#[cfg(feature = "redis-cache")]
+pub struct RedisCache {
+    client: redis::Client,
+    conn: std::sync::Mutex<Option<redis::Connection>>,
+}
+
+#[cfg(feature = "redis-cache")]
+impl RedisCache {
+    /// Opens the Redis server at `url`, e.g. `redis://127.0.0.1/`. The connection is established on first use
+    /// and again after an error.
+    pub fn open(url: &str) -> Result<RedisCache, ORMError> {
+        let client = redis::Client::open(url).map_err(|e| ORMError::Config(format!("invalid Redis URL: {}", e)))?;
+        Ok(RedisCache { client, conn: std::sync::Mutex::new(None) })
+    }
+
+    // Executes `command`, or logs the error and returns `None`.
+    fn query<T: redis::FromRedisValue>(&self, command: &redis::Cmd) -> Option<T> {
+        let mut conn = self.conn.lock().unwrap();
+        if conn.is_none() {
+            *conn = self.client.get_connection()
+                .map_err(|e| log::warn!("entity cache: cannot connect to Redis: {}", e))
+                .ok();
+        }
+        match command.query(conn.as_mut()?) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::warn!("entity cache: {}", e);
+                *conn = None;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(feature = "redis-cache")]
+impl EntityCache for RedisCache {
+    fn generation(&self, table: &str) -> u64 {
+        self.query::<Option<u64>>(redis::cmd("GET").arg(format!("parvati:{}:generation", table))).flatten().unwrap_or(0)
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.query::<Option<String>>(redis::cmd("GET").arg(key)).flatten()
+    }
+
+    fn set(&self, key: &str, value: &str, ttl: Duration) {
+        let _ = self.query::<()>(redis::cmd("SET").arg(key).arg(value).arg("PX").arg(ttl.as_millis().max(1) as u64));
+    }
+
+    fn invalidate(&self, table: &str) {
+        if self.query::<u64>(redis::cmd("INCR").arg(format!("parvati:{}:generation", table))).is_none() {
+            log::error!("entity cache: the entries of {} could not be invalidated", table);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::{decode_row, encode_row, looked_up_id};
+    use crate::Row;
+
+    #[test]
+    fn test_encode_row() {
+        let mut row = Row::with_names(Arc::new(vec!["id".to_string(), "name".to_string(), "note".to_string()]));
+        row.set(0, Some("5".to_string()));
+        row.set(2, Some("a\tb\\N".to_string()));
+        let decoded = decode_row(&encode_row(&row)).unwrap();
+        assert_eq!(decoded.get::<i32>(0), Some(5));
+        assert_eq!(decoded.get::<String>(1), None);
+        assert_eq!(decoded.get::<String>(2), Some("a\tb\\N".to_string()));
+
+        assert_eq!(looked_up_id("select * from user where id = 5", "user"), Some(5));
+        assert_eq!(looked_up_id("select * from user where id = 5 limit 1", "user"), None);
+        assert_eq!(looked_up_id("select * from users where id = 5", "user"), None);
+    }
+}
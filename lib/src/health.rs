@@ -0,0 +1,102 @@
+//! `health` is a module with the report of `ORMTrait::health`, which backs the liveness and readiness probes
+//! of a service, e.g. a `/healthz` endpoint.
+#![cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+
+use std::time::{Duration, Instant};
+use crate::statement_log::json_string;
+use crate::{ORMTrait, PoolStatus, Value};
+
+/// The statement executed to check the connection.
+const PROBE: &str = "select 1 as probe";
+
+/// The statements that read the version of the last migration applied by a `Migrator`
+/// and the number of the last script applied by `ORMTrait::change`, in the order they are tried.
+const MIGRATION_VERSIONS: [&str; 2] = [
+    "select max(version) as last from parvati_migrations",
+    "select last from ormlib_last_change",
+];
+
+/// `HealthReport` describes the state of the database connection of an ORM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    /// `connected` is `true` if the probe statement succeeded.
+    pub connected: bool,
+
+    /// `latency` is the time spent executing the probe statement.
+    pub latency: Duration,
+
+    /// `error` is the error of the failed probe statement.
+    pub error: Option<String>,
+
+    /// `migration_version` is the version of the newest migration applied by a `Migrator`, or else
+    /// the number of the last script applied by `ORMTrait::change`, or `None` if no script was applied.
+    pub migration_version: Option<u64>,
+
+    /// `pool` is the state of the connections.
+    pub pool: PoolStatus,
+}
+
+impl HealthReport {
+    /// Returns `true` if the database is reachable.
+    pub fn is_healthy(&self) -> bool {
+        self.connected
+    }
+
+    /// Returns the report as a single line JSON object for the body of a probe response:
+    /// `{"status":"up","latency_us":...,"migration_version":...,"pool":{"size":...,"in_use":...,"waits":...},"error":...}`.
+    pub fn to_json(&self) -> String {
+        let version = self.migration_version.map(|v| v.to_string()).unwrap_or_else(|| "null".to_string());
+        let error = self.error.as_deref().map(json_string).unwrap_or_else(|| "null".to_string());
+        format!("{{\"status\":\"{}\",\"latency_us\":{},\"migration_version\":{},\"pool\":{{\"size\":{},\"in_use\":{},\"waits\":{}}},\"error\":{}}}",
+                if self.connected { "up" } else { "down" }, self.latency.as_micros(), version,
+                self.pool.size, self.pool.in_use, self.pool.waits, error)
+    }
+}
+
+/// Probes the connection of `orm` and collects its state.
+pub(crate) async fn check<O: ORMTrait<O> + Sync>(orm: &O) -> HealthReport {
+    let start = Instant::now();
+    let probe = orm.query_map(PROBE).await;
+    let latency = start.elapsed();
+    let mut migration_version = None;
+    if probe.is_ok() {
+        for statement in MIGRATION_VERSIONS {
+            let last = match orm.query_map(statement).await {
+                Ok(rows) => rows.first().and_then(|row| row.get("last")).and_then(Value::as_i64),
+                Err(_) => None,
+            };
+            if let Some(last) = last.filter(|last| *last > 0) {
+                migration_version = Some(last as u64);
+                break;
+            }
+        }
+    }
+    HealthReport {
+        connected: probe.is_ok(),
+        latency,
+        error: probe.err().map(|e| format!("{:?}", e)),
+        migration_version,
+        pool: orm.pool_status(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::PoolStatus;
+    use super::HealthReport;
+
+    #[test]
+    fn test_to_json() {
+        let report = HealthReport {
+            connected: false,
+            latency: Duration::from_micros(1200),
+            error: Some("NoConnection".to_string()),
+            migration_version: None,
+            pool: PoolStatus { size: 1, idle: 1, ..Default::default() },
+        };
+        assert!(!report.is_healthy());
+        assert_eq!(report.to_json(), "{\"status\":\"down\",\"latency_us\":1200,\"migration_version\":null,\
+            \"pool\":{\"size\":1,\"in_use\":0,\"waits\":0},\"error\":\"NoConnection\"}");
+    }
+}
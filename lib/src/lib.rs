@@ -87,13 +87,87 @@
 #[cfg(any(feature = "sqlite", feature = "mysql"))]
 mod serializer_error;
 #[cfg(any(feature = "sqlite", feature = "mysql"))]
-mod serializer_types;
+mod deserializer_key_values;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+mod datetime;
 #[cfg(any(feature = "sqlite", feature = "mysql"))]
-mod serializer_values;
+mod serializer_fields;
+
+mod schema;
+pub use schema::{enum_variants, ColumnDef, ColumnType, ForeignKey, ReferentialAction};
+pub use schema::Dialect;
 #[cfg(any(feature = "sqlite", feature = "mysql"))]
-mod serializer_key_values;
+pub mod ddl;
 #[cfg(any(feature = "sqlite", feature = "mysql"))]
-mod deserializer_key_values;
+pub use ddl::{AlterTable, Ddl, DdlStatement};
+pub mod metadata;
+mod dynamic;
+pub use dynamic::DynamicEntity;
+mod statement_log;
+pub use statement_log::{fingerprint, normalize_statement, JsonLinesLogger, StatementLogger, StatementRecord};
+mod stats;
+mod config;
+mod secret;
+pub use secret::{Secret, SecretProvider};
+pub use stats::{IndexUsage, QueryStats, RowCount, TableSize};
+mod n_plus_one;
+mod write_hooks;
+pub use write_hooks::{TableWrite, WriteHook, WriteKind};
+mod policy;
+pub use policy::{Policy, StatementKind};
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+mod entity_cache;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub use entity_cache::EntityCache;
+#[cfg(feature = "redis-cache")]
+pub use entity_cache::RedisCache;
+mod health;
+pub use health::HealthReport;
+mod scope;
+pub use scope::{col, Scope, ScopeColumn, Scoped};
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+mod repository;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub use repository::{Page, Repository};
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+mod manager;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub use manager::DbManager;
+mod loader;
+pub use loader::Loader;
+mod queue;
+pub use queue::{Job, Queue};
+mod cond;
+pub use cond::{Cond, CondField};
+mod migrator;
+pub use migrator::{DestructiveOperation, Migration, MigrationPlan, MigrationStatus, Migrator, PlannedMigration, PlannedStatement};
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub mod testing;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub mod coordination;
+mod validation;
+pub use validation::{FieldError, Validate, ValidationErrors};
+
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+mod dual_write;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub use dual_write::{DualWrite, Journal, JournalEntry};
+#[cfg(feature = "sqlite")]
+mod outbox;
+#[cfg(feature = "sqlite")]
+pub use outbox::{Delivery, Outbox, OutboxEntry};
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+mod cassette;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub use cassette::{assert_sql_snapshot, Cassette, CassetteMode, Interaction, Recorded};
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+mod any;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub use any::AnyORM;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+mod backfill;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub use backfill::{Backfill, BackfillProgress};
 
 // The following module is only compiled if the "sqlite" feature is enabled.
 // This module contains the implementation details for SQLite database operations.
@@ -109,8 +183,19 @@ use std::collections::HashMap;
 use anyhow::Result;
 
 use std::fmt::Debug;
+use std::ops::Range;
 use std::str::FromStr;
+use std::time::Duration;
 use async_trait::async_trait;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+use futures::channel::mpsc;
+use std::sync::Arc;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+use std::future::Future;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+use futures::{future, stream, FutureExt, SinkExt, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 
 use thiserror::Error;
@@ -146,6 +231,89 @@ pub enum ORMError {
     /// This variant represents an error that occurs when there is no connection.
     #[error("No connection")]
     NoConnection,
+
+    /// This variant represents a transaction that cannot be started, committed or rolled back,
+    /// e.g. `commit` without `begin` or an isolation level that the backend does not support.
+    #[error("Transaction error: {0}")]
+    TransactionError(String),
+
+    /// This variant represents an error that occurs when a value read from the database cannot be decoded.
+    /// It is returned when strict decoding is enabled with `ConnectOptions::strict_decode`.
+    #[error("Decode error: {0}")]
+    DecodeError(#[from] DecodeError),
+
+    /// This variant represents an entity value that cannot be written to the database,
+    /// e.g. a NaN float with `FloatPolicy::Error`.
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+
+    /// This variant represents a `MergeStrategy` that cannot be applied, e.g. `KeepNewest` with an unknown column.
+    #[error("Merge error: {0}")]
+    MergeError(String),
+
+    /// This variant represents a record that is expected to exist but is not found, e.g. by `refresh`.
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    /// This variant represents a statement that cannot be replayed from a `Cassette`, because it was not recorded
+    /// or differs from the recorded one, or the recorded error of a statement that failed while recording.
+    #[error("Replay error: {0}")]
+    Replay(String),
+
+    /// This variant represents an entity that failed its `Validate` check before it was written.
+    #[error("Validation error: {0}")]
+    Validation(ValidationErrors),
+
+    /// This variant represents connection settings that are missing or invalid, e.g. in `ConnectOptions::from_env`.
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    /// This variant represents a batched lookup of a `Loader` whose query failed; it contains the error of the query,
+    /// which is shared by every lookup of the batch.
+    #[error("Loader error: {0}")]
+    Loader(String),
+
+    /// This variant represents a migration directory that cannot be read or a migration that cannot be applied
+    /// or reverted, e.g. a script that fails or a missing down script.
+    #[error("Migration error: {0}")]
+    Migration(String),
+
+    /// This variant represents an advisory lock that is held by another connection, see `with_advisory_lock`;
+    /// it contains the name of the lock.
+    #[error("Lock not acquired: {0}")]
+    LockNotAcquired(String),
+
+    /// This variant represents a statement that is denied by the policy set with `set_policy`;
+    /// it contains the statement, which was not executed.
+    #[error("Statement denied by policy: {0}")]
+    PolicyViolation(String),
+}
+
+/// `DecodeError` is an enumeration of errors that can occur while decoding values read from the database.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// This variant represents a column index that does not exist in the row.
+    #[error("column {0} does not exist")]
+    NoSuchColumn(i32),
+
+    /// This variant represents a column value that cannot be parsed as the requested type.
+    #[error("column {index} value {value:?} cannot be decoded as {type_name}")]
+    InvalidValue {
+        index: i32,
+        value: String,
+        type_name: &'static str,
+    },
+
+    /// This variant represents a NULL value in a column that is decoded into a non-optional type.
+    #[error("column {0} is NULL")]
+    UnexpectedNull(i32),
+
+    /// This variant represents a row that cannot be deserialized into an entity.
+    #[error("row cannot be decoded as {type_name}: {message}")]
+    InvalidEntity {
+        type_name: &'static str,
+        message: String,
+    },
 }
 
 
@@ -161,6 +329,13 @@ pub trait TableSerialize {
     fn get_id(&self) -> String {
         "0".to_string()
     }
+
+    /// Checks the entity before it is written. Derive with `#[table(validate)]` to call `Validate::validate`
+    /// and with `#[table(validator)]` to call `validator::Validate::validate`; the `max_len`, `min` and `max`
+    /// column constraints are checked too.
+    fn validation(&self) -> Result<(), ValidationErrors> {
+        Ok(())
+    }
 }
 /// `TableDeserialize` is a trait that provides methods for deserializing table data.
 /// This trait is used to convert data from a stored or transmitted format into table data.
@@ -174,6 +349,29 @@ pub trait TableDeserialize {
     fn fields() -> Vec<String>{
         Vec::new()
     }
+
+    /// Returns the column definitions of the table, used to generate its `CREATE TABLE` statement.
+    /// Reading an entity uses the former names of a column (`#[column(alias = "...")]`) and its read default
+    /// (`#[column(default = "...")]`) if the selected row lacks the column.
+    fn columns() -> Vec<ColumnDef> {
+        Vec::new()
+    }
+
+    /// Returns the documentation of the table, given with `#[table(comment = "...")]`.
+    fn table_comment() -> Option<String> {
+        None
+    }
+
+    /// Returns the schema of the table, given with `#[table(schema = "...")]`. It overrides `ConnectOptions::schema`.
+    fn schema() -> Option<String> {
+        None
+    }
+
+    /// Returns how long `find_one` keeps the entity in the `ConnectOptions::entity_cache`,
+    /// given with `#[table(cache_ttl = "60s")]`. Entities without a TTL are not cached.
+    fn cache_ttl() -> Option<Duration> {
+        None
+    }
 }
 
 
@@ -182,6 +380,9 @@ pub trait TableDeserialize {
 #[derive(Debug, Clone)]
 pub struct Row {
     pub columns: HashMap<i32,Option<String>>,
+
+    /// `names` contains the column names of the query result, shared by all rows of the result.
+    names: Arc<Vec<String>>,
 }
 
 impl Row {
@@ -189,7 +390,65 @@ impl Row {
     pub fn new() -> Self {
         let columns = HashMap::new();
         Row {
-            columns
+            columns,
+            names: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Constructs a new empty `Row` of a query result with the given column names.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn with_names(names: Arc<Vec<String>>) -> Self {
+        Row {
+            columns: HashMap::new(),
+            names,
+        }
+    }
+
+    /// Returns the number of columns of the row.
+    /// For rows read from the database it is the number of result columns, including NULL ones.
+    pub fn len(&self) -> usize {
+        let set = self.columns.keys().max().map_or(0, |i| *i as usize + 1);
+        set.max(self.names.len())
+    }
+
+    /// Returns `true` if the row has no columns.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the value at the column index is NULL or the column does not exist.
+    pub fn is_null(&self, index: i32) -> bool {
+        !matches!(self.columns.get(&index), Some(Some(_)))
+    }
+
+    /// Returns the name of the column at the index, if it is known.
+    pub fn name(&self, index: i32) -> Option<&str> {
+        usize::try_from(index).ok().and_then(|i| self.names.get(i)).map(|name| name.as_str())
+    }
+
+    /// Iterates over the columns of the row as `(index, name, value)`, where `value` is `None` for NULL.
+    pub fn iter(&self) -> impl Iterator<Item = (i32, Option<&str>, Option<&str>)> + '_ {
+        (0..self.len() as i32).map(move |i| {
+            (i, self.name(i), self.columns.get(&i).and_then(|v| v.as_deref()))
+        })
+    }
+
+    /// Returns the value of the entity field `field`, which is read from the column at `index` if it has the name
+    /// of the field. Otherwise the value is read from the column with the name or an alias of the field, or else it is
+    /// the read default of the field (see `ColumnDef`), so that entities can be read while a migration is in progress.
+    /// Without any of them, or if the column names are unknown, the column at `index` is read.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn field_value(&self, index: i32, field: &str, definition: Option<&ColumnDef>) -> Option<String> {
+        if self.names.is_empty() || self.name(index) == Some(field) {
+            return self.get(index);
+        }
+        let aliases = definition.map(|definition| definition.aliases.as_slice()).unwrap_or_default();
+        let position = std::iter::once(field).chain(aliases.iter().map(String::as_str))
+            .find_map(|name| self.names.iter().position(|column| column == name));
+        match (position, definition.and_then(|definition| definition.read_default.as_ref())) {
+            (Some(position), _) => self.get(position as i32),
+            (None, Some(default)) => Some(default.clone()),
+            (None, None) => self.get(index),
         }
     }
 
@@ -225,6 +484,27 @@ impl Row {
         }
     }
 
+    /// Retrieves a value from the `Row` by its column index, distinguishing NULL from decode failures.
+    /// Returns `Ok(None)` if the value is NULL, and a `DecodeError` if the column does not exist
+    /// or its value cannot be parsed as `Z`.
+    pub fn try_get<Z: FromStr>(&self, index: i32) -> Result<Option<Z>, DecodeError>
+    {
+        match self.columns.get(&index) {
+            None => Err(DecodeError::NoSuchColumn(index)),
+            Some(None) => Ok(None),
+            Some(Some(v)) => {
+                match Z::from_str(v.as_str()) {
+                    Ok(res) => Ok(Some(res)),
+                    Err(_) => Err(DecodeError::InvalidValue {
+                        index,
+                        value: v.clone(),
+                        type_name: std::any::type_name::<Z>(),
+                    }),
+                }
+            }
+        }
+    }
+
     /// Sets a value in the `Row` at the specified column index.
     /// The value is converted to a `String` before being stored.
     pub fn set<T: ToString>(&mut self, index: i32, value: Option<T>) {
@@ -240,96 +520,2467 @@ impl Row {
     }
 }
 
-/// `ORMTrait` is a trait that provides methods for interacting with a database.
-/// This trait is used to perform operations such as adding data, finding data, modifying data, and removing data.
-/// It also provides methods for executing arbitrary queries and escaping strings.
-#[async_trait]
-pub trait ORMTrait<O:ORMTrait<O>> {
-    /// Adds a new record to the database.
-    /// The data is serialized and inserted into the appropriate table.
-    fn add<T>(&self, data: T) -> QueryBuilder<T, T, O>
-        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static;
+/// `Column` is a struct that describes a column of a query result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    /// `name` is the column name or alias as returned by the database.
+    pub name: String,
 
-    /// Returns the row ID of the last inserted record.
-    async fn last_insert_rowid(&self)  -> Result<i64, ORMError>;
+    /// `declared_type` is the declared type of the column, e.g. `INTEGER` or `VARCHAR`.
+    /// It is `None` if the column is an expression without a declared type.
+    pub declared_type: Option<String>,
 
-    /// Closes the database connection.
-    async fn close(&self)  -> Result<(), ORMError>;
+    /// `nullable` tells whether the column accepts NULL values.
+    /// It is `None` if the backend does not report nullability (SQLite).
+    pub nullable: Option<bool>,
+}
 
-    /// Finds a record by its ID.
-    /// Returns an `Option` that contains the record if it exists.
-    fn find_one<T: TableDeserialize>(&self, id: u64) -> QueryBuilder<Option<T>, T, O>
-    where T: TableDeserialize + TableSerialize + for<'a> Deserialize<'a> + 'static;
+/// `ResultSet` is a struct that holds the rows of a query result together with the column metadata.
+#[derive(Debug, Clone, Default)]
+pub struct ResultSet {
+    /// `columns` describes the columns in the order of the row indices.
+    pub columns: Vec<Column>,
 
-    /// Finds multiple records that match the provided WHERE clause.
-    fn find_many<T>(&self, query_where: &str) -> QueryBuilder<Vec<T>, T, O>
-        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static;
+    /// `rows` contains the rows of the result.
+    pub rows: Vec<Row>,
+}
 
-    /// Finds all records in the table.
-    fn find_all<T>(&self) -> QueryBuilder<Vec<T>, T, O>
-        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static;
+impl ResultSet {
+    /// Returns the index of the column with the given name.
+    pub fn column_index(&self, name: &str) -> Option<i32> {
+        self.columns.iter().position(|c| c.name == name).map(|i| i as i32)
+    }
+}
 
-    /// Modifies an existing record in the database.
-    /// The data is serialized and updated in the appropriate table.
-    fn modify<T>(&self, data: T) -> QueryBuilder<usize, (), O>
-        where T: TableDeserialize + TableSerialize + Serialize + 'static;
+impl ResultSet {
+    /// Decodes every row positionally into `T`, e.g. a tuple `(i32, Option<String>, i64)`.
+    /// Returns a `DecodeError` if the result has fewer columns than `T` or a value cannot be decoded.
+    pub fn decode<T: FromRow>(&self) -> Result<Vec<T>, DecodeError> {
+        if T::COLUMNS > self.columns.len() {
+            return Err(DecodeError::NoSuchColumn(self.columns.len() as i32));
+        }
+        self.rows.iter().map(T::from_row).collect()
+    }
+}
 
-    /// Removes a record from the database.
-    fn remove<T>(&self, data: T) -> QueryBuilder<usize, (), O>
-        where T: TableDeserialize + TableSerialize + Serialize + 'static;
+/// The number of rows buffered between the database and the consumer of a row stream.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+const ROW_STREAM_BUFFER: usize = 64;
 
-    /// Executes an arbitrary query and returns the results.
-    fn query<T>(&self, query: &str) -> QueryBuilder<Vec<T>, T, O>;
+/// Builds a stream of rows from `producer`, which sends the rows into the given channel.
+/// The producer runs while the stream is polled and pauses when the buffer is full,
+/// so only a bounded number of rows is kept in memory. An error returned by the producer ends the stream.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn row_stream<'a, F, Fut>(producer: F) -> impl Stream<Item = Result<Row, ORMError>> + 'a
+    where F: FnOnce(mpsc::Sender<Result<Row, ORMError>>) -> Fut,
+          Fut: Future<Output = Result<(), ORMError>> + 'a
+{
+    let (tx, rx) = mpsc::channel(ROW_STREAM_BUFFER);
+    let mut error_tx = tx.clone();
+    let producer = producer(tx);
+    let producer = async move {
+        if let Err(e) = producer.await {
+            log::error!("{:?}", e);
+            let _ = error_tx.send(Err(e)).await;
+        }
+        None
+    }.into_stream();
+    stream::select(producer, rx.map(Some)).filter_map(future::ready)
+}
 
-    /// Executes an arbitrary update query and returns the number of affected rows.
-    fn query_update(&self, query: &str) -> QueryBuilder<usize, (), O>;
+/// `Value` is an enumeration of the dynamically typed values returned by `query_map`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A NULL value.
+    Null,
+    /// An integer value.
+    Int(i64),
+    /// A floating point value.
+    Float(f64),
+    /// A text value. MySQL DECIMAL values are returned as text to keep their precision.
+    Text(String),
+    /// A binary value.
+    Bytes(Vec<u8>),
+}
 
-    /// Escapes a string to protect against SQL injection.
-    fn protect(&self, value: &str) -> String;
+/// `Statement` is a SQL statement together with its bound params.
+pub type Statement = (String, Vec<Value>);
 
-    /// Escapes a string for use in a SQL query.
-    fn escape(str: &str) -> String;
+/// `CountedStatement` is a statement together with the statements that update the counter caches of its table.
+pub type CountedStatement = (String, Vec<Value>, Vec<Statement>);
 
-    /// Escapes a string for use in a JSON value.
-    fn escape_json(input: &str) -> String;
+impl Value {
+    /// Returns `true` if the value is NULL.
+    pub fn is_null(&self) -> bool {
+        *self == Value::Null
+    }
 
-    /// Initializes the database with a provided script.
-    async fn init(&self, script: &str) -> Result<(), ORMError>;
+    /// Returns the integer value, if the value is an integer.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
 
-    /// Executes an update query and returns a result.
-    async fn change(&self, update_query: &str) -> Result<(), ORMError>;
+    /// Returns the floating point value, if the value is a number.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(v) => Some(*v as f64),
+            Value::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns the text value, if the value is a text.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(v) => Some(v.as_str()),
+            _ => None,
+        }
+    }
 }
 
-/// `QueryBuilder` is a struct that represents a SQL query builder.
-/// It is used to construct SQL queries in a safe and convenient manner.
-/// The `QueryBuilder` struct is generic over the lifetime `'a`, the result type `R`, the entity type `E`, and the ORM type `O`.
-/// The ORM type `O` must implement the `ORMTrait`.
-#[allow(dead_code)]
-pub struct QueryBuilder<'a, R, E, O: ORMTrait<O>> {
-    /// `query` is a `String` that contains the SQL query.
-    query: String,
+macro_rules! impl_value_from_int {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Value {
+                fn from(v: $t) -> Self {
+                    Value::Int(v as i64)
+                }
+            }
+        )*
+    };
+}
 
-    /// `entity` is a marker for the entity type `E`.
-    /// It is used to ensure that the `QueryBuilder` is used correctly with respect to the entity type.
-    entity:  std::marker::PhantomData<E>,
+impl_value_from_int!(i8, i16, i32, i64, u8, u16, u32, u64, bool);
 
-    /// `orm` is a reference to an ORM object that implements the `ORMTrait`.
-    /// It is used to execute the SQL query.
-    orm: &'a O,
+impl From<f32> for Value {
+    fn from(v: f32) -> Self {
+        Value::Float(v as f64)
+    }
+}
 
-    /// `result` is a marker for the result type `R`.
-    /// It is used to ensure that the `QueryBuilder` is used correctly with respect to the result type.
-    result: std::marker::PhantomData<std::marker::PhantomData<R>>,
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Float(v)
+    }
 }
 
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Text(v.to_string())
+    }
+}
 
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Text(v)
+    }
+}
 
-#[cfg(test)]
-mod tests {
-    use crate::ORMError;
+impl From<Vec<u8>> for Value {
+    fn from(v: Vec<u8>) -> Self {
+        Value::Bytes(v)
+    }
+}
 
-    #[tokio::test]
-    async fn test() -> Result<(), ORMError> {
-        Ok(())
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map(Into::into).unwrap_or(Value::Null)
+    }
+}
+
+/// `FromColumn` is a trait for types that can be decoded from a single column value.
+/// `Option<T>` decodes NULL as `None`; other types return `DecodeError::UnexpectedNull`.
+pub trait FromColumn: Sized {
+    /// Decodes the value of the column at `index`. `value` is `None` if the value is NULL.
+    fn from_column(index: i32, value: Option<&str>) -> Result<Self, DecodeError>;
+}
+
+fn parse_column<Z: FromStr>(index: i32, value: &str) -> Result<Z, DecodeError> {
+    Z::from_str(value).map_err(|_| DecodeError::InvalidValue {
+        index,
+        value: value.to_string(),
+        type_name: std::any::type_name::<Z>(),
+    })
+}
+
+macro_rules! impl_from_column {
+    ($($t:ty),*) => {
+        $(
+            impl FromColumn for $t {
+                fn from_column(index: i32, value: Option<&str>) -> Result<Self, DecodeError> {
+                    match value {
+                        Some(v) => parse_column(index, v),
+                        None => Err(DecodeError::UnexpectedNull(index)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_column!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64, char, String);
+
+impl FromColumn for bool {
+    fn from_column(index: i32, value: Option<&str>) -> Result<Self, DecodeError> {
+        match value {
+            Some("1") => Ok(true),
+            Some("0") => Ok(false),
+            Some(v) => parse_column(index, v),
+            None => Err(DecodeError::UnexpectedNull(index)),
+        }
+    }
+}
+
+impl<T: FromColumn> FromColumn for Option<T> {
+    fn from_column(index: i32, value: Option<&str>) -> Result<Self, DecodeError> {
+        match value {
+            Some(_) => T::from_column(index, value).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// `FromRow` is a trait for types that can be decoded positionally from a `Row`.
+/// It is implemented for tuples of up to 12 `FromColumn` elements.
+pub trait FromRow: Sized {
+    /// The number of columns read from the row.
+    const COLUMNS: usize;
+
+    /// Decodes the row. A column missing from the row is treated as NULL.
+    fn from_row(row: &Row) -> Result<Self, DecodeError>;
+}
+
+macro_rules! impl_from_row {
+    ($count:expr; $($t:ident $i:tt),+) => {
+        impl<$($t: FromColumn),+> FromRow for ($($t,)+) {
+            const COLUMNS: usize = $count;
+
+            fn from_row(row: &Row) -> Result<Self, DecodeError> {
+                Ok(($($t::from_column($i, row.columns.get(&$i).and_then(|v| v.as_deref()))?,)+))
+            }
+        }
+    };
+}
+
+impl_from_row!(1; A 0);
+impl_from_row!(2; A 0, B 1);
+impl_from_row!(3; A 0, B 1, C 2);
+impl_from_row!(4; A 0, B 1, C 2, D 3);
+impl_from_row!(5; A 0, B 1, C 2, D 3, E 4);
+impl_from_row!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+impl_from_row!(7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_from_row!(8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_from_row!(9; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_from_row!(10; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_from_row!(11; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_from_row!(12; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
+/// `RetryPolicy` is a struct that describes how establishing a connection is retried.
+/// The delay before the next attempt starts at `initial_delay` and is multiplied by `multiplier` after every attempt,
+/// but never exceeds `max_delay`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// `max_attempts` is the maximum number of connection attempts, including the first one.
+    pub max_attempts: u32,
+
+    /// `initial_delay` is the delay after the first failed attempt.
+    pub initial_delay: Duration,
+
+    /// `max_delay` is the upper bound of the delay between attempts.
+    pub max_delay: Duration,
+
+    /// `multiplier` is the factor the delay grows by after every failed attempt.
+    pub multiplier: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 10,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Constructs a new `RetryPolicy` with the given number of attempts and the default delays.
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the delay after the first failed attempt.
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Sets the upper bound of the delay between attempts.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Sets the factor the delay grows by after every failed attempt.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Returns the delay after the failed attempt number `attempt` (starting from 1).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(attempt.saturating_sub(1) as i32);
+        let delay = self.initial_delay.as_secs_f64() * factor;
+        if delay.is_finite() && delay < self.max_delay.as_secs_f64() {
+            Duration::from_secs_f64(delay)
+        } else {
+            self.max_delay
+        }
+    }
+}
+
+/// `InsertMeta` is a struct that describes the outcome of an insert executed with `apply_with_meta`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InsertMeta {
+    /// The generated ID (SQLite rowid or MySQL `LAST_INSERT_ID()`) of the inserted row.
+    pub id: u64,
+
+    /// The number of rows affected by the insert.
+    pub affected_rows: usize,
+}
+
+/// `Persisted` wraps an entity and records whether it was read from or written to the database, together with its original ID.
+/// `ORMTrait::persist` inserts an entity that is not persisted yet and updates it afterwards, so the same instance
+/// is never inserted twice. The entity is accessible through `Deref` and `DerefMut`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Persisted<T> {
+    entity: T,
+    original_id: Option<String>,
+}
+
+impl<T> Persisted<T> {
+    /// Wraps an entity that is not stored in the database yet.
+    pub fn new(entity: T) -> Self {
+        Persisted { entity, original_id: None }
+    }
+
+    /// Wraps an entity that was read from the database, e.g. by `find_one`.
+    pub fn loaded(entity: T) -> Self
+        where T: TableSerialize
+    {
+        let original_id = Some(entity.get_id());
+        Persisted { entity, original_id }
+    }
+
+    /// Returns `true` if the entity is stored in the database.
+    pub fn is_persisted(&self) -> bool {
+        self.original_id.is_some()
+    }
+
+    /// Returns the ID the entity had when it was read or written, or `None` if it is not persisted.
+    pub fn original_id(&self) -> Option<&str> {
+        self.original_id.as_deref()
+    }
+
+    /// Returns the wrapped entity.
+    pub fn into_inner(self) -> T {
+        self.entity
+    }
+}
+
+impl<T> std::ops::Deref for Persisted<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.entity
+    }
+}
+
+impl<T> std::ops::DerefMut for Persisted<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.entity
+    }
+}
+
+/// `PoolStatus` is a struct that describes the state of the connections of an ORM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolStatus {
+    /// `size` is the number of open connections.
+    pub size: usize,
+
+    /// `idle` is the number of connections that are not in use.
+    pub idle: usize,
+
+    /// `in_use` is the number of connections that are currently used by a query.
+    pub in_use: usize,
+
+    /// `waits` is the number of times a connection was acquired.
+    pub waits: u64,
+
+    /// `wait_time` is the total time spent waiting for a connection.
+    pub wait_time: Duration,
+
+    /// `max_wait_time` is the longest time spent waiting for a connection.
+    pub max_wait_time: Duration,
+}
+
+/// `PoolMetrics` collects the time spent waiting for a connection.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+#[derive(Debug, Default)]
+pub(crate) struct PoolMetrics {
+    waits: AtomicU64,
+    wait_micros: AtomicU64,
+    max_wait_micros: AtomicU64,
+}
+
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+impl PoolMetrics {
+    pub(crate) fn record_wait(&self, wait: Duration) {
+        let micros = wait.as_micros() as u64;
+        self.waits.fetch_add(1, Ordering::Relaxed);
+        self.wait_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_wait_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    pub(crate) fn status(&self, size: usize, in_use: usize) -> PoolStatus {
+        PoolStatus {
+            size,
+            idle: size.saturating_sub(in_use),
+            in_use,
+            waits: self.waits.load(Ordering::Relaxed),
+            wait_time: Duration::from_micros(self.wait_micros.load(Ordering::Relaxed)),
+            max_wait_time: Duration::from_micros(self.max_wait_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Logs `pool_status()` of `orm` every `interval` until the ORM is dropped.
+/// Does nothing if it is called outside of a Tokio runtime.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn spawn_pool_status_logger<O>(orm: &Arc<O>, interval: Duration)
+    where O: ORMTrait<O> + Send + Sync + 'static
+{
+    let handle = match tokio::runtime::Handle::try_current() {
+        Ok(handle) => handle,
+        Err(_) => {
+            log::warn!("pool status logging requires a Tokio runtime");
+            return;
+        }
+    };
+    let orm = Arc::downgrade(orm);
+    handle.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match orm.upgrade() {
+                Some(orm) => log::info!("{:?}", orm.pool_status()),
+                None => break,
+            }
+        }
+    });
+}
+
+/// `IsolationLevel` is an enumeration of the isolation levels a transaction can be started with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IsolationLevel {
+    /// The default of the backend: `REPEATABLE READ` for MySQL (unless changed on the server) and `DEFERRED` for SQLite.
+    #[default]
+    Default,
+
+    /// `READ UNCOMMITTED` (MySQL). SQLite transactions are always serializable, so it starts a `DEFERRED` transaction.
+    ReadUncommitted,
+
+    /// `READ COMMITTED` (MySQL). SQLite transactions are always serializable, so it starts a `DEFERRED` transaction.
+    ReadCommitted,
+
+    /// `REPEATABLE READ` (MySQL). SQLite transactions are always serializable, so it starts a `DEFERRED` transaction.
+    RepeatableRead,
+
+    /// `SERIALIZABLE` (MySQL). On SQLite it starts an `IMMEDIATE` transaction, so that the write lock is taken up front
+    /// and the transaction cannot fail with `SQLITE_BUSY` when it starts writing.
+    Serializable,
+
+    /// `BEGIN DEFERRED` (SQLite only): locks are acquired when the database is first read or written.
+    Deferred,
+
+    /// `BEGIN IMMEDIATE` (SQLite only): the write lock is acquired when the transaction starts.
+    Immediate,
+
+    /// `BEGIN EXCLUSIVE` (SQLite only): no other connection can read or write until the transaction ends.
+    Exclusive,
+}
+
+/// Returns `true` if an ID returned by `TableSerialize::get_id` has not been assigned by the database yet.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn is_unset_id(id: &str) -> bool {
+    id.is_empty() || id == "0"
+}
+
+/// Returns the statement that starts a nested transaction at `depth` (1 for the first nested level).
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn savepoint_statement(depth: usize) -> String {
+    format!("SAVEPOINT parvati_sp_{}", depth)
+}
+
+/// Returns the statements that end the transaction at `depth` (1 for the outermost transaction).
+/// Nested transactions release or roll back to their savepoint.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn end_transaction_statements(depth: usize, commit: bool) -> Vec<String> {
+    match (depth, commit) {
+        (1, true) => vec!["COMMIT".to_string()],
+        (1, false) => vec!["ROLLBACK".to_string()],
+        (_, true) => vec![format!("RELEASE SAVEPOINT parvati_sp_{}", depth - 1)],
+        (_, false) => vec![
+            format!("ROLLBACK TO SAVEPOINT parvati_sp_{}", depth - 1),
+            format!("RELEASE SAVEPOINT parvati_sp_{}", depth - 1),
+        ],
+    }
+}
+
+/// `TransactionOwner` identifies the caller that begins a transaction: the current task, or the current thread outside
+/// of a task, e.g. in `Runtime::block_on`, which polls its future on the calling thread only.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum TransactionOwner {
+    Task(tokio::task::Id),
+    Thread(std::thread::ThreadId),
+}
+
+/// Returns the owner of the transactions begun by the current task: the statements of a transaction are the statements
+/// of its owner, those of other tasks are not part of it. Outside of a task the current thread is the owner,
+/// so callers that block on different threads do not share a transaction.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn transaction_owner() -> TransactionOwner {
+    match tokio::task::try_id() {
+        Some(id) => TransactionOwner::Task(id),
+        None => TransactionOwner::Thread(std::thread::current().id()),
+    }
+}
+
+/// Executes `execute` in a transaction of `orm` with `isolation`, or in a savepoint if a transaction is active.
+/// The transaction is committed if `execute` succeeds and rolled back if it fails.
+pub(crate) async fn in_transaction<O, T, E>(orm: &O, isolation: IsolationLevel, execute: impl std::future::Future<Output = Result<T, E>>) -> Result<T, E>
+    where O: ORMTrait<O> + Sync, E: From<ORMError>
+{
+    orm.begin(isolation).await?;
+    match execute.await {
+        Ok(value) => {
+            orm.commit().await?;
+            Ok(value)
+        }
+        Err(e) => {
+            let _ = orm.rollback().await;
+            Err(e)
+        }
+    }
+}
+
+/// `MergeFn` is the closure of `MergeStrategy::Custom`: it receives the stored and the incoming entity.
+pub type MergeFn<T> = Box<dyn Fn(&T, &T) -> Option<T> + Send + Sync>;
+
+/// `MergeStrategy` is an enumeration of the ways `merge` resolves an incoming entity that already exists in the table.
+/// Entities are matched by their ID; incoming entities without a stored row are always inserted.
+pub enum MergeStrategy<T> {
+    /// The entity with the greater value of the given column (e.g. `updated_at` or `version`) wins.
+    /// Numbers are compared numerically, other values as strings; NULL is older than any value.
+    /// If both values are equal, the stored row is kept.
+    KeepNewest(String),
+
+    /// The stored row is always kept.
+    KeepExisting,
+
+    /// The closure receives the stored and the incoming entity and returns the entity to store,
+    /// or `None` to keep the stored row.
+    Custom(MergeFn<T>),
+}
+
+/// `MergeResult` is a struct that counts what `merge` did with the incoming entities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MergeResult {
+    /// The number of inserted entities.
+    pub inserted: usize,
+
+    /// The number of updated rows.
+    pub updated: usize,
+
+    /// The number of entities for which the stored row was kept.
+    pub skipped: usize,
+}
+
+/// An action planned by `plan_merge` for a single incoming entity.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) enum MergeAction<T> {
+    Insert(T),
+    Update(T),
+    Skip,
+}
+
+/// Decides for every incoming entity whether it is inserted, updates the stored row with the same ID or is skipped.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn plan_merge<T>(incoming: Vec<T>, existing: Vec<T>, strategy: &MergeStrategy<T>) -> Result<Vec<MergeAction<T>>, ORMError>
+    where T: TableSerialize + Serialize + Clone
+{
+    let mut existing: HashMap<String, T> = existing.into_iter().map(|e| (e.get_id(), e)).collect();
+    let mut actions = Vec::with_capacity(incoming.len());
+    for entity in incoming {
+        let action = match existing.get(&entity.get_id()) {
+            None => MergeAction::Insert(entity),
+            Some(stored) => match strategy {
+                MergeStrategy::KeepExisting => MergeAction::Skip,
+                MergeStrategy::KeepNewest(column) => {
+                    let field = |e: &T| serializer_fields::field_value(e, column)
+                        .map_err(|e| ORMError::MergeError(format!("{:?}", e)))?
+                        .ok_or_else(|| ORMError::MergeError(format!("no such column: {}", column)));
+                    if compare_values(&field(&entity)?, &field(stored)?) == std::cmp::Ordering::Greater {
+                        MergeAction::Update(entity)
+                    } else {
+                        MergeAction::Skip
+                    }
+                }
+                MergeStrategy::Custom(resolve) => match resolve(stored, &entity) {
+                    Some(merged) => MergeAction::Update(merged),
+                    None => MergeAction::Skip,
+                },
+            },
+        };
+        if let MergeAction::Insert(ref e) | MergeAction::Update(ref e) = action {
+            // a later entity with the same ID is resolved against this one
+            existing.insert(e.get_id(), e.clone());
+        }
+        actions.push(action);
+    }
+    Ok(actions)
+}
+
+/// Compares two values for `MergeStrategy::KeepNewest`.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+fn compare_values(a: &Value, b: &Value) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        (Value::Int(a), Value::Int(b)) => a.cmp(b),
+        (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) =>
+            a.as_f64().unwrap().partial_cmp(&b.as_f64().unwrap()).unwrap_or(Ordering::Equal),
+        (Value::Bytes(a), Value::Bytes(b)) => a.cmp(b),
+        _ => value_string(a).cmp(&value_string(b)),
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+fn value_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Int(v) => v.to_string(),
+        Value::Float(v) => v.to_string(),
+        Value::Text(v) => v.clone(),
+        Value::Bytes(v) => String::from_utf8_lossy(v).to_string(),
+    }
+}
+
+/// `DateTimePolicy` is an enumeration of the ways datetime values are converted between Rust and the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateTimePolicy {
+    /// Values are written and read as they are.
+    #[default]
+    AsIs,
+
+    /// Datetimes with an offset (RFC 3339, e.g. `chrono::DateTime<Utc>`) are stored as UTC in the `YYYY-MM-DD HH:MM:SS` form,
+    /// and stored datetimes are read back as RFC 3339 UTC values. Only declared datetime columns are converted:
+    /// chrono datetime fields and string fields with `#[column(datetime)]`.
+    /// On MySQL the session `time_zone` defaults to `+00:00` so that TIMESTAMP columns are not shifted.
+    Utc,
+}
+
+/// `FloatPolicy` is an enumeration of the ways NaN and infinite float fields are written to the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatPolicy {
+    /// Writing a NaN or infinite value fails with `ORMError::InvalidValue`.
+    #[default]
+    Error,
+
+    /// NaN and infinite values are written as NULL.
+    Null,
+}
+
+/// `ConnectOptions` is a struct that describes how a connection to the database is established.
+/// It is passed to `connect_with` of the backend ORM.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// `url` is the database URL (MySQL) or the path to the database file (SQLite).
+    pub url: String,
+
+    /// `on_connect` contains the statements that are executed on every new connection,
+    /// e.g. `PRAGMA foreign_keys = ON` or `SET time_zone = '+00:00'`.
+    pub on_connect: Vec<String>,
+
+    /// `sql_mode` is the MySQL session `sql_mode`, e.g. `STRICT_TRANS_TABLES`. Ignored by SQLite.
+    pub sql_mode: Option<Vec<String>>,
+
+    /// `time_zone` is the MySQL session `time_zone`, e.g. `+00:00` or `Europe/Berlin`. Ignored by SQLite.
+    pub time_zone: Option<String>,
+
+    /// `wait_timeout` is the MySQL session `wait_timeout` in seconds. Ignored by SQLite.
+    pub wait_timeout: Option<u32>,
+
+    /// `charset` is the MySQL connection character set, e.g. `utf8mb4`. Ignored by SQLite.
+    pub charset: Option<String>,
+
+    /// `collation` is the MySQL connection collation, e.g. `utf8mb4_unicode_ci`. Ignored by SQLite.
+    pub collation: Option<String>,
+
+    /// `datetime_policy` defines how datetime values are converted when entities are written and read.
+    pub datetime_policy: DateTimePolicy,
+
+    /// `strict_decode` turns values that cannot be decoded into `ORMError::DecodeError` instead of silently skipping them.
+    pub strict_decode: bool,
+
+    /// `pool_status_interval` enables logging of `pool_status()` at the given interval.
+    pub pool_status_interval: Option<Duration>,
+
+    /// `pool_min` is the number of idle connections the MySQL pool keeps open. Ignored by SQLite.
+    pub pool_min: Option<usize>,
+
+    /// `pool_max` is the maximum number of connections of the MySQL pool. Ignored by SQLite.
+    pub pool_max: Option<usize>,
+
+    /// `readers` is the number of read-only SQLite connections the SELECTs outside of transactions run on, so that
+    /// concurrent reads do not wait for each other or for the writes. Ignored by MySQL.
+    pub readers: usize,
+
+    /// `float_policy` defines how NaN and infinite float fields of entities are written.
+    pub float_policy: FloatPolicy,
+
+    /// `statement_logger` receives every executed statement with its duration and number of rows.
+    pub statement_logger: Option<Arc<dyn StatementLogger>>,
+
+    /// `password` is the MySQL password. It replaces the password of `url`, so that the URL can be logged. Ignored by SQLite.
+    pub password: Option<Secret>,
+
+    /// `schema` qualifies the tables of the generated SQL, e.g. `billing.invoice`, unless an entity has its own schema.
+    /// It is a MySQL database or an attached SQLite database.
+    pub schema: Option<String>,
+
+    /// `cassette` records the executed statements with their responses, or replays them without a database.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub cassette: Option<Arc<Cassette>>,
+
+    /// `n_plus_one_threshold` enables the N+1 query detector in debug builds: a warning is logged when a statement
+    /// shape is executed more often than the threshold in one task or transaction.
+    pub n_plus_one_threshold: Option<usize>,
+
+    /// `entity_cache` is the second-level cache of the entities with a `TableDeserialize::cache_ttl`.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub entity_cache: Option<Arc<dyn EntityCache>>,
+}
+
+impl ConnectOptions {
+    /// Constructs new `ConnectOptions` for the given URL.
+    pub fn new(url: &str) -> Self {
+        ConnectOptions {
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Registers a statement that is executed on every new connection.
+    /// Statements are executed in the order they were registered.
+    pub fn on_connect(mut self, statement: &str) -> Self {
+        self.on_connect.push(statement.to_string());
+        self
+    }
+
+    /// Sets the MySQL session `sql_mode` to the given list of modes.
+    /// An empty list clears the mode.
+    pub fn sql_mode(mut self, modes: &[&str]) -> Self {
+        self.sql_mode = Some(modes.iter().map(|m| m.to_string()).collect());
+        self
+    }
+
+    /// Sets the MySQL session `time_zone`.
+    pub fn time_zone(mut self, time_zone: &str) -> Self {
+        self.time_zone = Some(time_zone.to_string());
+        self
+    }
+
+    /// Sets the MySQL session `wait_timeout` in seconds.
+    pub fn wait_timeout(mut self, seconds: u32) -> Self {
+        self.wait_timeout = Some(seconds);
+        self
+    }
+
+    /// Sets the MySQL connection character set, e.g. `utf8mb4` for 4-byte UTF-8 data such as emoji.
+    pub fn charset(mut self, charset: &str) -> Self {
+        self.charset = Some(charset.to_string());
+        self
+    }
+
+    /// Sets the MySQL connection collation, e.g. `utf8mb4_unicode_ci`.
+    /// The collation is only applied together with a `charset`.
+    pub fn collation(mut self, collation: &str) -> Self {
+        self.collation = Some(collation.to_string());
+        self
+    }
+
+    /// Sets the policy used to convert datetime values of entities.
+    pub fn datetime_policy(mut self, policy: DateTimePolicy) -> Self {
+        self.datetime_policy = policy;
+        self
+    }
+
+    /// Enables or disables strict decoding.
+    /// In strict mode column values that cannot be read and rows that cannot be deserialized into an entity
+    /// are reported as `ORMError::DecodeError`.
+    pub fn strict_decode(mut self, strict: bool) -> Self {
+        self.strict_decode = strict;
+        self
+    }
+
+    /// Sets the policy used to write NaN and infinite float fields of entities.
+    pub fn float_policy(mut self, policy: FloatPolicy) -> Self {
+        self.float_policy = policy;
+        self
+    }
+
+    /// Logs `pool_status()` every `interval` while the ORM is alive.
+    pub fn log_pool_status(mut self, interval: Duration) -> Self {
+        self.pool_status_interval = Some(interval);
+        self
+    }
+
+    /// Sets the minimum number of idle connections and the maximum number of connections of the pool.
+    /// Connecting fails with `ORMError::Config` if `max` is 0 or less than `min`.
+    pub fn pool_size(mut self, min: usize, max: usize) -> Self {
+        self.pool_min = Some(min);
+        self.pool_max = Some(max);
+        self
+    }
+
+    /// Opens `readers` read-only SQLite connections next to the connection that writes, see `ConnectOptions::readers`.
+    /// The database is switched to WAL mode, in which readers and the writer do not block each other.
+    pub fn readers(mut self, readers: usize) -> Self {
+        self.readers = readers;
+        self
+    }
+
+    /// Reports every executed statement to `logger`, e.g. a `JsonLinesLogger`.
+    pub fn statement_logger(mut self, logger: Arc<dyn StatementLogger>) -> Self {
+        self.statement_logger = Some(logger);
+        self
+    }
+
+    /// Sets the MySQL password.
+    pub fn password(mut self, password: &str) -> Self {
+        self.password = Some(Secret::Value(password.to_string()));
+        self
+    }
+
+    /// Reads the MySQL password from the environment variable `name` on every connect.
+    pub fn password_from_env(mut self, name: &str) -> Self {
+        self.password = Some(Secret::Env(name.to_string()));
+        self
+    }
+
+    /// Reads the MySQL password from the file `path` on every connect, e.g. a mounted Docker or Kubernetes secret.
+    pub fn password_from_file<P: Into<std::path::PathBuf>>(mut self, path: P) -> Self {
+        self.password = Some(Secret::File(path.into()));
+        self
+    }
+
+    /// Asks `provider` for the MySQL password on every connect.
+    pub fn password_provider(mut self, provider: Arc<dyn SecretProvider>) -> Self {
+        self.password = Some(Secret::Provider(provider));
+        self
+    }
+
+    /// Sets the default schema of the tables of the generated SQL, so that one connection can span several schemas.
+    /// Entities with `#[table(schema = "...")]` keep their own schema.
+    pub fn schema(mut self, schema: &str) -> Self {
+        self.schema = Some(schema.to_string());
+        self
+    }
+
+    /// Warns with a backtrace when a statement with the same shape, e.g. `select * from user where id = ?`,
+    /// is executed more than `threshold` times in one task or transaction, which points at a lookup in a loop
+    /// that should load its rows with one query. The detector only runs in debug builds.
+    pub fn detect_n_plus_one(mut self, threshold: usize) -> Self {
+        self.n_plus_one_threshold = Some(threshold);
+        self
+    }
+
+    /// Caches the entities with a `cache_ttl` read by `find_one` in `cache`, e.g. a `RedisCache`.
+    /// The entries of a table are invalidated by every write of the table, see `EntityCache`.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub fn entity_cache(mut self, cache: Arc<dyn EntityCache>) -> Self {
+        self.entity_cache = Some(cache);
+        self
+    }
+
+    /// Returns the table name of `T` for the generated SQL, qualified with the schema of `T` or the default schema.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn table_name<T: TableDeserialize>(&self) -> String {
+        let table = T::same_name();
+        match T::schema().or_else(|| self.schema.clone()) {
+            Some(schema) if !table.contains('.') => format!("{}.{}", schema, table),
+            _ => table,
+        }
+    }
+
+    /// Returns `table` qualified with the default schema, unless it is already qualified.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn qualify(&self, table: &str) -> String {
+        match &self.schema {
+            Some(schema) if !table.contains('.') => format!("{}.{}", schema, table),
+            _ => table.to_string(),
+        }
+    }
+
+    /// Records the executed statements to `cassette` or, if it was loaded with `Cassette::replay` or created with
+    /// `Cassette::capture`, connects without a database and answers the statements from the cassette.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub fn cassette(mut self, cassette: Arc<Cassette>) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Returns the cassette if statements are replayed instead of executed.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn replaying(&self) -> Option<&Cassette> {
+        self.cassette.as_deref().filter(|cassette| cassette.answers())
+    }
+
+    /// Records the response of an executed statement if a cassette is recording.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn record<T, E: Debug>(&self, statement: &str, result: &Result<T, E>, response: impl FnOnce(&T) -> Recorded) {
+        if let Some(cassette) = self.cassette.as_ref().filter(|cassette| !cassette.answers()) {
+            let response = match result {
+                Ok(value) => response(value),
+                Err(e) => Recorded::Error(format!("{:?}", e)),
+            };
+            cassette.record_interaction(statement, response);
+        }
+    }
+
+    /// Reports a statement started at `start` to the statement logger, if there is one.
+    /// `rows` returns the number of affected or returned rows of a successful statement.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn log_statement<T, E: Debug>(&self, statement: &str, start: std::time::Instant,
+                                                          result: &Result<T, E>, rows: impl FnOnce(&T) -> usize) {
+        if let Some(logger) = &self.statement_logger {
+            let duration = start.elapsed();
+            logger.log(&StatementRecord {
+                timestamp: std::time::SystemTime::now() - duration,
+                duration,
+                rows: result.as_ref().ok().map(rows),
+                statement: statement.to_string(),
+                error: result.as_ref().err().map(|e| format!("{:?}", e)),
+            });
+        }
+    }
+
+    /// Maps an entity deserialization error to `ORMError::DecodeError` in strict mode
+    /// and to `ORMError::Unknown` otherwise.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn decode_error<T>(&self, error: serializer_error::Error) -> ORMError {
+        if self.strict_decode {
+            ORMError::DecodeError(DecodeError::InvalidEntity {
+                type_name: std::any::type_name::<T>(),
+                message: format!("{:?}", error),
+            })
+        } else {
+            ORMError::Unknown
+        }
+    }
+}
+
+/// `ToSqlLiteral` is a trait for values that can be rendered as SQL literals.
+/// It is used by `ORMTrait::protect_value` to safely embed numbers, booleans, NULLs, dates and byte arrays into raw queries.
+pub trait ToSqlLiteral {
+    /// Returns the value as a SQL literal of `dialect`, e.g. `42`, `'text'`, `NULL` or `X'0aff'`.
+    /// NaN and infinite floats are rendered according to `float_policy`.
+    fn to_sql_literal(&self, dialect: Dialect, float_policy: FloatPolicy) -> Result<String, ORMError>;
+}
+
+macro_rules! impl_to_sql_literal_number {
+    ($($t:ty),*) => {
+        $(
+            impl ToSqlLiteral for $t {
+                fn to_sql_literal(&self, _dialect: Dialect, _float_policy: FloatPolicy) -> Result<String, ORMError> {
+                    Ok(self.to_string())
+                }
+            }
+        )*
+    };
+}
+
+impl_to_sql_literal_number!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+// Returns the literal of a float, or what `float_policy` defines for NaN and infinite values.
+fn float_literal(is_finite: bool, literal: String, float_policy: FloatPolicy) -> Result<String, ORMError> {
+    match (is_finite, float_policy) {
+        (true, _) => Ok(literal),
+        (false, FloatPolicy::Null) => Ok("NULL".to_string()),
+        (false, FloatPolicy::Error) => Err(ORMError::InvalidValue(format!("{} is not a finite float", literal))),
+    }
+}
+
+impl ToSqlLiteral for f32 {
+    fn to_sql_literal(&self, _dialect: Dialect, float_policy: FloatPolicy) -> Result<String, ORMError> {
+        float_literal(self.is_finite(), self.to_string(), float_policy)
+    }
+}
+
+impl ToSqlLiteral for f64 {
+    fn to_sql_literal(&self, _dialect: Dialect, float_policy: FloatPolicy) -> Result<String, ORMError> {
+        float_literal(self.is_finite(), self.to_string(), float_policy)
+    }
+}
+
+impl ToSqlLiteral for bool {
+    fn to_sql_literal(&self, _dialect: Dialect, _float_policy: FloatPolicy) -> Result<String, ORMError> {
+        Ok(if *self { "1".to_string() } else { "0".to_string() })
+    }
+}
+
+/// Strings are single-quoted with `'` doubled. MySQL also treats `\` as an escape character in string literals,
+/// so it is doubled as well; with the `NO_BACKSLASH_ESCAPES` SQL mode this stores a doubled backslash, use params there.
+impl ToSqlLiteral for str {
+    fn to_sql_literal(&self, dialect: Dialect, _float_policy: FloatPolicy) -> Result<String, ORMError> {
+        let escaped = match dialect {
+            Dialect::Sqlite => self.replace('\'', "''"),
+            Dialect::MySql => self.replace('\\', "\\\\").replace('\'', "''"),
+        };
+        Ok(format!("'{}'", escaped))
+    }
+}
+
+impl ToSqlLiteral for String {
+    fn to_sql_literal(&self, dialect: Dialect, float_policy: FloatPolicy) -> Result<String, ORMError> {
+        self.as_str().to_sql_literal(dialect, float_policy)
+    }
+}
+
+impl ToSqlLiteral for [u8] {
+    fn to_sql_literal(&self, _dialect: Dialect, _float_policy: FloatPolicy) -> Result<String, ORMError> {
+        let hex: String = self.iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(format!("X'{}'", hex))
+    }
+}
+
+impl ToSqlLiteral for Vec<u8> {
+    fn to_sql_literal(&self, dialect: Dialect, float_policy: FloatPolicy) -> Result<String, ORMError> {
+        self.as_slice().to_sql_literal(dialect, float_policy)
+    }
+}
+
+impl<T: ToSqlLiteral> ToSqlLiteral for Option<T> {
+    fn to_sql_literal(&self, dialect: Dialect, float_policy: FloatPolicy) -> Result<String, ORMError> {
+        match self {
+            Some(v) => v.to_sql_literal(dialect, float_policy),
+            None => Ok("NULL".to_string()),
+        }
+    }
+}
+
+impl ToSqlLiteral for Value {
+    fn to_sql_literal(&self, dialect: Dialect, float_policy: FloatPolicy) -> Result<String, ORMError> {
+        match self {
+            Value::Null => Ok("NULL".to_string()),
+            Value::Int(v) => v.to_sql_literal(dialect, float_policy),
+            Value::Float(v) => v.to_sql_literal(dialect, float_policy),
+            Value::Text(v) => v.to_sql_literal(dialect, float_policy),
+            Value::Bytes(v) => v.to_sql_literal(dialect, float_policy),
+        }
+    }
+}
+
+impl<T: ToSqlLiteral + ?Sized> ToSqlLiteral for &T {
+    fn to_sql_literal(&self, dialect: Dialect, float_policy: FloatPolicy) -> Result<String, ORMError> {
+        (**self).to_sql_literal(dialect, float_policy)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ToSqlLiteral for chrono::NaiveDate {
+    fn to_sql_literal(&self, _dialect: Dialect, _float_policy: FloatPolicy) -> Result<String, ORMError> {
+        Ok(format!("'{}'", self.format("%Y-%m-%d")))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ToSqlLiteral for chrono::NaiveDateTime {
+    fn to_sql_literal(&self, _dialect: Dialect, _float_policy: FloatPolicy) -> Result<String, ORMError> {
+        Ok(format!("'{}'", self.format("%Y-%m-%d %H:%M:%S%.f")))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ToSqlLiteral for chrono::DateTime<chrono::Utc> {
+    fn to_sql_literal(&self, dialect: Dialect, float_policy: FloatPolicy) -> Result<String, ORMError> {
+        self.naive_utc().to_sql_literal(dialect, float_policy)
+    }
+}
+
+/// Builds a WHERE condition from Rust tokens, e.g. `r#where!(age > #min_age && name like #pattern)`.
+///
+/// The macro expands to a `Cond`: `#name` and `#(expression)` are replaced with a `?` placeholder and the value,
+/// converted with `Into<Value>`, is bound as a param, so values are never part of the SQL. Rust literals are bound the
+/// same way, e.g. `name like "J%"` becomes `name like ?`; a leading `-` stays an operator of the SQL. `&&`, `||`, `!`,
+/// `==` and `!=` are lowered to `and`, `or`, `not`, `=` and `<>`; all other tokens are copied as they are.
+/// `where` is a keyword, so the macro has to be invoked as `r#where!`.
+///
+/// ```
+/// use parvati::Value;
+///
+/// let min_age = 18;
+/// let pattern = "J%";
+/// let condition = parvati::r#where!(age > #min_age && (name like #pattern || name is null));
+/// assert_eq!(condition.sql(), "age > ? and (name like ? or name is null)");
+/// assert_eq!(condition.params(), &[Value::Int(18), Value::Text("J%".to_string())]);
+/// ```
+#[macro_export]
+macro_rules! r#where {
+    (@push $condition:ident, $token:expr) => {{
+        let token: &str = &$token;
+        if !$condition.is_empty() && !$condition.ends_with('(') && token != ")" && token != "," {
+            $condition.push(' ');
+        }
+        $condition.push_str(token);
+    }};
+    (@munch $condition:ident, $params:ident;) => {};
+    (@munch $condition:ident, $params:ident; # $value:ident $($rest:tt)*) => {
+        $crate::r#where!(@push $condition, "?");
+        $params.push($crate::Value::from(::std::clone::Clone::clone(&$value)));
+        $crate::r#where!(@munch $condition, $params; $($rest)*);
+    };
+    (@munch $condition:ident, $params:ident; # ($value:expr) $($rest:tt)*) => {
+        $crate::r#where!(@push $condition, "?");
+        $params.push($crate::Value::from(::std::clone::Clone::clone(&$value)));
+        $crate::r#where!(@munch $condition, $params; $($rest)*);
+    };
+    (@munch $condition:ident, $params:ident; ($($inner:tt)*) $($rest:tt)*) => {
+        $crate::r#where!(@push $condition, "(");
+        $crate::r#where!(@munch $condition, $params; $($inner)*);
+        $crate::r#where!(@push $condition, ")");
+        $crate::r#where!(@munch $condition, $params; $($rest)*);
+    };
+    (@munch $condition:ident, $params:ident; $table:ident . $column:ident $($rest:tt)*) => {
+        $crate::r#where!(@push $condition, concat!(stringify!($table), ".", stringify!($column)));
+        $crate::r#where!(@munch $condition, $params; $($rest)*);
+    };
+    (@munch $condition:ident, $params:ident; && $($rest:tt)*) => {
+        $crate::r#where!(@push $condition, "and");
+        $crate::r#where!(@munch $condition, $params; $($rest)*);
+    };
+    (@munch $condition:ident, $params:ident; || $($rest:tt)*) => {
+        $crate::r#where!(@push $condition, "or");
+        $crate::r#where!(@munch $condition, $params; $($rest)*);
+    };
+    (@munch $condition:ident, $params:ident; == $($rest:tt)*) => {
+        $crate::r#where!(@push $condition, "=");
+        $crate::r#where!(@munch $condition, $params; $($rest)*);
+    };
+    (@munch $condition:ident, $params:ident; != $($rest:tt)*) => {
+        $crate::r#where!(@push $condition, "<>");
+        $crate::r#where!(@munch $condition, $params; $($rest)*);
+    };
+    (@munch $condition:ident, $params:ident; ! $($rest:tt)*) => {
+        $crate::r#where!(@push $condition, "not");
+        $crate::r#where!(@munch $condition, $params; $($rest)*);
+    };
+    (@munch $condition:ident, $params:ident; - $($rest:tt)*) => {
+        $crate::r#where!(@push $condition, "-");
+        $crate::r#where!(@munch $condition, $params; $($rest)*);
+    };
+    (@munch $condition:ident, $params:ident; $value:literal $($rest:tt)*) => {
+        $crate::r#where!(@push $condition, "?");
+        $params.push($crate::Value::from($value));
+        $crate::r#where!(@munch $condition, $params; $($rest)*);
+    };
+    (@munch $condition:ident, $params:ident; $token:tt $($rest:tt)*) => {
+        $crate::r#where!(@push $condition, stringify!($token));
+        $crate::r#where!(@munch $condition, $params; $($rest)*);
+    };
+    ($($tokens:tt)+) => {{
+        let mut condition = String::new();
+        let mut params: Vec<$crate::Value> = Vec::new();
+        $crate::r#where!(@munch condition, params; $($tokens)+);
+        $crate::Cond::with_params(&condition, params)
+    }};
+}
+
+/// `ORMTrait` is a trait that provides methods for interacting with a database.
+/// This trait is used to perform operations such as adding data, finding data, modifying data, and removing data.
+/// It also provides methods for executing arbitrary queries and escaping strings.
+#[async_trait]
+pub trait ORMTrait<O:ORMTrait<O>> {
+    /// Adds a new record to the database.
+    /// The data is serialized and inserted into the appropriate table.
+    /// The query fails with `ORMError::Validation` if the data fails its `TableSerialize::validation` check.
+    fn add<T>(&self, data: T) -> QueryBuilder<T, T, O>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static;
+
+    /// Inserts several records of the same table with a single multi-row INSERT and returns their generated IDs
+    /// in the order of `data`, so that the inserted rows can be referenced right away, e.g. by child records.
+    /// Nothing is inserted if a record fails its `TableSerialize::validation` check.
+    async fn add_all<T>(&self, data: Vec<T>) -> Result<Vec<u64>, ORMError>
+        where T: TableDeserialize + TableSerialize + Serialize + Send + 'static;
+
+    /// Inserts several records of the same table with multi-row INSERTs of at most `AddMany::chunk_size` rows each,
+    /// all inside one transaction. `apply()` returns the inserted records as they are stored, including their IDs.
+    fn add_many<T>(&self, data: Vec<T>) -> AddMany<'_, T, O>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static;
+
+    /// Inserts `data` or updates the record that already exists, matched by the columns given with
+    /// `AddOrUpdate::on_conflict` (`id` by default), with one statement that is safe to repeat.
+    /// `apply()` returns the record as it is stored. An entity whose unset ID is the conflict column is inserted.
+    fn add_or_update<T>(&self, data: T) -> AddOrUpdate<'_, T, O>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static;
+
+    /// Returns the row ID of the last inserted record.
+    async fn last_insert_rowid(&self)  -> Result<i64, ORMError>;
+
+    /// Returns the generated ID of the last inserted record, the SQLite rowid or the MySQL `LAST_INSERT_ID()`.
+    /// It is `0` if nothing was inserted on the connection yet. MySQL runs the statements on connections of its pool,
+    /// so there it is the ID of the last record inserted by the current task.
+    async fn last_insert_id(&self) -> Result<u64, ORMError>;
+
+    /// Closes the database connection.
+    async fn close(&self)  -> Result<(), ORMError>;
+
+    /// Finds a record by its ID.
+    /// Returns an `Option` that contains the record if it exists.
+    fn find_one<T: TableDeserialize>(&self, id: u64) -> QueryBuilder<Option<T>, T, O>
+    where T: TableDeserialize + TableSerialize + for<'a> Deserialize<'a> + 'static;
+
+    /// Checks whether a record with the given ID exists without fetching it.
+    async fn exists_by_id<T>(&self, id: u64) -> Result<bool, ORMError>
+        where T: TableDeserialize + 'static;
+
+    /// Finds the records with the given IDs with a single query and returns them in the order of `ids`,
+    /// with `None` for an ID that has no record, e.g. to load the entities referenced by a list of rows.
+    async fn find_by_ids<T>(&self, ids: &[u64]) -> Result<Vec<Option<T>>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + Clone + Send + Sync + 'static;
+
+    /// Finds the descendants of the record `id` in a self-referential table whose `parent_id` column references
+    /// the parent record: its children, their children and so on, ordered by depth and then by ID.
+    /// The hierarchy is walked with a recursive CTE, so MySQL 8 or later is required. A cycle in the hierarchy
+    /// is walked up to `TREE_MAX_DEPTH` levels deep; every record is returned once, at its smallest depth.
+    async fn descendants_of<T>(&self, id: u64) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + Send + Sync + 'static;
+
+    /// Finds the ancestors of the record `id` in a self-referential table whose `parent_id` column references
+    /// the parent record: its parent, the parent of the parent and so on up to the root, nearest first.
+    /// Like `descendants_of`, it uses a recursive CTE.
+    async fn ancestors_of<T>(&self, id: u64) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + Send + Sync + 'static;
+
+    /// Re-reads the record of `data` by its ID and overwrites `data` with the stored values,
+    /// e.g. to resync a long-lived entity after it was changed by someone else.
+    /// Returns `ORMError::NotFound` if the record no longer exists; `data` is left unchanged then.
+    async fn refresh<T>(&self, data: &mut T) -> Result<(), ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + Send + Sync + 'static;
+
+    /// Inserts `data` if its ID is unset (`0` or empty) and updates the stored record otherwise.
+    /// Returns the entity as it is stored, including the generated ID of an inserted record.
+    /// Returns `ORMError::NotFound` if `data` has an ID but there is no record with that ID.
+    async fn save<T>(&self, data: T) -> Result<T, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Send + Sync + 'static;
+
+    /// Inserts the entity if it is not persisted yet and updates its record otherwise, see `save`.
+    /// Afterwards `entity` holds the stored values and is marked as persisted.
+    /// Returns `ORMError::InvalidValue` if the ID of a persisted entity was changed.
+    async fn persist<T>(&self, entity: &mut Persisted<T>) -> Result<(), ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + Send + Sync + 'static;
+
+    /// Finds the first record that matches the provided WHERE clause, a raw SQL fragment or a `Cond`.
+    /// `LIMIT 1` is added to the query, so use `order_by` to choose which record is the first one.
+    fn find_one_where<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, Option<T>, T, O>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static;
+
+    /// Finds multiple records that match the provided WHERE clause, either a raw SQL fragment like `"id > 0"`
+    /// or a `Cond`, whose values are bound as params, e.g. `Cond::field("age").gt(18)`.
+    fn find_many<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<Vec<T>, T, O>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static;
+
+    /// Finds all records in the table.
+    fn find_all<T>(&self) -> QueryBuilder<Vec<T>, T, O>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static;
+
+    /// Counts the records that match the provided WHERE clause, a raw SQL fragment or a `Cond`, with `SELECT COUNT(*)`.
+    fn count<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, u64, T, O>
+        where T: TableDeserialize + 'static;
+
+    /// Checks whether a record matches the provided WHERE clause, a raw SQL fragment or a `Cond`, with `SELECT EXISTS`.
+    fn exists<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, bool, T, O>
+        where T: TableDeserialize + 'static;
+
+    /// Finds the records of `table` that match the provided WHERE clause as projections `P`, read models with a subset
+    /// of the columns of the table, e.g. `conn.find_as::<UserSummary>("user", "age > 30")`.
+    /// Only the fields of `P` are selected. The query fails with `ORMError::InvalidValue` if `table` is not a plain table name.
+    fn find_as<P>(&self, table: &str, query_where: &str) -> QueryBuilder<'_, Vec<P>, P, O>
+        where P: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static;
+
+    /// Executes a select built with `find_many`, `find_all`, `find_as` or `query` and returns its records.
+    /// Unlike `run`, it can be called by code that is generic over the backend, such as `Repository`.
+    async fn fetch<T>(&self, query: QueryBuilder<'_, Vec<T>, T, O>) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + Send + Sync + 'static;
+
+    /// Modifies an existing record in the database.
+    /// The data is serialized and updated in the appropriate table.
+    /// The query fails with `ORMError::Validation` if the data fails its `TableSerialize::validation` check.
+    fn modify<T>(&self, data: T) -> QueryBuilder<usize, (), O>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static;
+
+    /// Updates only the listed columns of an existing record, leaving the other columns as they are.
+    /// The query fails with `ORMError::InvalidValue` if `fields` is empty or names an unknown column or `id`.
+    fn modify_fields<T>(&self, data: &T, fields: &[&str]) -> QueryBuilder<'_, usize, (), O>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static;
+
+    /// Adds `by` to the numeric `column` of the record with the given ID in a single `UPDATE ... SET column = column + by`,
+    /// so that counters can be changed without reading the record first.
+    /// The query fails with `ORMError::InvalidValue` if `column` is not a field of `T` or is `id`.
+    fn increment<T>(&self, id: u64, column: &str, by: i64) -> QueryBuilder<'_, usize, (), O>
+        where T: TableDeserialize + 'static;
+
+    /// Subtracts `by` from the numeric `column` of the record with the given ID, see `increment`.
+    /// The query fails with `ORMError::InvalidValue` if `by` is `i64::MIN`, which cannot be negated.
+    fn decrement<T>(&self, id: u64, column: &str, by: i64) -> QueryBuilder<'_, usize, (), O>
+        where T: TableDeserialize + 'static
+    {
+        match by.checked_neg() {
+            Some(by) => self.increment::<T>(id, column, by),
+            None => {
+                let mut query = self.increment::<T>(id, column, 0);
+                query.error = Some(format!("cannot decrement field {} by {}", column, by));
+                query
+            }
+        }
+    }
+
+    /// Inserts `rows` and updates the rows that already exist, matched by the columns given with `on_conflict` (`id` by default).
+    /// Large lists are written in chunks of multi-row statements, all inside one transaction.
+    /// `run()` returns the number of affected rows as reported by the database.
+    fn upsert_all<T>(&self, rows: Vec<T>) -> UpsertAll<'_, T, O>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static;
+
+    /// Inserts `rows` in chunks of multi-row statements with partial success, e.g. for imports: each chunk is wrapped
+    /// in a savepoint, so a chunk with a bad row is rolled back and reported while the other chunks are inserted.
+    /// `run()` returns an `ImportReport` with the number of inserted rows and the failed chunks.
+    fn import_all<T>(&self, rows: Vec<T>) -> ImportAll<'_, T, O>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static;
+
+    /// Returns the job queue `name`, see `Queue`. All queues share the table `parvati_queue`,
+    /// which is created with `Queue::create_table`.
+    fn queue(&self, name: &str) -> Queue<'_, O>;
+
+    /// Creates the table of `T` from its column definitions (see `TableDeserialize::columns`),
+    /// including the foreign keys with their `ON DELETE` and `ON UPDATE` actions.
+    /// The query fails with `ORMError::InvalidValue` if `T` has no column definitions.
+    fn create_table<T>(&self) -> QueryBuilder<'_, usize, (), O>
+        where T: TableDeserialize + 'static;
+
+    /// Executes a schema statement built with `Ddl`, rendered in the dialect of the connection,
+    /// and returns the number of affected rows.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    fn ddl<D: DdlStatement>(&self, ddl: &D) -> QueryBuilder<'_, usize, (), O>;
+
+    /// Changes the existing table of `T` to its column definitions, e.g. in a migration:
+    /// `conn.alter_table::<User>().add_column("email").rename_column("name", "full_name").run().await?`.
+    /// SQLite rebuilds the table for changes that its `ALTER TABLE` does not support, see `AlterTable`.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    fn alter_table<T>(&self) -> AlterTable<'_, T, O>
+        where T: TableDeserialize + 'static;
+
+    /// Stores the table and column comments of `T` for an existing table and returns the number of stored comments.
+    /// MySQL keeps them in the schema (`create_table` already renders them); SQLite has no comments,
+    /// so they replace the rows of the table in `parvati_comments (table_name, column_name, comment)`,
+    /// where the table comment has an empty column name.
+    async fn store_comments<T>(&self) -> Result<usize, ORMError>
+        where T: TableDeserialize + 'static;
+
+    /// Removes a record from the database.
+    fn remove<T>(&self, data: T) -> QueryBuilder<usize, (), O>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static;
+
+    /// Removes the record with the given ID without constructing or fetching the entity.
+    fn remove_by_id<T>(&self, id: u64) -> QueryBuilder<'_, usize, (), O>
+        where T: TableDeserialize + 'static;
+
+    /// Removes the records with the given IDs with a single statement and returns the number of removed records.
+    async fn remove_by_ids<T>(&self, ids: &[u64]) -> Result<usize, ORMError>
+        where T: TableDeserialize + 'static;
+
+    /// Returns a reader of the TEXT or BLOB value of `column` of the record with the given ID, which reads
+    /// a large value in chunks, e.g. `conn.read_blob::<Document>(id, "content").copy_to(&mut file).await?`.
+    fn read_blob<T>(&self, id: u64, column: &str) -> BlobReader<'_, T, O>
+        where T: TableDeserialize + 'static;
+
+    /// Returns the length in bytes of the value of `column` of the record with the given ID; 0 if it is NULL.
+    async fn blob_length<T>(&self, id: u64, column: &str) -> Result<u64, ORMError>
+        where T: TableDeserialize + 'static;
+
+    /// Reads at most `len` bytes of the value of `column` of the record with the given ID, starting at byte `offset`.
+    async fn read_blob_chunk<T>(&self, id: u64, column: &str, offset: u64, len: usize) -> Result<Vec<u8>, ORMError>
+        where T: TableDeserialize + 'static;
+
+    /// Returns a handle that restricts the finds, modifies and removes of the entities registered with
+    /// `Scoped::entity` to the rows matching `scope`, e.g. `conn.scoped(col("org_id").eq(org)).entity::<Project>()`.
+    fn scoped(&self, scope: Scope) -> Scoped<'_, O>;
+
+    /// Inserts a record of a table that is only known at runtime and returns it as it is stored,
+    /// including its generated ID. A set `id` value is ignored.
+    async fn add_dynamic(&self, entity: &DynamicEntity) -> Result<DynamicEntity, ORMError>;
+
+    /// Finds the record of `table` with the given ID as a `DynamicEntity` with all its columns.
+    async fn find_dynamic(&self, table: &str, id: u64) -> Result<Option<DynamicEntity>, ORMError>;
+
+    /// Finds the records of `table` that match the provided WHERE clause, a raw SQL fragment or a `Cond`,
+    /// as `DynamicEntity` values.
+    async fn find_many_dynamic<W: Into<Cond> + Send>(&self, table: &str, query_where: W) -> Result<Vec<DynamicEntity>, ORMError>;
+
+    /// Updates the set columns of the record with the ID of `entity` and returns the number of updated rows.
+    /// Fails with `ORMError::InvalidValue` if `entity` has no ID.
+    async fn modify_dynamic(&self, entity: &DynamicEntity) -> Result<usize, ORMError>;
+
+    /// Removes the record with the ID of `entity` and returns the number of removed rows.
+    /// Fails with `ORMError::InvalidValue` if `entity` has no ID.
+    async fn remove_dynamic(&self, entity: &DynamicEntity) -> Result<usize, ORMError>;
+
+    /// Executes an arbitrary query and returns the results.
+    fn query<T>(&self, query: &str) -> QueryBuilder<Vec<T>, T, O>;
+
+    /// Executes an arbitrary update query and returns the number of affected rows.
+    fn query_update(&self, query: &str) -> QueryBuilder<usize, (), O>;
+
+    /// Executes an arbitrary select query and decodes every row positionally into `T`,
+    /// e.g. `conn.query_as::<(i32, Option<String>, i64)>("select id, name, count(*) ...")`.
+    async fn query_as<T: FromRow + Send>(&self, query: &str) -> Result<Vec<T>, ORMError>;
+
+    /// Executes an arbitrary select query and returns every row as a map from the column name to its `Value`.
+    /// It is intended for dynamic queries whose column set is not known at compile time.
+    /// If several columns have the same name, the last one wins.
+    async fn query_map(&self, query: &str) -> Result<Vec<HashMap<String, Value>>, ORMError>;
+
+    /// Runs independent read queries concurrently and returns their rows in the order of `queries`.
+    /// If one of the queries fails, the error is returned.
+    async fn join_all(&self, queries: Vec<QueryBuilder<'_, Vec<Row>, Row, O>>) -> Result<Vec<Vec<Row>>, ORMError>;
+
+    /// Reconciles a batch of incoming entities with the stored rows, e.g. in a sync pipeline.
+    /// Entities without a stored row with the same ID are inserted; for the others `strategy` decides
+    /// whether the stored row is updated or kept. All changes are applied in one transaction (see `exec_all`).
+    async fn merge<T>(&self, entities: Vec<T>, strategy: MergeStrategy<T>) -> Result<MergeResult, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + Send + Sync + 'static;
+
+    /// Returns the number of open, idle and used connections and the time spent waiting for a connection.
+    fn pool_status(&self) -> PoolStatus;
+
+    /// Returns the counters of the statements executed since the ORM was connected or `reset_query_stats` was called,
+    /// grouped by fingerprint and ordered by the total duration, the most expensive statements first.
+    fn query_stats(&self) -> Vec<QueryStats>;
+
+    /// Clears the counters of `query_stats`.
+    fn reset_query_stats(&self);
+
+    /// Calls `hook` after every successful INSERT, REPLACE, UPDATE or DELETE of `table`, e.g. to invalidate an external
+    /// cache. The table is parsed from the executed statements; a hook for `user` also receives the writes of `main.user`.
+    /// Inside a transaction the hooks are called on commit and not at all on rollback, see `TableWrite`.
+    fn on_table_write(&self, table: &str, hook: impl Fn(&TableWrite) + Send + Sync + 'static);
+
+    /// Sets the policy that every statement is checked against before it is executed, replacing the previous one,
+    /// e.g. to deny unscoped DELETEs in production. A denied statement returns `ORMError::PolicyViolation`.
+    ///
+    /// ```ignore
+    /// conn.set_policy(Policy::deny(StatementKind::Delete).unless_in_transaction());
+    /// ```
+    fn set_policy(&self, policy: Policy);
+
+    /// Checks the connection with a probe statement and returns its latency, the migration version of a `Migrator`
+    /// or of `change` and the `pool_status`, e.g. for a `/healthz` endpoint. A failed probe is reported in the `HealthReport`.
+    async fn health(&self) -> HealthReport;
+
+    /// Returns the `migrations` that `Migrator::migrate_up` would apply, in order, with their statements and the
+    /// operations that may lose data, e.g. to review them in CI. Nothing is written, not even the tracking table.
+    ///
+    /// ```ignore
+    /// let plan = conn.plan_migrations(&Migrator::new(&*conn, "migrations").migrations()?).await?;
+    /// println!("{}", plan);
+    /// ```
+    async fn plan_migrations(&self, migrations: &[Migration]) -> Result<MigrationPlan, ORMError>;
+
+    /// Returns the storage used by the tables of the database, or of `ConnectOptions::schema`, ordered by table name.
+    async fn table_sizes(&self) -> Result<Vec<TableSize>, ORMError>;
+
+    /// Returns the number of rows of the tables, ordered by table name.
+    /// SQLite counts the rows; MySQL returns the estimate of `information_schema`, which is fast for large tables.
+    async fn row_counts(&self) -> Result<Vec<RowCount>, ORMError>;
+
+    /// Returns the indexes of the tables, ordered by table and index name. MySQL returns how often each index was read,
+    /// which needs the `performance_schema`; SQLite only lists the indexes.
+    async fn index_usage(&self) -> Result<Vec<IndexUsage>, ORMError>;
+
+    /// Starts a transaction with the given isolation level on the connection of the ORM.
+    /// The following queries of the current task run inside the transaction until it calls `commit` or `rollback`;
+    /// the queries of other tasks are not part of it (on SQLite they wait until it ends).
+    /// If the task already has an active transaction, a savepoint is created instead and the isolation level is ignored,
+    /// so that nested `begin`/`commit` pairs can be composed.
+    /// Returns `ORMError::TransactionError` if the level is not supported by the backend.
+    async fn begin(&self, isolation: IsolationLevel) -> Result<(), ORMError>;
+
+    /// Commits the active transaction, or releases the innermost savepoint of a nested transaction.
+    async fn commit(&self) -> Result<(), ORMError>;
+
+    /// Rolls back the active transaction, or rolls back to the innermost savepoint of a nested transaction.
+    async fn rollback(&self) -> Result<(), ORMError>;
+
+    /// Starts a batch of statements that are executed together.
+    /// `execute()` returns the number of affected rows for every statement.
+    fn batch(&self) -> Batch<'_, O>;
+
+    /// Executes several update queries inside one transaction.
+    /// Returns the total number of affected rows; if one of the queries fails, the transaction is rolled back
+    /// and the error is returned. Inside a transaction started with `begin`, a savepoint is used instead.
+    async fn exec_all(&self, queries: &[QueryBuilder<'_, usize, (), O>]) -> Result<usize, ORMError>;
+
+    /// Escapes a string to protect against SQL injection.
+    fn protect(&self, value: &str) -> String;
+
+    /// Renders any `ToSqlLiteral` value (number, boolean, string, `None`, date, byte array) as a SQL literal
+    /// escaped for the database of the connection. NaN and infinite floats fail with `ORMError::InvalidValue`
+    /// unless the connection was opened with `FloatPolicy::Null`, which renders them as `NULL`.
+    fn protect_value<V: ToSqlLiteral>(&self, value: V) -> Result<String, ORMError>;
+
+    /// Escapes a string for use in a SQL query.
+    fn escape(str: &str) -> String;
+
+    /// Escapes a string for use in a JSON value.
+    fn escape_json(input: &str) -> String;
+
+    /// Initializes the database with a provided script.
+    async fn init(&self, script: &str) -> Result<(), ORMError>;
+
+    /// Executes an update query and returns a result.
+    async fn change(&self, update_query: &str) -> Result<(), ORMError>;
+}
+
+/// `QueryBuilder` is a struct that represents a SQL query builder.
+/// It is used to construct SQL queries in a safe and convenient manner.
+/// The `QueryBuilder` struct is generic over the lifetime `'a`, the result type `R`, the entity type `E`, and the ORM type `O`.
+/// The ORM type `O` must implement the `ORMTrait`.
+#[allow(dead_code)]
+pub struct QueryBuilder<'a, R, E, O: ORMTrait<O>> {
+    /// `query` is a `String` that contains the SQL query.
+    query: String,
+
+    /// `params` are the values bound to the `?` placeholders of the query, in order.
+    params: Vec<Value>,
+
+    /// `counters` are the counter cache updates of the written entity, executed before the query
+    /// in the same transaction, see `ColumnDef::counter_cache`.
+    counters: Vec<Statement>,
+
+    /// `entity` is a marker for the entity type `E`.
+    /// It is used to ensure that the `QueryBuilder` is used correctly with respect to the entity type.
+    entity:  std::marker::PhantomData<E>,
+
+    /// `orm` is a reference to an ORM object that implements the `ORMTrait`.
+    /// It is used to execute the SQL query.
+    orm: &'a O,
+
+    /// `result` is a marker for the result type `R`.
+    /// It is used to ensure that the `QueryBuilder` is used correctly with respect to the result type.
+    result: std::marker::PhantomData<std::marker::PhantomData<R>>,
+
+    /// `error` is an error that occurred while the query was built, e.g. a NaN field with `FloatPolicy::Error`.
+    /// It is returned when the query is executed.
+    error: Option<String>,
+
+    /// `modifiers` are the ORDER BY, LIMIT and OFFSET clauses applied to the query.
+    modifiers: Modifiers,
+
+    /// `validation` holds the invalid fields of the written entity. It is returned when the query is executed,
+    /// unless the check is skipped with `skip_validation`.
+    validation: Option<ValidationErrors>,
+
+    /// `table` is the entity table the query was generated for, which can be replaced with `into_table`.
+    /// It is `None` for arbitrary queries.
+    table: Option<String>,
+}
+
+impl<R, E, O: ORMTrait<O> + Sync> QueryBuilder<'_, R, E, O> {
+    /// Executes the counter cache updates of the query and then `execute`, the execution of the query,
+    /// in one transaction. A query without counter cache updates is executed on its own.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) async fn with_counters<T>(&self, execute: impl std::future::Future<Output = Result<T, ORMError>>) -> Result<T, ORMError> {
+        if self.counters.is_empty() {
+            return execute.await;
+        }
+        let counters: Vec<_> = self.counters.iter().map(|(query, params)| self.orm.query_update(query).bind(params.clone())).collect();
+        in_transaction(self.orm, IsolationLevel::Default, async {
+            self.orm.exec_all(&counters).await?;
+            execute.await
+        }).await
+    }
+}
+
+/// `Order` is an enumeration of the sort directions of `order_by`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    /// Ascending order.
+    #[default]
+    Asc,
+
+    /// Descending order.
+    Desc,
+}
+
+/// `Modifiers` holds the ORDER BY, LIMIT and OFFSET clauses of a query, so that they can be applied in any order
+/// and are always rendered in the order SQL expects, and the priority the query is executed with.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+pub(crate) struct Modifiers {
+    /// The length of the query without modifiers, set when the first modifier is applied.
+    base_len: Option<usize>,
+    order_by: Vec<String>,
+    limit: Option<i64>,
+    offset: Option<u64>,
+    low_priority: bool,
+}
+
+impl<'a, R, E, O: ORMTrait<O>> QueryBuilder<'a, R, E, O> {
+    /// Returns the SQL the builder executes, including the applied modifiers, without executing it.
+    /// The values of the `?` placeholders are returned by `params`.
+    pub fn sql(&self) -> &str {
+        &self.query
+    }
+
+    /// Returns the values bound to the `?` placeholders of the SQL, e.g. the fields of an entity written with `add`.
+    pub fn params(&self) -> &[Value] {
+        &self.params
+    }
+
+    /// Returns the builder with the query targeting `table` instead of the table of the entity, e.g. to write to
+    /// a partition like `events_2024_05` or to a per-tenant table with the same columns.
+    /// The query fails with `ORMError::InvalidValue` if `table` is not a plain table name or the query was not
+    /// generated for an entity table.
+    pub fn into_table(mut self, table: &str) -> Self {
+        let replaced = match &self.table {
+            Some(_) if !is_table_name(table) => Err(format!("invalid table name {:?}", table)),
+            Some(current) => replace_table(&self.query, current, table).ok_or_else(|| format!("table {} not found in query", current)),
+            None => Err("into_table requires a query generated for an entity table".to_string()),
+        };
+        match replaced {
+            Ok(query) => {
+                let current_len = self.table.as_ref().map_or(0, |current| current.len());
+                if let Some(base_len) = self.modifiers.base_len.as_mut() {
+                    *base_len = *base_len + table.len() - current_len;
+                }
+                self.query = query;
+                self.table = Some(table.to_string());
+            }
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+
+    /// Returns the table the query targets, if it was generated for an entity table.
+    pub fn table(&self) -> Option<&str> {
+        self.table.as_deref()
+    }
+
+    /// Writes the entity even if it fails its `Validate` check.
+    pub fn skip_validation(mut self) -> Self {
+        self.validation = None;
+        self
+    }
+
+    /// Returns the error that occurred while the query was built, if any.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn check(&self) -> Result<(), ORMError> {
+        if let Some(errors) = &self.validation {
+            return Err(ORMError::Validation(errors.clone()));
+        }
+        match &self.error {
+            Some(e) => Err(ORMError::InvalidValue(e.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the builder with `params` bound to the `?` placeholders of the query.
+    pub(crate) fn bind(mut self, params: Vec<Value>) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Returns the statement under which the query is recorded in a cassette, see `cassette::statement_key`.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn statement_key(&self, dialect: Dialect) -> std::borrow::Cow<'_, str> {
+        cassette::statement_key(&self.query, &self.params, dialect)
+    }
+
+    /// Returns the query as an update with its params, or the error that occurred while it was built.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn into_update(self) -> Result<QueryBuilder<'a, usize, (), O>, ORMError> {
+        self.check()?;
+        Ok(QueryBuilder {
+            query: self.query,
+            params: self.params,
+            counters: self.counters,
+            entity: std::marker::PhantomData,
+            orm: self.orm,
+            result: std::marker::PhantomData,
+            error: None,
+            modifiers: self.modifiers,
+            validation: None,
+            table: self.table,
+        })
+    }
+
+    /// Returns a copy of the builder with changed modifiers. `unlimited` is the LIMIT value the backend
+    /// uses for an OFFSET without a LIMIT.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn with_modifiers(&self, unlimited: &str, change: impl FnOnce(&mut Modifiers)) -> QueryBuilder<'a, R, E, O> {
+        let mut modifiers = self.modifiers.clone();
+        let base_len = *modifiers.base_len.get_or_insert(self.query.len());
+        change(&mut modifiers);
+        let mut query = self.query[..base_len].to_string();
+        if !modifiers.order_by.is_empty() {
+            query += &format!(" order by {}", modifiers.order_by.join(", "));
+        }
+        match (modifiers.limit, modifiers.offset) {
+            (Some(limit), None) => query += &format!(" limit {}", limit),
+            (Some(limit), Some(offset)) => query += &format!(" limit {} offset {}", limit, offset),
+            (None, Some(offset)) => query += &format!(" limit {} offset {}", unlimited, offset),
+            (None, None) => {}
+        }
+        QueryBuilder {
+            query,
+            params: self.params.clone(),
+            counters: self.counters.clone(),
+            entity: std::marker::PhantomData,
+            orm: self.orm,
+            result: std::marker::PhantomData,
+            error: self.error.clone(),
+            modifiers,
+            validation: self.validation.clone(),
+            table: self.table.clone(),
+        }
+    }
+
+    /// Returns a copy of the builder that is executed with `orm`, e.g. the backend of an `AnyORM`.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn with_orm<'b, P: ORMTrait<P>>(&self, orm: &'b P) -> QueryBuilder<'b, R, E, P> {
+        QueryBuilder {
+            query: self.query.clone(),
+            params: self.params.clone(),
+            counters: self.counters.clone(),
+            entity: std::marker::PhantomData,
+            orm,
+            result: std::marker::PhantomData,
+            error: self.error.clone(),
+            modifiers: self.modifiers.clone(),
+            validation: self.validation.clone(),
+            table: self.table.clone(),
+        }
+    }
+
+    /// Returns a statement that counts the records of the query without its modifiers, with the same params.
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn count_statement(&self) -> String {
+        let base_len = self.modifiers.base_len.unwrap_or(self.query.len());
+        format!("select count(*) from ({}) as counted", &self.query[..base_len])
+    }
+}
+
+impl<'a, E, O: ORMTrait<O>> QueryBuilder<'a, usize, E, O> {
+    /// Marks the statement as a low priority write, e.g. of a bulk maintenance job, so that it does not hold up
+    /// interactive statements. MySQL executes it with `LOW_PRIORITY`, which delays it while other sessions read the table
+    /// (only for storage engines with table-level locking, such as MyISAM). SQLite executes it only when no other
+    /// statement of the ORM is waiting for the connection. The priority applies to `run` and `exec`.
+    pub fn low_priority(mut self) -> Self {
+        self.modifiers.low_priority = true;
+        self
+    }
+
+    /// Returns `true` if the statement is a low priority write.
+    pub fn is_low_priority(&self) -> bool {
+        self.modifiers.low_priority
+    }
+}
+
+/// Returns the INSERT statement of an entity with a `?` placeholder per field and the params of the fields,
+/// or the error that prevents the entity from being serialized.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn insert_statement<T: Serialize + TableDeserialize>(table_name: &str, data: &T, options: &ConnectOptions) -> Result<Statement, String> {
+    let fields = serializer_fields::to_params(data, &T::columns(), options.datetime_policy, options.float_policy).map_err(|e| format!("{:?}", e))?;
+    let columns: Vec<&str> = fields.iter().map(|(field, _)| field.as_str()).collect();
+    let placeholders = vec!["?"; fields.len()];
+    let query = format!("insert into {} ({}) values ({})", table_name, columns.join(","), placeholders.join(","));
+    Ok((query, fields.into_iter().map(|(_, value)| value).collect()))
+}
+
+/// Returns the multi-row INSERT statement of `rows` with a group of `?` placeholders per row, the inserted columns
+/// and the params of all rows, or the error that prevents a row from being serialized. `with_id` inserts the IDs too.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn insert_rows_statement<T: Serialize + TableDeserialize>(table_name: &str, rows: &[T], with_id: bool, options: &ConnectOptions)
+                                                                    -> Result<(String, Vec<String>, Vec<Value>), ORMError> {
+    let definitions = T::columns();
+    let mut columns = Vec::new();
+    let mut groups = Vec::with_capacity(rows.len());
+    let mut params = Vec::new();
+    for row in rows {
+        let fields = if with_id {
+            serializer_fields::to_params_with_id(row, &definitions, options.datetime_policy, options.float_policy)
+        } else {
+            serializer_fields::to_params(row, &definitions, options.datetime_policy, options.float_policy)
+        }.map_err(|e| ORMError::InvalidValue(format!("{:?}", e)))?;
+        if columns.is_empty() {
+            columns = fields.iter().map(|(field, _)| field.clone()).collect();
+        }
+        groups.push(format!("({})", vec!["?"; fields.len()].join(",")));
+        params.extend(fields.into_iter().map(|(_, value)| value));
+    }
+    let query = format!("insert into {} ({}) values {}", table_name, columns.join(","), groups.join(", "));
+    Ok((query, columns, params))
+}
+
+/// Returns the UPDATE statement of the `fields` of the entity with the ID `id` with a `?` placeholder per field
+/// and the params of the fields and the ID, or the error that prevents the entity from being serialized.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn update_fields_statement<T: Serialize + TableDeserialize>(table_name: &str, data: &T, fields: &[&str], id: &str, options: &ConnectOptions) -> Result<Statement, String> {
+    let values: Vec<(String, Value)> = serializer_fields::to_params(data, &T::columns(), options.datetime_policy, options.float_policy)
+        .map_err(|e| format!("{:?}", e))?
+        .into_iter()
+        .filter(|(field, _)| fields.contains(&field.as_str()))
+        .collect();
+    let assignments: Vec<String> = values.iter().map(|(field, _)| format!("{} = ?", field)).collect();
+    let query = format!("update {} set {} where id = ?", table_name, assignments.join(","));
+    let mut params: Vec<Value> = values.into_iter().map(|(_, value)| value).collect();
+    params.push(id_param(id));
+    Ok((query, params))
+}
+
+/// Returns the UPDATE statement of the entity with the ID `id` with a `?` placeholder per field and the params
+/// of the fields and the ID, or the error that prevents the entity from being serialized.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn update_statement<T: Serialize + TableDeserialize>(table_name: &str, data: &T, id: &str, options: &ConnectOptions) -> Result<Statement, String> {
+    let fields = serializer_fields::to_params(data, &T::columns(), options.datetime_policy, options.float_policy).map_err(|e| format!("{:?}", e))?;
+    let assignments: Vec<String> = fields.iter().map(|(field, _)| format!("{} = ?", field)).collect();
+    let query = format!("update {} set {} where id = ?", table_name, assignments.join(","));
+    let mut params: Vec<Value> = fields.into_iter().map(|(_, value)| value).collect();
+    params.push(id_param(id));
+    Ok((query, params))
+}
+
+/// Returns the counter cache updates of inserting (`increment`) or removing `data` with the ID `id` from `table_name`,
+/// see `ColumnDef::counter_cache`. A decrement only applies while the row exists, so removing a row that is gone
+/// already leaves the counter unchanged.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn counter_statements<T: TableDeserialize + Serialize>(table_name: &str, data: &T, id: &str, increment: bool) -> Vec<Statement> {
+    T::columns().iter()
+        .filter_map(|column| {
+            let (counter, foreign_key) = (column.counter_cache.as_ref()?, column.foreign_key.as_ref()?);
+            let value = serializer_fields::field_value(data, &column.name).ok().flatten().filter(|value| !value.is_null())?;
+            let operator = if increment { "+" } else { "-" };
+            let mut query = format!("update {} set {} = {} {} 1 where {} = ?", foreign_key.table, counter, counter, operator, foreign_key.column);
+            let mut params = vec![value];
+            if !increment {
+                query += &format!(" and exists (select 1 from {} where id = ?)", table_name);
+                params.push(id_param(id));
+            }
+            Some((query, params))
+        })
+        .collect()
+}
+
+/// Returns the statements of `queries` with their params in the order they are executed: the counter cache updates
+/// of a query precede it. The flag is `true` for the queries themselves, whose affected rows are reported.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn exec_all_statements<'q, O: ORMTrait<O>>(queries: &'q [QueryBuilder<'_, usize, (), O>]) -> Vec<(&'q str, &'q [Value], bool)> {
+    queries.iter()
+        .flat_map(|qb| qb.counters.iter()
+            .map(|(counter, params)| (counter.as_str(), params.as_slice(), false))
+            .chain(std::iter::once((qb.query.as_str(), qb.params.as_slice(), true))))
+        .collect()
+}
+
+/// The maximum depth of the hierarchy walked by `ORMTrait::descendants_of` and `ORMTrait::ancestors_of`,
+/// below the default `cte_max_recursion_depth` of MySQL.
+pub const TREE_MAX_DEPTH: usize = 999;
+
+/// Returns the SELECT statement of the descendants, or the ancestors, of the record `id` of `table_name`
+/// in the hierarchy of its `parent_id` column, see `ORMTrait::descendants_of`.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn tree_statement(table_name: &str, id: u64, descendants: bool) -> String {
+    let (start, step) = if descendants {
+        (format!("select id, 1 from {table_name} where parent_id = {id}"),
+         format!("select node.id, parvati_tree.depth + 1 from {table_name} node join parvati_tree on node.parent_id = parvati_tree.id \
+                  where parvati_tree.depth < {TREE_MAX_DEPTH}"))
+    } else {
+        (format!("select parent_id, 1 from {table_name} where id = {id} and parent_id is not null"),
+         format!("select node.parent_id, parvati_tree.depth + 1 from {table_name} node join parvati_tree on node.id = parvati_tree.id \
+                  where parvati_tree.depth < {TREE_MAX_DEPTH} and node.parent_id is not null"))
+    };
+    format!("with recursive parvati_tree (id, depth) as ({start} union all {step}) \
+             select node.* from {table_name} node join (select id, min(depth) as depth from parvati_tree group by id) tree \
+             on node.id = tree.id order by tree.depth, node.id")
+}
+
+/// Returns the param of the ID `id` as returned by `TableSerialize::get_id`.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn id_param(id: &str) -> Value {
+    id.parse().map(Value::Int).unwrap_or_else(|_| Value::Text(id.to_string()))
+}
+
+/// Returns the INSERT, REPLACE, UPDATE or DELETE statement with the MySQL `LOW_PRIORITY` modifier,
+/// e.g. `delete low_priority from log where ...`. Other statements are returned unchanged.
+#[cfg(feature = "mysql")]
+pub(crate) fn low_priority_statement(query: &str) -> String {
+    let trimmed = query.trim_start();
+    let keyword_len = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+    let keyword = trimmed[..keyword_len].to_ascii_lowercase();
+    let rest = trimmed[keyword_len..].trim_start();
+    let already = rest.get(..12).is_some_and(|next| next.eq_ignore_ascii_case("low_priority"));
+    match keyword.as_str() {
+        "insert" | "replace" | "update" | "delete" if !already => format!("{} LOW_PRIORITY {}", &trimmed[..keyword_len], rest),
+        _ => query.to_string(),
+    }
+}
+
+// Returns `true` if `table` is a plain, optionally schema-qualified table name that is safe to put into a query.
+fn is_table_name(table: &str) -> bool {
+    !table.is_empty() && table.split('.').all(|part| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+// Replaces the first occurrence of the table name `current` in `query`, which follows a space and is followed by
+// a space, an opening parenthesis or the end of the query, e.g. in `update user set ...` or `select * from user`.
+fn replace_table(query: &str, current: &str, table: &str) -> Option<String> {
+    let mut start = 0;
+    while let Some(found) = query[start..].find(current) {
+        let begin = start + found;
+        let end = begin + current.len();
+        let before = query[..begin].ends_with(' ');
+        let after = query[end..].chars().next().is_none_or(|c| c == ' ' || c == '(');
+        if before && after {
+            return Some(format!("{}{}{}", &query[..begin], table, &query[end..]));
+        }
+        start = end;
+    }
+    None
+}
+
+/// `Batch` is a list of statements that are sent to the database together.
+/// On MySQL the statements are pipelined in a single round trip, on SQLite they are executed one after another
+/// while holding the connection.
+pub struct Batch<'a, O: ORMTrait<O>> {
+    /// `queries` contains the statements in the order they were pushed.
+    queries: Vec<String>,
+
+    /// `orm` is a reference to an ORM object that implements the `ORMTrait`.
+    orm: &'a O,
+}
+
+impl<'a, O: ORMTrait<O>> Batch<'a, O> {
+    /// Appends a statement to the batch.
+    pub fn push(mut self, query: &str) -> Self {
+        self.queries.push(query.to_string());
+        self
+    }
+
+    /// Returns the number of statements in the batch.
+    pub fn len(&self) -> usize {
+        self.queries.len()
+    }
+
+    /// Returns `true` if the batch contains no statements.
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+}
+
+/// `AddMany` inserts a list of entities and reads them back, see `ORMTrait::add_many`.
+/// The rows are written with multi-row statements of at most `chunk_size` rows each, all inside one transaction.
+pub struct AddMany<'a, T, O: ORMTrait<O>> {
+    /// `rows` are the entities to insert.
+    rows: Vec<T>,
+
+    /// `chunk_size` is the maximum number of rows of one statement.
+    chunk_size: usize,
+
+    /// `orm` is a reference to an ORM object that implements the `ORMTrait`.
+    #[cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+    orm: &'a O,
+}
+
+impl<'a, T, O: ORMTrait<O>> AddMany<'a, T, O> {
+    /// The default maximum number of rows of one statement.
+    pub const DEFAULT_CHUNK_SIZE: usize = 1000;
+
+    /// Constructs the `AddMany` that inserts `rows` with `orm`.
+    #[cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+    pub(crate) fn new(orm: &'a O, rows: Vec<T>) -> Self {
+        AddMany { rows, chunk_size: Self::DEFAULT_CHUNK_SIZE, orm }
+    }
+
+    /// Sets the maximum number of rows of one statement.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Returns the number of rows to insert.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns `true` if there are no rows to insert.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+impl<'a, T, O> AddMany<'a, T, O>
+    where T: for<'de> Deserialize<'de> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + Send + Sync + 'static,
+          O: ORMTrait<O> + Sync
+{
+    /// Inserts the rows and returns them as they are stored, in the order of the rows.
+    /// If a chunk fails, e.g. because a row fails its `TableSerialize::validation` check, nothing is inserted.
+    pub async fn apply(&self) -> Result<Vec<T>, ORMError> {
+        if self.rows.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids = in_transaction(self.orm, IsolationLevel::Default, async {
+            let mut ids = Vec::with_capacity(self.rows.len());
+            for chunk in self.rows.chunks(self.chunk_size) {
+                ids.extend(self.orm.add_all(chunk.to_vec()).await?);
+            }
+            Ok::<_, ORMError>(ids)
+        }).await?;
+        self.orm.find_by_ids::<T>(&ids).await?.into_iter()
+            .map(|row| row.ok_or(ORMError::InsertError))
+            .collect()
+    }
+}
+
+/// `UpsertAll` inserts a list of entities and updates the rows that already exist, see `ORMTrait::upsert_all`.
+/// The rows are written with multi-row statements of at most `chunk_size` rows each, all inside one transaction.
+pub struct UpsertAll<'a, T, O: ORMTrait<O>> {
+    /// `rows` are the entities to write.
+    rows: Vec<T>,
+
+    /// `conflict_columns` identify an existing row; `id` if empty.
+    conflict_columns: Vec<String>,
+
+    /// `chunk_size` is the maximum number of rows of one statement.
+    chunk_size: usize,
+
+    /// `orm` is a reference to an ORM object that implements the `ORMTrait`.
+    orm: &'a O,
+}
+
+impl<'a, T, O: ORMTrait<O>> UpsertAll<'a, T, O> {
+    /// The default maximum number of rows of one statement.
+    pub const DEFAULT_CHUNK_SIZE: usize = 1000;
+
+    /// Adds a column that identifies an existing row. Call it several times for a composite key.
+    /// If the key does not contain `id`, new rows get a generated ID.
+    /// SQLite needs a unique index on exactly these columns; MySQL matches any unique key of the table.
+    pub fn on_conflict(mut self, column: &str) -> Self {
+        self.conflict_columns.push(column.to_string());
+        self
+    }
+
+    /// Sets the maximum number of rows of one statement.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Returns the number of rows to write.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns `true` if there are no rows to write.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+impl<'a, T: TableDeserialize + TableSerialize + Serialize, O: ORMTrait<O>> UpsertAll<'a, T, O> {
+    /// Renders one statement per chunk with the table name and the policies of `options`, together with its params.
+    /// `on_conflict` renders the backend clause from the conflict columns and the columns to update.
+    pub(crate) fn statements(&self, options: &ConnectOptions,
+                             on_conflict: impl Fn(&[String], &[String]) -> String) -> Result<Vec<Statement>, ORMError> {
+        if self.rows.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conflict_columns = if self.conflict_columns.is_empty() { vec!["id".to_string()] } else { self.conflict_columns.clone() };
+        let fields = T::fields();
+        if let Some(column) = conflict_columns.iter().find(|column| !fields.contains(column)) {
+            return Err(ORMError::InvalidValue(format!("unknown conflict column {}", column)));
+        }
+        let with_id = conflict_columns.iter().any(|column| column == "id");
+        let table_name = options.table_name::<T>();
+        let mut statements = Vec::new();
+        for chunk in self.rows.chunks(self.chunk_size) {
+            let (query, columns, params) = insert_rows_statement(&table_name, chunk, with_id, options)?;
+            let update_columns: Vec<String> = columns.into_iter()
+                .filter(|column| column != "id" && !conflict_columns.contains(column))
+                .collect();
+            statements.push((format!("{} {}", query, on_conflict(&conflict_columns, &update_columns)), params));
+        }
+        Ok(statements)
+    }
+}
+
+/// `AddOrUpdate` inserts an entity or updates the row that already exists and reads it back, see `ORMTrait::add_or_update`.
+/// The entity is written with the upsert statement of the backend, `INSERT ... ON CONFLICT (...) DO UPDATE` for SQLite
+/// and `INSERT ... ON DUPLICATE KEY UPDATE` for MySQL.
+pub struct AddOrUpdate<'a, T, O: ORMTrait<O>> {
+    /// `upsert` writes the entity as a single row.
+    upsert: UpsertAll<'a, T, O>,
+}
+
+impl<'a, T, O: ORMTrait<O>> AddOrUpdate<'a, T, O> {
+    /// Constructs the `AddOrUpdate` that writes `data` with `orm`.
+    #[cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+    pub(crate) fn new(orm: &'a O, data: T) -> Self {
+        let upsert = UpsertAll { rows: vec![data], conflict_columns: Vec::new(), chunk_size: 1, orm };
+        AddOrUpdate { upsert }
+    }
+
+    /// Adds a column that identifies an existing row, see `UpsertAll::on_conflict`. `id` by default.
+    pub fn on_conflict(mut self, column: &str) -> Self {
+        self.upsert = self.upsert.on_conflict(column);
+        self
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+impl<'a, T: TableDeserialize + TableSerialize + Serialize, O: ORMTrait<O>> AddOrUpdate<'a, T, O> {
+    /// Returns the condition that finds the written row by its conflict columns,
+    /// or `None` if the row is matched by an ID that is not set yet, so it is always inserted.
+    pub(crate) fn lookup(&self, options: &ConnectOptions) -> Result<Option<Cond>, ORMError> {
+        let row = &self.upsert.rows[0];
+        let fields = T::fields();
+        let values = serializer_fields::to_params(row, &T::columns(), options.datetime_policy, options.float_policy)
+            .map_err(|e| ORMError::InvalidValue(format!("{:?}", e)))?;
+        let mut conditions = Vec::new();
+        for column in self.upsert.conflict_columns.iter() {
+            if !fields.contains(column) {
+                return Err(ORMError::InvalidValue(format!("unknown conflict column {}", column)));
+            }
+            if let Some((_, value)) = values.iter().find(|(field, _)| field == column) {
+                conditions.push(Cond::field(column).eq(value.clone()));
+            }
+        }
+        if self.upsert.conflict_columns.is_empty() || self.upsert.conflict_columns.iter().any(|column| column == "id") {
+            let id = row.get_id();
+            if is_unset_id(&id) {
+                return Ok(None);
+            }
+            conditions.push(Cond::field("id").eq(id_param(&id)));
+        }
+        Ok(conditions.into_iter().reduce(Cond::and))
+    }
+}
+
+/// `ImportAll` inserts a list of entities with partial success, see `ORMTrait::import_all`.
+/// The rows are written with multi-row statements of at most `chunk_size` rows each inside one transaction, and every
+/// chunk is wrapped in a savepoint, so a bad row only rolls back the rows of its chunk and the import continues.
+pub struct ImportAll<'a, T, O: ORMTrait<O>> {
+    /// `rows` are the entities to insert.
+    rows: Vec<T>,
+
+    /// `chunk_size` is the maximum number of rows of one statement.
+    chunk_size: usize,
+
+    /// `orm` is a reference to an ORM object that implements the `ORMTrait`.
+    #[cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+    orm: &'a O,
+}
+
+impl<'a, T, O: ORMTrait<O>> ImportAll<'a, T, O> {
+    /// The default maximum number of rows of one statement.
+    pub const DEFAULT_CHUNK_SIZE: usize = 100;
+
+    /// Sets the maximum number of rows of one statement. Smaller chunks lose fewer rows to a bad row,
+    /// larger chunks are faster; a chunk size of 1 inserts every valid row.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Returns the number of rows to insert.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns `true` if there are no rows to insert.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+impl<'a, T: TableDeserialize + TableSerialize + Serialize, O: ORMTrait<O> + Sync> ImportAll<'a, T, O> {
+    /// Renders one statement per chunk with the table name and the policies of `options`, together with the range
+    /// of its rows and its params. A chunk with a row that fails its validation or cannot be serialized gets the error instead.
+    pub(crate) fn statements(&self, options: &ConnectOptions) -> Vec<(Range<usize>, Result<Statement, ORMError>)> {
+        let table_name = options.table_name::<T>();
+        self.rows.chunks(self.chunk_size).enumerate()
+            .map(|(index, chunk)| {
+                let start = index * self.chunk_size;
+                let statement = chunk.iter()
+                    .try_for_each(|row| row.validation().map_err(ORMError::Validation))
+                    .and_then(|_| insert_rows_statement(&table_name, chunk, false, options))
+                    .map(|(query, _, params)| (query, params));
+                (start..start + chunk.len(), statement)
+            })
+            .collect()
+    }
+
+    /// Executes the statements inside one transaction. `exec_all` wraps each of them in a savepoint,
+    /// so a failed statement is recorded in the report and only rolls back its own chunk.
+    pub(crate) async fn execute(&self, statements: Vec<(Range<usize>, Result<Statement, ORMError>)>) -> Result<ImportReport, ORMError> {
+        let mut report = ImportReport { inserted: 0, failures: Vec::new() };
+        if statements.is_empty() {
+            return Ok(report);
+        }
+        self.orm.begin(IsolationLevel::Default).await?;
+        for (rows, statement) in statements {
+            let result = match statement {
+                Ok((statement, params)) => self.orm.exec_all(&[self.orm.query_update(&statement).bind(params)]).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(inserted) => report.inserted += inserted,
+                Err(ORMError::NoConnection) => {
+                    let _ = self.orm.rollback().await;
+                    return Err(ORMError::NoConnection);
+                }
+                Err(error) => {
+                    log::warn!("import of rows {:?} failed: {:?}", rows, error);
+                    report.failures.push(ChunkFailure { rows, error });
+                }
+            }
+        }
+        self.orm.commit().await?;
+        Ok(report)
+    }
+}
+
+/// `ImportReport` is the result of `ImportAll::run`.
+#[derive(Debug)]
+pub struct ImportReport {
+    /// `inserted` is the number of inserted rows.
+    pub inserted: usize,
+
+    /// `failures` are the chunks that were rolled back, in the order of their rows.
+    pub failures: Vec<ChunkFailure>,
+}
+
+impl ImportReport {
+    /// Returns `true` if every row was inserted.
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Returns the indexes of the rows that were not inserted, e.g. to retry them with a chunk size of 1.
+    pub fn failed_rows(&self) -> Vec<usize> {
+        self.failures.iter().flat_map(|failure| failure.rows.clone()).collect()
+    }
+}
+
+/// `ChunkFailure` is a chunk of an `ImportAll` that was rolled back.
+#[derive(Debug)]
+pub struct ChunkFailure {
+    /// `rows` are the indexes of the rows of the chunk in the imported list.
+    pub rows: Range<usize>,
+
+    /// `error` is the error of the statement of the chunk.
+    pub error: ORMError,
+}
+
+/// `BlobReader` reads a large TEXT or BLOB value in chunks instead of loading it with its row, see `ORMTrait::read_blob`.
+/// SQLite reads the chunks with incremental blob I/O, MySQL with one `SUBSTRING` query per chunk.
+pub struct BlobReader<'a, T, O: ORMTrait<O>> {
+    /// `orm` is a reference to an ORM object that implements the `ORMTrait`.
+    orm: &'a O,
+
+    /// `id` is the ID of the record.
+    id: u64,
+
+    /// `column` is the column of the value.
+    column: String,
+
+    /// `offset` is the position of the next chunk.
+    offset: u64,
+
+    /// `len` is the length of the value in bytes, once it is known.
+    len: Option<u64>,
+
+    /// `chunk_size` is the maximum number of bytes of a chunk.
+    chunk_size: usize,
+
+    entity: std::marker::PhantomData<T>,
+}
+
+impl<'a, T: TableDeserialize + 'static, O: ORMTrait<O>> BlobReader<'a, T, O> {
+    /// The default maximum number of bytes of a chunk.
+    pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn new(orm: &'a O, id: u64, column: &str) -> Self {
+        BlobReader {
+            orm,
+            id,
+            column: column.to_string(),
+            offset: 0,
+            len: None,
+            chunk_size: BlobReader::<T, O>::DEFAULT_CHUNK_SIZE,
+            entity: std::marker::PhantomData,
+        }
+    }
+
+    /// Sets the maximum number of bytes of a chunk.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Returns the length of the value in bytes; 0 if it is NULL.
+    /// It fails with `ORMError::NotFound` if there is no record with the ID.
+    pub async fn len(&mut self) -> Result<u64, ORMError> {
+        if let Some(len) = self.len {
+            return Ok(len);
+        }
+        let len = self.orm.blob_length::<T>(self.id, &self.column).await?;
+        self.len = Some(len);
+        Ok(len)
+    }
+
+    /// Returns the next chunk of the value, or `None` after the last one.
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, ORMError> {
+        let len = self.len().await?;
+        if self.offset >= len {
+            return Ok(None);
+        }
+        let size = (len - self.offset).min(self.chunk_size as u64) as usize;
+        let chunk = self.orm.read_blob_chunk::<T>(self.id, &self.column, self.offset, size).await?;
+        if chunk.is_empty() {
+            return Ok(None);
+        }
+        self.offset += chunk.len() as u64;
+        Ok(Some(chunk))
+    }
+
+    /// Writes the rest of the value to `writer` chunk by chunk and returns the number of written bytes.
+    pub async fn copy_to<W: std::io::Write>(&mut self, writer: &mut W) -> Result<u64, ORMError> {
+        let mut written = 0;
+        while let Some(chunk) = self.next_chunk().await? {
+            writer.write_all(&chunk)?;
+            written += chunk.len() as u64;
+        }
+        Ok(written)
+    }
+
+    /// Returns the chunks of the rest of the value as a stream.
+    pub fn into_stream(self) -> impl futures::Stream<Item = Result<Vec<u8>, ORMError>> + 'a {
+        futures::stream::unfold(Some(self), |reader| async move {
+            let mut reader = reader?;
+            match reader.next_chunk().await {
+                Ok(Some(chunk)) => Some((Ok(chunk), Some(reader))),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{ORMError, RetryPolicy, Value};
+
+    #[tokio::test]
+    async fn test() -> Result<(), ORMError> {
+        Ok(())
+    }
+
+    #[test]
+    fn test_where_macro() {
+        let min_age = 18;
+        let name = "O\"Brien".to_string();
+        let ids = [1, 2];
+        assert_eq!(crate::r#where!(age >= #min_age), crate::Cond::with_params("age >= ?", vec![Value::Int(18)]));
+        let condition = crate::r#where!(name == #name || !(user.age != #(min_age + 1)));
+        assert_eq!(condition.sql(), "name = ? or not (user.age <> ?)");
+        assert_eq!(condition.params(), &[Value::Text("O\"Brien".to_string()), Value::Int(19)]);
+        let condition = crate::r#where!(id in (#(ids[0]), #(ids[1])) && deleted_at is null);
+        assert_eq!(condition.sql(), "id in (?, ?) and deleted_at is null");
+        assert_eq!(condition.params(), &[Value::Int(1), Value::Int(2)]);
+        let condition = crate::r#where!(name like "J%\"" && age > -1 && score - 2.5 > 0);
+        assert_eq!(condition.sql(), "name like ? and age > - ? and score - ? > ?");
+        assert_eq!(condition.params(), &[Value::Text("J%\"".to_string()), Value::Int(1), Value::Float(2.5), Value::Int(0)]);
+    }
+
+    #[test]
+    fn test_replace_table() {
+        assert_eq!(super::replace_table("select * from user", "user", "user_2").unwrap(), "select * from user_2");
+        assert_eq!(super::replace_table("update user set user_name = \"user\" where id = 1", "user", "tenant_1.user").unwrap(),
+                   "update tenant_1.user set user_name = \"user\" where id = 1");
+        assert_eq!(super::replace_table("insert into user(name) values (\"x\")", "user", "u").unwrap(), "insert into u(name) values (\"x\")");
+        assert!(super::replace_table("select * from users", "user", "u").is_none());
+        assert!(super::is_table_name("events_2024_05") && super::is_table_name("tenant.events"));
+        assert!(!super::is_table_name("events; drop table user") && !super::is_table_name("") && !super::is_table_name("a."));
+    }
+
+    #[test]
+    fn test_to_sql_literal() {
+        use crate::{Dialect, FloatPolicy, ToSqlLiteral};
+        let literal = |value: &dyn ToSqlLiteral, dialect| value.to_sql_literal(dialect, FloatPolicy::Error).unwrap();
+        assert_eq!(literal(&"it's", Dialect::Sqlite), "'it''s'");
+        assert_eq!(literal(&"a\\' or 1=1 -- \"", Dialect::Sqlite), "'a\\'' or 1=1 -- \"'");
+        assert_eq!(literal(&"a\\' or 1=1 -- \"", Dialect::MySql), "'a\\\\'' or 1=1 -- \"'");
+        assert_eq!(literal(&Value::Text("x".to_string()), Dialect::MySql), "'x'");
+        assert_eq!(literal(&Some(7), Dialect::Sqlite), "7");
+        assert_eq!(literal(&-1.5f64, Dialect::Sqlite), "-1.5");
+        assert!(f64::NAN.to_sql_literal(Dialect::Sqlite, FloatPolicy::Error).is_err());
+        assert_eq!(Some(f32::INFINITY).to_sql_literal(Dialect::MySql, FloatPolicy::Null).unwrap(), "NULL");
+    }
+
+    #[cfg(feature = "mysql")]
+    #[test]
+    fn test_low_priority_statement() {
+        use super::low_priority_statement;
+        assert_eq!(low_priority_statement("delete from log where id < 10"), "delete LOW_PRIORITY from log where id < 10");
+        assert_eq!(low_priority_statement("  UPDATE user set age = 1"), "UPDATE LOW_PRIORITY user set age = 1");
+        assert_eq!(low_priority_statement("insert low_priority into log values (1)"), "insert low_priority into log values (1)");
+        assert_eq!(low_priority_statement("create table log (id INT)"), "create table log (id INT)");
+    }
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy::new(5).initial_delay(Duration::from_millis(100)).max_delay(Duration::from_secs(1));
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(4), Duration::from_millis(800));
+        assert_eq!(policy.delay(5), Duration::from_secs(1));
+        assert_eq!(policy.delay(1000), Duration::from_secs(1));
     }
 }
\ No newline at end of file
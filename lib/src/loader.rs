@@ -0,0 +1,116 @@
+//! `loader` is a module for batching the lookups of records by ID, e.g. in the resolvers of a GraphQL layer:
+//!
+//! ```ignore
+//! let authors = Loader::<Author, _>::new(&conn);
+//! // the three lookups are sent as one `WHERE id IN (...)` query
+//! let (a, b, c) = futures::join!(authors.load(1), authors.load(2), authors.load(1));
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+use std::time::Duration;
+use futures::channel::oneshot;
+use serde::Deserialize;
+use crate::{ORMError, ORMTrait, TableDeserialize, TableSerialize};
+
+/// `LoadResult` is the record of one lookup, sent from the lookup that runs the batch to the waiting one.
+type LoadResult<T> = Result<Option<T>, ORMError>;
+
+/// `Loader` coalesces the `load` calls of concurrent futures or tasks within a time window into one
+/// `find_by_ids` query and distributes the records to the callers.
+pub struct Loader<'a, T, O: ORMTrait<O>> {
+    /// `orm` is a reference to an ORM object that implements the `ORMTrait`.
+    orm: &'a O,
+
+    /// `window` is the time a lookup waits for other lookups before the batch is queried.
+    window: Duration,
+
+    /// `max_batch` is the maximum number of IDs of one query.
+    max_batch: usize,
+
+    /// `pending` are the IDs of the lookups of the next batch and the channels of their callers.
+    pending: Mutex<Vec<(u64, oneshot::Sender<LoadResult<T>>)>>,
+
+    entity: PhantomData<T>,
+}
+
+impl<'a, T, O> Loader<'a, T, O>
+    where T: for<'de> Deserialize<'de> + TableDeserialize + TableSerialize + Debug + Clone + Send + Sync + 'static,
+          O: ORMTrait<O> + Sync
+{
+    /// The default time a lookup waits for other lookups.
+    pub const DEFAULT_WINDOW: Duration = Duration::from_millis(2);
+
+    /// The default maximum number of IDs of one query.
+    pub const DEFAULT_MAX_BATCH: usize = 500;
+
+    /// Constructs a `Loader` of the records of `T`.
+    pub fn new(orm: &'a O) -> Self {
+        Loader {
+            orm,
+            window: Self::DEFAULT_WINDOW,
+            max_batch: Self::DEFAULT_MAX_BATCH,
+            pending: Mutex::new(Vec::new()),
+            entity: PhantomData,
+        }
+    }
+
+    /// Sets the time a lookup waits for other lookups before the batch is queried.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the maximum number of IDs of one query; larger batches are split into several queries.
+    pub fn max_batch(mut self, max_batch: usize) -> Self {
+        self.max_batch = max_batch.max(1);
+        self
+    }
+
+    /// Returns the record with the given ID, or `None` if there is none.
+    /// If the query of the batch fails, every lookup of the batch returns `ORMError::Loader` with its error.
+    pub async fn load(&self, id: u64) -> Result<Option<T>, ORMError> {
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().push((id, sender));
+        tokio::time::sleep(self.window).await;
+        // the first lookup that wakes up queries the batch, including the lookups of callers that were cancelled
+        self.dispatch().await;
+        receiver.await.unwrap_or_else(|_| Err(ORMError::Loader("the lookup of the batch was cancelled".to_string())))
+    }
+
+    /// Returns the records with the given IDs in the order of `ids`, with `None` for an ID that has no record.
+    pub async fn load_many(&self, ids: &[u64]) -> Result<Vec<Option<T>>, ORMError> {
+        futures::future::try_join_all(ids.iter().map(|id| self.load(*id))).await
+    }
+
+    // Queries the pending lookups and sends each caller its record.
+    async fn dispatch(&self) {
+        let batch = std::mem::take(&mut *self.pending.lock().unwrap());
+        if batch.is_empty() {
+            return;
+        }
+        let mut ids: Vec<u64> = batch.iter().map(|(id, _)| *id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        let mut found: HashMap<u64, T> = HashMap::new();
+        let mut error = None;
+        for chunk in ids.chunks(self.max_batch) {
+            match self.orm.find_by_ids::<T>(chunk).await {
+                Ok(records) => found.extend(chunk.iter().zip(records).filter_map(|(id, record)| record.map(|record| (*id, record)))),
+                Err(e) => {
+                    error = Some(format!("{:?}", e));
+                    break;
+                }
+            }
+        }
+        for (id, sender) in batch {
+            let result = match &error {
+                Some(e) => Err(ORMError::Loader(e.clone())),
+                None => Ok(found.get(&id).cloned()),
+            };
+            let _ = sender.send(result);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! `manager` is a module for applications that use several databases of the same backend, e.g. `main`, `analytics`
+//! and `cache`, configured in one TOML file:
+//!
+//! ```toml
+//! [databases.main]
+//! url = "mysql://app@db:3306/app"
+//!
+//! [databases.analytics]
+//! url = "mysql://app@warehouse:3306/events"
+//! ```
+
+use std::sync::Arc;
+use crate::{HealthReport, ORMError, ORMTrait};
+
+/// `DbManager` holds the ORMs of several databases by name and closes them together.
+pub struct DbManager<O> {
+    databases: Vec<(String, Arc<O>)>,
+}
+
+impl<O> Default for DbManager<O> {
+    fn default() -> Self {
+        DbManager { databases: Vec::new() }
+    }
+}
+
+impl<O: ORMTrait<O> + Send + Sync> DbManager<O> {
+    /// Constructs a `DbManager` without databases.
+    pub fn new() -> Self {
+        DbManager::default()
+    }
+
+    /// Adds the ORM of the database `name` and returns the ORM it replaces, if there was one.
+    pub fn insert(&mut self, name: &str, orm: Arc<O>) -> Option<Arc<O>> {
+        match self.databases.iter_mut().find(|(existing, _)| existing == name) {
+            Some((_, existing)) => Some(std::mem::replace(existing, orm)),
+            None => {
+                self.databases.push((name.to_string(), orm));
+                None
+            }
+        }
+    }
+
+    /// Returns the ORM of the database `name`, or `ORMError::NotFound` if there is none.
+    pub fn get(&self, name: &str) -> Result<Arc<O>, ORMError> {
+        self.databases.iter().find(|(existing, _)| existing == name)
+            .map(|(_, orm)| orm.clone())
+            .ok_or_else(|| ORMError::NotFound(format!("database {}", name)))
+    }
+
+    /// Returns the names of the databases in the order they were added.
+    pub fn names(&self) -> Vec<&str> {
+        self.databases.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Checks every database, see `ORMTrait::health`.
+    pub async fn health(&self) -> Vec<(String, HealthReport)> {
+        let mut reports = Vec::with_capacity(self.databases.len());
+        for (name, orm) in self.databases.iter() {
+            reports.push((name.clone(), orm.health().await));
+        }
+        reports
+    }
+
+    /// Closes every database in the reverse order they were added, also if closing one of them fails.
+    /// Returns the first error.
+    pub async fn close(&self) -> Result<(), ORMError> {
+        let mut result = Ok(());
+        for (name, orm) in self.databases.iter().rev() {
+            if let Err(e) = orm.close().await {
+                log::warn!("closing database {} failed: {:?}", name, e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,120 @@
+//! `metadata` is a module that describes entity tables at runtime, so that generic tools, e.g. admin UIs or
+//! exporters, can work with any entity without knowing its type.
+//!
+//! The description is built from what `#[derive(TableDeserialize)]` generates. `table::<User>()` returns it and
+//! registers the table, so that it is listed by `tables()` and found by name with `find("user")`.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use crate::{ColumnDef, ForeignKey, TableDeserialize};
+
+/// `TableMeta` describes an entity table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableMeta {
+    /// The name of the table.
+    pub name: String,
+    /// The Rust type of the entity, e.g. `app::model::User`.
+    pub entity: &'static str,
+    /// The documentation of the table, given with `#[table(comment = "...")]`.
+    pub comment: Option<String>,
+    /// The columns of the table in field order.
+    pub columns: Vec<ColumnDef>,
+}
+
+impl TableMeta {
+    /// Describes the table of `T`.
+    pub fn of<T: TableDeserialize + 'static>() -> Self {
+        TableMeta {
+            name: T::same_name(),
+            entity: std::any::type_name::<T>(),
+            comment: T::table_comment(),
+            columns: T::columns(),
+        }
+    }
+
+    /// Returns the column with the given name.
+    pub fn column(&self, name: &str) -> Option<&ColumnDef> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// Returns the names of the columns.
+    pub fn column_names(&self) -> Vec<&str> {
+        self.columns.iter().map(|column| column.name.as_str()).collect()
+    }
+
+    /// Returns the primary key column.
+    pub fn primary_key(&self) -> Option<&ColumnDef> {
+        self.columns.iter().find(|column| column.is_primary_key())
+    }
+
+    /// Returns the columns that reference other tables with their foreign keys.
+    pub fn foreign_keys(&self) -> Vec<(&str, &ForeignKey)> {
+        self.columns.iter()
+            .filter_map(|column| column.foreign_key.as_ref().map(|foreign_key| (column.name.as_str(), foreign_key)))
+            .collect()
+    }
+}
+
+fn registry() -> &'static Mutex<BTreeMap<String, TableMeta>> {
+    static REGISTRY: OnceLock<Mutex<BTreeMap<String, TableMeta>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+/// Registers the table of `T` and returns its description.
+/// An entity registered under the name of another entity's table replaces it.
+pub fn table<T: TableDeserialize + 'static>() -> TableMeta {
+    let meta = TableMeta::of::<T>();
+    registry().lock().unwrap().insert(meta.name.clone(), meta.clone());
+    meta
+}
+
+/// Registers the table of `T`, e.g. at startup, so that it is listed by `tables`.
+pub fn register<T: TableDeserialize + 'static>() {
+    table::<T>();
+}
+
+/// Returns the descriptions of the registered tables, ordered by table name.
+pub fn tables() -> Vec<TableMeta> {
+    registry().lock().unwrap().values().cloned().collect()
+}
+
+/// Returns the description of the registered table with the given name.
+pub fn find(name: &str) -> Option<TableMeta> {
+    registry().lock().unwrap().get(name).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ColumnDef, ColumnType, ForeignKey, TableDeserialize};
+    use super::{find, register, table};
+
+    struct Order;
+
+    impl TableDeserialize for Order {
+        fn same_name() -> String {
+            "metadata_order".to_string()
+        }
+
+        fn columns() -> Vec<ColumnDef> {
+            vec![
+                ColumnDef::new("id", ColumnType::Integer),
+                ColumnDef::new("user_id", ColumnType::Integer).references(ForeignKey::new("user", "id")),
+                ColumnDef::new("note", ColumnType::Text).nullable(true),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        assert!(find("metadata_order").is_none());
+        register::<Order>();
+        let meta = find("metadata_order").unwrap();
+        assert_eq!(meta, table::<Order>());
+        assert!(meta.entity.ends_with("Order"));
+        assert_eq!(meta.column_names(), vec!["id", "user_id", "note"]);
+        assert_eq!(meta.primary_key().unwrap().name, "id");
+        assert!(meta.column("note").unwrap().nullable);
+        assert_eq!(meta.foreign_keys()[0].0, "user_id");
+        assert_eq!(meta.foreign_keys()[0].1.table, "user");
+    }
+}
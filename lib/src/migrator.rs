@@ -0,0 +1,652 @@
+//! `migrator` is a module for versioned migrations: a directory of numbered scripts, each with an up script
+//! and an optional down script that reverts it:
+//!
+//! ```text
+//! migrations/
+//!     001_create_user.up.sql
+//!     001_create_user.down.sql
+//!     002_add_user_email.up.sql
+//! ```
+//!
+//! ```ignore
+//! let migrator = Migrator::new(&*conn, "migrations");
+//! let applied = migrator.migrate_up().await?;
+//! migrator.migrate_down(1).await?;
+//! ```
+//!
+//! The applied versions are recorded in a tracking table, so every pending script is applied exactly once,
+//! also if it is numbered lower than the applied ones. A script may contain several statements separated by `;`.
+//! The statements of a script and its record in the tracking table are executed in one transaction, but MySQL
+//! commits DDL statements implicitly, so a failing MySQL script may be applied partially.
+//!
+//! `Migrator::plan` and `ORMTrait::plan_migrations` are dry runs of `migrate_up`, e.g. to review the pending
+//! migrations in CI before they are applied:
+//!
+//! ```ignore
+//! let plan = conn.plan_migrations(&migrator.migrations()?).await?;
+//! println!("{}", plan);
+//! assert!(!plan.is_destructive(), "review the destructive operations before merging");
+//! ```
+#![cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::{ORMError, ORMTrait, QueryBuilder, StatementKind, Value};
+
+/// The table of the applied versions.
+pub(crate) const TABLE: &str = "parvati_migrations";
+
+/// `Migration` is a numbered script of a migration directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    /// `version` is the number at the start of the file names, which orders the migrations.
+    pub version: u64,
+
+    /// `name` is the part of the file names between the version and `.up.sql`.
+    pub name: String,
+
+    /// `up` is the script that applies the migration.
+    pub up: String,
+
+    /// `down` is the script that reverts the migration, or `None` if it cannot be reverted.
+    pub down: Option<String>,
+}
+
+/// `MigrationStatus` is the state of a migration returned by `Migrator::status`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStatus {
+    /// `version` is the version of the migration.
+    pub version: u64,
+
+    /// `name` is the name of the migration.
+    pub name: String,
+
+    /// `applied_at` is the time the migration was applied in seconds since the Unix epoch,
+    /// or `None` if it is pending.
+    pub applied_at: Option<u64>,
+
+    /// `missing` is `true` if the migration was applied but its scripts are no longer in the directory.
+    pub missing: bool,
+}
+
+impl MigrationStatus {
+    /// Returns `true` if the migration was applied.
+    pub fn is_applied(&self) -> bool {
+        self.applied_at.is_some()
+    }
+}
+
+/// `MigrationPlan` lists the migrations that `Migrator::migrate_up` would apply, returned by `ORMTrait::plan_migrations`.
+/// Its `Display` implementation renders it for review, one statement per line, e.g.:
+///
+/// ```text
+/// 3_drop_legacy
+/// + create table archive (id INTEGER, payload TEXT)
+/// ! drop table legacy
+///     drops the table legacy
+/// 1 migration, 2 statements, 1 destructive operation
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationPlan {
+    /// `migrations` are the pending migrations in the order they would be applied.
+    pub migrations: Vec<PlannedMigration>,
+}
+
+impl MigrationPlan {
+    /// Returns `true` if no migration is pending.
+    pub fn is_empty(&self) -> bool {
+        self.migrations.is_empty()
+    }
+
+    /// Returns the versions of the pending migrations.
+    pub fn versions(&self) -> Vec<u64> {
+        self.migrations.iter().map(|migration| migration.version).collect()
+    }
+
+    /// Returns the destructive operations of all pending migrations.
+    pub fn destructive(&self) -> Vec<&DestructiveOperation> {
+        self.migrations.iter().flat_map(PlannedMigration::destructive).collect()
+    }
+
+    /// Returns `true` if a pending migration may lose data.
+    pub fn is_destructive(&self) -> bool {
+        !self.destructive().is_empty()
+    }
+}
+
+impl fmt::Display for MigrationPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no pending migrations");
+        }
+        let mut statements = 0;
+        for migration in self.migrations.iter() {
+            writeln!(f, "{}_{}", migration.version, migration.name)?;
+            for statement in migration.statements.iter() {
+                let marker = if statement.destructive.is_empty() { '+' } else { '!' };
+                writeln!(f, "{} {}", marker, statement.sql.replace('\n', "\n  "))?;
+                for operation in statement.destructive.iter() {
+                    writeln!(f, "    {}", operation)?;
+                }
+            }
+            statements += migration.statements.len();
+        }
+        let destructive = self.destructive().len();
+        writeln!(f, "{} migration{}, {} statement{}, {} destructive operation{}",
+                 self.migrations.len(), plural(self.migrations.len()), statements, plural(statements), destructive, plural(destructive))
+    }
+}
+
+/// `PlannedMigration` is a pending migration of a `MigrationPlan`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedMigration {
+    /// `version` is the version of the migration.
+    pub version: u64,
+
+    /// `name` is the name of the migration.
+    pub name: String,
+
+    /// `statements` are the statements of the up script in the order they would be executed.
+    pub statements: Vec<PlannedStatement>,
+}
+
+impl PlannedMigration {
+    /// Returns the destructive operations of the statements of the migration.
+    pub fn destructive(&self) -> impl Iterator<Item = &DestructiveOperation> {
+        self.statements.iter().flat_map(|statement| statement.destructive.iter())
+    }
+}
+
+/// `PlannedStatement` is a statement of a `PlannedMigration`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedStatement {
+    /// `sql` is the statement without comments.
+    pub sql: String,
+
+    /// `destructive` are the operations of the statement that may lose data.
+    pub destructive: Vec<DestructiveOperation>,
+}
+
+/// `DestructiveOperation` is an operation of a migration that may lose data. The operations are estimated from the
+/// statements without looking at the schema, so e.g. a type change is reported whether it narrows the column or not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DestructiveOperation {
+    /// A `DROP TABLE`, also of the old table of a SQLite table rebuild.
+    DropTable { table: String },
+
+    /// An `ALTER TABLE ... DROP COLUMN`.
+    DropColumn { table: String, column: String },
+
+    /// A `TRUNCATE TABLE`.
+    Truncate { table: String },
+
+    /// A `DELETE` without a `WHERE` clause.
+    DeleteAll { table: String },
+
+    /// A change of the type of a column, which loses data if the new type is narrower,
+    /// e.g. `ALTER TABLE ... MODIFY COLUMN name VARCHAR(50)`.
+    ChangeColumnType { table: String, column: String, column_type: String },
+}
+
+impl fmt::Display for DestructiveOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DestructiveOperation::DropTable { table } => write!(f, "drops the table {}", table),
+            DestructiveOperation::DropColumn { table, column } => write!(f, "drops the column {}.{}", table, column),
+            DestructiveOperation::Truncate { table } => write!(f, "truncates the table {}", table),
+            DestructiveOperation::DeleteAll { table } => write!(f, "deletes every row of the table {}", table),
+            DestructiveOperation::ChangeColumnType { table, column, column_type } =>
+                write!(f, "changes the type of the column {}.{} to {}, which narrows it if the old type is wider", table, column, column_type),
+        }
+    }
+}
+
+/// `Migrator` applies and reverts the migrations of a directory, see the module documentation.
+pub struct Migrator<'a, O> {
+    /// `orm` is a reference to an ORM object that implements the `ORMTrait`.
+    orm: &'a O,
+
+    /// `dir` is the directory of the scripts.
+    dir: PathBuf,
+}
+
+impl<'a, O: ORMTrait<O> + Sync> Migrator<'a, O> {
+    /// Constructs the `Migrator` of the scripts in the directory `dir`.
+    pub fn new<P: AsRef<Path>>(orm: &'a O, dir: P) -> Self {
+        Migrator { orm, dir: dir.as_ref().to_path_buf() }
+    }
+
+    /// Returns the migrations of the directory ordered by version.
+    /// Returns `ORMError::Migration` if a `.sql` file is not named `NNN_name.up.sql` or `NNN_name.down.sql`,
+    /// if two migrations have the same version, or if a down script has no up script.
+    pub fn migrations(&self) -> Result<Vec<Migration>, ORMError> {
+        let mut scripts: BTreeMap<u64, (String, Option<String>, Option<String>)> = BTreeMap::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if !path.is_file() || !file_name.ends_with(".sql") {
+                continue;
+            }
+            let (version, name, up) = parse_file_name(file_name)
+                .ok_or_else(|| ORMError::Migration(format!("{} is not named NNN_name.up.sql or NNN_name.down.sql", file_name)))?;
+            let script = std::fs::read_to_string(&path)?;
+            let (stored_name, up_script, down_script) = scripts.entry(version).or_insert_with(|| (name.to_string(), None, None));
+            let slot = if up { up_script } else { down_script };
+            if stored_name != name || slot.is_some() {
+                return Err(ORMError::Migration(format!("the migrations {} and {} have the same version {}", stored_name, name, version)));
+            }
+            *slot = Some(script);
+        }
+        scripts.into_iter()
+            .map(|(version, (name, up, down))| match up {
+                Some(up) => Ok(Migration { version, name, up, down }),
+                None => Err(ORMError::Migration(format!("the migration {}_{} has no up script", version, name))),
+            })
+            .collect()
+    }
+
+    /// Applies the pending migrations in the order of their versions and returns their versions.
+    /// Stops at the first script that fails and returns its error; the migrations applied before it stay applied.
+    pub async fn migrate_up(&self) -> Result<Vec<u64>, ORMError> {
+        let migrations = self.migrations()?;
+        let applied = self.applied().await?;
+        let mut versions = Vec::new();
+        for migration in migrations.iter().filter(|migration| !applied.contains_key(&migration.version)) {
+            log::info!("applying migration {}_{}", migration.version, migration.name);
+            let record = self.orm.query_update(&format!("insert into {} (version, name, applied_at) values (?, ?, ?)", TABLE))
+                .bind(vec![Value::Int(migration.version as i64), Value::Text(migration.name.clone()), Value::Int(now())]);
+            self.execute(&migration.up, record).await
+                .map_err(|e| ORMError::Migration(format!("migration {}_{} failed: {:?}", migration.version, migration.name, e)))?;
+            versions.push(migration.version);
+        }
+        Ok(versions)
+    }
+
+    /// Reverts the last `n` applied migrations, the newest first, and returns their versions.
+    /// Returns `ORMError::Migration` without reverting any migration if one of them has no down script.
+    pub async fn migrate_down(&self, n: usize) -> Result<Vec<u64>, ORMError> {
+        let migrations: BTreeMap<u64, Migration> = self.migrations()?.into_iter().map(|migration| (migration.version, migration)).collect();
+        let applied = self.applied().await?;
+        let mut reverted = Vec::new();
+        for (version, (name, _)) in applied.iter().rev().take(n) {
+            match migrations.get(version).and_then(|migration| migration.down.as_ref()) {
+                Some(down) => reverted.push((*version, name, down)),
+                None => return Err(ORMError::Migration(format!("the migration {}_{} has no down script", version, name))),
+            }
+        }
+        let mut versions = Vec::new();
+        for (version, name, down) in reverted {
+            log::info!("reverting migration {}_{}", version, name);
+            let record = self.orm.query_update(&format!("delete from {} where version = ?", TABLE)).bind(vec![Value::Int(version as i64)]);
+            self.execute(down, record).await
+                .map_err(|e| ORMError::Migration(format!("reverting migration {}_{} failed: {:?}", version, name, e)))?;
+            versions.push(version);
+        }
+        Ok(versions)
+    }
+
+    /// Returns the migrations of the directory that `migrate_up` would apply, without applying them,
+    /// see `ORMTrait::plan_migrations`.
+    pub async fn plan(&self) -> Result<MigrationPlan, ORMError> {
+        self.orm.plan_migrations(&self.migrations()?).await
+    }
+
+    /// Returns the state of the migrations of the directory and of the applied migrations
+    /// whose scripts are no longer in the directory, ordered by version.
+    pub async fn status(&self) -> Result<Vec<MigrationStatus>, ORMError> {
+        let mut applied = self.applied().await?;
+        let mut status: Vec<MigrationStatus> = self.migrations()?.into_iter()
+            .map(|migration| MigrationStatus {
+                applied_at: applied.remove(&migration.version).map(|(_, applied_at)| applied_at),
+                version: migration.version,
+                name: migration.name,
+                missing: false,
+            })
+            .collect();
+        status.extend(applied.into_iter().map(|(version, (name, applied_at))| MigrationStatus {
+            version,
+            name,
+            applied_at: Some(applied_at),
+            missing: true,
+        }));
+        status.sort_by_key(|migration| migration.version);
+        Ok(status)
+    }
+
+    // Returns the names and times of the applied migrations by version, creating the tracking table if needed.
+    async fn applied(&self) -> Result<BTreeMap<u64, (String, u64)>, ORMError> {
+        let create = self.orm.query_update(&format!("create table if not exists {} (version BIGINT NOT NULL PRIMARY KEY, \
+            name VARCHAR(255) NOT NULL, applied_at BIGINT NOT NULL)", TABLE));
+        self.orm.exec_all(&[create]).await?;
+        let rows = self.orm.query_map(&format!("select version, name, applied_at from {}", TABLE)).await?;
+        Ok(rows.iter()
+            .map(|row| {
+                let version = row.get("version").and_then(Value::as_i64).unwrap_or_default() as u64;
+                let name = row.get("name").and_then(Value::as_str).unwrap_or_default().to_string();
+                let applied_at = row.get("applied_at").and_then(Value::as_i64).unwrap_or_default() as u64;
+                (version, (name, applied_at))
+            })
+            .collect())
+    }
+
+    // Executes the statements of `script` and `record`, the change of the tracking table, in one transaction.
+    async fn execute(&self, script: &str, record: QueryBuilder<'_, usize, (), O>) -> Result<usize, ORMError> {
+        let mut queries: Vec<_> = split_script(script).iter().map(|statement| self.orm.query_update(statement)).collect();
+        queries.push(record);
+        self.orm.exec_all(&queries).await
+    }
+}
+
+/// Returns the plan of the `migrations` that are not applied to the database of `orm`, see `ORMTrait::plan_migrations`.
+/// `tracked` tells whether the tracking table exists, which the backend looks up in its catalog; before the first
+/// migration it does not, so no migration is applied.
+/// Unlike `Migrator::migrate_up`, it executes no statement that writes, so it also runs with a read-only `Policy`.
+pub(crate) async fn plan<O: ORMTrait<O> + Sync>(orm: &O, migrations: &[Migration], tracked: bool) -> Result<MigrationPlan, ORMError> {
+    let applied: BTreeSet<u64> = if tracked {
+        orm.query_map(&format!("select version from {}", TABLE)).await?.iter()
+            .filter_map(|row| row.get("version").and_then(Value::as_i64))
+            .map(|version| version as u64)
+            .collect()
+    } else {
+        BTreeSet::new()
+    };
+    let mut pending: Vec<&Migration> = migrations.iter().filter(|migration| !applied.contains(&migration.version)).collect();
+    pending.sort_by_key(|migration| migration.version);
+    Ok(MigrationPlan {
+        migrations: pending.into_iter()
+            .map(|migration| PlannedMigration {
+                version: migration.version,
+                name: migration.name.clone(),
+                statements: split_script(&migration.up).into_iter()
+                    .map(|sql| PlannedStatement { destructive: destructive_operations(&sql), sql })
+                    .collect(),
+            })
+            .collect(),
+    })
+}
+
+/// Returns the operations of `statement` that may lose data, estimated from its keywords.
+pub(crate) fn destructive_operations(statement: &str) -> Vec<DestructiveOperation> {
+    let tokens = tokens(statement);
+    let is = |i: usize, keyword: &str| tokens.get(i).is_some_and(|token| token.eq_ignore_ascii_case(keyword));
+    let mut operations = Vec::new();
+    if is(0, "drop") && is(1, "table") {
+        let mut i = if is(2, "if") && is(3, "exists") { 4 } else { 2 };
+        while let Some(table) = qualified_name(&tokens, &mut i) {
+            operations.push(DestructiveOperation::DropTable { table });
+            if !is(i, ",") {
+                break;
+            }
+            i += 1;
+        }
+    } else if is(0, "truncate") {
+        let mut i = if is(1, "table") { 2 } else { 1 };
+        if let Some(table) = qualified_name(&tokens, &mut i) {
+            operations.push(DestructiveOperation::Truncate { table });
+        }
+    } else if is(0, "delete") && is(1, "from") && StatementKind::UnscopedDelete.matches(statement) {
+        let mut i = 2;
+        if let Some(table) = qualified_name(&tokens, &mut i) {
+            operations.push(DestructiveOperation::DeleteAll { table });
+        }
+    } else if is(0, "alter") && is(1, "table") {
+        let mut i = if is(2, "if") && is(3, "exists") { 4 } else { 2 };
+        let Some(table) = qualified_name(&tokens, &mut i) else {
+            return operations;
+        };
+        // the changes of one statement are separated by commas outside of parentheses
+        let mut depth = 0usize;
+        let mut start = i;
+        for end in i..=tokens.len() {
+            match tokens.get(end).map(String::as_str) {
+                Some("(") => depth += 1,
+                Some(")") => depth = depth.saturating_sub(1),
+                Some(",") if depth == 0 => {
+                    operations.extend(altered_column(&table, &tokens[start..end]));
+                    start = end + 1;
+                }
+                Some(_) => {}
+                None => operations.extend(altered_column(&table, &tokens[start..end])),
+            }
+        }
+    }
+    operations
+}
+
+// Returns the destructive operation of one change of an `ALTER TABLE` statement: `DROP [COLUMN] c`,
+// `MODIFY [COLUMN] c type`, `CHANGE [COLUMN] c new_name type` or `ALTER [COLUMN] c [SET DATA] TYPE type`.
+fn altered_column(table: &str, change: &[String]) -> Option<DestructiveOperation> {
+    let is = |i: usize, keyword: &str| change.get(i).is_some_and(|token| token.eq_ignore_ascii_case(keyword));
+    let mut i = if is(1, "column") { 2 } else { 1 };
+    if is(0, "drop") {
+        // DROP INDEX, DROP PRIMARY KEY, DROP CONSTRAINT and the like keep the rows
+        if i == 1 && ["index", "key", "primary", "foreign", "constraint", "check", "partition", "unique"].iter().any(|keyword| is(1, keyword)) {
+            return None;
+        }
+        if is(i, "if") && is(i + 1, "exists") {
+            i += 2;
+        }
+        let column = change.get(i)?.clone();
+        return Some(DestructiveOperation::DropColumn { table: table.to_string(), column });
+    }
+    let column = change.get(i)?.clone();
+    let type_start = if is(0, "modify") {
+        i + 1
+    } else if is(0, "change") {
+        i + 2
+    } else if is(0, "alter") {
+        let at = change.iter().position(|token| token.eq_ignore_ascii_case("type"))?;
+        at + 1
+    } else {
+        return None;
+    };
+    // the type ends at the first column option, e.g. NOT NULL or DEFAULT
+    let options = ["not", "null", "default", "auto_increment", "primary", "unique", "comment", "collate", "character",
+        "references", "check", "generated", "first", "after", "using"];
+    let type_end = (type_start..change.len()).find(|at| options.iter().any(|option| is(*at, option))).unwrap_or(change.len());
+    if type_start >= type_end {
+        return None;
+    }
+    Some(DestructiveOperation::ChangeColumnType { table: table.to_string(), column, column_type: join_tokens(&change[type_start..type_end]) })
+}
+
+// Returns the name at `tokens[*i]`, qualified with its schema if it is followed by a dot, and moves `i` after it.
+fn qualified_name(tokens: &[String], i: &mut usize) -> Option<String> {
+    let mut name = tokens.get(*i).filter(|token| is_name(token))?.clone();
+    *i += 1;
+    while tokens.get(*i).map(String::as_str) == Some(".") {
+        let Some(part) = tokens.get(*i + 1).filter(|token| is_name(token)) else {
+            break;
+        };
+        name = format!("{}.{}", name, part);
+        *i += 2;
+    }
+    Some(name)
+}
+
+fn is_name(token: &str) -> bool {
+    token.chars().next().is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+// Joins the tokens of a type with the spacing of SQL, e.g. `DECIMAL(10, 2)`.
+fn join_tokens(tokens: &[String]) -> String {
+    let mut joined = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 && !matches!(token.as_str(), "(" | ")" | ",") && !joined.ends_with('(') {
+            joined.push(' ');
+        }
+        joined.push_str(token);
+    }
+    joined
+}
+
+// Splits `statement`, which has no comments, into its words, quoted identifiers without their quotes,
+// string literals with their quotes and single punctuation characters.
+fn tokens(statement: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = statement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' | '[' => {
+                let close = if c == '[' { ']' } else { c };
+                let quoted: String = chars.by_ref().take_while(|next| *next != close).collect();
+                tokens.push(if c == '\'' { format!("'{}'", quoted) } else { quoted });
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(next) = chars.next_if(|next| next.is_alphanumeric() || *next == '_' || *next == '$') {
+                    word.push(next);
+                }
+                tokens.push(word);
+            }
+            c if c.is_whitespace() => {}
+            c => tokens.push(c.to_string()),
+        }
+    }
+    tokens
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 { "" } else { "s" }
+}
+
+// Returns the version, the name and `true` for an up script of a file named `NNN_name.up.sql` or `NNN_name.down.sql`.
+fn parse_file_name(file_name: &str) -> Option<(u64, &str, bool)> {
+    let (stem, up) = match file_name.strip_suffix(".up.sql") {
+        Some(stem) => (stem, true),
+        None => (file_name.strip_suffix(".down.sql")?, false),
+    };
+    let (version, name) = stem.split_once('_')?;
+    if version.is_empty() || !version.chars().all(|c| c.is_ascii_digit()) || name.is_empty() {
+        return None;
+    }
+    Some((version.parse().ok()?, name, up))
+}
+
+/// Splits `script` into its statements at the semicolons outside of quotes and comments, removing the comments.
+/// The semicolons inside the body of a `CREATE TRIGGER` statement do not end it, only the one after its `END`.
+pub(crate) fn split_script(script: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut chars = script.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                current.push(c);
+                for next in chars.by_ref() {
+                    current.push(next);
+                    if next == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        current.push('\n');
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+                current.push(' ');
+            }
+            ';' if !is_trigger(&current) || ends_with_end(&current) => {
+                let statement = current.trim();
+                if !statement.is_empty() {
+                    statements.push(statement.to_string());
+                }
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    let statement = current.trim();
+    if !statement.is_empty() {
+        statements.push(statement.to_string());
+    }
+    statements
+}
+
+// Returns `true` if `statement` is a `CREATE [TEMP|TEMPORARY] TRIGGER` statement.
+fn is_trigger(statement: &str) -> bool {
+    let mut words = statement.split_whitespace().map(str::to_ascii_uppercase);
+    words.next().as_deref() == Some("CREATE") && words.take(2).any(|word| word == "TRIGGER")
+}
+
+// Returns `true` if `statement` ends with the keyword `END`.
+fn ends_with_end(statement: &str) -> bool {
+    let statement = statement.trim_end();
+    let len = statement.len();
+    len >= 3 && statement.is_char_boundary(len - 3) && statement[len - 3..].eq_ignore_ascii_case("end")
+        && !statement[..len - 3].ends_with(|c: char| c.is_alphanumeric() || c == '_')
+}
+
+// Returns the current time in seconds since the Unix epoch, the unit of `applied_at`.
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs() as i64).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{destructive_operations, parse_file_name, split_script, DestructiveOperation};
+
+    #[test]
+    fn test_parse_file_name() {
+        assert_eq!(parse_file_name("001_create_user.up.sql"), Some((1, "create_user", true)));
+        assert_eq!(parse_file_name("20240101_add_email.down.sql"), Some((20240101, "add_email", false)));
+        assert_eq!(parse_file_name("create_user.up.sql"), None);
+        assert_eq!(parse_file_name("001_.up.sql"), None);
+        assert_eq!(parse_file_name("001_create_user.sql"), None);
+    }
+
+    #[test]
+    fn test_split_script() {
+        let script = "create table user (id INTEGER, name TEXT); -- the users; all of them\n\
+                      insert into user (name) values ('a;b'), (\"it''s\");\n\
+                      /* the log; */ create trigger log after insert on user begin\n\
+                      insert into log values (new.id); update stats set users = users + 1;\n\
+                      end;\n;";
+        assert_eq!(split_script(script), vec![
+            "create table user (id INTEGER, name TEXT)".to_string(),
+            "insert into user (name) values ('a;b'), (\"it''s\")".to_string(),
+            "create trigger log after insert on user begin\ninsert into log values (new.id); update stats set users = users + 1;\nend".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_destructive_operations() {
+        let table = |table: &str| table.to_string();
+        assert_eq!(destructive_operations("DROP TABLE IF EXISTS legacy, billing.`old invoice`"), vec![
+            DestructiveOperation::DropTable { table: table("legacy") },
+            DestructiveOperation::DropTable { table: table("billing.old invoice") },
+        ]);
+        assert_eq!(destructive_operations("truncate table session"), vec![DestructiveOperation::Truncate { table: table("session") }]);
+        assert_eq!(destructive_operations("delete from session"), vec![DestructiveOperation::DeleteAll { table: table("session") }]);
+        assert!(destructive_operations("delete from session where expires_at < 100").is_empty());
+        assert_eq!(destructive_operations("ALTER TABLE user DROP COLUMN age, DROP INDEX user_name, \
+                                           MODIFY name VARCHAR(50) NOT NULL, CHANGE COLUMN price cost DECIMAL(10, 2) DEFAULT 0"), vec![
+            DestructiveOperation::DropColumn { table: table("user"), column: "age".to_string() },
+            DestructiveOperation::ChangeColumnType { table: table("user"), column: "name".to_string(), column_type: "VARCHAR(50)".to_string() },
+            DestructiveOperation::ChangeColumnType { table: table("user"), column: "price".to_string(), column_type: "DECIMAL(10, 2)".to_string() },
+        ]);
+        assert_eq!(destructive_operations("alter table user alter column age type SMALLINT"), vec![
+            DestructiveOperation::ChangeColumnType { table: table("user"), column: "age".to_string(), column_type: "SMALLINT".to_string() },
+        ]);
+        assert!(destructive_operations("alter table user add column email TEXT, rename to member").is_empty());
+        assert!(destructive_operations("insert into log (message) values ('drop table user')").is_empty());
+        assert!(destructive_operations("create table drop_log (id INTEGER)").is_empty());
+    }
+}
@@ -2,20 +2,180 @@
 
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, VecDeque};
+use std::ops::{Deref, DerefMut};
 use async_trait::async_trait;
-use futures::lock::Mutex;
-use mysql_async::Conn;
+use futures::lock::{Mutex, OwnedMutexGuard};
+use futures::{SinkExt, StreamExt};
+use futures::stream::BoxStream;
+use mysql_async::{Conn, Pool};
+use mysql_async::consts::{ColumnFlags, ColumnType};
 use mysql_async::prelude::*;
 
 use serde::{Deserialize, Serialize};
-use crate::{deserializer_key_values, ORMError, ORMTrait, QueryBuilder, Row, serializer_error, serializer_key_values, serializer_types, serializer_values, TableDeserialize, TableSerialize};
+use crate::cassette::statement_key;
+use crate::dynamic;
+use crate::queue::{self, Job};
+use crate::schema::{self, Dialect};
+use crate::health::{self, HealthReport};
+use crate::migrator::{self, Migration, MigrationPlan};
+use crate::n_plus_one::NPlusOneDetector;
+use crate::write_hooks::WriteHooks;
+use crate::entity_cache::{self, CachedLookup};
+use crate::stats::{self, IndexUsage, QueryStats, QueryStatsCollector, RowCount, TableSize};
+use crate::{AddMany, AddOrUpdate, AlterTable, Batch, BlobReader, Column, Cond, ConnectOptions, counter_statements, datetime, DateTimePolicy, DbManager, DdlStatement, DecodeError, deserializer_key_values, DynamicEntity, end_transaction_statements, exec_all_statements, FromRow, id_param, ImportAll, in_transaction, ImportReport, InsertMeta, insert_rows_statement, insert_statement, is_table_name, is_unset_id, IsolationLevel, low_priority_statement, MergeAction, MergeResult, MergeStrategy, Order, ORMError, ORMTrait, Page, Persisted, Policy, PoolMetrics, PoolStatus, plan_merge, QueryBuilder, Queue, Recorded, ResultSet, RetryPolicy, Row, row_stream, savepoint_statement, Scope, Scoped, serializer_error, spawn_pool_status_logger, TableDeserialize, TableSerialize, TableWrite, ToSqlLiteral, transaction_owner, TransactionOwner, tree_statement, update_fields_statement, update_statement, UpsertAll, Value};
+
+/// `ColumnKind` is an enumeration of the ways the values of a result column are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnKind {
+    Signed,
+    Unsigned,
+    Date,
+    Text,
+}
+
+/// `Checkout` is the connection a statement runs on: the session connection of its task, or a connection of the pool,
+/// which goes back to the pool when the checkout is dropped.
+enum Checkout<'a> {
+    Session(OwnedMutexGuard<Option<Conn>>),
+    Pooled(Option<Conn>, &'a std::sync::Mutex<Connections>),
+}
+
+impl Deref for Checkout<'_> {
+    type Target = Option<Conn>;
+
+    fn deref(&self) -> &Option<Conn> {
+        match self {
+            Checkout::Session(conn) => conn,
+            Checkout::Pooled(conn, _) => conn,
+        }
+    }
+}
+
+impl DerefMut for Checkout<'_> {
+    fn deref_mut(&mut self) -> &mut Option<Conn> {
+        match self {
+            Checkout::Session(conn) => conn,
+            Checkout::Pooled(conn, _) => conn,
+        }
+    }
+}
+
+impl Drop for Checkout<'_> {
+    fn drop(&mut self) {
+        if let Checkout::Pooled(Some(_), connections) = self {
+            connections.lock().unwrap().check_in(false);
+        }
+    }
+}
+
+/// `UNLIMITED` is the LIMIT MySQL uses for an OFFSET without a LIMIT, the largest BIGINT UNSIGNED.
+const UNLIMITED: &str = "18446744073709551615";
+
+/// `DEFAULT_POOL_MIN` is the number of idle connections the pool keeps open if `ConnectOptions::pool_min` is not set.
+const DEFAULT_POOL_MIN: usize = 1;
+
+/// `DEFAULT_POOL_MAX` is the maximum number of connections of the pool if `ConnectOptions::pool_max` is not set.
+const DEFAULT_POOL_MAX: usize = 10;
+
+/// `Connections` counts the open connections of the pool. The pool is created without an inactive connection TTL,
+/// so it keeps up to `pool_min` of the connections that come back open for reuse and closes the others,
+/// and a checkout reuses an idle connection if there is one.
+#[derive(Debug)]
+struct Connections {
+    in_use: usize,
+    idle: usize,
+    pool_min: usize,
+}
+
+impl Connections {
+    /// Counts a connection checked out of the pool.
+    fn check_out(&mut self) {
+        self.in_use += 1;
+        self.idle = self.idle.saturating_sub(1);
+    }
+
+    /// Counts a checked out connection that goes back to the pool, or is closed if `closed`.
+    fn check_in(&mut self, closed: bool) {
+        self.in_use = self.in_use.saturating_sub(1);
+        if !closed && self.idle < self.pool_min {
+            self.idle += 1;
+        }
+    }
+}
+
+/// `INSERT_IDS` is the number of tasks whose last insert ID is kept for `last_insert_id`.
+const INSERT_IDS: usize = 1024;
+
+/// `Session` is the connection of the pool that a task pinned for its transaction or advisory lock, see `ORM::pin`.
+/// It runs all statements of the task until the last pin is removed, while the statements of other tasks keep
+/// using the pool.
+#[derive(Debug)]
+struct Session {
+    conn: Arc<Mutex<Option<Conn>>>,
+    pins: usize,
+    transaction_depth: usize,
+}
+
+/// `SessionGuard` is a pin of the session of a task, see `ORM::pin`. The pin is removed with `release`.
+/// If the guard is dropped before, e.g. with the future of a transaction that was cancelled by a timeout,
+/// the session is discarded and its connection is closed, so that the server rolls back its transaction
+/// and releases its advisory locks.
+struct SessionGuard<'a> {
+    orm: &'a ORM,
+    owner: TransactionOwner,
+    released: bool,
+}
+
+impl SessionGuard<'_> {
+    /// Removes the pin, see `ORM::unpin`.
+    fn release(mut self) {
+        self.released = true;
+    }
+
+    /// Keeps the pin after the guard is gone, for the transaction begun with `begin`, which `commit` or `rollback` removes.
+    fn keep(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for SessionGuard<'_> {
+    fn drop(&mut self) {
+        if self.released {
+            self.orm.unpin(self.owner);
+        } else {
+            self.orm.discard(self.owner);
+        }
+    }
+}
+
+/// `InsertIds` is the last insert ID of each task that inserted records, see `ORM::record_insert_id`.
+/// Only the `INSERT_IDS` tasks that inserted last are kept, so that finished tasks do not accumulate.
+#[derive(Debug, Default)]
+struct InsertIds {
+    ids: HashMap<TransactionOwner, u64>,
+    order: VecDeque<TransactionOwner>,
+}
 
 /// `ORM` is a struct that represents an Object-Relational Mapping (ORM) for a MySQL database.
-/// It contains a `Mutex` that guards an `Option` wrapping a `Conn` object from the `mysql_async` crate.
-/// The `Conn` object represents a connection to the MySQL database.
+/// It contains a `mysql_async::Pool`, from which every statement checks out a connection, so that concurrent
+/// tasks do not wait for each other. A transaction or an advisory lock pins one connection of the pool as the
+/// session connection of its task, see `transaction_owner`, which runs the statements of that task until
+/// the transaction ends or the lock is released.
 #[derive(Debug)]
 pub struct ORM {
-    conn: Mutex<Option<Conn>>,
+    pool: std::sync::RwLock<Option<Pool>>,
+    sessions: std::sync::Mutex<HashMap<TransactionOwner, Session>>,
+    insert_ids: std::sync::Mutex<InsertIds>,
+    connections: std::sync::Mutex<Connections>,
+    metrics: PoolMetrics,
+    query_stats: QueryStatsCollector,
+    n_plus_one: NPlusOneDetector,
+    write_hooks: WriteHooks,
+    policy: std::sync::RwLock<Policy>,
+    change_count: Mutex<u32>,
+    options: ConnectOptions,
 }
 
 impl ORM {
@@ -27,13 +187,672 @@ impl ORM {
     pub async fn connect(url: String) -> Result<Arc<ORM>, ORMError>
         where Arc<ORM>: Send + Sync + 'static
     {
-        let pool = mysql_async::Pool::new(url.as_str());
-        let conn = pool.get_conn().await?;
-        Ok(Arc::new(ORM {
-            conn: Mutex::new(Some(conn)),
-        }))
+        ORM::connect_with(ConnectOptions::new(url.as_str())).await
+    }
+
+    /// `connect_with` is an asynchronous function that establishes a connection to a MySQL database described by `options`.
+    /// The `on_connect` statements of the options are passed to the driver as init statements,
+    /// so they are executed on every new connection.
+    /// It returns a `Result` that contains an `Arc<ORM>` if the connection is successful.
+    /// If the URL is malformed or the connection is not successful, the `Result` contains an `ORMError`.
+    /// If `options` replay a cassette, no connection is established and the URL is not used.
+    pub async fn connect_with(options: ConnectOptions) -> Result<Arc<ORM>, ORMError>
+        where Arc<ORM>: Send + Sync + 'static
+    {
+        let (min, max) = (options.pool_min.unwrap_or(DEFAULT_POOL_MIN), options.pool_max.unwrap_or(DEFAULT_POOL_MAX));
+        let pool = if options.replaying().is_some() {
+            None
+        } else {
+            let constraints = mysql_async::PoolConstraints::new(min, max).filter(|_| max > 0)
+                .ok_or_else(|| ORMError::Config(format!("invalid pool size: min {} max {}", min, max)))?;
+            let opts = mysql_async::Opts::from_url(options.url.as_str()).map_err(mysql_async::Error::from)?;
+            let mut opts = mysql_async::OptsBuilder::from_opts(opts)
+                .init(ORM::init_statements(&options))
+                .pool_opts(mysql_async::PoolOpts::default().with_constraints(constraints));
+            if let Some(password) = &options.password {
+                opts = opts.pass(Some(password.resolve()?));
+            }
+            let pool = Pool::new(opts);
+            // checks out a connection, so that an unreachable server fails here and not on the first query
+            drop(pool.get_conn().await?);
+            Some(pool)
+        };
+        // the connection checked out above stays open if the pool keeps idle connections
+        let idle = if pool.is_some() { min.min(1) } else { 0 };
+        let orm = Arc::new(ORM {
+            pool: std::sync::RwLock::new(pool),
+            sessions: Default::default(),
+            insert_ids: Default::default(),
+            connections: std::sync::Mutex::new(Connections { in_use: 0, idle, pool_min: min }),
+            metrics: PoolMetrics::default(),
+            query_stats: QueryStatsCollector::default(),
+            n_plus_one: NPlusOneDetector::new(options.n_plus_one_threshold),
+            write_hooks: WriteHooks::default(),
+            policy: Default::default(),
+            change_count: 0.into(),
+            options,
+        });
+        if let Some(cache) = orm.options.entity_cache.clone() {
+            orm.write_hooks.add_all_tables(Arc::new(move |write| cache.invalidate(&write.table.to_lowercase())));
+        }
+        if let Some(interval) = orm.options.pool_status_interval {
+            spawn_pool_status_logger(&orm, interval);
+        }
+        Ok(orm)
+    }
+
+    /// `from_env` is an asynchronous function that connects to the database configured by the `DATABASE_*`
+    /// environment variables, see `ConnectOptions::from_env`.
+    pub async fn from_env() -> Result<Arc<ORM>, ORMError>
+        where Arc<ORM>: Send + Sync + 'static
+    {
+        ORM::connect_with(ConnectOptions::from_env()?).await
+    }
+
+    /// `from_config` is an asynchronous function that connects to the database configured in the `[database]`
+    /// section of the TOML file `path`, see `ConnectOptions::from_config`.
+    pub async fn from_config<P: AsRef<std::path::Path>>(path: P) -> Result<Arc<ORM>, ORMError>
+        where Arc<ORM>: Send + Sync + 'static
+    {
+        ORM::connect_with(ConnectOptions::from_config(path)?).await
+    }
+
+    /// `transaction` is an asynchronous method that executes `f` in a transaction.
+    /// `f` receives the connection, whose queries run inside the transaction, and returns a future.
+    /// If the future succeeds, the transaction is committed and its value is returned;
+    /// if it fails, the transaction is rolled back and its error is returned.
+    /// If the future is dropped before, the session connection is closed, so that the server rolls back the transaction.
+    /// Inside another transaction a savepoint is used, so transactions can be nested.
+    pub async fn transaction<F, Fut, T, E>(self: &Arc<Self>, f: F) -> Result<T, E>
+        where F: FnOnce(Arc<ORM>) -> Fut, Fut: std::future::Future<Output = Result<T, E>>, E: From<ORMError>
+    {
+        let session = self.pin().await?;
+        let result = in_transaction(self.as_ref(), IsolationLevel::Default, f(self.clone())).await;
+        session.release();
+        result
+    }
+
+    /// `with_advisory_lock` is an asynchronous method that executes `f` while holding the advisory lock `name`
+    /// taken with `GET_LOCK`, e.g. so that only one instance of a cron job runs against the shared database.
+    /// `f` receives the connection, whose session holds the lock; the lock is released with `RELEASE_LOCK` when
+    /// the future of `f` completes. If the future is dropped before, the session connection is closed,
+    /// so that the server releases the lock.
+    /// If another session holds the lock, it returns an `ORMError::LockNotAcquired` without calling `f`.
+    pub async fn with_advisory_lock<F, Fut, T, E>(self: &Arc<Self>, name: &str, f: F) -> Result<T, E>
+        where F: FnOnce(Arc<ORM>) -> Fut, Fut: std::future::Future<Output = Result<T, E>>, E: From<ORMError>
+    {
+        let session = self.pin().await?;
+        let result = self.locked(name, f).await;
+        session.release();
+        result
+    }
+
+    /// `locked` is an asynchronous method that executes `f` between `GET_LOCK` and `RELEASE_LOCK` of `name`.
+    async fn locked<F, Fut, T, E>(self: &Arc<Self>, name: &str, f: F) -> Result<T, E>
+        where F: FnOnce(Arc<ORM>) -> Fut, Fut: std::future::Future<Output = Result<T, E>>, E: From<ORMError>
+    {
+        let rows = self.query::<Row>("select get_lock(?, 0)").bind(vec![Value::Text(name.to_string())]).exec().await?;
+        if rows.first().and_then(|row| row.get::<i64>(0)) != Some(1) {
+            return Err(ORMError::LockNotAcquired(name.to_string()).into());
+        }
+        let result = f(self.clone()).await;
+        let released = self.query::<Row>("select release_lock(?)").bind(vec![Value::Text(name.to_string())]).exec().await;
+        let value = result?;
+        released?;
+        Ok(value)
+    }
+
+    /// `lock` is an asynchronous method that returns the connection for the next statement and records the time
+    /// spent waiting for it: the session connection while the current task has pinned one, a connection of the pool otherwise.
+    /// The connection is `None` if the ORM is closed.
+    async fn lock(&self) -> Result<Checkout<'_>, ORMError> {
+        let start = Instant::now();
+        let session = self.sessions.lock().unwrap().get(&transaction_owner()).map(|session| session.conn.clone());
+        if let Some(session) = session {
+            let conn = session.lock_owned().await;
+            // without a session connection, e.g. while replaying, the statement takes the pool path
+            if conn.is_some() {
+                self.metrics.record_wait(start.elapsed());
+                return Ok(Checkout::Session(conn));
+            }
+        }
+        let pool = self.pool.read().unwrap().clone();
+        let conn = match pool {
+            Some(pool) => {
+                let conn = pool.get_conn().await?;
+                self.connections.lock().unwrap().check_out();
+                Some(conn)
+            }
+            None => None,
+        };
+        self.metrics.record_wait(start.elapsed());
+        Ok(Checkout::Pooled(conn, &self.connections))
+    }
+
+    /// `pin` is an asynchronous method that checks out a connection of the pool as the session connection
+    /// of the current task, unless the task pinned one already, and returns the guard of the pin.
+    /// Pins are counted, see `unpin`. While replaying, the session has no connection and only keeps the transaction depth.
+    async fn pin(&self) -> Result<SessionGuard<'_>, ORMError> {
+        let owner = transaction_owner();
+        let guard = SessionGuard { orm: self, owner, released: false };
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(&owner) {
+            session.pins += 1;
+            return Ok(guard);
+        }
+        let conn = match self.options.replaying() {
+            Some(_) => None,
+            None => {
+                let pool = self.pool.read().unwrap().clone().ok_or(ORMError::NoConnection)?;
+                let conn = pool.get_conn().await?;
+                self.connections.lock().unwrap().check_out();
+                Some(conn)
+            }
+        };
+        let session = Session { conn: Arc::new(Mutex::new(conn)), pins: 1, transaction_depth: 0 };
+        self.sessions.lock().unwrap().insert(owner, session);
+        Ok(guard)
+    }
+
+    /// `unpin` is a method that removes a pin of the session of `owner` and returns its connection to the pool
+    /// when the last pin is removed.
+    fn unpin(&self, owner: TransactionOwner) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.get_mut(&owner) {
+            session.pins -= 1;
+            if session.pins == 0 {
+                sessions.remove(&owner);
+                if self.options.replaying().is_none() {
+                    self.connections.lock().unwrap().check_in(false);
+                }
+            }
+        }
+    }
+
+    /// `discard` is a method that removes the session of `owner` with all its pins and closes its connection,
+    /// once the statement that may still hold it is dropped. Without a runtime to close it on,
+    /// the connection goes back to the pool, which resets it.
+    fn discard(&self, owner: TransactionOwner) {
+        let session = self.sessions.lock().unwrap().remove(&owner);
+        let Some(session) = session else {
+            return;
+        };
+        if self.options.replaying().is_none() {
+            self.connections.lock().unwrap().check_in(true);
+        }
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                let conn = session.conn.lock().await.take();
+                if let Some(conn) = conn {
+                    if let Err(e) = conn.disconnect().await {
+                        log::warn!("closing a discarded session failed: {}", e);
+                    }
+                }
+            });
+        }
+    }
+
+    /// `transaction_depth` is a method that returns the number of nested transactions of the current task.
+    /// The transactions of other tasks do not count, see `transaction_owner`.
+    fn transaction_depth(&self) -> usize {
+        self.sessions.lock().unwrap().get(&transaction_owner()).map_or(0, |session| session.transaction_depth)
+    }
+
+    /// `set_transaction_depth` is a method that sets the number of nested transactions of the session of the current task.
+    fn set_transaction_depth(&self, depth: usize) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(&transaction_owner()) {
+            session.transaction_depth = depth;
+        }
+    }
+
+    /// `record_insert_id` is a method that keeps the ID generated by an INSERT of the current task for `last_insert_id`,
+    /// whether it ran on the session connection of the task or on a connection of the pool.
+    fn record_insert_id(&self, id: Option<u64>) {
+        let Some(id) = id.filter(|id| *id > 0) else {
+            return;
+        };
+        let owner = transaction_owner();
+        let mut insert_ids = self.insert_ids.lock().unwrap();
+        if insert_ids.ids.insert(owner, id).is_none() {
+            insert_ids.order.push_back(owner);
+            if insert_ids.order.len() > INSERT_IDS {
+                let oldest = insert_ids.order.pop_front().unwrap();
+                insert_ids.ids.remove(&oldest);
+            }
+        }
+    }
+
+    /// `connect_with_retry` is an asynchronous function that establishes a connection to a MySQL database
+    /// and retries failed attempts with exponential backoff according to `policy`.
+    /// It is useful when the application may start before the database server, e.g. in docker-compose.
+    /// A malformed URL is not retried.
+    /// If all attempts fail, the `Result` contains the `ORMError` of the last attempt.
+    pub async fn connect_with_retry(url: String, policy: RetryPolicy) -> Result<Arc<ORM>, ORMError>
+        where Arc<ORM>: Send + Sync + 'static
+    {
+        ORM::connect_with_options_retry(ConnectOptions::new(url.as_str()), policy).await
+    }
+
+    /// `connect_with_options_retry` is an asynchronous function that works like `connect_with_retry`
+    /// but connects with the given `ConnectOptions`.
+    pub async fn connect_with_options_retry(options: ConnectOptions, policy: RetryPolicy) -> Result<Arc<ORM>, ORMError>
+        where Arc<ORM>: Send + Sync + 'static
+    {
+        let mut attempt = 1;
+        loop {
+            match ORM::connect_with(options.clone()).await {
+                Ok(orm) => return Ok(orm),
+                Err(ORMError::MySQLError(mysql_async::Error::Url(e))) => {
+                    return Err(ORMError::MySQLError(mysql_async::Error::Url(e)));
+                }
+                Err(e) if attempt < policy.max_attempts => {
+                    let delay = policy.delay(attempt);
+                    log::warn!("connection attempt {} failed: {}, retrying in {:?}", attempt, e, delay);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// `create_database` is an asynchronous function that creates the database `name` on the server of `url`,
+    /// if it does not exist, e.g. in the setup of integration tests. The database of `url` is ignored,
+    /// so that it may be the database to create.
+    /// If `name` is not a plain identifier, the `Result` contains `ORMError::InvalidValue`.
+    pub async fn create_database(url: &str, name: &str) -> Result<(), ORMError> {
+        ORM::execute_on_server(url, format!("CREATE DATABASE IF NOT EXISTS {}", ORM::database_name(name)?)).await
+    }
+
+    /// `drop_database` is an asynchronous function that drops the database `name` on the server of `url`, if it exists.
+    pub async fn drop_database(url: &str, name: &str) -> Result<(), ORMError> {
+        ORM::execute_on_server(url, format!("DROP DATABASE IF EXISTS {}", ORM::database_name(name)?)).await
+    }
+
+    /// `database_name` is a function that returns `name` quoted, if it is a plain identifier.
+    fn database_name(name: &str) -> Result<String, ORMError> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(ORMError::InvalidValue(format!("invalid database name {}", name)));
+        }
+        Ok(format!("`{}`", name))
+    }
+
+    /// `log_statement` is a function that reports a statement to the statement logger and counts it in `query_stats`.
+    fn log_statement<T, E: Debug>(&self, statement: &str, start: Instant, result: &Result<T, E>, rows: impl FnOnce(&T) -> usize) {
+        self.query_stats.record(statement, start.elapsed(), result.is_err());
+        self.n_plus_one.record(statement);
+        if result.is_ok() {
+            self.write_hooks.record(statement, self.transaction_depth() > 0);
+        }
+        self.options.log_statement(statement, start, result, rows);
+    }
+
+    /// `check_policy` is a function that returns `ORMError::PolicyViolation` if the policy set with `set_policy` denies `statement`.
+    fn check_policy(&self, statement: &str) -> Result<(), ORMError> {
+        self.policy.read().unwrap().check(statement, self.transaction_depth() > 0)
+    }
+
+    /// `read_values` is an asynchronous method that executes the select `query` with `params` bound to its placeholders
+    /// and returns every row as a `HashMap`, see `query_map`.
+    async fn read_values(&self, query: &str, params: &[Value]) -> Result<Vec<HashMap<String, Value>>, ORMError> {
+        log::debug!("{:?} {:?}", query, params);
+        self.check_policy(query)?;
+        let key = statement_key(query, params, Dialect::MySql);
+        if let Some(cassette) = self.options.replaying() {
+            return cassette.replay_values(&key);
+        }
+        let mut conn = self.lock().await?;
+        if conn.is_none() {
+            return Err(ORMError::NoConnection);
+        }
+        let conn = conn.as_mut().unwrap();
+        let start = Instant::now();
+        let rows: mysql_async::Result<Vec<mysql_async::Row>> = if params.is_empty() {
+            conn.query(query).await
+        } else {
+            conn.exec(query, ORM::params(params)).await
+        };
+        let result = rows.map(|rows| {
+            rows.iter().map(|row| {
+                row.columns_ref().iter().enumerate()
+                    .map(|(i, column)| (column.name_str().to_string(), ORM::dynamic_value(column, row.as_ref(i))))
+                    .collect::<HashMap<String, Value>>()
+            }).collect::<Vec<_>>()
+        });
+        self.log_statement(query, start, &result, |rows| rows.len());
+        self.options.record(&key, &result, |rows| Recorded::values(rows));
+        Ok(result?)
+    }
+
+    /// `execute_on_server` is an asynchronous function that executes `statement` with a new connection
+    /// to the server of `url` without selecting a database.
+    async fn execute_on_server(url: &str, statement: String) -> Result<(), ORMError> {
+        let opts = mysql_async::Opts::from_url(url).map_err(mysql_async::Error::from)?;
+        let opts = mysql_async::OptsBuilder::from_opts(opts).db_name(None::<String>);
+        let mut conn = Conn::new(opts).await?;
+        log::debug!("{:?}", statement);
+        let r = conn.query_drop(statement).await;
+        conn.disconnect().await?;
+        Ok(r?)
+    }
+
+    /// `information_schema` is a method that returns the SQL of the schema whose metadata is queried:
+    /// `ConnectOptions::schema` or the database of the connection.
+    fn information_schema(&self) -> String {
+        match &self.options.schema {
+            Some(schema) => schema::quote(schema),
+            None => "DATABASE()".to_string(),
+        }
+    }
+
+    /// `end_transaction` is an asynchronous method that ends the innermost transaction.
+    /// The outermost transaction is ended with `COMMIT` or `ROLLBACK`, a nested one releases or rolls back to its savepoint.
+    /// The session connection of the outermost transaction is returned to the pool afterwards, also if it failed.
+    /// The transaction of another task cannot be ended.
+    async fn end_transaction(&self, commit: bool) -> Result<(), ORMError> {
+        let depth = self.transaction_depth();
+        if depth == 0 {
+            return Err(ORMError::TransactionError("no active transaction".to_string()));
+        }
+        let mut conn = self.lock().await?;
+        if conn.is_none() && self.options.replaying().is_none() {
+            return Err(ORMError::NoConnection);
+        }
+        self.set_transaction_depth(depth - 1);
+        // the pin that `begin` kept for the outermost transaction
+        let session = (depth == 1).then(|| SessionGuard { orm: self, owner: transaction_owner(), released: false });
+        if depth == 1 {
+            self.n_plus_one.reset_task();
+        }
+        if self.options.replaying().is_some() {
+            drop(conn);
+            if let Some(session) = session {
+                session.release();
+            }
+            return Ok(());
+        }
+        let mut result = Ok(());
+        for statement in end_transaction_statements(depth, commit) {
+            log::debug!("{:?}", statement);
+            if let Err(e) = conn.as_mut().unwrap().query_drop(statement).await {
+                result = Err(ORMError::MySQLError(e));
+                break;
+            }
+        }
+        drop(conn);
+        if let Some(session) = session {
+            self.write_hooks.end_transaction(commit && result.is_ok());
+            session.release();
+        }
+        result
+    }
+
+    /// `start_transaction` is an asynchronous method that starts a transaction on the session connection,
+    /// or creates a savepoint if a transaction of `depth` is active.
+    async fn start_transaction(&self, depth: usize, level: Option<&str>) -> Result<(), ORMError> {
+        let mut conn = self.lock().await?;
+        if conn.is_none() {
+            return Err(ORMError::NoConnection);
+        }
+        let conn = conn.as_mut().unwrap();
+        if depth == 0 {
+            self.n_plus_one.reset_task();
+        }
+        if depth > 0 {
+            let statement = savepoint_statement(depth);
+            log::debug!("{:?}", statement);
+            conn.query_drop(statement).await?;
+            self.set_transaction_depth(depth + 1);
+            return Ok(());
+        }
+        if let Some(level) = level {
+            let statement = format!("SET TRANSACTION ISOLATION LEVEL {}", level);
+            log::debug!("{:?}", statement);
+            conn.query_drop(statement).await?;
+        }
+        log::debug!("{:?}", "START TRANSACTION");
+        conn.query_drop("START TRANSACTION").await?;
+        self.set_transaction_depth(1);
+        Ok(())
+    }
+
+    /// `column_kind` is a function that tells how the values of a column are read:
+    /// integer columns are read as `i64`, or as `u64` if they are declared `UNSIGNED`, other columns as `String`.
+    fn column_kind(column: &mysql_async::Column) -> ColumnKind {
+        match column.column_type() {
+            ColumnType::MYSQL_TYPE_TINY | ColumnType::MYSQL_TYPE_SHORT | ColumnType::MYSQL_TYPE_LONG
+            | ColumnType::MYSQL_TYPE_INT24 | ColumnType::MYSQL_TYPE_LONGLONG => {
+                if column.flags().contains(ColumnFlags::UNSIGNED_FLAG) {
+                    ColumnKind::Unsigned
+                } else {
+                    ColumnKind::Signed
+                }
+            }
+            ColumnType::MYSQL_TYPE_DATE | ColumnType::MYSQL_TYPE_NEWDATE => ColumnKind::Date,
+            _ => ColumnKind::Text,
+        }
+    }
+
+    /// `binary_text` is a function that formats a number or a temporal value read with the binary protocol
+    /// like the text protocol sends it, e.g. `2024-05-01 12:30:00` for a DATETIME value.
+    fn binary_text(value: &mysql_async::Value, kind: ColumnKind) -> Option<String> {
+        match value {
+            mysql_async::Value::Int(v) => Some(v.to_string()),
+            mysql_async::Value::UInt(v) => Some(v.to_string()),
+            mysql_async::Value::Float(v) => Some(v.to_string()),
+            mysql_async::Value::Double(v) => Some(v.to_string()),
+            mysql_async::Value::Date(year, month, day, ..) if kind == ColumnKind::Date => Some(format!("{:04}-{:02}-{:02}", year, month, day)),
+            mysql_async::Value::Date(year, month, day, hour, minute, second, micros) => {
+                let fraction = if *micros > 0 { format!(".{:06}", micros) } else { String::new() };
+                Some(format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}{}", year, month, day, hour, minute, second, fraction))
+            }
+            mysql_async::Value::Time(negative, days, hours, minutes, seconds, micros) => {
+                let fraction = if *micros > 0 { format!(".{:06}", micros) } else { String::new() };
+                Some(format!("{}{:02}:{:02}:{:02}{}", if *negative { "-" } else { "" }, days * 24 + *hours as u32, minutes, seconds, fraction))
+            }
+            mysql_async::Value::NULL | mysql_async::Value::Bytes(_) => None,
+        }
+    }
+
+    /// `read_row` is a function that converts a `mysql_async::Row` into a `Row`.
+    /// `names` are the column names shared by all rows of the result.
+    /// `columns_kind` tells for every column how its values are read (see `column_kind`).
+    /// In strict mode the first value that cannot be decoded is stored in `decode_error`.
+    fn read_row(row: &mysql_async::Row, names: &Arc<Vec<String>>, columns_kind: &[ColumnKind], strict: bool, decode_error: &mut Option<DecodeError>) -> Row {
+        let mut r: Row = Row::with_names(names.clone());
+        for (i, kind) in columns_kind.iter().enumerate() {
+            let res = match kind {
+                ColumnKind::Signed => row.get_opt::<Option<i64>, usize>(i).map(|v| v.map(|v| v.map(|v| v.to_string()))),
+                ColumnKind::Unsigned => row.get_opt::<Option<u64>, usize>(i).map(|v| v.map(|v| v.map(|v| v.to_string()))),
+                ColumnKind::Date | ColumnKind::Text => match row.as_ref(i).and_then(|value| ORM::binary_text(value, *kind)) {
+                    Some(text) => Some(Ok(Some(text))),
+                    None => row.get_opt::<Option<String>, usize>(i),
+                },
+            };
+            match res {
+                Some(Ok(Some(v))) => r.set(i as i32, Some(v)),
+                // a column that is not set is read as NULL
+                Some(Ok(None)) => {
+                    if strict {
+                        r.set(i as i32, None::<String>)
+                    }
+                }
+                Some(Err(e)) => {
+                    if strict && decode_error.is_none() {
+                        *decode_error = Some(DecodeError::InvalidValue {
+                            index: i as i32,
+                            value: format!("{:?}", e.0),
+                            type_name: match kind {
+                                ColumnKind::Signed => "i64",
+                                ColumnKind::Unsigned => "u64",
+                                ColumnKind::Date | ColumnKind::Text => "String",
+                            },
+                        });
+                    }
+                }
+                None => {
+                    if strict && decode_error.is_none() {
+                        *decode_error = Some(DecodeError::NoSuchColumn(i as i32));
+                    }
+                    break;
+                }
+            }
+        }
+        r
+    }
+
+    /// `dynamic_value` is a function that converts a value read with the text protocol into a `Value` using the column type.
+    fn dynamic_value(column: &mysql_async::Column, value: Option<&mysql_async::Value>) -> Value {
+        match value {
+            None | Some(mysql_async::Value::NULL) => Value::Null,
+            Some(mysql_async::Value::Int(v)) => Value::Int(*v),
+            Some(mysql_async::Value::UInt(v)) => i64::try_from(*v).map(Value::Int).unwrap_or_else(|_| Value::Text(v.to_string())),
+            Some(mysql_async::Value::Float(v)) => Value::Float(*v as f64),
+            Some(mysql_async::Value::Double(v)) => Value::Float(*v),
+            Some(mysql_async::Value::Bytes(bytes)) => {
+                let text = String::from_utf8_lossy(bytes).to_string();
+                match column.column_type() {
+                    ColumnType::MYSQL_TYPE_TINY | ColumnType::MYSQL_TYPE_SHORT | ColumnType::MYSQL_TYPE_LONG
+                    | ColumnType::MYSQL_TYPE_INT24 | ColumnType::MYSQL_TYPE_LONGLONG | ColumnType::MYSQL_TYPE_YEAR => {
+                        text.parse().map(Value::Int).unwrap_or(Value::Text(text))
+                    }
+                    ColumnType::MYSQL_TYPE_FLOAT | ColumnType::MYSQL_TYPE_DOUBLE => {
+                        text.parse().map(Value::Float).unwrap_or(Value::Text(text))
+                    }
+                    ColumnType::MYSQL_TYPE_TINY_BLOB | ColumnType::MYSQL_TYPE_MEDIUM_BLOB | ColumnType::MYSQL_TYPE_LONG_BLOB
+                    | ColumnType::MYSQL_TYPE_BLOB | ColumnType::MYSQL_TYPE_VAR_STRING | ColumnType::MYSQL_TYPE_STRING
+                    | ColumnType::MYSQL_TYPE_VARCHAR if column.character_set() == 63 => Value::Bytes(bytes.clone()),
+                    _ => Value::Text(text),
+                }
+            }
+            Some(v) => Value::Text(v.as_sql(true).trim_matches('\'').to_string()),
+        }
+    }
+
+    /// `table_options` is a method that returns the table options clause for `CREATE TABLE` statements.
+    /// It renders the `charset` and `collation` of the connect options, e.g. `DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci`,
+    /// so that generated tables use the same character set as the connection.
+    /// If no `charset` was configured, it returns an empty string.
+    pub fn table_options(&self) -> String {
+        match (&self.options.charset, &self.options.collation) {
+            (Some(charset), Some(collation)) => format!("DEFAULT CHARSET={} COLLATE={}", charset, collation),
+            (Some(charset), None) => format!("DEFAULT CHARSET={}", charset),
+            _ => "".to_string(),
+        }
+    }
+
+    /// `init_statements` is a function that builds the statements executed on every new connection.
+    /// The `charset` and `collation` of `options` are rendered as a `SET NAMES` statement and
+    /// the typed session variables (`sql_mode`, `time_zone`, `wait_timeout`) are rendered as `SET SESSION` statements.
+    /// With `DateTimePolicy::Utc` and no explicit `time_zone` the session time zone is set to UTC.
+    /// The statements are followed by the user supplied `on_connect` statements.
+    fn init_statements(options: &ConnectOptions) -> Vec<String> {
+        let mut statements: Vec<String> = Vec::new();
+        if let Some(charset) = &options.charset {
+            match &options.collation {
+                Some(collation) => statements.push(format!("SET NAMES '{}' COLLATE '{}'", charset.replace('\'', "''"), collation.replace('\'', "''"))),
+                None => statements.push(format!("SET NAMES '{}'", charset.replace('\'', "''"))),
+            }
+        }
+        if let Some(sql_mode) = &options.sql_mode {
+            statements.push(format!("SET SESSION sql_mode = '{}'", sql_mode.join(",").replace('\'', "''")));
+        }
+        if let Some(time_zone) = &options.time_zone {
+            statements.push(format!("SET SESSION time_zone = '{}'", time_zone.replace('\'', "''")));
+        } else if options.datetime_policy == DateTimePolicy::Utc {
+            statements.push("SET SESSION time_zone = '+00:00'".to_string());
+        }
+        if let Some(wait_timeout) = options.wait_timeout {
+            statements.push(format!("SET SESSION wait_timeout = {}", wait_timeout));
+        }
+        statements.extend(options.on_connect.iter().cloned());
+        statements
+    }
+
+    /// `insert` is an asynchronous method that executes an INSERT and returns the generated ID and the number of inserted rows.
+    /// It returns `ORMError::InsertError` if no ID was generated.
+    pub(crate) async fn insert(&self, query: &str, params: &[Value]) -> Result<(u64, usize), ORMError> {
+        log::debug!("{:?} {:?}", query, params);
+        self.check_policy(query)?;
+        let key = statement_key(query, params, Dialect::MySql);
+        if let Some(cassette) = self.options.replaying() {
+            let (affected_rows, r) = cassette.replay_affected(&key)?;
+            self.record_insert_id(r);
+            return Ok((r.ok_or(ORMError::InsertError)?, affected_rows as usize));
+        }
+        let mut conn = self.lock().await?;
+        if conn.is_none() {
+            return Err(ORMError::NoConnection);
+        }
+        let conn = conn.as_mut().unwrap();
+        let start = Instant::now();
+        let r = ORM::execute(conn, query, params).await.map(|(affected_rows, r)| (r, affected_rows));
+        self.log_statement(query, start, &r, |(_, affected_rows)| *affected_rows as usize);
+        self.options.record(&key, &r, |(r, affected_rows)| Recorded::Affected { rows: *affected_rows, last_insert_id: *r });
+        let (r, affected_rows) = r?;
+        self.record_insert_id(r);
+        match r {
+            Some(r) => Ok((r, affected_rows as usize)),
+            None => Err(ORMError::InsertError),
+        }
+    }
+
+    /// `auto_increment_increment` is an asynchronous method that reads `@@auto_increment_increment`, the step between
+    /// the AUTO_INCREMENT values that one INSERT generates.
+    async fn auto_increment_increment(&self) -> Result<u64, ORMError> {
+        let rows = self.read_values("select @@auto_increment_increment as increment", &[]).await?;
+        match rows.first().and_then(|row| row.get("increment")) {
+            Some(Value::Int(increment)) if *increment > 0 => Ok(*increment as u64),
+            value => Err(ORMError::InvalidValue(format!("unexpected auto_increment_increment {:?}", value))),
+        }
+    }
+
+    /// `execute` is an asynchronous function that executes `query` on `conn` and returns the number of affected rows
+    /// and the last insert ID. A query with `params` is executed as a prepared statement with the params bound
+    /// to its placeholders, other queries are sent as text.
+    async fn execute<Q: Queryable>(conn: &mut Q, query: &str, params: &[Value]) -> mysql_async::Result<(u64, Option<u64>)> {
+        if params.is_empty() {
+            let result = conn.query_iter(query).await?;
+            return Ok((result.affected_rows(), result.last_insert_id()));
+        }
+        let result = conn.exec_iter(query, ORM::params(params)).await?;
+        Ok((result.affected_rows(), result.last_insert_id()))
+    }
+
+    /// `params` is a function that converts the params of a query into the values of a prepared statement.
+    fn params(params: &[Value]) -> Vec<mysql_async::Value> {
+        params.iter()
+            .map(|param| match param {
+                Value::Null => mysql_async::Value::NULL,
+                Value::Int(v) => mysql_async::Value::Int(*v),
+                Value::Float(v) => mysql_async::Value::Double(*v),
+                Value::Text(v) => mysql_async::Value::Bytes(v.as_bytes().to_vec()),
+                Value::Bytes(v) => mysql_async::Value::Bytes(v.clone()),
+            })
+            .collect()
+    }
+}
+
+impl DbManager<ORM> {
+    /// `from_config` is an asynchronous function that connects to the databases configured in the
+    /// `[databases.<name>]` sections of the TOML file `path`, see `ConnectOptions::databases_from_config`.
+    /// If one of the connections fails, the databases connected so far are closed and the error is returned.
+    pub async fn from_config<P: AsRef<std::path::Path>>(path: P) -> Result<DbManager<ORM>, ORMError> {
+        let mut manager = DbManager::new();
+        for (name, options) in ConnectOptions::databases_from_config(path)? {
+            match ORM::connect_with(options).await {
+                Ok(orm) => {
+                    manager.insert(&name, orm);
+                }
+                Err(e) => {
+                    let _ = manager.close().await;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(manager)
     }
 }
+
 /// This is the implementation of the `ORMTrait` for the `ORM` struct.
 /// The `ORMTrait` provides a set of methods for interacting with a database.
 /// These methods include adding data, finding data, modifying data, and removing data.
@@ -54,53 +873,183 @@ impl ORMTrait<ORM> for ORM {
     fn add<T>(&self, data: T) -> QueryBuilder<T, T, ORM>
         where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static
     {
-        let table_name = data.name();
-        let types = serializer_types::to_string(&data).unwrap();
-        let values = serializer_values::to_string(&data).unwrap();
-        let query: String = format!("insert into {table_name} {types} values {values}");
+        let table_name = self.options.table_name::<T>();
+        let (query, params, error) = match insert_statement(&table_name, &data, &self.options) {
+            Ok((query, params)) => (query, params, None),
+            Err(e) => (format!("insert into {table_name}"), Vec::new(), Some(e)),
+        };
         let qb = QueryBuilder::<T,T, ORM> {
-            query: query,
+            query,
+            params,
+            counters: counter_statements(&table_name, &data, &data.get_id(), true),
             entity: Default::default(),
             orm: self,
             result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: data.validation().err(),
+            table: Some(table_name),
         };
         qb
     }
-    /// `last_insert_rowid` is an asynchronous method that retrieves the row ID of the last inserted record.
+    /// `add_or_update` is a method that constructs an `AddOrUpdate` that writes `data` with `INSERT ... ON DUPLICATE KEY UPDATE`.
+    fn add_or_update<T>(&self, data: T) -> AddOrUpdate<'_, T, ORM>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static
+    {
+        AddOrUpdate::new(self, data)
+    }
+    /// `add_many` is a method that constructs an `AddMany` that inserts `data` with multi-row statements in one transaction.
+    fn add_many<T>(&self, data: Vec<T>) -> AddMany<'_, T, ORM>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static
+    {
+        AddMany::new(self, data)
+    }
+    /// `add_all` is an asynchronous method that inserts several records of the same table with a single multi-row INSERT
+    /// and returns their generated IDs in the order of `data`.
+    /// MySQL reports the ID of the first inserted row; the following IDs are derived from it, which relies on
+    /// the AUTO_INCREMENT values of a single multi-row insert being allocated without gaps (`innodb_autoinc_lock_mode` 0, 1 or 2).
+    /// They are `@@auto_increment_increment` apart, which is read on the same connection, as it is not 1 in multi-primary
+    /// setups such as Galera or Group Replication. `@@auto_increment_offset` only moves the first ID.
+    async fn add_all<T>(&self, data: Vec<T>) -> Result<Vec<u64>, ORMError>
+        where T: TableDeserialize + TableSerialize + Serialize + Send + 'static
+    {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        for d in data.iter() {
+            d.validation().map_err(ORMError::Validation)?;
+        }
+        let table_name = self.options.table_name::<T>();
+        let (query, _, params) = insert_rows_statement(&table_name, &data, false, &self.options)?;
+        // the session keeps the insert and the variable on one connection, whose session may override the global step
+        let session = self.pin().await?;
+        let inserted = async {
+            let (first_id, affected_rows) = self.insert(&query, &params).await?;
+            Ok::<_, ORMError>((first_id, affected_rows, self.auto_increment_increment().await?))
+        }.await;
+        session.release();
+        let (first_id, affected_rows, increment) = inserted?;
+        if affected_rows != data.len() {
+            return Err(ORMError::InsertError);
+        }
+        Ok((0..affected_rows as u64).map(|i| first_id + i * increment).collect())
+    }
+
+    /// `last_insert_rowid` is an asynchronous method that retrieves the row ID of the last inserted record, see `last_insert_id`.
     /// It returns a `Result` that contains the row ID as an `i64` if the operation is successful.
     /// If the operation is not successful, the `Result` contains an `ORMError`.
-    /// Currently, this method is hardcoded to always return `0` as the row ID.
-    /// It first locks the `conn` field of the `ORM` struct, which is a `Mutex` guarding an `Option` wrapping a `Conn` object.
-    /// If the `conn` field is `None`, it returns an `ORMError::NoConnection`.
-    /// Otherwise, it returns `Ok(0)`.
     async fn last_insert_rowid(&self)  -> Result<i64, ORMError>{
-        let conn = self.conn.lock().await;
-        if conn.is_none() {
+        Ok(self.last_insert_id().await? as i64)
+    }
+    /// `last_insert_id` is an asynchronous method that retrieves the generated ID of the last record the current task
+    /// inserted, like `LAST_INSERT_ID()`. The statements of a task may run on different connections of the pool,
+    /// so the ID is kept per task and not read from a connection. It is `0` if the task did not insert a record yet.
+    /// If the ORM is closed, it returns an `ORMError::NoConnection`.
+    async fn last_insert_id(&self) -> Result<u64, ORMError> {
+        if self.options.replaying().is_none() && self.pool.read().unwrap().is_none() {
             return Err(ORMError::NoConnection);
         }
-        Ok(0)
+        Ok(self.insert_ids.lock().unwrap().ids.get(&transaction_owner()).copied().unwrap_or(0))
     }
-    /// `close` is an asynchronous method that closes the database connection.
-    /// It first locks the `conn` field of the `ORM` struct, which is a `Mutex` guarding an `Option` wrapping a `Conn` object.
-    /// If the `conn` field is `None`, it returns an `ORMError::NoConnection`.
-    /// Otherwise, it attempts to disconnect the `Conn` object.
-    /// If the disconnection is successful, it returns `Ok(())`.
+    /// `close` is an asynchronous method that closes the connections of the pool.
+    /// It waits until the connections that are in use are returned to the pool, so statements that are running complete.
+    /// If the ORM is closed already, it returns an `ORMError::NoConnection`.
     /// If the disconnection is not successful, it returns an `ORMError::MySQLError` containing the error from the `mysql_async` library.
     async fn close(&self)  -> Result<(), ORMError>{
-        let mut conn_lock = self.conn.lock().await;
-        if conn_lock.is_none() {
+        if self.options.replaying().is_some() {
+            return Ok(());
+        }
+        let pool = self.pool.write().unwrap().take();
+        let Some(pool) = pool else {
             return Err(ORMError::NoConnection);
+        };
+        let sessions: Vec<_> = self.sessions.lock().unwrap().drain().map(|(_, session)| session.conn).collect();
+        for conn in sessions {
+            conn.lock().await.take();
         }
-        let conn = conn_lock.take();
-        let r = conn.unwrap().disconnect().await;
-        match r {
-            Ok(_) => {
+        pool.disconnect().await.map_err(ORMError::MySQLError)
+    }
+    /// `exists_by_id` is a method that checks whether the table of `T` contains a record with the given `id`.
+    /// It runs `SELECT 1 ... LIMIT 1`, so the record is neither transferred nor deserialized.
+    async fn exists_by_id<T>(&self, id: u64) -> Result<bool, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let rows: Vec<Row> = self.query(format!("select 1 from {table_name} where id = {id} limit 1").as_str()).exec().await?;
+        Ok(!rows.is_empty())
+    }
+    /// `find_by_ids` is an asynchronous method that fetches the records of the table of `T` with the given `ids`
+    /// in a single query and returns them in the order of `ids`, with `None` for an ID that has no record.
+    async fn find_by_ids<T>(&self, ids: &[u64]) -> Result<Vec<Option<T>>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + Clone + Send + Sync + 'static
+    {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let found: Vec<T> = self.find_many(Cond::field("id").is_in(ids.iter().copied())).run().await?;
+        let by_id: HashMap<String, T> = found.into_iter().map(|data| (data.get_id(), data)).collect();
+        Ok(ids.iter().map(|id| by_id.get(&id.to_string()).cloned()).collect())
+    }
+    /// `descendants_of` is an asynchronous method that fetches the descendants of the record `id` of the table of `T`
+    /// in the hierarchy of its `parent_id` column with a recursive CTE, which requires MySQL 8 or later.
+    async fn descendants_of<T>(&self, id: u64) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + Send + Sync + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        self.query(tree_statement(&table_name, id, true).as_str()).run().await
+    }
+    /// `ancestors_of` is an asynchronous method that fetches the ancestors of the record `id` of the table of `T`
+    /// in the hierarchy of its `parent_id` column with a recursive CTE, which requires MySQL 8 or later.
+    async fn ancestors_of<T>(&self, id: u64) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + Send + Sync + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        self.query(tree_statement(&table_name, id, false).as_str()).run().await
+    }
+    /// `refresh` is a method that re-reads the record of the given data object by its ID and overwrites the data object with it.
+    /// It returns `ORMError::NotFound` and leaves the data object unchanged if the record no longer exists.
+    async fn refresh<T>(&self, data: &mut T) -> Result<(), ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + Send + Sync + 'static
+    {
+        let id = data.get_id();
+        let stored: Option<T> = self.find_one_where(Cond::field("id").eq(id_param(&id))).run().await?;
+        match stored {
+            Some(stored) => {
+                *data = stored;
                 Ok(())
             }
-            Err(e) => {
-                Err(ORMError::MySQLError(e))
-            }
+            None => Err(ORMError::NotFound(format!("{} with id {}", T::same_name(), id))),
+        }
+    }
+    /// `save` is a method that inserts the given data object if its ID is unset (`0` or empty) and updates the stored record otherwise.
+    /// The stored record is read back after the update, because MySQL reports no affected rows for an update that changes nothing.
+    /// It returns the stored data object, or `ORMError::NotFound` if the data object has an ID but there is no record with that ID.
+    async fn save<T>(&self, data: T) -> Result<T, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Send + Sync + 'static
+    {
+        let id = data.get_id();
+        if is_unset_id(&id) {
+            return self.add(data).apply().await;
         }
+        self.modify(data).run().await?;
+        let stored: Option<T> = self.find_one_where(Cond::field("id").eq(id_param(&id))).run().await?;
+        stored.ok_or_else(|| ORMError::NotFound(format!("{} with id {}", T::same_name(), id)))
+    }
+    /// `persist` is a method that inserts a `Persisted` entity that is not stored yet and updates its record otherwise.
+    /// Afterwards the entity holds the stored values and its original ID, so a second call updates instead of inserting again.
+    /// It returns `ORMError::InvalidValue` if the ID of a persisted entity was changed.
+    async fn persist<T>(&self, entity: &mut Persisted<T>) -> Result<(), ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + Send + Sync + 'static
+    {
+        let stored = match entity.original_id() {
+            None => self.add(T::clone(entity)).apply().await?,
+            Some(id) if id != entity.get_id() => {
+                return Err(ORMError::InvalidValue(format!("the id of a persisted entity changed from {} to {}", id, entity.get_id())));
+            }
+            Some(_) => self.save(T::clone(entity)).await?,
+        };
+        *entity = Persisted::loaded(stored);
+        Ok(())
     }
     /// `find_one` is a method that constructs a SQL select query to find a record by its ID.
     /// It takes a generic parameter `T` that represents the data object and an `id` of type `u64`.
@@ -113,40 +1062,81 @@ impl ORMTrait<ORM> for ORM {
     fn find_one<T: TableDeserialize>(&self, id: u64) -> QueryBuilder<Option<T>, T, ORM>
         where T: TableDeserialize + TableSerialize + for<'a> Deserialize<'a> + 'static
     {
-        let table_name = T::same_name();
+        let table_name = self.options.table_name::<T>();
 
         let query: String = format!("select * from {table_name} where id = {id}");
 
         let qb = QueryBuilder::<Option<T>, T, ORM> {
             query,
+            params: Vec::new(),
+            counters: Vec::new(),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
+            error: None,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
         };
         qb
     }
+    /// `find_one_where` is a method that constructs a SQL select query to find the first record that matches the provided WHERE clause.
+    /// It takes a generic parameter `T` that represents the data object and a `query_where` which is the WHERE clause of the SQL query,
+    /// either a raw SQL fragment or a `Cond` whose values are bound as params.
+    /// The query is limited to one record with `LIMIT 1`; `order_by` can be used to choose which record is returned.
+    /// The method returns a `QueryBuilder` object whose `run` method returns `None` if no record matches.
+    fn find_one_where<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, Option<T>, T, ORM>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+
+        let (query_where, params, error) = query_where.into().into_parts();
+        let query: String = format!("select * from {table_name} where {query_where}");
+
+        let qb = QueryBuilder::<Option<T>, T, ORM> {
+            query,
+            params,
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        };
+        qb.limit(1)
+    }
     /// `find_many` is a method that constructs a SQL select query to find multiple records that match the provided WHERE clause.
-    /// It takes a generic parameter `T` that represents the data object and a `query_where` of type `&str` which is the WHERE clause of the SQL query.
+    /// It takes a generic parameter `T` that represents the data object and a `query_where` which is the WHERE clause of the SQL query,
+    /// either a raw SQL fragment or a `Cond` whose values are bound as params.
     /// The data object must implement the `Deserialize`, `TableDeserialize` traits and have a static lifetime.
     /// The `Deserialize` trait is used to deserialize the data object from a serialized format.
     /// The `TableDeserialize` trait is used to convert the data object from a table format.
     /// The method returns a `QueryBuilder` object that represents the SQL select query.
     /// The `QueryBuilder` object is generic over the lifetime `'a`, the result type `R`, the entity type `E`, and the ORM type `O`.
     /// The ORM type `O` must implement the `ORMTrait`.
-    fn find_many<T>(&self, query_where: &str) -> QueryBuilder<Vec<T>, T, ORM>
+    fn find_many<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<Vec<T>, T, ORM>
         where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
 
     {
 
-        let table_name = T::same_name();
+        let table_name = self.options.table_name::<T>();
 
+        let (query_where, params, error) = query_where.into().into_parts();
         let query: String = format!("select * from {table_name} where {query_where}");
 
         let qb = QueryBuilder::<Vec<T>, T, ORM> {
             query,
+            params,
+            counters: Vec::new(),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
         };
         qb
     }
@@ -161,18 +1151,88 @@ impl ORMTrait<ORM> for ORM {
     /// The ORM type `O` must implement the `ORMTrait`.
     fn find_all<T>(&self) -> QueryBuilder<Vec<T>, T, ORM>
         where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static {
-        let table_name = T::same_name();
+        let table_name = self.options.table_name::<T>();
 
         let query: String = format!("select * from {table_name}");
 
         let qb = QueryBuilder::<Vec<T>, T, ORM> {
             query,
+            params: Vec::new(),
+            counters: Vec::new(),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
+            error: None,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
         };
         qb
     }
+    /// `count` is a method that constructs a `select count(*)` query of the records that match the provided WHERE clause.
+    fn count<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, u64, T, ORM>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let (query_where, params, error) = query_where.into().into_parts();
+        QueryBuilder::<u64, T, ORM> {
+            query: format!("select count(*) from {table_name} where {query_where}"),
+            params,
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        }
+    }
+    /// `exists` is a method that constructs a `select exists(...)` query that checks whether a record matches the provided WHERE clause.
+    fn exists<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, bool, T, ORM>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let (query_where, params, error) = query_where.into().into_parts();
+        QueryBuilder::<bool, T, ORM> {
+            query: format!("select exists(select 1 from {table_name} where {query_where})"),
+            params,
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        }
+    }
+    /// `find_as` is a method that constructs a SQL select query of the fields of the projection `P` from `table`.
+    fn find_as<P>(&self, table: &str, query_where: &str) -> QueryBuilder<'_, Vec<P>, P, ORM>
+        where P: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+    {
+        let table_name = self.options.qualify(table);
+        let columns = P::fields().join(", ");
+        let query: String = format!("select {columns} from {table_name} where {query_where}");
+        QueryBuilder::<Vec<P>, P, ORM> {
+            query,
+            params: Vec::new(),
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error: (!is_table_name(table)).then(|| format!("invalid table name {:?}", table)),
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        }
+    }
+    /// `fetch` is an asynchronous method that runs a select query of records of `T`.
+    async fn fetch<T>(&self, query: QueryBuilder<'_, Vec<T>, T, ORM>) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + Send + Sync + 'static
+    {
+        query.run().await
+    }
     /// `modify` is a method that constructs a SQL update query for a given data object.
     /// It takes a generic parameter `T` that represents the data object.
     /// The data object must implement the `TableDeserialize`, `TableSerialize`, `Serialize` traits and have a static lifetime.
@@ -185,20 +1245,167 @@ impl ORMTrait<ORM> for ORM {
     fn modify<T>(&self, data: T) -> QueryBuilder<usize, (), ORM>
         where T: TableDeserialize + TableSerialize + Serialize + 'static
     {
-        let table_name = data.name();
-        let key_value_str = serializer_key_values::to_string(&data).unwrap();
-        // remove first and last char
-        let key_value = &key_value_str[1..key_value_str.len()-1];
-        let id = data.get_id();
-        let query: String = format!("update {table_name} set {key_value} where id = {id}");
+        let table_name = self.options.table_name::<T>();
+        let (query, params, error) = match update_statement(&table_name, &data, &data.get_id(), &self.options) {
+            Ok((query, params)) => (query, params, None),
+            Err(e) => (format!("update {table_name}"), Vec::new(), Some(e)),
+        };
         let qb = QueryBuilder::<usize, (), ORM> {
             query,
+            params,
+            counters: Vec::new(),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: data.validation().err(),
+            table: Some(table_name),
         };
         qb
     }
+    /// `modify_fields` is a method that constructs a SQL update query that sets only the listed `fields` of a given data object.
+    /// It is a lighter alternative to `modify` when only a few columns have changed; the record is found by its ID.
+    /// If `fields` is empty or contains `id` or a name that is not a field of `T`, the returned `QueryBuilder` fails with `ORMError::InvalidValue` when it is run.
+    fn modify_fields<T>(&self, data: &T, fields: &[&str]) -> QueryBuilder<'_, usize, (), ORM>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let columns = T::fields();
+        let ((query, params), error) = if fields.is_empty() {
+            ((String::new(), Vec::new()), Some("no fields to modify".to_string()))
+        } else if let Some(field) = fields.iter().find(|field| **field == "id" || !columns.iter().any(|column| column == *field)) {
+            ((String::new(), Vec::new()), Some(format!("cannot modify field {}", field)))
+        } else {
+            match update_fields_statement(&table_name, data, fields, &data.get_id(), &self.options) {
+                Ok(statement) => (statement, None),
+                Err(e) => ((String::new(), Vec::new()), Some(e)),
+            }
+        };
+        QueryBuilder::<usize, (), ORM> {
+            query,
+            params,
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: data.validation().err(),
+            table: Some(table_name),
+        }
+    }
+
+    /// `increment` is a method that constructs a SQL update query that adds `by` to the numeric `column` of the record with the given `id`.
+    /// The new value is computed by the database (`column = column + by`), so concurrent increments are not lost
+    /// the way they are with a read-modify-write of the entity. A negative `by` decrements the column.
+    /// If `column` is `id` or not a field of `T`, the returned `QueryBuilder` fails with `ORMError::InvalidValue` when it is run.
+    fn increment<T>(&self, id: u64, column: &str, by: i64) -> QueryBuilder<'_, usize, (), ORM>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let error = if column == "id" || !T::fields().iter().any(|field| field == column) {
+            Some(format!("cannot increment field {}", column))
+        } else {
+            None
+        };
+        let query: String = format!("update {table_name} set {column} = {column} + ({by}) where id = {id}");
+        QueryBuilder::<usize, (), ORM> {
+            query,
+            params: Vec::new(),
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        }
+    }
+
+    /// `upsert_all` is a method that starts an `UpsertAll` of the given rows.
+    /// The rows are written when `run` is called; use `on_conflict` and `chunk_size` to configure the statements.
+    fn upsert_all<T>(&self, rows: Vec<T>) -> UpsertAll<'_, T, ORM>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        UpsertAll {
+            rows,
+            conflict_columns: Vec::new(),
+            chunk_size: UpsertAll::<T, ORM>::DEFAULT_CHUNK_SIZE,
+            orm: self,
+        }
+    }
+
+    /// `import_all` is a method that starts an `ImportAll` of the given rows.
+    /// The rows are inserted when `run` is called; use `chunk_size` to configure the statements.
+    fn import_all<T>(&self, rows: Vec<T>) -> ImportAll<'_, T, ORM>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        ImportAll {
+            rows,
+            chunk_size: ImportAll::<T, ORM>::DEFAULT_CHUNK_SIZE,
+            orm: self,
+        }
+    }
+
+    /// `queue` is a method that returns the job queue `name`, see `Queue`.
+    fn queue(&self, name: &str) -> Queue<'_, ORM> {
+        Queue::new(self, name)
+    }
+
+    /// `create_table` is a method that constructs a `CREATE TABLE` statement from the column definitions of `T`.
+    /// Foreign keys are rendered as `FOREIGN KEY` constraints with their `ON DELETE` and `ON UPDATE` actions,
+    /// and the table options of the connection (`table_options`) are appended.
+    /// If `T` has no column definitions, the returned `QueryBuilder` fails with `ORMError::InvalidValue` when it is run.
+    fn create_table<T>(&self) -> QueryBuilder<'_, usize, (), ORM>
+        where T: TableDeserialize + 'static
+    {
+        let columns = T::columns();
+        let error = if columns.is_empty() { Some(format!("{} has no column definitions", T::same_name())) } else { None };
+        let query = format!("{} {}", schema::create_table_sql(&self.options.table_name::<T>(), T::table_comment().as_deref(), &columns, Dialect::MySql), self.table_options());
+        QueryBuilder::<usize, (), ORM> {
+            query: query.trim_end().to_string(),
+            params: Vec::new(),
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: None,
+            table: None,
+        }
+    }
+
+    /// `ddl` is a method that renders a schema statement built with `Ddl` in the MySQL dialect
+    /// and returns a `QueryBuilder` whose `run` method executes it.
+    fn ddl<D: DdlStatement>(&self, ddl: &D) -> QueryBuilder<'_, usize, (), ORM> {
+        self.query_update(ddl.to_sql(Dialect::MySql).as_str())
+    }
+
+    /// `alter_table` is a method that returns an `AlterTable` builder for the table of `T`.
+    /// Its `run` method executes all changes with one `ALTER TABLE` statement.
+    fn alter_table<T>(&self) -> AlterTable<'_, T, ORM>
+        where T: TableDeserialize + 'static
+    {
+        AlterTable::new(self, self.options.table_name::<T>())
+    }
+
+    /// `store_comments` is a method that stores the `#[table(comment)]` and `#[column(comment)]` documentation of `T`
+    /// in the schema of an existing table, with one `ALTER TABLE` statement that sets the table comment and
+    /// modifies the commented columns. Tables created with `create_table` already have the comments.
+    /// It returns the number of stored comments.
+    async fn store_comments<T>(&self) -> Result<usize, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        let columns = T::columns();
+        let comment = T::table_comment();
+        let statements = schema::comment_statements(&self.options.table_name::<T>(), comment.as_deref(), &columns, Dialect::MySql);
+        let queries: Vec<_> = statements.iter().map(|query| self.query_update(query)).collect();
+        self.exec_all(&queries).await?;
+        Ok(comment.iter().count() + columns.iter().filter(|column| column.comment.is_some()).count())
+    }
     /// `remove` is a method that constructs a SQL delete query for a given data object.
     /// It takes a generic parameter `T` that represents the data object.
     /// The data object must implement the `TableDeserialize`, `TableSerialize`, `Serialize` traits and have a static lifetime.
@@ -210,17 +1417,127 @@ impl ORMTrait<ORM> for ORM {
     fn remove<T>(&self, data: T) -> QueryBuilder<usize, (), ORM>
         where T: TableDeserialize + TableSerialize + Serialize + 'static
     {
-        let table_name = data.name();
+        let table_name = self.options.table_name::<T>();
         let id = data.get_id();
-        let query: String = format!("delete from {table_name} where id = {id}");
+        let query: String = format!("delete from {table_name} where id = ?");
         let qb = QueryBuilder::<usize, (), ORM> {
             query,
+            params: vec![id_param(&id)],
+            counters: counter_statements(&table_name, &data, &id, false),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
+            error: None,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
         };
         qb
     }
+
+    /// `remove_by_id` is a method that constructs a SQL delete query for the record of the table of `T` with the given `id`.
+    /// Unlike `remove`, it does not need an entity value, so the record does not have to be fetched before it is deleted.
+    /// The method returns a `QueryBuilder` object whose `run` method returns the number of deleted rows.
+    fn remove_by_id<T>(&self, id: u64) -> QueryBuilder<'_, usize, (), ORM>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let query: String = format!("delete from {table_name} where id = {id}");
+        QueryBuilder::<usize, (), ORM> {
+            query,
+            params: Vec::new(),
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error: None,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        }
+    }
+    /// `remove_by_ids` is an asynchronous method that deletes the records of the table of `T` with the given `ids`
+    /// with a single `DELETE ... WHERE id IN (?, ...)`, the IDs bound as params, and returns the number of deleted rows.
+    /// No statement is executed if `ids` is empty.
+    async fn remove_by_ids<T>(&self, ids: &[u64]) -> Result<usize, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let table_name = self.options.table_name::<T>();
+        let cond = Cond::field("id").is_in(ids.iter().copied());
+        self.query_update(format!("delete from {table_name} where {}", cond.sql()).as_str()).bind(cond.params().to_vec()).run().await
+    }
+    /// `read_blob` is a method that returns a `BlobReader`, which reads the value with one `SUBSTRING` query per chunk.
+    fn read_blob<T>(&self, id: u64, column: &str) -> BlobReader<'_, T, ORM>
+        where T: TableDeserialize + 'static
+    {
+        BlobReader::new(self, id, column)
+    }
+
+    /// `blob_length` is an asynchronous method that returns the `LENGTH` of the value in bytes.
+    async fn blob_length<T>(&self, id: u64, column: &str) -> Result<u64, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let rows = self.query_map(format!("select length({column}) as len from {table_name} where id = {id}").as_str()).await?;
+        match rows.first() {
+            Some(row) => Ok(row.get("len").and_then(Value::as_i64).unwrap_or(0) as u64),
+            None => Err(ORMError::NotFound(format!("{} with id {}", T::same_name(), id))),
+        }
+    }
+
+    /// `read_blob_chunk` is an asynchronous method that reads a part of the value with `SUBSTRING`.
+    /// The value is cast to binary, so that TEXT values are read by bytes instead of characters.
+    async fn read_blob_chunk<T>(&self, id: u64, column: &str, offset: u64, len: usize) -> Result<Vec<u8>, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let query = format!("select substring(cast({column} as binary), {}, {len}) as chunk from {table_name} where id = {id}", offset + 1);
+        let rows = self.query_map(query.as_str()).await?;
+        match rows.into_iter().next().and_then(|mut row| row.remove("chunk")) {
+            Some(Value::Bytes(chunk)) => Ok(chunk),
+            Some(Value::Text(chunk)) => Ok(chunk.into_bytes()),
+            Some(_) => Ok(Vec::new()),
+            None => Err(ORMError::NotFound(format!("{} with id {}", T::same_name(), id))),
+        }
+    }
+
+    /// `scoped` is a method that returns a `Scoped` handle for `scope` without registered entities.
+    fn scoped(&self, scope: Scope) -> Scoped<'_, ORM> {
+        Scoped::new(self, scope)
+    }
+
+    /// `add_dynamic` is an asynchronous method that inserts the values of a `DynamicEntity` into its table
+    /// and reads the inserted record back by its generated ID.
+    async fn add_dynamic(&self, entity: &DynamicEntity) -> Result<DynamicEntity, ORMError> {
+        let (query, params) = entity.insert_statement()?;
+        let (id, _) = self.insert(&query, &params).await?;
+        self.find_dynamic(entity.table(), id).await?.ok_or(ORMError::InsertError)
+    }
+    /// `find_dynamic` is an asynchronous method that finds the record of `table` with the given `id`.
+    async fn find_dynamic(&self, table: &str, id: u64) -> Result<Option<DynamicEntity>, ORMError> {
+        Ok(self.find_many_dynamic(table, Cond::field("id").eq(id)).await?.into_iter().next())
+    }
+    /// `find_many_dynamic` is an asynchronous method that finds the records of `table` matching `query_where`
+    /// and returns every row as a `DynamicEntity` of the values returned by `query_map`.
+    async fn find_many_dynamic<W: Into<Cond> + Send>(&self, table: &str, query_where: W) -> Result<Vec<DynamicEntity>, ORMError> {
+        let query_where = query_where.into();
+        let query = format!("select * from {} where {}", dynamic::checked_table(table)?, query_where.sql());
+        let rows = self.read_values(&query, query_where.params()).await?;
+        Ok(rows.into_iter().map(|row| DynamicEntity::from_map(table, row)).collect())
+    }
+    /// `modify_dynamic` is an asynchronous method that updates the record with the ID of the `DynamicEntity`.
+    async fn modify_dynamic(&self, entity: &DynamicEntity) -> Result<usize, ORMError> {
+        let (query, params) = entity.update_statement()?;
+        self.query_update(&query).bind(params).run().await
+    }
+    /// `remove_dynamic` is an asynchronous method that deletes the record with the ID of the `DynamicEntity`.
+    async fn remove_dynamic(&self, entity: &DynamicEntity) -> Result<usize, ORMError> {
+        let (query, params) = entity.delete_statement()?;
+        self.query_update(&query).bind(params).run().await
+    }
     /// `query` is a method that constructs a `QueryBuilder` for a given SQL query.
     /// It takes a `query` of type `&str` which is the SQL query.
     /// The method returns a `QueryBuilder` object that represents the SQL query.
@@ -229,9 +1546,15 @@ impl ORMTrait<ORM> for ORM {
     fn query<T>(&self, query: &str) -> QueryBuilder<Vec<T>, T, ORM> {
         let qb = QueryBuilder::<Vec<T>, T, ORM> {
             query: query.to_string(),
+            params: Vec::new(),
+            counters: Vec::new(),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
+            error: None,
+            modifiers: Default::default(),
+            validation: None,
+            table: None,
         };
         qb
     }
@@ -243,18 +1566,293 @@ impl ORMTrait<ORM> for ORM {
     fn query_update(&self, query: &str) -> QueryBuilder<usize, (), ORM> {
         let qb = QueryBuilder::<usize, (), ORM> {
             query: query.to_string(),
+            params: Vec::new(),
+            counters: Vec::new(),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
+            error: None,
+            modifiers: Default::default(),
+            validation: None,
+            table: None,
         };
         qb
     }
 
+    /// `batch` is a method that starts a `Batch` of statements.
+    /// The statements pushed into the batch are sent to the server in a single round trip when `execute` is called.
+    fn batch(&self) -> Batch<'_, ORM> {
+        Batch {
+            queries: Vec::new(),
+            orm: self,
+        }
+    }
+
+    /// `query_as` is an asynchronous method that executes an arbitrary select query and decodes every row positionally into `T`.
+    /// `T` is usually a tuple, e.g. `(i32, Option<String>, i64)`; NULL values are decoded as `None`.
+    /// If the result has fewer columns than `T` or a value cannot be decoded, the `Result` contains an `ORMError::DecodeError`.
+    async fn query_as<T: FromRow + Send>(&self, query: &str) -> Result<Vec<T>, ORMError> {
+        let result_set = self.query(query).exec_result_set().await?;
+        Ok(result_set.decode()?)
+    }
+
+    /// `query_map` is an asynchronous method that executes an arbitrary select query and returns every row as a `HashMap`.
+    /// It converts every column value into a `Value` according to the column type.
+    /// Integer columns become `Value::Int`, FLOAT and DOUBLE columns become `Value::Float`, binary columns become `Value::Bytes`,
+    /// and all other columns (including DECIMAL, dates and unsigned values that do not fit into `i64`) become `Value::Text`.
+    /// If the ORM is closed, it returns an `ORMError::NoConnection`.
+    async fn query_map(&self, query: &str) -> Result<Vec<HashMap<String, Value>>, ORMError> {
+        self.read_values(query, &[]).await
+    }
+
+    /// `pool_status` is a method that returns the state of the connection pool of the `ORM` struct.
+    /// `in_use` counts the connections checked out by statements and the session connections of the tasks
+    /// in a transaction or an advisory lock, `idle` the connections the pool keeps open for reuse, at most `pool_min`.
+    /// The pool does not report them, so they are counted as connections are checked out and come back, see `Connections`.
+    /// The wait counters cover every checkout of a connection since the ORM was created.
+    fn pool_status(&self) -> PoolStatus {
+        if self.pool.read().unwrap().is_none() {
+            return self.metrics.status(0, 0);
+        }
+        let connections = self.connections.lock().unwrap();
+        self.metrics.status(connections.in_use + connections.idle, connections.in_use)
+    }
+
+    /// `query_stats` is a method that returns the statement counters collected by `log_statement`.
+    fn query_stats(&self) -> Vec<QueryStats> {
+        self.query_stats.snapshot()
+    }
+
+    /// `reset_query_stats` is a method that clears the statement counters.
+    fn reset_query_stats(&self) {
+        self.query_stats.reset()
+    }
+    /// `on_table_write` is a method that registers `hook` for the successful writes of `table`, see `TableWrite`.
+    fn on_table_write(&self, table: &str, hook: impl Fn(&TableWrite) + Send + Sync + 'static) {
+        self.write_hooks.add(table, Arc::new(hook));
+    }
+
+    /// `set_policy` is a method that sets the policy that every statement is checked against before it is executed.
+    fn set_policy(&self, policy: Policy) {
+        *self.policy.write().unwrap() = policy;
+    }
+
+    /// `health` is an asynchronous method that probes the connection with `SELECT 1`.
+    async fn health(&self) -> HealthReport {
+        health::check(self).await
+    }
+
+    /// `plan_migrations` is an asynchronous method that reads the applied versions from the tracking table of `Migrator`
+    /// and returns the pending migrations without executing them.
+    async fn plan_migrations(&self, migrations: &[Migration]) -> Result<MigrationPlan, ORMError> {
+        let tracked = self.query_map(format!("SELECT table_name FROM information_schema.tables WHERE table_schema = DATABASE() \
+            AND table_name = {}", schema::quote(migrator::TABLE)).as_str()).await?;
+        migrator::plan(self, migrations, !tracked.is_empty()).await
+    }
+
+    /// `table_sizes` is an asynchronous method that reads the data and index length of the base tables
+    /// from `information_schema.tables`.
+    async fn table_sizes(&self) -> Result<Vec<TableSize>, ORMError> {
+        let rows = self.query_map(format!("SELECT table_name AS table_name, data_length AS data_bytes, index_length AS index_bytes \
+            FROM information_schema.tables WHERE table_schema = {} AND table_type = 'BASE TABLE' ORDER BY table_name",
+            self.information_schema()).as_str()).await?;
+        Ok(stats::table_sizes(&rows))
+    }
+
+    /// `row_counts` is an asynchronous method that reads the estimated number of rows of the base tables
+    /// from `information_schema.tables`.
+    async fn row_counts(&self) -> Result<Vec<RowCount>, ORMError> {
+        let rows = self.query_map(format!("SELECT table_name AS table_name, table_rows AS row_count \
+            FROM information_schema.tables WHERE table_schema = {} AND table_type = 'BASE TABLE' ORDER BY table_name",
+            self.information_schema()).as_str()).await?;
+        Ok(stats::row_counts(&rows, false))
+    }
+
+    /// `index_usage` is an asynchronous method that reads the number of rows read with each index
+    /// from `performance_schema.table_io_waits_summary_by_index_usage`.
+    async fn index_usage(&self) -> Result<Vec<IndexUsage>, ORMError> {
+        let rows = self.query_map(format!("SELECT object_name AS table_name, index_name AS index_name, count_read AS `reads` \
+            FROM performance_schema.table_io_waits_summary_by_index_usage \
+            WHERE object_schema = {} AND index_name IS NOT NULL ORDER BY object_name, index_name",
+            self.information_schema()).as_str()).await?;
+        Ok(stats::index_usage(&rows))
+    }
+
+    /// `merge` is an asynchronous method that reconciles a batch of incoming entities with the stored rows.
+    /// The stored rows are read with one `SELECT ... WHERE id IN (...)`, `strategy` decides which entities are inserted or updated,
+    /// and all changes are applied in one transaction with `exec_all`.
+    async fn merge<T>(&self, entities: Vec<T>, strategy: MergeStrategy<T>) -> Result<MergeResult, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + Send + Sync + 'static
+    {
+        let mut result = MergeResult::default();
+        if entities.is_empty() {
+            return Ok(result);
+        }
+        let existing = self.find_many::<T>(Cond::field("id").is_in(entities.iter().map(|e| id_param(&e.get_id())))).run().await?;
+        let mut queries = Vec::new();
+        for action in plan_merge(entities, existing, &strategy)? {
+            match action {
+                MergeAction::Insert(e) => {
+                    result.inserted += 1;
+                    queries.push(self.add(e).into_update()?);
+                }
+                MergeAction::Update(e) => {
+                    result.updated += 1;
+                    queries.push(self.modify(e).into_update()?);
+                }
+                MergeAction::Skip => result.skipped += 1,
+            }
+        }
+        self.exec_all(&queries).await?;
+        Ok(result)
+    }
+
+    /// `join_all` is an asynchronous method that runs independent read queries concurrently.
+    /// The queries are polled together and every query checks out its own connection of the pool, so the server executes them
+    /// in parallel. Inside a transaction or an advisory lock they share the session connection of the task and run one after another.
+    /// It returns a `Result` that contains the rows of every query in the order of `queries`.
+    /// If one of the queries fails, the `Result` contains the `ORMError` of that query.
+    async fn join_all(&self, queries: Vec<QueryBuilder<'_, Vec<Row>, Row, ORM>>) -> Result<Vec<Vec<Row>>, ORMError> {
+        futures::future::try_join_all(queries.iter().map(|query| query.exec())).await
+    }
+
+    /// `begin` is an asynchronous method that starts a transaction on a connection of the pool, which runs all statements
+    /// of the current task until `commit` or `rollback` ends the transaction. The statements of other tasks keep using the pool.
+    /// Unlike `ORM::transaction`, the transaction stays open if the task does not end it.
+    /// For the ANSI isolation levels it first executes `SET TRANSACTION ISOLATION LEVEL ...`, which applies to the next transaction only,
+    /// and then `START TRANSACTION`. `IsolationLevel::Default` and `IsolationLevel::Deferred` use the isolation level of the session.
+    /// If a transaction is already active, it creates a savepoint instead and the isolation level is ignored.
+    /// If the level is SQLite specific (`Immediate`, `Exclusive`), the `Result` contains an `ORMError::TransactionError`.
+    async fn begin(&self, isolation: IsolationLevel) -> Result<(), ORMError> {
+        let level = match isolation {
+            IsolationLevel::Default | IsolationLevel::Deferred => None,
+            IsolationLevel::ReadUncommitted => Some("READ UNCOMMITTED"),
+            IsolationLevel::ReadCommitted => Some("READ COMMITTED"),
+            IsolationLevel::RepeatableRead => Some("REPEATABLE READ"),
+            IsolationLevel::Serializable => Some("SERIALIZABLE"),
+            IsolationLevel::Immediate | IsolationLevel::Exclusive => {
+                return Err(ORMError::TransactionError(format!("{:?} is not supported by MySQL", isolation)));
+            }
+        };
+        let depth = self.transaction_depth();
+        let session = if depth == 0 { Some(self.pin().await?) } else { None };
+        let started = match self.options.replaying() {
+            Some(_) => {
+                self.set_transaction_depth(depth + 1);
+                Ok(())
+            }
+            None => self.start_transaction(depth, level).await,
+        };
+        // the pin of the outermost transaction is removed by `commit` or `rollback`
+        match (session, started.is_ok()) {
+            (Some(session), true) => session.keep(),
+            (Some(session), false) => session.release(),
+            (None, _) => {}
+        }
+        started
+    }
+
+    /// `commit` is an asynchronous method that commits the transaction started with `begin`,
+    /// or releases the savepoint of a nested transaction.
+    /// If no transaction is active, the `Result` contains an `ORMError::TransactionError`.
+    async fn commit(&self) -> Result<(), ORMError> {
+        self.end_transaction(true).await
+    }
+
+    /// `rollback` is an asynchronous method that rolls back the transaction started with `begin`,
+    /// or rolls back to the savepoint of a nested transaction.
+    /// If no transaction is active, the `Result` contains an `ORMError::TransactionError`.
+    async fn rollback(&self) -> Result<(), ORMError> {
+        self.end_transaction(false).await
+    }
+
+    /// `exec_all` is an asynchronous method that executes several update queries inside one transaction.
+    /// It checks out one connection, the session connection of the current task or a connection of the pool,
+    /// and starts a transaction on it.
+    /// If one of the queries fails, the transaction is rolled back and the `Result` contains an `ORMError`.
+    /// Otherwise, the transaction is committed and the total number of affected rows is returned.
+    /// Inside a transaction started with `begin`, the queries are wrapped into a savepoint instead.
+    async fn exec_all(&self, queries: &[QueryBuilder<'_, usize, (), ORM>]) -> Result<usize, ORMError> {
+        for qb in queries.iter() {
+            qb.check()?;
+        }
+        let statements = exec_all_statements(queries);
+        // the statements are executed in a transaction
+        let policy = self.policy.read().unwrap().clone();
+        for (query, _, _) in statements.iter() {
+            policy.check(query, true)?;
+        }
+        if let Some(cassette) = self.options.replaying() {
+            let mut updated_rows: usize = 0;
+            for (query, params, counted) in statements {
+                log::debug!("{:?} {:?}", query, params);
+                let r = cassette.replay_affected(&statement_key(query, params, Dialect::MySql))?.0 as usize;
+                updated_rows += if counted { r } else { 0 };
+            }
+            return Ok(updated_rows);
+        }
+        let mut conn = self.lock().await?;
+        if conn.is_none() {
+            return Err(ORMError::NoConnection);
+        }
+        let conn = conn.as_mut().unwrap();
+        if self.transaction_depth() > 0 {
+            // inside `begin` the queries are wrapped into a savepoint instead of a new transaction
+            conn.query_drop("SAVEPOINT parvati_exec_all").await?;
+            let mut updated_rows: usize = 0;
+            for (query, params, counted) in statements {
+                log::debug!("{:?} {:?}", query, params);
+                let start = Instant::now();
+                let r = ORM::execute(conn, query, params).await.map(|(affected_rows, _)| affected_rows);
+                self.log_statement(query, start, &r, |r| *r as usize);
+                self.options.record(&statement_key(query, params, Dialect::MySql), &r, |r| Recorded::Affected { rows: *r, last_insert_id: None });
+                match r {
+                    Ok(r) if counted => {
+                        updated_rows += r as usize;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        conn.query_drop("ROLLBACK TO SAVEPOINT parvati_exec_all").await?;
+                        conn.query_drop("RELEASE SAVEPOINT parvati_exec_all").await?;
+                        return Err(ORMError::MySQLError(e));
+                    }
+                }
+            }
+            conn.query_drop("RELEASE SAVEPOINT parvati_exec_all").await?;
+            return Ok(updated_rows);
+        }
+        let mut tx = conn.start_transaction(mysql_async::TxOpts::default()).await?;
+        let mut updated_rows: usize = 0;
+        for (query, params, counted) in statements {
+            log::debug!("{:?} {:?}", query, params);
+            let start = Instant::now();
+            let r = ORM::execute(&mut tx, query, params).await.map(|(affected_rows, _)| affected_rows);
+            self.log_statement(query, start, &r, |r| *r as usize);
+            self.options.record(&statement_key(query, params, Dialect::MySql), &r, |r| Recorded::Affected { rows: *r, last_insert_id: None });
+            match r {
+                Ok(r) if counted => {
+                    updated_rows += r as usize;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tx.rollback().await?;
+                    return Err(ORMError::MySQLError(e));
+                }
+            }
+        }
+        tx.commit().await?;
+        Ok(updated_rows)
+    }
+
     fn protect(&self, value: &str) -> String {
         let protected: String = format!("\"{}\"", ORM::escape(value));
         protected
 
     }
+    fn protect_value<V: ToSqlLiteral>(&self, value: V) -> Result<String, ORMError> {
+        value.to_sql_literal(Dialect::MySql, self.options.float_policy)
+    }
+
     fn escape(str: &str) -> String {
         let mut escaped = String::new();
 
@@ -310,8 +1908,30 @@ impl ORMTrait<ORM> for ORM {
         Ok(())
     }
 
-    async fn change(&self, _update_query: &str) -> anyhow::Result<(), ORMError> {
-        todo!()
+    /// `change` is an asynchronous method that applies a schema change script once.
+    /// The calls of `change` are numbered in the order they are made, and the number of the last applied change
+    /// is stored in the `ormlib_last_change` table, which is created if it does not exist.
+    /// A change whose number is not greater than the stored one was applied before and is skipped,
+    /// so the same sequence of `change` calls can be run on every start of the application.
+    /// It returns a `Result` that contains `()` if the operation is successful.
+    /// If the execution of the change is not successful, the `Result` contains an `ORMError` and the number is not stored.
+    async fn change(&self, update_query: &str) -> anyhow::Result<(), ORMError> {
+        let _: usize = self.query_update("CREATE TABLE IF NOT EXISTS ormlib_last_change (id INT PRIMARY KEY AUTO_INCREMENT, last INT)").exec().await?;
+        let rows = self.query_map("select id, last from ormlib_last_change").await?;
+        let last = match rows.first() {
+            Some(row) => row.get("last").and_then(Value::as_i64).unwrap_or_default() as u32,
+            None => {
+                let _: usize = self.query_update("insert into ormlib_last_change (last) values (0)").exec().await?;
+                0
+            }
+        };
+        let mut change_count = self.change_count.lock().await;
+        *change_count += 1;
+        if *change_count > last {
+            let _updated_rows: usize = self.query_update(update_query).exec().await?;
+            let _updated_rows: usize = self.query_update(format!("update ormlib_last_change set last = {}", *change_count).as_str()).exec().await?;
+        }
+        Ok(())
     }
 }
 
@@ -320,30 +1940,166 @@ impl ORMTrait<ORM> for ORM {
 impl<T> QueryBuilder<'_, usize, T, ORM>{
 
     /// `exec` is an asynchronous method that executes the SQL query represented by the `QueryBuilder` object.
-    /// It first locks the `conn` field of the `ORM` struct, which is a `Mutex` guarding an `Option` wrapping a `Conn` object.
-    /// If the `conn` field is `None`, it returns an `ORMError::NoConnection`.
+    /// It runs on the session connection of the current task, or on a connection checked out of the pool, see `ORM`.
+    /// If the ORM is closed, it returns an `ORMError::NoConnection`.
     /// Otherwise, it executes the SQL query and returns a `Result` that contains the number of affected rows as an `usize`.
     /// If the execution of the SQL query is not successful, the `Result` contains an `ORMError`.
     pub async fn exec(&self) -> Result<usize, ORMError> {
-        log::debug!("{:?}", self.query);
-        let mut conn = self.orm.conn.lock().await;
+        self.check()?;
+        let query = if self.modifiers.low_priority { low_priority_statement(&self.query) } else { self.query.clone() };
+        log::debug!("{:?} {:?}", query, self.params);
+        self.orm.check_policy(&query)?;
+        if let Some(cassette) = self.orm.options.replaying() {
+            return Ok(cassette.replay_affected(&statement_key(&query, &self.params, Dialect::MySql))?.0 as usize);
+        }
+        let mut conn = self.orm.lock().await?;
+        if conn.is_none() {
+            return Err(ORMError::NoConnection);
+        }
+        let conn = conn.as_mut().unwrap();
+        let start = Instant::now();
+        let r = ORM::execute(conn, &query, &self.params).await.map(|(affected_rows, id)| {
+            self.orm.record_insert_id(id);
+            affected_rows
+        });
+        self.orm.log_statement(&query, start, &r, |r| *r as usize);
+        self.orm.options.record(&statement_key(&query, &self.params, Dialect::MySql), &r, |r| Recorded::Affected { rows: *r, last_insert_id: None });
+        Ok(r? as usize)
+    }
+}
+/// Implementation of the `Batch` struct for the `ORM` struct.
+impl Batch<'_, ORM> {
+
+    /// `execute` is an asynchronous method that sends all statements of the batch as one multi-statement query.
+    /// It runs on the session connection of the current task, or on a connection checked out of the pool, see `ORM`.
+    /// If the ORM is closed, it returns an `ORMError::NoConnection`.
+    /// Otherwise, it walks through the result sets of the query and collects the number of affected rows of every statement.
+    /// If one of the statements fails, the `Result` contains an `ORMError` and the following statements are not executed.
+    pub async fn execute(&self) -> Result<Vec<usize>, ORMError> {
+        let mut result: Vec<usize> = Vec::new();
+        if self.queries.is_empty() {
+            return Ok(result);
+        }
+        let query = self.queries.join(";\n");
+        log::debug!("{:?}", query);
+        self.orm.check_policy(&query)?;
+        if let Some(cassette) = self.orm.options.replaying() {
+            return cassette.replay_batch(&query);
+        }
+        let mut conn = self.orm.lock().await?;
         if conn.is_none() {
             return Err(ORMError::NoConnection);
         }
         let conn = conn.as_mut().unwrap();
-        let r = conn.query_iter(self.query.as_str()).await.map(|result| {
-            result.affected_rows()
+        let start = Instant::now();
+        let r = Self::read_affected_rows(conn, &query, &mut result).await;
+        self.orm.log_statement(&query, start, &r, |_| result.iter().sum());
+        self.orm.options.record(&query, &r, |_| Recorded::Batch(result.iter().map(|rows| *rows as u64).collect()));
+        r?;
+        Ok(result)
+    }
+
+    async fn read_affected_rows(conn: &mut Conn, query: &str, result: &mut Vec<usize>) -> Result<(), mysql_async::Error> {
+        let mut query_result = conn.query_iter(query).await?;
+        loop {
+            result.push(query_result.affected_rows() as usize);
+            let _rows: Vec<mysql_async::Row> = query_result.collect().await?;
+            if query_result.is_empty() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Implementation of the `UpsertAll` struct for the `ORM` struct.
+impl<T: TableDeserialize> AlterTable<'_, T, ORM> {
+    /// `run` is a method that executes the changes with one `ALTER TABLE` statement.
+    /// MySQL commits an open transaction implicitly before it.
+    pub async fn run(&self) -> Result<(), ORMError> {
+        for statement in self.alter_statements(Dialect::MySql)?.iter() {
+            self.orm.query_update(statement).run().await?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: TableDeserialize + TableSerialize + Serialize> ImportAll<'_, T, ORM> {
+    /// `run` is a method that inserts the rows chunk by chunk inside one transaction and returns an `ImportReport`.
+    /// A chunk that fails is rolled back to its savepoint; the other chunks are committed.
+    pub async fn run(&self) -> Result<ImportReport, ORMError> {
+        self.execute(self.statements(&self.orm.options)).await
+    }
+}
+
+impl Queue<'_, ORM> {
+    /// `create_table` is an asynchronous method that creates the table of the job queues if it does not exist.
+    pub async fn create_table(&self) -> Result<(), ORMError> {
+        let query = format!("create table if not exists {0} (id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY, \
+            queue VARCHAR(255) NOT NULL, payload LONGTEXT NOT NULL, attempts INT NOT NULL, visible_at BIGINT NOT NULL, \
+            INDEX {0}_visible (queue, visible_at))", queue::TABLE);
+        self.orm.exec_all(&[self.orm.query_update(&query)]).await?;
+        Ok(())
+    }
+
+    /// `push` is an asynchronous method that adds a job with `payload` to the queue and returns its ID.
+    /// The job can be claimed right away.
+    pub async fn push(&self, payload: &str) -> Result<u64, ORMError> {
+        let (query, params) = self.push_statement(payload);
+        Ok(self.orm.insert(&query, &params).await?.0)
+    }
+
+    /// `claim` is an asynchronous method that claims up to `n` visible jobs in the order they were pushed and hides
+    /// them from other workers for `visibility_timeout`. The jobs are selected with `FOR UPDATE SKIP LOCKED`, so
+    /// concurrent workers skip the jobs another worker is claiming instead of waiting for them (MySQL 8.0 or later).
+    pub async fn claim(&self, n: usize, visibility_timeout: Duration) -> Result<Vec<Job>, ORMError> {
+        self.claim_locked(n, visibility_timeout, IsolationLevel::Default, " for update skip locked").await
+    }
+}
+
+impl<T: TableDeserialize + TableSerialize + Serialize> UpsertAll<'_, T, ORM> {
+    /// `run` is a method that writes the rows with `INSERT ... ON DUPLICATE KEY UPDATE` statements inside one transaction.
+    /// MySQL matches existing rows by any unique key of the table, so the `on_conflict` columns only decide
+    /// whether the `id` is written and which columns are not updated.
+    /// It returns the number of affected rows, where MySQL counts an updated row twice.
+    pub async fn run(&self) -> Result<usize, ORMError> {
+        let statements = self.statements(&self.orm.options, |conflict_columns, update_columns| {
+            if update_columns.is_empty() {
+                format!("on duplicate key update {column} = {column}", column = conflict_columns[0])
+            } else {
+                let set: Vec<String> = update_columns.iter().map(|column| format!("{column} = values({column})")).collect();
+                format!("on duplicate key update {}", set.join(", "))
+            }
         })?;
-        Ok(r as usize)
+        let queries: Vec<_> = statements.into_iter().map(|(query, params)| self.orm.query_update(&query).bind(params)).collect();
+        self.orm.exec_all(&queries).await
+    }
+}
+
+impl<T> AddOrUpdate<'_, T, ORM>
+    where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + 'static
+{
+    /// `apply` is an asynchronous method that writes the entity with `INSERT ... ON DUPLICATE KEY UPDATE`
+    /// and returns it as it is stored. An entity with an unset ID is inserted with `add`.
+    pub async fn apply(&self) -> Result<T, ORMError> {
+        let Some(lookup) = self.lookup(&self.upsert.orm.options)? else {
+            return self.upsert.orm.add(self.upsert.rows[0].clone()).apply().await;
+        };
+        in_transaction(self.upsert.orm, IsolationLevel::Default, async {
+            self.upsert.run().await?;
+            let stored: Option<T> = self.upsert.orm.find_one_where(lookup).run().await?;
+            stored.ok_or(ORMError::InsertError)
+        }).await
     }
 }
+
 /// Implementation of the `QueryBuilder` struct for the `ORM` struct.
 /// The `QueryBuilder` struct is used to construct SQL queries in a safe and convenient manner.
 impl<T> QueryBuilder<'_, T,T, ORM>{
 
     /// `apply` is an asynchronous method that executes the SQL insert query represented by the `QueryBuilder` object and returns the inserted record.
-    /// It first locks the `conn` field of the `ORM` struct, which is a `Mutex` guarding an `Option` wrapping a `Conn` object.
-    /// If the `conn` field is `None`, it returns an `ORMError::NoConnection`.
+    /// It runs on the session connection of the current task, or on a connection checked out of the pool, see `ORM`.
+    /// If the ORM is closed, it returns an `ORMError::NoConnection`.
     /// Otherwise, it executes the SQL insert query and retrieves the row ID of the last inserted record.
     /// If the row ID is `None`, it returns an `ORMError::InsertError`.
     /// Otherwise, it constructs a SQL select query to find the inserted record by its row ID and executes the select query.
@@ -353,32 +2109,25 @@ impl<T> QueryBuilder<'_, T,T, ORM>{
     pub async fn apply(&self) -> Result<T, ORMError>
         where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + 'static
     {
-        log::debug!("{:?}", self.query);
-        let r = {
-            let mut conn = self.orm.conn.lock().await;
-            if conn.is_none() {
-                return Err(ORMError::NoConnection);
-            }
-            let conn = conn.as_mut().unwrap();
-            let r = conn.query_iter(self.query.as_str()).await.map(|result| {
-                result.last_insert_id()
-            })?;
-            if r.is_none() {
-                return Err(ORMError::InsertError);
-            }
-            r.unwrap()
+        self.apply_with_meta().await.map(|(t, _)| t)
+    }
 
-        };
-        let rows: Vec<T> = self.orm.find_many(format!("id = {}", r).as_str()).run().await?;
-        if rows.len() == 0 {
-            return Err(ORMError::InsertError);
+    /// `apply_with_meta` is an asynchronous method that works like `apply`, but also returns an `InsertMeta`
+    /// with the generated ID and the number of rows affected by the insert, so that no second query is needed to log them.
+    pub async fn apply_with_meta(&self) -> Result<(T, InsertMeta), ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + 'static
+    {
+        self.check()?;
+        let (r, affected_rows) = self.with_counters(self.orm.insert(&self.query, &self.params)).await?;
+        let mut read_back = self.orm.find_many(format!("id = {}", r).as_str());
+        if let Some(table) = &self.table {
+            read_back = read_back.into_table(table);
         }
-        let t_opt = rows.into_iter().next();
-        match t_opt {
-            Some(t) => Ok(t),
+        let rows: Vec<T> = read_back.run().await?;
+        match rows.into_iter().next() {
+            Some(t) => Ok((t, InsertMeta { id: r, affected_rows })),
             None => Err(ORMError::InsertError),
         }
-
     }
 }
 /// Implementation of the `QueryBuilder` struct for the `ORM` struct.
@@ -386,19 +2135,48 @@ impl<T> QueryBuilder<'_, T,T, ORM>{
 impl<T> QueryBuilder<'_, usize,T, ORM> {
 
     /// `run` is an asynchronous method that executes the SQL query represented by the `QueryBuilder` object.
-    /// It first locks the `conn` field of the `ORM` struct, which is a `Mutex` guarding an `Option` wrapping a `Conn` object.
-    /// If the `conn` field is `None`, it returns an `ORMError::NoConnection`.
+    /// It runs on the session connection of the current task, or on a connection checked out of the pool, see `ORM`.
+    /// If the ORM is closed, it returns an `ORMError::NoConnection`.
     /// Otherwise, it executes the SQL query and returns a `Result` that contains the number of affected rows as an `usize`.
     /// If the execution of the SQL query is not successful, the `Result` contains an `ORMError`.
     pub async fn run(&self) -> Result<usize, ORMError> {
-        log::debug!("{:?}", self.query);
-        let mut conn = self.orm.conn.lock().await;
-        if conn.is_none() {
-            return Err(ORMError::NoConnection);
-        }
-        let conn = conn.as_mut().unwrap();
-        let r = conn.query_iter(self.query.as_str()).await?;
-        Ok(r.affected_rows() as usize)
+        self.check()?;
+        self.with_counters(async {
+            let query = if self.modifiers.low_priority { low_priority_statement(&self.query) } else { self.query.clone() };
+            log::debug!("{:?} {:?}", query, self.params);
+            self.orm.check_policy(&query)?;
+            if let Some(cassette) = self.orm.options.replaying() {
+                return Ok(cassette.replay_affected(&statement_key(&query, &self.params, Dialect::MySql))?.0 as usize);
+            }
+            let mut conn = self.orm.lock().await?;
+            if conn.is_none() {
+                return Err(ORMError::NoConnection);
+            }
+            let conn = conn.as_mut().unwrap();
+            let start = Instant::now();
+            let r = ORM::execute(conn, &query, &self.params).await.map(|(affected_rows, _)| affected_rows as usize);
+            self.orm.log_statement(&query, start, &r, |r| *r);
+            self.orm.options.record(&statement_key(&query, &self.params, Dialect::MySql), &r, |r| Recorded::Affected { rows: *r as u64, last_insert_id: None });
+            Ok(r?)
+        }).await
+    }
+}
+/// Implementation of the `QueryBuilder` struct for the `count` queries of the `ORM` struct.
+impl<T> QueryBuilder<'_, u64,T, ORM> {
+    /// `run` is an asynchronous method that executes the `select count(*)` query and returns the number of matching records.
+    pub async fn run(&self) -> Result<u64, ORMError> {
+        self.check()?;
+        let rows = self.orm.query(self.query.as_str()).bind(self.params.clone()).exec().await?;
+        Ok(rows.first().and_then(|row| row.get::<u64>(0)).unwrap_or(0))
+    }
+}
+/// Implementation of the `QueryBuilder` struct for the `exists` queries of the `ORM` struct.
+impl<T> QueryBuilder<'_, bool,T, ORM> {
+    /// `run` is an asynchronous method that executes the `select exists(...)` query and returns `true` if a record matches.
+    pub async fn run(&self) -> Result<bool, ORMError> {
+        self.check()?;
+        let rows = self.orm.query(self.query.as_str()).bind(self.params.clone()).exec().await?;
+        Ok(rows.first().and_then(|row| row.get::<i64>(0)) == Some(1))
     }
 }
 /// Implementation of the `QueryBuilder` struct for the `ORM` struct.
@@ -418,9 +2196,11 @@ impl<T> QueryBuilder<'_, Option<T>,T, ORM>
     /// If the deserialization is successful, it returns `Ok(Some(T))`.
     /// If the deserialization is not successful, it returns an `ORMError::Unknown`.
     pub async fn run(&self) -> Result<Option<T>, ORMError> {
-
-        let rows  = self.orm.query(self.query.clone().as_str()).exec().await?;
+        self.check()?;
+        let query = self.orm.query(self.query.clone().as_str()).bind(self.params.clone());
+        let rows = entity_cache::read_through(self.cached_lookup(), query.exec()).await?;
         let columns: Vec<String> =T::fields();
+        let definitions = T::columns();
         if rows.len() == 0 {
             return Ok(None);
         } else {
@@ -428,9 +2208,11 @@ impl<T> QueryBuilder<'_, Option<T>,T, ORM>
             for row in rows {
                 let mut i = 0;
                 for column in columns.iter() {
-                    let value_opt:Option<String> = row.get(i);
+                    let definition = definitions.iter().find(|definition| definition.name == *column);
+                    let value_opt:Option<String> = row.field_value(i, column, definition);
                     let value = match value_opt {
                         Some(v) => {
+                            let v = datetime::from_storage(v.as_str(), definition, self.orm.options.datetime_policy);
                             format!("\"{}\"", ORM::escape_json(v.as_str()))
                         }
                         None => {
@@ -443,12 +2225,32 @@ impl<T> QueryBuilder<'_, Option<T>,T, ORM>
             }
             let user_str = format!("{{{}}}", column_str.join(","));
             // log::debug!("zzz{}", user_str);
-            let user: T = deserializer_key_values::from_str(&user_str).unwrap();
-            Ok(Some(user))
+            let user_result: std::result::Result<T, serializer_error::Error> = deserializer_key_values::from_str(&user_str);
+            match user_result {
+                Ok(user) => Ok(Some(user)),
+                Err(e) => {
+                    log::error!("{:?}", e);
+                    log::error!("{}", user_str);
+                    Err(self.orm.options.decode_error::<T>(e))
+                }
+            }
 
         }
 
     }
+
+    /// `cached_lookup` is a method that returns the lookup of the query in the entity cache, if it is a `find_one`
+    /// of an entity with a `cache_ttl` outside a transaction.
+    fn cached_lookup(&self) -> Option<CachedLookup<'_>> {
+        let cache = self.orm.options.entity_cache.as_ref()?;
+        let ttl = T::cache_ttl()?;
+        if !self.params.is_empty() || self.orm.transaction_depth() > 0 {
+            return None;
+        }
+        let table = self.table.as_deref()?;
+        let id = entity_cache::looked_up_id(&self.query, table)?;
+        Some(CachedLookup { cache, ttl, table, id })
+    }
 }
 
 /// Implementation of the `QueryBuilder` struct for the `ORM` struct.
@@ -456,67 +2258,135 @@ impl<T> QueryBuilder<'_, Option<T>,T, ORM>
 impl<R> QueryBuilder<'_, Vec<Row>,R, ORM> {
 
     /// `exec` is an asynchronous method that executes the SQL query represented by the `QueryBuilder` object.
-    /// It first locks the `conn` field of the `ORM` struct, which is a `Mutex` guarding an `Option` wrapping a `Conn` object.
-    /// If the `conn` field is `None`, it returns an `ORMError::NoConnection`.
+    /// It runs on the session connection of the current task, or on a connection checked out of the pool, see `ORM`.
+    /// If the ORM is closed, it returns an `ORMError::NoConnection`.
     /// Otherwise, it executes the SQL query and retrieves the rows that match the query.
     /// It then iterates over the rows and columns to construct a `Row` object for each row.
     /// The `Row` object contains a `HashMap` where the keys are column indices and the values are the column values.
     /// The column values are retrieved from the row using the `get` method.
     /// If a column value is `None`, it breaks the loop and moves on to the next row.
     /// Otherwise, it sets the column value in the `Row` object using the `set` method.
+    /// If `ConnectOptions::strict_decode` is enabled, `NULL` values are kept as `None` and a value that cannot be converted
+    /// results in an `ORMError::DecodeError` instead of a silently truncated row.
     /// The `Row` object is then pushed to the `result` vector.
     /// After all rows have been processed, it returns a `Result` that contains the `result` vector.
     /// If the execution of the SQL query is not successful, the `Result` contains an `ORMError`.
     pub async fn exec(&self) -> Result<Vec<Row>, ORMError>
     {
-        log::debug!("{:?}", self.query);
-        let mut conn = self.orm.conn.lock().await;
+        Ok(self.exec_result_set().await?.rows)
+    }
+
+    /// `stream` is a method that executes the SQL query represented by the `QueryBuilder` object and returns a `Stream` of rows.
+    /// Rows are read from the server as they arrive and converted like in `exec`, so the whole result is never buffered in memory.
+    /// The connection stays checked out until the stream is exhausted or dropped.
+    /// If the ORM is closed, the stream yields an `ORMError::NoConnection`.
+    /// If the query fails or a value cannot be decoded in strict mode, the stream yields an `ORMError`.
+    pub fn stream(&self) -> BoxStream<'_, Result<Row, ORMError>>
+        where R: Sync
+    {
+        row_stream(move |mut tx| async move {
+            if self.orm.options.cassette.is_some() || !self.params.is_empty() {
+                // recorded and replayed selects and prepared statements with params are read at once
+                for row in self.exec_result_set().await?.rows {
+                    if tx.send(Ok(row)).await.is_err() {
+                        break;
+                    }
+                }
+                return Ok(());
+            }
+            log::debug!("{:?}", self.query);
+            self.orm.check_policy(&self.query)?;
+            let mut conn = self.orm.lock().await?;
+            if conn.is_none() {
+                return Err(ORMError::NoConnection);
+            }
+            let conn = conn.as_mut().unwrap();
+            let strict = self.orm.options.strict_decode;
+            let mut query_result = conn.query_iter(self.query.as_str()).await?;
+            let columns_kind: Vec<ColumnKind> = query_result.columns_ref().iter().map(ORM::column_kind).collect();
+            let names = Arc::new(query_result.columns_ref().iter().map(|column| column.name_str().to_string()).collect());
+            while let Some(row) = query_result.next().await? {
+                let mut decode_error: Option<DecodeError> = None;
+                let r = ORM::read_row(&row, &names, &columns_kind, strict, &mut decode_error);
+                let item = match decode_error {
+                    Some(e) => Err(ORMError::DecodeError(e)),
+                    None => Ok(r),
+                };
+                if tx.send(item).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        }).boxed()
+    }
+
+    /// `exec_result_set` is an asynchronous method that executes the SQL query like `exec`
+    /// and returns the rows together with the column metadata as a `ResultSet`.
+    /// For every column it reports the name, the declared type (e.g. `MYSQL_TYPE_LONG`) and whether the column is nullable.
+    pub async fn exec_result_set(&self) -> Result<ResultSet, ORMError>
+    {
+        log::debug!("{:?} {:?}", self.query, self.params);
+        self.orm.check_policy(&self.query)?;
+        if let Some(cassette) = self.orm.options.replaying() {
+            return cassette.replay_result_set(&self.statement_key(Dialect::MySql));
+        }
+        let mut conn = self.orm.lock().await?;
         if conn.is_none() {
             return Err(ORMError::NoConnection);
         }
         let conn = conn.as_mut().unwrap();
-        let stmt_result = conn.query_iter( self.query.as_str()).await;
-         if stmt_result.is_err() {
+        let start = Instant::now();
+        let result = self.read_result_set(conn).await;
+        self.orm.log_statement(&self.query, start, &result, |result_set| result_set.rows.len());
+        self.orm.options.record(&self.statement_key(Dialect::MySql), &result, Recorded::result_set);
+        result
+    }
+
+    async fn read_result_set(&self, conn: &mut Conn) -> Result<ResultSet, ORMError>
+    {
+        // a query with params is executed as a prepared statement, whose rows are sent with the binary protocol
+        let stmt_result = if self.params.is_empty() {
+            match conn.query_iter(self.query.as_str()).await {
+                Ok(mut stmt) => Ok((stmt.columns(), stmt.collect::<mysql_async::Row>().await)),
+                Err(e) => Err(e),
+            }
+        } else {
+            match conn.exec_iter(self.query.as_str(), ORM::params(&self.params)).await {
+                Ok(mut stmt) => Ok((stmt.columns(), stmt.collect::<mysql_async::Row>().await)),
+                Err(e) => Err(e),
+            }
+        };
+        if stmt_result.is_err() {
             let e = stmt_result.err().unwrap();
             log::error!("{:?}", e);
             return Err(ORMError::MySQLError(e));
         }
-        let mut stmt = stmt_result.unwrap();
-        let columns =stmt.columns();
-        let columns = columns.unwrap();
-        let columns_type: Vec<bool> = columns.iter().map(|column| {
-            column.column_type().is_numeric_type()
+        let (columns, rows) = stmt_result.unwrap();
+        let columns = columns.unwrap_or_else(|| Vec::new().into());
+        let columns_kind: Vec<ColumnKind> = columns.iter().map(ORM::column_kind).collect();
+        let names = Arc::new(columns.iter().map(|column| column.name_str().to_string()).collect());
+        let columns: Vec<Column> = columns.iter().map(|column| {
+            Column {
+                name: column.name_str().to_string(),
+                declared_type: Some(format!("{:?}", column.column_type())),
+                nullable: Some(!column.flags().contains(ColumnFlags::NOT_NULL_FLAG)),
+            }
         }).collect();
         let mut result: Vec<Row> = Vec::new();
-        // println!("{:?}", columns_type);
-        stmt.for_each(|row| {
-            let mut i = 0;
-            let mut r: Row = Row::new();
-            loop {
-                if i > columns_type.len() - 1 {
-                    break;
-                }
-                if columns_type[i] {
-                    let res: Option<i32>= row.get(i);
-                    if res.is_none() {
-                        break;
-                    }
-                    r.set(i.try_into().unwrap(), res);
-                } else {
-                    let res: Option<String>= row.get(i);
-                    if res.is_none() {
-                        break;
-                    }
-                    r.set(i.try_into().unwrap(), res);
-                }
-                i = i + 1;
-            }
+        let strict = self.orm.options.strict_decode;
+        let mut decode_error: Option<DecodeError> = None;
+        for row in rows? {
+            let r = ORM::read_row(&row, &names, &columns_kind, strict, &mut decode_error);
             result.push(r);
-        }).await?;
+        }
 
         // log::debug!("{:?}", result);
+        if let Some(e) = decode_error {
+            log::error!("{}", e);
+            return Err(ORMError::DecodeError(e));
+        }
 
-        Ok(result)
+        Ok(ResultSet { columns, rows: result })
     }
 
 
@@ -535,23 +2405,27 @@ impl<T> QueryBuilder<'_, Vec<T>,T, ORM> {
     /// The JSON string is then deserialized into the data object `T` using the `deserializer_key_values::from_str` function.
     /// If the deserialization is successful, the data object is pushed to the `result` vector.
     /// After all rows have been processed, it returns a `Result` that contains the `result` vector.
-    /// If the deserialization is not successful, it returns an `ORMError::Unknown`.
+    /// If the deserialization is not successful, it returns an `ORMError::Unknown`,
+    /// or an `ORMError::DecodeError` naming the entity type if `ConnectOptions::strict_decode` is enabled.
     /// If the execution of the SQL select query is not successful, the `Result` contains an `ORMError`.
     pub async fn run(&self) -> Result<Vec<T>, ORMError>
         where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
     {
-
+        self.check()?;
         let mut result: Vec<T> = Vec::new();
-        let rows  = self.orm.query(self.query.clone().as_str()).exec().await?;
+        let rows  = self.orm.query(self.query.clone().as_str()).bind(self.params.clone()).exec().await?;
         let columns: Vec<String> =T::fields();
+        let definitions = T::columns();
         for row in rows {
             let mut column_str: Vec<String> = Vec::new();
             let mut i = 0;
             // println!("{:?}", row);
             for column in columns.iter() {
-                let value_opt:Option<String> = row.get(i);
+                let definition = definitions.iter().find(|definition| definition.name == *column);
+                let value_opt:Option<String> = row.field_value(i, column, definition);
                 let value = match value_opt {
                     Some(v) => {
+                        let v = datetime::from_storage(v.as_str(), definition, self.orm.options.datetime_policy);
                         format!("\"{}\"", ORM::escape_json(v.as_str()))
                     }
                     None => {
@@ -571,7 +2445,7 @@ impl<T> QueryBuilder<'_, Vec<T>,T, ORM> {
                 Err(e) => {
                     log::error!("{:?}", e);
                     log::error!("{}", user_str);
-                    return Err(ORMError::Unknown);
+                    return Err(self.orm.options.decode_error::<T>(e));
                 }
             }
 
@@ -579,21 +2453,132 @@ impl<T> QueryBuilder<'_, Vec<T>,T, ORM> {
 
         Ok(result)
     }
-    /// `limit` is a method that modifies the SQL query represented by the `QueryBuilder` object to limit the number of records returned.
-    /// It takes a parameter `limit` of type `i32` which is the maximum number of records to return.
-    /// The method constructs a new SQL query by appending "limit {limit}" to the existing SQL query, where {limit} is the `limit` parameter.
-    /// It then returns a new `QueryBuilder` object that represents the modified SQL query.
-    /// The `QueryBuilder` object is generic over the lifetime `'a`, the result type `R`, the entity type `E`, and the ORM type `O`.
-    /// The ORM type `O` must implement the `ORMTrait`.
-    pub fn limit(&self, limit: i32) -> QueryBuilder<Vec<T>, T, ORM> {
 
-        let qb =  QueryBuilder::<Vec<T>,T, ORM> {
-            query: format!("{} limit {}", self.query, limit),
-            entity: std::marker::PhantomData,
-            orm: self.orm,
-            result: std::marker::PhantomData,
+    /// `paginate` is a method that returns the page `page` (starting at 1) of the records of the query, with `per_page`
+    /// records per page, together with the total number of records, which is counted with a second statement.
+    /// The order of the records should be set with `order_by`; a limit or offset of the query is replaced.
+    /// Returns `ORMError::InvalidValue` if `page` or `per_page` is 0.
+    pub async fn paginate(&self, page: u64, per_page: u64) -> Result<Page<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+    {
+        if page == 0 || per_page == 0 {
+            return Err(ORMError::InvalidValue(format!("invalid page {} of {} records", page, per_page)));
+        }
+        self.check()?;
+        let counted = self.orm.query(self.count_statement().as_str()).bind(self.params.clone()).exec().await?;
+        let total = counted.first().and_then(|row| row.get::<u64>(0)).unwrap_or(0);
+        let items = self.with_modifiers(UNLIMITED, |m| {
+            m.limit = Some(per_page as i64);
+            m.offset = Some((page - 1) * per_page);
+        }).run().await?;
+        Ok(Page { items, total, page, per_page })
+    }
+
+    /// `run_into_map` is an asynchronous method that runs the query and returns the records keyed by `key`, e.g. `.run_into_map(|user| user.id)`.
+    /// If several records have the same key, the last one wins.
+    pub async fn run_into_map<K, F>(&self, key: F) -> Result<HashMap<K, T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static, K: Eq + std::hash::Hash, F: Fn(&T) -> K
+    {
+        Ok(self.run().await?.into_iter().map(|data| (key(&data), data)).collect())
+    }
+
+    /// `run_grouped` is an asynchronous method that runs the query and returns the records grouped by `key`, e.g. `.run_grouped(|order| order.user_id)`.
+    /// The records of a group keep the order of the query.
+    pub async fn run_grouped<K, F>(&self, key: F) -> Result<HashMap<K, Vec<T>>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static, K: Eq + std::hash::Hash, F: Fn(&T) -> K
+    {
+        let mut groups: HashMap<K, Vec<T>> = HashMap::new();
+        for data in self.run().await? {
+            groups.entry(key(&data)).or_default().push(data);
+        }
+        Ok(groups)
+    }
+}
+
+
+/// Implementation of the query modifiers for every `QueryBuilder` of the `ORM` struct.
+/// The modifiers can be applied in any order; they are rendered as `order by ... limit ... offset ...`.
+impl<'a, R, E> QueryBuilder<'a, R, E, ORM> {
+    /// `limit` is a method that limits the number of records returned by the query.
+    /// It returns a new `QueryBuilder` object; calling it again replaces the previous limit.
+    pub fn limit(&self, limit: i32) -> QueryBuilder<'a, R, E, ORM> {
+        self.with_modifiers(UNLIMITED, |m| m.limit = Some(limit as i64))
+    }
+
+    /// `offset` is a method that skips the first `offset` records returned by the query.
+    /// It returns a new `QueryBuilder` object; calling it again replaces the previous offset.
+    pub fn offset(&self, offset: u64) -> QueryBuilder<'a, R, E, ORM> {
+        self.with_modifiers(UNLIMITED, |m| m.offset = Some(offset))
+    }
+
+    /// `order_by` is a method that sorts the records returned by the query by `column` in the given `order`.
+    /// It can be chained to sort by several columns, e.g. `.order_by("age", Order::Desc).order_by("name", Order::Asc)`.
+    /// The query fails with `ORMError::InvalidValue` if `column` is not a plain, optionally qualified column name.
+    pub fn order_by(&self, column: &str, order: Order) -> QueryBuilder<'a, R, E, ORM> {
+        let clause = match order {
+            Order::Asc => format!("{} asc", column),
+            Order::Desc => format!("{} desc", column),
         };
-        qb
+        let ordered = self.with_modifiers(UNLIMITED, |m| m.order_by.push(clause));
+        if is_table_name(column) {
+            return ordered;
+        }
+        QueryBuilder { error: ordered.error.or_else(|| Some(format!("invalid column name {:?}", column))), ..ordered }
+    }
+}
+
+
+/// `Manager` is a struct that creates MySQL connections for the deadpool and bb8 pools.
+/// Every pooled object is an `Arc<ORM>` connected with the `ConnectOptions` of the manager,
+/// so the session settings of the options are applied to every pooled connection.
+#[cfg(any(feature = "deadpool", feature = "bb8"))]
+#[derive(Debug, Clone)]
+pub struct Manager {
+    options: ConnectOptions,
+}
+
+#[cfg(any(feature = "deadpool", feature = "bb8"))]
+impl Manager {
+    /// `new` is a function that constructs a `Manager` for the given connect options.
+    pub fn new(options: ConnectOptions) -> Manager {
+        Manager { options }
+    }
+}
+
+/// Implementation of the deadpool `Manager` trait.
+/// A connection is recycled after a `select 1` round trip succeeds.
+#[cfg(feature = "deadpool")]
+#[async_trait]
+impl deadpool::managed::Manager for Manager {
+    type Type = Arc<ORM>;
+    type Error = ORMError;
+
+    async fn create(&self) -> Result<Arc<ORM>, ORMError> {
+        ORM::connect_with(self.options.clone()).await
+    }
+
+    async fn recycle(&self, orm: &mut Arc<ORM>, _metrics: &deadpool::managed::Metrics) -> deadpool::managed::RecycleResult<ORMError> {
+        orm.query::<Row>("select 1").exec().await.map(|_| ()).map_err(deadpool::managed::RecycleError::Backend)
     }
 }
 
+/// Implementation of the bb8 `ManageConnection` trait.
+/// A connection is valid if a `select 1` round trip succeeds and broken once it has been closed.
+#[cfg(feature = "bb8")]
+#[async_trait]
+impl bb8::ManageConnection for Manager {
+    type Connection = Arc<ORM>;
+    type Error = ORMError;
+
+    async fn connect(&self) -> Result<Arc<ORM>, ORMError> {
+        ORM::connect_with(self.options.clone()).await
+    }
+
+    async fn is_valid(&self, orm: &mut Arc<ORM>) -> Result<(), ORMError> {
+        orm.query::<Row>("select 1").exec().await.map(|_| ())
+    }
+
+    fn has_broken(&self, orm: &mut Arc<ORM>) -> bool {
+        orm.pool_status().size == 0
+    }
+}
@@ -0,0 +1,85 @@
+//! `n_plus_one` is a module with the detector of N+1 query patterns enabled with `ConnectOptions::detect_n_plus_one`:
+//! a statement with the same shape (see `normalize_statement`) that is executed again and again in one task or
+//! transaction, typically a lookup in a loop over the rows of a previous query that should load them all at once,
+//! e.g. with `find_by_ids` or a `Loader`.
+//!
+//! The detector only runs in debug builds, so it costs nothing in release builds even if it is enabled.
+#![cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::task;
+use crate::normalize_statement;
+
+/// The maximum number of counted statement shapes; the counters are reset when it is reached,
+/// so that tasks that ended without a transaction cannot exhaust the memory.
+const MAX_SHAPES: usize = 10_000;
+
+/// `NPlusOneDetector` counts the executions of every statement shape per task and logs a warning with a backtrace
+/// when a shape is executed more often than the threshold. The counters of a task are reset when it starts
+/// or ends a transaction, so a transaction is counted on its own.
+#[derive(Debug, Default)]
+pub(crate) struct NPlusOneDetector {
+    threshold: Option<usize>,
+    counts: Mutex<HashMap<(Option<task::Id>, String), usize>>,
+}
+
+impl NPlusOneDetector {
+    /// Constructs a detector that warns when a shape is executed more than `threshold` times, or a disabled one.
+    pub(crate) fn new(threshold: Option<usize>) -> Self {
+        NPlusOneDetector { threshold: threshold.filter(|_| cfg!(debug_assertions)), counts: Mutex::default() }
+    }
+
+    /// Counts an execution of `statement` in the current task and returns `true` if it exceeded the threshold
+    /// for the first time, in which case the warning is logged.
+    pub(crate) fn record(&self, statement: &str) -> bool {
+        let Some(threshold) = self.threshold else {
+            return false;
+        };
+        let shape = normalize_statement(statement);
+        let count = {
+            let mut counts = self.counts.lock().unwrap();
+            if counts.len() >= MAX_SHAPES {
+                counts.clear();
+            }
+            let count = counts.entry((task::try_id(), shape.clone())).or_insert(0);
+            *count += 1;
+            *count
+        };
+        if count != threshold + 1 {
+            return false;
+        }
+        log::warn!("possible N+1 query: {:?} was executed more than {} times in one task or transaction, \
+                    consider loading the rows with one query, e.g. with find_by_ids or a Loader\n{}",
+                   shape, threshold, Backtrace::force_capture());
+        true
+    }
+
+    /// Resets the counters of the current task, e.g. when it starts or ends a transaction.
+    pub(crate) fn reset_task(&self) {
+        if self.threshold.is_some() {
+            let id = task::try_id();
+            self.counts.lock().unwrap().retain(|(task, _), _| *task != id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NPlusOneDetector;
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_record() {
+        let detector = NPlusOneDetector::new(Some(2));
+        assert!(!detector.record("select * from author where id = 1"));
+        assert!(!detector.record("select * from author where id = 2"));
+        assert!(!detector.record("select * from book"));
+        assert!(detector.record("select * from author where id = 3"));
+        assert!(!detector.record("select * from author where id = 4"));
+        detector.reset_task();
+        assert!(!detector.record("select * from author where id = 5"));
+        assert!(!NPlusOneDetector::new(None).record("select 1"));
+    }
+}
@@ -0,0 +1,229 @@
+//! `outbox` is a module for applications at the edge of a flaky network, e.g. a point of sale that writes to a remote
+//! MySQL database: inserts and updates that fail because the database is unreachable are stored in a local SQLite
+//! journal and replayed in order once the database is back.
+//!
+//! ```ignore
+//! let outbox = Outbox::open(remote.clone(), "outbox.db").await?;
+//! outbox.spawn_replayer(Duration::from_secs(30));
+//! match outbox.add(sale).await? {
+//!     Delivery::Written(_) => {}
+//!     Delivery::Queued => show_offline_banner(),
+//! }
+//! ```
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::cassette::{format_params, parse_params};
+use crate::{sqlite, CountedStatement, ORMError, ORMTrait, QueryBuilder, Statement, TableDeserialize, TableSerialize, Value};
+
+/// The table of the journal.
+const TABLE: &str = "parvati_outbox";
+
+/// `Delivery` is the outcome of a write through an `Outbox`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// The statement was executed on the database; it contains the number of affected rows.
+    Written(usize),
+
+    /// The database is unreachable, or older writes are still queued, so the statement is stored in the journal.
+    Queued,
+}
+
+/// `OutboxEntry` is a statement stored in the journal of an `Outbox`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxEntry {
+    /// `id` orders the entries in the order they were queued.
+    pub id: u64,
+
+    /// `statement` is the statement that still has to be executed on the database.
+    pub statement: String,
+
+    /// `params` are the values bound to the placeholders of the statement.
+    pub params: Vec<Value>,
+
+    /// `error` is the error of the last attempt to execute the statement.
+    pub error: String,
+}
+
+/// `Outbox` writes entities to a database and queues the writes that fail because the database is unreachable
+/// in a local SQLite journal, see the module documentation. Queued writes are replayed in order by `replay`
+/// or by the task started with `spawn_replayer`; while writes are queued, new writes are queued behind them.
+pub struct Outbox<O> {
+    orm: Arc<O>,
+    journal: Arc<sqlite::ORM>,
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl<O: ORMTrait<O> + Send + Sync + 'static> Outbox<O> {
+    /// Opens the journal at the SQLite file `path` for writes to `orm`, creating it if needed.
+    /// Writes queued by a previous process are kept and replayed first.
+    pub async fn open(orm: Arc<O>, path: &str) -> Result<Arc<Self>, ORMError> {
+        let journal = sqlite::ORM::connect(path.to_string())?;
+        let _: usize = journal.query_update(format!("create table if not exists {} (id INTEGER PRIMARY KEY AUTOINCREMENT, \
+            statement TEXT NOT NULL, params TEXT NOT NULL, error TEXT NOT NULL)", TABLE).as_str()).run().await?;
+        Ok(Arc::new(Outbox { orm, journal, lock: tokio::sync::Mutex::new(()) }))
+    }
+
+    /// Inserts `data`, or queues the insert if the database is unreachable.
+    /// Returns the error without queueing if `data` cannot be rendered or fails its validation.
+    pub async fn add<T>(&self, data: T) -> Result<Delivery, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static
+    {
+        let statement = Outbox::<O>::statement(self.orm.add(data))?;
+        self.write(statement).await
+    }
+
+    /// Updates the record with the ID of `data`, or queues the update if the database is unreachable.
+    /// Returns the error without queueing if `data` cannot be rendered or fails its validation.
+    pub async fn modify<T>(&self, data: T) -> Result<Delivery, ORMError>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        let statement = Outbox::<O>::statement(self.orm.modify(data))?;
+        self.write(statement).await
+    }
+
+    /// Returns the queued statements in the order they are replayed.
+    pub async fn entries(&self) -> Result<Vec<OutboxEntry>, ORMError> {
+        let rows = self.journal.query_map(format!("select id, statement, params, error from {} order by id", TABLE).as_str()).await?;
+        rows.iter()
+            .map(|row| {
+                let id = row.get("id").and_then(Value::as_i64).unwrap_or_default() as u64;
+                let params = row.get("params").and_then(Value::as_str).and_then(parse_params)
+                    .ok_or_else(|| ORMError::InvalidValue(format!("invalid params of queued statement {}", id)))?;
+                Ok(OutboxEntry {
+                    id,
+                    statement: row.get("statement").and_then(Value::as_str).unwrap_or_default().to_string(),
+                    params,
+                    error: row.get("error").and_then(Value::as_str).unwrap_or_default().to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the number of queued statements.
+    pub async fn pending(&self) -> Result<usize, ORMError> {
+        let rows = self.journal.query_map(format!("select count(*) as pending from {}", TABLE).as_str()).await?;
+        Ok(rows.first().and_then(|row| row.get("pending")).and_then(Value::as_i64).unwrap_or_default() as usize)
+    }
+
+    /// Removes the queued statement `id` without executing it, e.g. an update that the database keeps rejecting.
+    pub async fn discard(&self, id: u64) -> Result<bool, ORMError> {
+        let _guard = self.lock.lock().await;
+        let removed: usize = self.journal.query_update(format!("delete from {} where id = {}", TABLE, id).as_str()).run().await?;
+        Ok(removed > 0)
+    }
+
+    /// Executes the queued statements in order and removes them from the journal.
+    /// Replaying stops at the first statement that fails; it stays queued together with the following ones
+    /// and the error is returned. Returns the number of replayed statements.
+    pub async fn replay(&self) -> Result<usize, ORMError> {
+        let _guard = self.lock.lock().await;
+        self.replay_queued().await
+    }
+
+    /// Replays the queued statements every `interval` until the outbox is dropped.
+    /// Does nothing if it is called outside of a Tokio runtime.
+    pub fn spawn_replayer(self: &Arc<Self>, interval: Duration) {
+        let handle = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                log::warn!("replaying the outbox requires a Tokio runtime");
+                return;
+            }
+        };
+        let outbox = Arc::downgrade(self);
+        handle.spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(outbox) = outbox.upgrade() else {
+                    break;
+                };
+                match outbox.replay().await {
+                    Ok(0) => {}
+                    Ok(replayed) => log::info!("replayed {} queued writes", replayed),
+                    Err(e) if is_unreachable(&e) => log::debug!("the database is still unreachable: {:?}", e),
+                    Err(e) => log::warn!("replaying the outbox failed: {:?}", e),
+                }
+            }
+        });
+    }
+
+    // Returns the statement of a write, its params and its counter cache updates,
+    // or the error that prevents it from being executed at all.
+    fn statement<R, E>(qb: QueryBuilder<'_, R, E, O>) -> Result<CountedStatement, ORMError> {
+        if let Some(e) = qb.error {
+            return Err(ORMError::InvalidValue(e));
+        }
+        if let Some(e) = qb.validation {
+            return Err(ORMError::Validation(e));
+        }
+        Ok((qb.query, qb.params, qb.counters))
+    }
+
+    // Executes `statement` after the queued ones, or queues it.
+    async fn write(&self, (statement, params, counters): CountedStatement) -> Result<Delivery, ORMError> {
+        let _guard = self.lock.lock().await;
+        if self.pending().await? > 0 {
+            let replayed = self.replay_queued().await;
+            if replayed.is_err() || self.pending().await? > 0 {
+                let error = replayed.err().map(|e| format!("{:?}", e)).unwrap_or_default();
+                self.enqueue(&statement, &params, &counters, &error).await?;
+                return Ok(Delivery::Queued);
+            }
+        }
+        let mut query = self.orm.query_update(&statement).bind(params.clone());
+        query.counters = counters.clone();
+        match self.orm.exec_all(&[query]).await {
+            Ok(rows) => Ok(Delivery::Written(rows)),
+            Err(e) if is_unreachable(&e) => {
+                log::warn!("the database is unreachable, the write is queued: {:?}", e);
+                self.enqueue(&statement, &params, &counters, &format!("{:?}", e)).await?;
+                Ok(Delivery::Queued)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // Queues `statement` after its counter cache updates, which are replayed as statements of their own.
+    async fn enqueue(&self, statement: &str, params: &[Value], counters: &[Statement], error: &str) -> Result<(), ORMError> {
+        let entries = counters.iter().map(|(counter, params)| (counter.as_str(), params.as_slice())).chain(std::iter::once((statement, params)));
+        let queries: Vec<_> = entries
+            .map(|(statement, params)| {
+                let params = vec![Value::Text(statement.to_string()), Value::Text(format_params(params)), Value::Text(error.to_string())];
+                self.journal.query_update(format!("insert into {} (statement, params, error) values (?, ?, ?)", TABLE).as_str()).bind(params)
+            })
+            .collect();
+        self.journal.exec_all(&queries).await?;
+        Ok(())
+    }
+
+    async fn replay_queued(&self) -> Result<usize, ORMError> {
+        let mut replayed = 0;
+        for entry in self.entries().await? {
+            if let Err(e) = self.orm.exec_all(&[self.orm.query_update(&entry.statement).bind(entry.params)]).await {
+                let _: usize = self.journal.query_update(format!("update {} set error = ? where id = {}", TABLE, entry.id).as_str())
+                    .bind(vec![Value::Text(format!("{:?}", e))]).run().await?;
+                return Err(e);
+            }
+            let _: usize = self.journal.query_update(format!("delete from {} where id = {}", TABLE, entry.id).as_str()).run().await?;
+            replayed += 1;
+        }
+        Ok(replayed)
+    }
+}
+
+/// Returns `true` if `error` means that the database could not be reached, so that the write may succeed later.
+fn is_unreachable(error: &ORMError) -> bool {
+    match error {
+        ORMError::NoConnection | ORMError::StdError(_) => true,
+        #[cfg(feature = "mysql")]
+        ORMError::MySQLError(mysql_async::Error::Io(_) | mysql_async::Error::Driver(_)) => true,
+        ORMError::RusqliteError(rusqlite::Error::SqliteFailure(e, _)) => matches!(e.code,
+            rusqlite::ErrorCode::CannotOpen | rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked),
+        _ => false,
+    }
+}
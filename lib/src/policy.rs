@@ -0,0 +1,231 @@
+//! `policy` is a module for the statement policies set with `ORMTrait::set_policy`, which block dangerous statements
+//! at runtime, e.g. in production builds:
+//!
+//! ```ignore
+//! if cfg!(not(debug_assertions)) {
+//!     conn.set_policy(Policy::deny(StatementKind::Delete).unless_in_transaction()
+//!         .and_deny(StatementKind::UnscopedUpdate)
+//!         .and_deny(StatementKind::Ddl));
+//! }
+//! ```
+//!
+//! Every statement is checked before it is executed, also the statements of the helpers of this crate, e.g. of
+//! `Queue`. The kind of a statement is parsed from its first keyword; an UPDATE or DELETE without a WHERE clause
+//! outside of its subqueries is unscoped. A string of several statements is denied if one of them is denied.
+#![cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+
+use crate::ORMError;
+
+/// `StatementKind` is an enumeration of the categories of statements that a `Policy` allows or denies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatementKind {
+    /// A `SELECT` statement, also one that starts with a `WITH` clause.
+    Select,
+
+    /// An `INSERT` or `REPLACE` statement.
+    Insert,
+
+    /// An `UPDATE` statement.
+    Update,
+
+    /// A `DELETE` statement.
+    Delete,
+
+    /// An `UPDATE` statement without a `WHERE` clause, which updates every row of the table.
+    UnscopedUpdate,
+
+    /// A `DELETE` statement without a `WHERE` clause, which deletes every row of the table.
+    UnscopedDelete,
+
+    /// A statement that changes the schema: `CREATE`, `ALTER`, `DROP`, `TRUNCATE` or `RENAME`.
+    Ddl,
+}
+
+impl StatementKind {
+    /// Returns `true` if `sql` is a statement of this kind or contains one, e.g. an unscoped DELETE is a `Delete`
+    /// and an `UnscopedDelete`.
+    pub fn matches(&self, sql: &str) -> bool {
+        top_level_words(sql).iter().any(|words| self.matches_words(words))
+    }
+
+    fn matches_words(&self, words: &[String]) -> bool {
+        // the statement of a WITH clause follows its common table expressions, which are in parentheses
+        let start = if words[0] == "with" {
+            match words.iter().position(|word| matches!(word.as_str(), "select" | "insert" | "replace" | "update" | "delete")) {
+                Some(start) => start,
+                None => return false,
+            }
+        } else {
+            0
+        };
+        let scoped = || words[start..].iter().any(|word| word == "where");
+        match (self, words[start].as_str()) {
+            (StatementKind::Select, "select") => true,
+            (StatementKind::Insert, "insert" | "replace") => true,
+            (StatementKind::Update, "update") => true,
+            (StatementKind::Delete, "delete") => true,
+            (StatementKind::UnscopedUpdate, "update") => !scoped(),
+            (StatementKind::UnscopedDelete, "delete") => !scoped(),
+            (StatementKind::Ddl, "create" | "alter" | "drop" | "truncate" | "rename") => true,
+            _ => false,
+        }
+    }
+}
+
+/// `Policy` is a list of rules that the statements of a connection are checked against, see `ORMTrait::set_policy`.
+/// A statement is executed if no rule denies it. The default policy allows every statement.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    /// `kinds` are the denied kinds, or the only allowed kinds if `allow_only` is `true`.
+    kinds: Vec<StatementKind>,
+
+    /// `allow_only` is `true` if the rule denies every statement that is not of one of `kinds`.
+    allow_only: bool,
+
+    /// `unless_in_transaction` is `true` if the rule does not apply inside a transaction.
+    unless_in_transaction: bool,
+}
+
+impl Policy {
+    /// Constructs a policy that allows every statement, e.g. to remove the policy of a connection.
+    pub fn allow_all() -> Policy {
+        Policy::default()
+    }
+
+    /// Constructs a policy that denies the statements of `kind`.
+    pub fn deny(kind: StatementKind) -> Policy {
+        Policy::allow_all().and_deny(kind)
+    }
+
+    /// Constructs a policy that denies every statement that is not of one of `kinds`,
+    /// e.g. `&[StatementKind::Select]` for a read-only connection.
+    pub fn allow_only(kinds: &[StatementKind]) -> Policy {
+        let mut policy = Policy::allow_all();
+        policy.rules.push(Rule { kinds: kinds.to_vec(), allow_only: true, unless_in_transaction: false });
+        policy
+    }
+
+    /// Adds a rule that denies the statements of `kind`.
+    pub fn and_deny(mut self, kind: StatementKind) -> Policy {
+        self.rules.push(Rule { kinds: vec![kind], allow_only: false, unless_in_transaction: false });
+        self
+    }
+
+    /// Exempts the statements executed inside a transaction from the rule added last, e.g. to allow a DELETE only
+    /// together with the writes it belongs to. The transactions of `begin`, `transaction` and `exec_all` count.
+    /// Only the transaction of the statement itself counts: while a task is in a transaction, the statements
+    /// of other tasks are checked as outside a transaction, also on a pooled MySQL connection.
+    pub fn unless_in_transaction(mut self) -> Policy {
+        if let Some(rule) = self.rules.last_mut() {
+            rule.unless_in_transaction = true;
+        }
+        self
+    }
+
+    /// Returns `true` if the policy allows `sql`, executed inside a transaction or not.
+    pub fn allows(&self, sql: &str, in_transaction: bool) -> bool {
+        let statements = top_level_words(sql);
+        self.rules.iter()
+            .filter(|rule| !(rule.unless_in_transaction && in_transaction))
+            .all(|rule| statements.iter().all(|words| {
+                rule.allow_only == rule.kinds.iter().any(|kind| kind.matches_words(words))
+            }))
+    }
+
+    /// Returns `ORMError::PolicyViolation` if the policy denies `sql`.
+    pub(crate) fn check(&self, sql: &str, in_transaction: bool) -> Result<(), ORMError> {
+        if self.allows(sql, in_transaction) {
+            Ok(())
+        } else {
+            Err(ORMError::PolicyViolation(sql.to_string()))
+        }
+    }
+}
+
+// Splits `sql` into its statements, separated by semicolons, and returns the lowercase words of each statement
+// outside of parentheses, string literals, quoted identifiers and comments. Statements without words are skipped.
+fn top_level_words(sql: &str) -> Vec<Vec<String>> {
+    let mut statements: Vec<Vec<String>> = vec![Vec::new()];
+    let mut word = String::new();
+    let mut depth = 0usize;
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_alphanumeric() || c == '_' {
+            word.extend(c.to_lowercase());
+            continue;
+        }
+        let word = std::mem::take(&mut word);
+        if !word.is_empty() && depth == 0 {
+            statements.last_mut().unwrap().push(word);
+        }
+        match c {
+            // a doubled quote ends the literal and starts the next one, which skips it as well
+            '\'' | '"' | '`' => {
+                while let Some(q) = chars.next() {
+                    if q == '\\' && c != '`' {
+                        chars.next();
+                    } else if q == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                chars.by_ref().find(|q| *q == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for q in chars.by_ref() {
+                    if previous == '*' && q == '/' {
+                        break;
+                    }
+                    previous = q;
+                }
+            }
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ';' if depth == 0 => statements.push(Vec::new()),
+            _ => {}
+        }
+    }
+    if !word.is_empty() && depth == 0 {
+        statements.last_mut().unwrap().push(word);
+    }
+    statements.retain(|words| !words.is_empty());
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Policy, StatementKind};
+
+    #[test]
+    fn test_policy() {
+        assert!(StatementKind::UnscopedDelete.matches("DELETE FROM user"));
+        assert!(StatementKind::Delete.matches("delete from user where id = 1"));
+        assert!(!StatementKind::UnscopedDelete.matches("delete from user where id = 1"));
+        assert!(StatementKind::UnscopedUpdate.matches("update user set age = (select max(age) from user where id = 1)"));
+        assert!(!StatementKind::UnscopedUpdate.matches("update user set name = 'where' where id = 1 -- where"));
+        assert!(StatementKind::UnscopedUpdate.matches("update user set name = 'it''s where'"));
+        assert!(StatementKind::Delete.matches("with old as (select id from user) delete from user where id in (select id from old)"));
+        assert!(StatementKind::Select.matches("/* report */ with old as (delete from user) select 1"));
+        assert!(StatementKind::Ddl.matches("select 1; DROP TABLE user"));
+        assert!(!StatementKind::Ddl.matches("select 'drop table user'"));
+
+        let policy = Policy::deny(StatementKind::Delete).unless_in_transaction().and_deny(StatementKind::Ddl);
+        assert!(!policy.allows("delete from user where id = 1", false));
+        assert!(policy.allows("delete from user where id = 1", true));
+        assert!(!policy.allows("create table user (id INTEGER)", true));
+        assert!(policy.allows("insert into user (name) values ('a')", false));
+        assert!(Policy::allow_all().allows("drop table user", false));
+
+        let read_only = Policy::allow_only(&[StatementKind::Select]);
+        assert!(read_only.allows("select * from user", false));
+        assert!(!read_only.allows("select 1; update user set age = 1 where id = 1", false));
+    }
+}
@@ -0,0 +1,127 @@
+//! `queue` is a module for job queues stored in a table of the database, e.g. the emails a background worker sends:
+//!
+//! ```ignore
+//! let emails = conn.queue("emails");
+//! emails.create_table().await?;
+//! emails.push(&serde_json::to_string(&email)?).await?;
+//! // in the worker
+//! for job in emails.claim(10, Duration::from_secs(60)).await? {
+//!     send(&job.payload).await?;
+//!     emails.ack(job.id).await?;
+//! }
+//! ```
+//!
+//! A claimed job is hidden from other workers for the visibility timeout. If it is not acknowledged in time,
+//! e.g. because the worker crashed, it is claimed again, so jobs are delivered at least once.
+#![cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::schema::quote;
+use crate::{in_transaction, IsolationLevel, ORMError, ORMTrait, Statement, Value};
+
+/// The table of the jobs of all queues.
+pub(crate) const TABLE: &str = "parvati_queue";
+
+/// `Job` is a job claimed from a `Queue`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    /// `id` identifies the job, e.g. to acknowledge it with `Queue::ack`.
+    pub id: u64,
+
+    /// `payload` is the payload the job was pushed with.
+    pub payload: String,
+
+    /// `attempts` is the number of times the job was claimed, including this time.
+    pub attempts: u32,
+}
+
+/// `Queue` pushes jobs to the queue `name` and claims them for workers, see the module documentation.
+/// The SQLite backend claims jobs inside a `BEGIN IMMEDIATE` transaction, the MySQL backend locks them with
+/// `FOR UPDATE SKIP LOCKED`, so concurrent workers never claim the same job.
+pub struct Queue<'a, O> {
+    /// `orm` is a reference to an ORM object that implements the `ORMTrait`.
+    pub(crate) orm: &'a O,
+
+    /// `name` is the name of the queue.
+    pub(crate) name: String,
+}
+
+impl<'a, O: ORMTrait<O> + Sync> Queue<'a, O> {
+    /// Constructs the `Queue` named `name`.
+    pub(crate) fn new(orm: &'a O, name: &str) -> Self {
+        Queue { orm, name: name.to_string() }
+    }
+
+    /// Returns the name of the queue.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Removes the job `id` after it was processed.
+    /// Returns `false` if there is no such job, e.g. because it was acknowledged already.
+    pub async fn ack(&self, id: u64) -> Result<bool, ORMError> {
+        let query = self.orm.query_update(&format!("delete from {} where id = ? and queue = ?", TABLE))
+            .bind(vec![Value::Int(id as i64), Value::Text(self.name.clone())]);
+        Ok(self.orm.exec_all(&[query]).await? > 0)
+    }
+
+    /// Makes the claimed job `id` visible again right away, e.g. to retry it after a failure.
+    /// Returns `false` if there is no such job.
+    pub async fn release(&self, id: u64) -> Result<bool, ORMError> {
+        let query = self.orm.query_update(&format!("update {} set visible_at = 0 where id = ? and queue = ?", TABLE))
+            .bind(vec![Value::Int(id as i64), Value::Text(self.name.clone())]);
+        Ok(self.orm.exec_all(&[query]).await? > 0)
+    }
+
+    /// Returns the number of jobs in the queue, including the claimed ones that were not acknowledged yet.
+    pub async fn len(&self) -> Result<usize, ORMError> {
+        let rows = self.orm.query_map(&format!("select count(*) as jobs from {} where queue = {}", TABLE, quote(&self.name))).await?;
+        Ok(rows.first().and_then(|row| row.get("jobs")).and_then(Value::as_i64).unwrap_or_default() as usize)
+    }
+
+    /// Returns `true` if the queue has no jobs.
+    pub async fn is_empty(&self) -> Result<bool, ORMError> {
+        Ok(self.len().await? == 0)
+    }
+
+    /// Returns the INSERT statement of a job with `payload` and its params.
+    pub(crate) fn push_statement(&self, payload: &str) -> Statement {
+        let query = format!("insert into {} (queue, payload, attempts, visible_at) values (?, ?, 0, ?)", TABLE);
+        (query, vec![Value::Text(self.name.clone()), Value::Text(payload.to_string()), Value::Int(now())])
+    }
+
+    /// Claims up to `n` visible jobs in a transaction with `isolation`. `lock` is appended to the SELECT of the jobs.
+    pub(crate) async fn claim_locked(&self, n: usize, visibility_timeout: Duration, isolation: IsolationLevel, lock: &str) -> Result<Vec<Job>, ORMError> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        in_transaction(self.orm, isolation, self.hide_visible(n, visibility_timeout, lock)).await
+    }
+
+    // Selects up to `n` visible jobs and hides them for `visibility_timeout`.
+    async fn hide_visible(&self, n: usize, visibility_timeout: Duration, lock: &str) -> Result<Vec<Job>, ORMError> {
+        let now = now();
+        let rows = self.orm.query_map(&format!("select id, payload, attempts from {} where queue = {} and visible_at <= {} order by id limit {}{}",
+                                               TABLE, quote(&self.name), now, n, lock)).await?;
+        let jobs: Vec<Job> = rows.iter()
+            .map(|row| Job {
+                id: row.get("id").and_then(Value::as_i64).unwrap_or_default() as u64,
+                payload: row.get("payload").and_then(Value::as_str).unwrap_or_default().to_string(),
+                attempts: row.get("attempts").and_then(Value::as_i64).unwrap_or_default() as u32 + 1,
+            })
+            .collect();
+        if !jobs.is_empty() {
+            let ids: Vec<String> = jobs.iter().map(|job| job.id.to_string()).collect();
+            let visible_at = now + visibility_timeout.as_millis() as i64;
+            let query = self.orm.query_update(&format!("update {} set visible_at = {}, attempts = attempts + 1 where id in ({})",
+                                                       TABLE, visible_at, ids.join(", ")));
+            self.orm.exec_all(&[query]).await?;
+        }
+        Ok(jobs)
+    }
+}
+
+// Returns the current time in milliseconds since the Unix epoch, the unit of `visible_at`.
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_millis() as i64).unwrap_or_default()
+}
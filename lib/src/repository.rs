@@ -0,0 +1,108 @@
+//! `repository` is a module with a small storage interface for the entities of a service, implemented for every
+//! `ORMTrait` backend, so that services can depend on `Repository<T>` instead of a concrete ORM and use a mock in tests.
+
+use std::fmt::Debug;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use crate::{ORMError, ORMTrait, TableDeserialize, TableSerialize, Value};
+
+/// `Page` is one page of records and the total number of records, see `Repository::paginate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    /// `items` are the records of the page.
+    pub items: Vec<T>,
+
+    /// `total` is the number of records of all pages.
+    pub total: u64,
+
+    /// `page` is the number of the page, starting at 1.
+    pub page: u64,
+
+    /// `per_page` is the maximum number of records of a page.
+    pub per_page: u64,
+}
+
+impl<T> Page<T> {
+    /// Returns the number of pages.
+    pub fn pages(&self) -> u64 {
+        self.total.div_ceil(self.per_page.max(1))
+    }
+
+    /// Returns `true` if there is a page after this one.
+    pub fn has_next(&self) -> bool {
+        self.page < self.pages()
+    }
+}
+
+/// `Repository` is a trait for storing entities of type `T` by their ID.
+#[async_trait]
+pub trait Repository<T>: Send + Sync {
+    /// Returns the record with the given ID, or `None` if there is none.
+    async fn get(&self, id: u64) -> Result<Option<T>, ORMError>;
+
+    /// Returns all records ordered by ID.
+    async fn list(&self) -> Result<Vec<T>, ORMError>;
+
+    /// Inserts `entity` and returns it as it is stored, including its generated ID.
+    async fn create(&self, entity: T) -> Result<T, ORMError>;
+
+    /// Updates the record with the ID of `entity`. Returns `ORMError::NotFound` if there is no such record.
+    async fn update(&self, entity: T) -> Result<(), ORMError>;
+
+    /// Removes the record with the given ID and returns `true` if it existed.
+    async fn delete(&self, id: u64) -> Result<bool, ORMError>;
+
+    /// Returns the page `page` (starting at 1) of the records ordered by ID, with `per_page` records per page.
+    /// Returns `ORMError::InvalidValue` if `page` or `per_page` is 0.
+    async fn paginate(&self, page: u64, per_page: u64) -> Result<Page<T>, ORMError>;
+}
+
+#[async_trait]
+impl<T, O> Repository<T> for O
+    where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + Send + Sync + 'static,
+          O: ORMTrait<O> + Send + Sync
+{
+    async fn get(&self, id: u64) -> Result<Option<T>, ORMError> {
+        Ok(self.find_by_ids::<T>(&[id]).await?.pop().flatten())
+    }
+
+    async fn list(&self) -> Result<Vec<T>, ORMError> {
+        let query = self.find_all::<T>().with_modifiers("", |m| m.order_by.push("id asc".to_string()));
+        self.fetch(query).await
+    }
+
+    async fn create(&self, entity: T) -> Result<T, ORMError> {
+        let id = self.add_all(vec![entity]).await?.pop().ok_or(ORMError::InsertError)?;
+        self.get(id).await?.ok_or(ORMError::InsertError)
+    }
+
+    async fn update(&self, entity: T) -> Result<(), ORMError> {
+        let id = entity.get_id();
+        match self.exec_all(&[self.modify(entity)]).await? {
+            0 => Err(ORMError::NotFound(format!("{} with id {}", T::same_name(), id))),
+            _ => Ok(()),
+        }
+    }
+
+    async fn delete(&self, id: u64) -> Result<bool, ORMError> {
+        Ok(self.remove_by_ids::<T>(&[id]).await? > 0)
+    }
+
+    async fn paginate(&self, page: u64, per_page: u64) -> Result<Page<T>, ORMError> {
+        if page == 0 || per_page == 0 {
+            return Err(ORMError::InvalidValue(format!("invalid page {} of {} records", page, per_page)));
+        }
+        let query = self.find_all::<T>().with_modifiers("", |m| {
+            m.order_by.push("id asc".to_string());
+            m.limit = Some(per_page as i64);
+            m.offset = Some((page - 1) * per_page);
+        });
+        let count = format!("select count(*) as total from {}", query.table().unwrap_or_default());
+        let total = match self.query_map(count.as_str()).await?.first().and_then(|row| row.get("total")) {
+            Some(Value::Int(total)) => *total as u64,
+            _ => 0,
+        };
+        let items = self.fetch(query).await?;
+        Ok(Page { items, total, page, per_page })
+    }
+}
@@ -0,0 +1,406 @@
+//! `schema` is a module that describes the columns of an entity table and renders them as `CREATE TABLE` statements.
+//!
+//! The column definitions are generated by `#[derive(TableDeserialize)]` from the field types and the `#[column(...)]`
+//! attributes, e.g. `#[column(references = "team(id)", on_delete = "cascade")]` or `#[column(enumeration)]`.
+//! `#[column(datetime)]` declares a string field as a datetime column, e.g. one holding RFC 3339 values.
+//! Comments given with `#[table(comment = "...")]` and `#[column(comment = "...")]` are part of the MySQL DDL;
+//! SQLite has no comments, so they are stored in the `parvati_comments` table instead.
+
+use serde::de::{self, DeserializeOwned, Visitor};
+
+/// `ColumnType` is an enumeration of the column types that entity fields are mapped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Integers up to 32 bits: `INTEGER` on SQLite, `INT` on MySQL.
+    Integer,
+    /// 64-bit integers: `INTEGER` on SQLite, `BIGINT` on MySQL.
+    BigInt,
+    /// Floating point numbers: `REAL` on SQLite, `DOUBLE` on MySQL.
+    Real,
+    /// Booleans: `INTEGER` on SQLite, `BOOLEAN` on MySQL.
+    Boolean,
+    /// Strings: `TEXT` on SQLite, `VARCHAR(255)` on MySQL.
+    Text,
+    /// Byte arrays: `BLOB`.
+    Blob,
+    /// Dates: `TEXT` on SQLite, `DATE` on MySQL.
+    Date,
+    /// Datetimes: `TEXT` on SQLite, `DATETIME` on MySQL. Only these columns are converted by `DateTimePolicy::Utc`.
+    DateTime,
+}
+
+/// `ReferentialAction` is an enumeration of the `ON DELETE` and `ON UPDATE` actions of a foreign key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferentialAction {
+    /// `CASCADE`: the referencing rows are deleted or updated as well.
+    Cascade,
+    /// `SET NULL`: the referencing column is set to NULL.
+    SetNull,
+    /// `SET DEFAULT`: the referencing column is set to its default value. Not supported by InnoDB.
+    SetDefault,
+    /// `RESTRICT`: the change of the referenced row is rejected immediately.
+    Restrict,
+    /// `NO ACTION`: the change of the referenced row is rejected if references remain (the default).
+    NoAction,
+}
+
+impl ReferentialAction {
+    /// Returns the action as it is written in SQL.
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            ReferentialAction::Cascade => "CASCADE",
+            ReferentialAction::SetNull => "SET NULL",
+            ReferentialAction::SetDefault => "SET DEFAULT",
+            ReferentialAction::Restrict => "RESTRICT",
+            ReferentialAction::NoAction => "NO ACTION",
+        }
+    }
+}
+
+/// `ForeignKey` describes the column referenced by a column and the actions taken when the referenced row changes.
+/// SQLite only enforces foreign keys with `PRAGMA foreign_keys = ON`, see `ConnectOptions::on_connect`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKey {
+    /// The referenced table.
+    pub table: String,
+    /// The referenced column.
+    pub column: String,
+    /// The action taken when the referenced row is deleted.
+    pub on_delete: Option<ReferentialAction>,
+    /// The action taken when the referenced column is updated.
+    pub on_update: Option<ReferentialAction>,
+}
+
+impl ForeignKey {
+    /// Constructs a foreign key that references `column` of `table`, without referential actions.
+    pub fn new(table: &str, column: &str) -> Self {
+        ForeignKey {
+            table: table.to_string(),
+            column: column.to_string(),
+            on_delete: None,
+            on_update: None,
+        }
+    }
+
+    /// Sets the `ON DELETE` action.
+    pub fn on_delete(mut self, action: ReferentialAction) -> Self {
+        self.on_delete = Some(action);
+        self
+    }
+
+    /// Sets the `ON UPDATE` action.
+    pub fn on_update(mut self, action: ReferentialAction) -> Self {
+        self.on_update = Some(action);
+        self
+    }
+}
+
+/// `ColumnDef` describes a column of an entity table. The column named `id` is the auto-incremented primary key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnDef {
+    /// The name of the column.
+    pub name: String,
+    /// The type of the column.
+    pub column_type: ColumnType,
+    /// `true` if the column accepts NULL, i.e. the field is an `Option`.
+    pub nullable: bool,
+    /// The column referenced by this column, if any.
+    pub foreign_key: Option<ForeignKey>,
+    /// The allowed values of an enum-backed column: a `CHECK` constraint on SQLite, an `ENUM` type on MySQL.
+    pub values: Option<Vec<String>>,
+    /// The documentation of the column.
+    pub comment: Option<String>,
+    /// Former names of the column, which are read if a result has no column with the name of the field,
+    /// e.g. while a rolling migration renames the column.
+    pub aliases: Vec<String>,
+    /// The value of the field if a result has neither the column nor one of its aliases,
+    /// e.g. while a rolling migration adds the column. It is not a `DEFAULT` of the table.
+    pub read_default: Option<String>,
+    /// The column of the referenced row that counts the rows referencing it, e.g. `orders_count` of a customer.
+    /// It is incremented by `add` and decremented by `remove` in the same transaction.
+    pub counter_cache: Option<String>,
+}
+
+impl ColumnDef {
+    /// Constructs a NOT NULL column without a foreign key.
+    pub fn new(name: &str, column_type: ColumnType) -> Self {
+        ColumnDef {
+            name: name.to_string(),
+            column_type,
+            nullable: false,
+            foreign_key: None,
+            values: None,
+            comment: None,
+            aliases: Vec::new(),
+            read_default: None,
+            counter_cache: None,
+        }
+    }
+
+    /// Sets whether the column accepts NULL.
+    pub fn nullable(mut self, nullable: bool) -> Self {
+        self.nullable = nullable;
+        self
+    }
+
+    /// Sets the column referenced by this column.
+    pub fn references(mut self, foreign_key: ForeignKey) -> Self {
+        self.foreign_key = Some(foreign_key);
+        self
+    }
+
+    /// Restricts the column to the given values.
+    pub fn values(mut self, values: Vec<String>) -> Self {
+        self.values = Some(values);
+        self
+    }
+
+    /// Sets the documentation of the column.
+    pub fn comment(mut self, comment: &str) -> Self {
+        self.comment = Some(comment.to_string());
+        self
+    }
+
+    /// Adds a former name of the column, see `aliases`.
+    pub fn alias(mut self, alias: &str) -> Self {
+        self.aliases.push(alias.to_string());
+        self
+    }
+
+    /// Sets the value of the field if a result lacks the column, see `read_default`.
+    pub fn read_default(mut self, value: &str) -> Self {
+        self.read_default = Some(value.to_string());
+        self
+    }
+
+    /// Sets the counter column of the referenced row, see `counter_cache`. It requires a foreign key.
+    pub fn counter_cache(mut self, column: &str) -> Self {
+        self.counter_cache = Some(column.to_string());
+        self
+    }
+
+    /// Returns `true` if the column is the primary key.
+    pub fn is_primary_key(&self) -> bool {
+        self.name == "id"
+    }
+}
+
+/// Returns the names of the unit variants of the enum `T` as they are stored by the ORM, honoring `#[serde(rename)]`.
+/// Returns an empty list if `T` is not an enum. It is used by `#[column(enumeration)]`.
+pub fn enum_variants<T: DeserializeOwned>() -> Vec<String> {
+    let mut variants = Vec::new();
+    let _ = T::deserialize(VariantNames(&mut variants));
+    variants.into_iter().map(|variant| variant.to_string()).collect()
+}
+
+// A deserializer that only records the variant names serde passes to `deserialize_enum`.
+struct VariantNames<'a>(&'a mut Vec<&'static str>);
+
+impl<'de, 'a> de::Deserializer<'de> for VariantNames<'a> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not an enum"))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, variants: &'static [&'static str], _visitor: V) -> Result<V::Value, Self::Error> {
+        self.0.extend_from_slice(variants);
+        Err(de::Error::custom("variants recorded"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option unit unit_struct
+        newtype_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// `Dialect` is an enumeration of the SQL dialects the DDL and literals are rendered for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// The SQL of SQLite.
+    Sqlite,
+    /// The SQL of MySQL.
+    MySql,
+}
+
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn type_sql(column_type: ColumnType, dialect: Dialect) -> &'static str {
+    match (dialect, column_type) {
+        (Dialect::Sqlite, ColumnType::Integer | ColumnType::BigInt | ColumnType::Boolean) => "INTEGER",
+        (Dialect::Sqlite, ColumnType::Real) => "REAL",
+        (Dialect::Sqlite, ColumnType::Text | ColumnType::Date | ColumnType::DateTime) => "TEXT",
+        (Dialect::Sqlite, ColumnType::Blob) => "BLOB",
+        (Dialect::MySql, ColumnType::Integer) => "INT",
+        (Dialect::MySql, ColumnType::BigInt) => "BIGINT",
+        (Dialect::MySql, ColumnType::Real) => "DOUBLE",
+        (Dialect::MySql, ColumnType::Boolean) => "BOOLEAN",
+        (Dialect::MySql, ColumnType::Text) => "VARCHAR(255)",
+        (Dialect::MySql, ColumnType::Blob) => "BLOB",
+        (Dialect::MySql, ColumnType::Date) => "DATE",
+        (Dialect::MySql, ColumnType::DateTime) => "DATETIME",
+    }
+}
+
+pub(crate) fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// Renders a column definition. `create` is `false` for `ALTER TABLE ... MODIFY COLUMN`, where the primary key
+// of an existing table must not be declared again.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn column_sql(column: &ColumnDef, dialect: Dialect, create: bool) -> String {
+    let values = column.values.as_ref().map(|values| values.iter().map(|value| quote(value)).collect::<Vec<_>>().join(", "));
+    let type_sql = match (&values, dialect) {
+        (Some(values), Dialect::MySql) => format!("ENUM({})", values),
+        _ => type_sql(column.column_type, dialect).to_string(),
+    };
+    let integer = matches!(column.column_type, ColumnType::Integer | ColumnType::BigInt);
+    let mut sql = match (column.is_primary_key(), integer, dialect) {
+        (true, true, Dialect::Sqlite) => format!("{} INTEGER PRIMARY KEY AUTOINCREMENT", column.name),
+        (true, true, Dialect::MySql) if create => format!("{} {} AUTO_INCREMENT PRIMARY KEY", column.name, type_sql),
+        (true, true, Dialect::MySql) => format!("{} {} NOT NULL AUTO_INCREMENT", column.name, type_sql),
+        (true, false, _) if create => format!("{} {} PRIMARY KEY", column.name, type_sql),
+        (false, _, _) if column.nullable => format!("{} {}", column.name, type_sql),
+        (_, _, _) => format!("{} {} NOT NULL", column.name, type_sql),
+    };
+    if let (Some(values), Dialect::Sqlite) = (values, dialect) {
+        sql += &format!(" CHECK ({} IN ({}))", column.name, values);
+    }
+    if let (Some(comment), Dialect::MySql) = (&column.comment, dialect) {
+        sql += &format!(" COMMENT {}", quote(comment));
+    }
+    sql
+}
+
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn foreign_key_sql(column: &str, foreign_key: &ForeignKey) -> String {
+    let mut sql = format!("FOREIGN KEY ({}) REFERENCES {}({})", column, foreign_key.table, foreign_key.column);
+    if let Some(action) = foreign_key.on_delete {
+        sql += &format!(" ON DELETE {}", action.as_sql());
+    }
+    if let Some(action) = foreign_key.on_update {
+        sql += &format!(" ON UPDATE {}", action.as_sql());
+    }
+    sql
+}
+
+/// Renders the `CREATE TABLE` statement of `table`. Foreign keys are rendered as table constraints,
+/// because InnoDB ignores `REFERENCES` in a column definition.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn create_table_sql(table: &str, comment: Option<&str>, columns: &[ColumnDef], dialect: Dialect) -> String {
+    let mut definitions: Vec<String> = columns.iter().map(|column| column_sql(column, dialect, true)).collect();
+    for column in columns.iter() {
+        if let Some(foreign_key) = &column.foreign_key {
+            definitions.push(foreign_key_sql(&column.name, foreign_key));
+        }
+    }
+    match (comment, dialect) {
+        (Some(comment), Dialect::MySql) => format!("CREATE TABLE {} ({}) COMMENT = {}", table, definitions.join(", "), quote(comment)),
+        _ => format!("CREATE TABLE {} ({})", table, definitions.join(", ")),
+    }
+}
+
+/// Renders the statements that store the comments of an existing `table`. On SQLite the comments replace the rows
+/// of the table in `parvati_comments`, where the table comment has an empty column name. On MySQL the table
+/// and the commented columns are altered; nothing is rendered if there are no comments.
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) fn comment_statements(table: &str, comment: Option<&str>, columns: &[ColumnDef], dialect: Dialect) -> Vec<String> {
+    let comments = comment.map(|comment| ("", comment)).into_iter()
+        .chain(columns.iter().filter_map(|column| column.comment.as_deref().map(|comment| (column.name.as_str(), comment))));
+    match dialect {
+        Dialect::Sqlite => {
+            let mut statements = vec![
+                "CREATE TABLE IF NOT EXISTS parvati_comments (table_name TEXT NOT NULL, column_name TEXT NOT NULL, \
+                comment TEXT NOT NULL, PRIMARY KEY (table_name, column_name))".to_string(),
+                format!("DELETE FROM parvati_comments WHERE table_name = {}", quote(table)),
+            ];
+            statements.extend(comments.map(|(column, comment)| {
+                format!("INSERT INTO parvati_comments (table_name, column_name, comment) VALUES ({}, {}, {})",
+                        quote(table), quote(column), quote(comment))
+            }));
+            statements
+        }
+        Dialect::MySql => {
+            let mut alterations: Vec<String> = comment.map(|comment| format!("COMMENT = {}", quote(comment))).into_iter().collect();
+            alterations.extend(columns.iter().filter(|column| column.comment.is_some())
+                .map(|column| format!("MODIFY COLUMN {}", column_sql(column, dialect, false))));
+            if alterations.is_empty() {
+                Vec::new()
+            } else {
+                vec![format!("ALTER TABLE {} {}", table, alterations.join(", "))]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+mod tests {
+    use serde_derive::Deserialize;
+    use super::{comment_statements, create_table_sql, enum_variants, ColumnDef, ColumnType, Dialect, ForeignKey, ReferentialAction};
+
+    #[test]
+    fn test_create_table_sql() {
+        let columns = vec![
+            ColumnDef::new("id", ColumnType::Integer),
+            ColumnDef::new("name", ColumnType::Text).nullable(true),
+            ColumnDef::new("team_id", ColumnType::BigInt)
+                .references(ForeignKey::new("team", "id").on_delete(ReferentialAction::Cascade).on_update(ReferentialAction::Restrict)),
+        ];
+        assert_eq!(create_table_sql("player", None, &columns, Dialect::Sqlite),
+                   "CREATE TABLE player (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT, team_id INTEGER NOT NULL, \
+                   FOREIGN KEY (team_id) REFERENCES team(id) ON DELETE CASCADE ON UPDATE RESTRICT)");
+        assert_eq!(create_table_sql("player", None, &columns, Dialect::MySql),
+                   "CREATE TABLE player (id INT AUTO_INCREMENT PRIMARY KEY, name VARCHAR(255), team_id BIGINT NOT NULL, \
+                   FOREIGN KEY (team_id) REFERENCES team(id) ON DELETE CASCADE ON UPDATE RESTRICT)");
+        let columns = vec![ColumnDef::new("id", ColumnType::Text), ColumnDef::new("team_id", ColumnType::Integer).references(ForeignKey::new("team", "id"))];
+        assert_eq!(create_table_sql("player", None, &columns, Dialect::Sqlite),
+                   "CREATE TABLE player (id TEXT PRIMARY KEY, team_id INTEGER NOT NULL, FOREIGN KEY (team_id) REFERENCES team(id))");
+    }
+
+    #[test]
+    fn test_enum_columns() {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        enum Status {
+            Active,
+            #[serde(rename = "on_hold")]
+            OnHold,
+            Closed,
+        }
+
+        assert_eq!(enum_variants::<Status>(), vec!["Active", "on_hold", "Closed"]);
+        assert!(enum_variants::<String>().is_empty());
+        let columns = vec![
+            ColumnDef::new("status", ColumnType::Text).values(enum_variants::<Status>()),
+            ColumnDef::new("previous", ColumnType::Text).nullable(true).values(vec!["it's".to_string()]),
+        ];
+        assert_eq!(create_table_sql("task", None, &columns, Dialect::Sqlite),
+                   "CREATE TABLE task (status TEXT NOT NULL CHECK (status IN ('Active', 'on_hold', 'Closed')), \
+                   previous TEXT CHECK (previous IN ('it''s')))");
+        assert_eq!(create_table_sql("task", None, &columns, Dialect::MySql),
+                   "CREATE TABLE task (status ENUM('Active', 'on_hold', 'Closed') NOT NULL, previous ENUM('it''s'))");
+    }
+
+    #[test]
+    fn test_comments() {
+        let columns = vec![
+            ColumnDef::new("id", ColumnType::Integer).comment("the key"),
+            ColumnDef::new("name", ColumnType::Text).nullable(true).comment("user's name"),
+            ColumnDef::new("age", ColumnType::Integer),
+        ];
+        assert_eq!(create_table_sql("user", Some("registered users"), &columns, Dialect::MySql),
+                   "CREATE TABLE user (id INT AUTO_INCREMENT PRIMARY KEY COMMENT 'the key', \
+                   name VARCHAR(255) COMMENT 'user''s name', age INT NOT NULL) COMMENT = 'registered users'");
+        assert_eq!(create_table_sql("user", Some("registered users"), &columns, Dialect::Sqlite),
+                   "CREATE TABLE user (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT, age INTEGER NOT NULL)");
+        assert_eq!(comment_statements("user", Some("registered users"), &columns, Dialect::MySql),
+                   vec!["ALTER TABLE user COMMENT = 'registered users', MODIFY COLUMN id INT NOT NULL AUTO_INCREMENT COMMENT 'the key', \
+                   MODIFY COLUMN name VARCHAR(255) COMMENT 'user''s name'"]);
+        assert!(comment_statements("user", None, &columns[2..], Dialect::MySql).is_empty());
+        let statements = comment_statements("user", None, &columns, Dialect::Sqlite);
+        assert_eq!(statements.len(), 4);
+        assert_eq!(statements[1], "DELETE FROM parvati_comments WHERE table_name = 'user'");
+        assert_eq!(statements[3], "INSERT INTO parvati_comments (table_name, column_name, comment) VALUES ('user', 'name', 'user''s name')");
+    }
+}
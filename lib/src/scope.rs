@@ -0,0 +1,179 @@
+//! `scope` is a module for handles that restrict the queries of registered entities to the rows of one scope,
+//! e.g. of one tenant:
+//!
+//! ```ignore
+//! let tenant = conn.scoped(col("org_id").eq(org)).entity::<Project>().entity::<Invoice>();
+//! let projects: Vec<Project> = tenant.find_many::<Project>("archived = 0").run().await?;
+//! ```
+//!
+//! Every find, modify and remove of a registered entity gets the condition `org_id = ?` appended to its WHERE
+//! clause, with the value of the scope bound as a param, so rows of other tenants can neither be read nor changed through the handle. Entities that are not
+//! registered, e.g. shared lookup tables, are queried without the condition. Inserts are not checked.
+#![cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+
+use std::any::TypeId;
+use std::fmt::Debug;
+use serde::{Deserialize, Serialize};
+use crate::{is_table_name, Cond, ORMTrait, QueryBuilder, TableDeserialize, TableSerialize, Value};
+
+/// Returns the column `name` for a `Scope` condition, e.g. `col("org_id").eq(42)`.
+pub fn col(name: &str) -> ScopeColumn {
+    ScopeColumn { name: name.to_string() }
+}
+
+/// `ScopeColumn` is the column of a `Scope` condition, see `col`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeColumn {
+    name: String,
+}
+
+impl ScopeColumn {
+    /// Returns the condition that the column is equal to `value`.
+    pub fn eq<V: Into<Value>>(self, value: V) -> Scope {
+        Scope { column: self.name, value: value.into() }
+    }
+}
+
+/// `Scope` is the condition that restricts the queries of a `Scoped` handle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+    column: String,
+    value: Value,
+}
+
+impl Scope {
+    /// Returns the condition with the value bound as a param, e.g. `org_id = ?` with the param `42`,
+    /// or an error if the column is not a plain column name.
+    pub fn cond(&self) -> Result<Cond, String> {
+        if !is_table_name(&self.column) {
+            return Err(format!("invalid scope column {:?}", self.column));
+        }
+        Ok(Cond::field(&self.column).eq(self.value.clone()))
+    }
+}
+
+/// `Scoped` is a handle returned by `ORMTrait::scoped` that appends its `Scope` condition to every find,
+/// modify and remove of the entities registered with `entity`.
+pub struct Scoped<'a, O: ORMTrait<O>> {
+    orm: &'a O,
+    scope: Scope,
+    entities: Vec<TypeId>,
+}
+
+impl<'a, O: ORMTrait<O>> Scoped<'a, O> {
+    /// Constructs a handle without registered entities.
+    pub fn new(orm: &'a O, scope: Scope) -> Self {
+        Scoped { orm, scope, entities: Vec::new() }
+    }
+
+    /// Registers the entity `T`, whose queries are restricted to the scope.
+    pub fn entity<T: 'static>(mut self) -> Self {
+        if !self.is_scoped::<T>() {
+            self.entities.push(TypeId::of::<T>());
+        }
+        self
+    }
+
+    /// Returns `true` if the queries of `T` are restricted to the scope.
+    pub fn is_scoped<T: 'static>(&self) -> bool {
+        self.entities.contains(&TypeId::of::<T>())
+    }
+
+    /// Returns the scope condition of the handle.
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+
+    /// Finds a record by its ID, if it is in the scope.
+    pub fn find_one<T>(&self, id: u64) -> QueryBuilder<'a, Option<T>, T, O>
+        where T: for<'de> Deserialize<'de> + TableDeserialize + TableSerialize + Debug + 'static
+    {
+        if !self.is_scoped::<T>() {
+            return self.orm.find_one(id);
+        }
+        match self.scope.cond() {
+            Ok(condition) => self.orm.find_one_where(Cond::field("id").eq(id).and(condition)),
+            Err(e) => QueryBuilder { error: Some(e), ..self.orm.find_one(id) },
+        }
+    }
+
+    /// Finds the records in the scope that match the provided WHERE clause, a raw SQL fragment or a `Cond`.
+    pub fn find_many<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'a, Vec<T>, T, O>
+        where T: for<'de> Deserialize<'de> + TableDeserialize + Debug + 'static
+    {
+        if !self.is_scoped::<T>() {
+            return self.orm.find_many(query_where);
+        }
+        match self.scope.cond() {
+            Ok(condition) => self.orm.find_many(query_where.into().and(condition)),
+            Err(e) => QueryBuilder { error: Some(e), ..self.orm.find_many(query_where) },
+        }
+    }
+
+    /// Finds all records in the scope.
+    pub fn find_all<T>(&self) -> QueryBuilder<'a, Vec<T>, T, O>
+        where T: for<'de> Deserialize<'de> + TableDeserialize + Debug + 'static
+    {
+        if !self.is_scoped::<T>() {
+            return self.orm.find_all();
+        }
+        match self.scope.cond() {
+            Ok(condition) => self.orm.find_many(condition),
+            Err(e) => QueryBuilder { error: Some(e), ..self.orm.find_all() },
+        }
+    }
+
+    /// Updates the record with the ID of `data`, if it is in the scope.
+    pub fn modify<T>(&self, data: T) -> QueryBuilder<'a, usize, (), O>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        let qb = self.orm.modify(data);
+        self.restrict::<T, _, _>(qb)
+    }
+
+    /// Removes the record with the ID of `data`, if it is in the scope.
+    pub fn remove<T>(&self, data: T) -> QueryBuilder<'a, usize, (), O>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        let qb = self.orm.remove(data);
+        self.restrict::<T, _, _>(qb)
+    }
+
+    /// Removes the record with the given ID, if it is in the scope.
+    pub fn remove_by_id<T>(&self, id: u64) -> QueryBuilder<'a, usize, (), O>
+        where T: TableDeserialize + 'static
+    {
+        let qb = self.orm.remove_by_id::<T>(id);
+        self.restrict::<T, _, _>(qb)
+    }
+
+    // Appends the scope condition to a query that ends with its WHERE clause, if `T` is registered.
+    fn restrict<T: 'static, R, E>(&self, mut qb: QueryBuilder<'a, R, E, O>) -> QueryBuilder<'a, R, E, O> {
+        if self.is_scoped::<T>() {
+            match self.scope.cond() {
+                Ok(condition) => {
+                    qb.query = format!("{} and {}", qb.query, condition.sql());
+                    qb.params.extend_from_slice(condition.params());
+                }
+                Err(e) => qb.error = Some(e),
+            }
+        }
+        qb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cond, Value};
+    use super::col;
+
+    #[test]
+    fn test_scope_cond() {
+        assert_eq!(col("org_id").eq(42).cond().unwrap(), Cond::field("org_id").eq(42));
+        let condition = col("t.org").eq("acme\" or 1 = 1 -- ").cond().unwrap();
+        assert_eq!(condition.sql(), "t.org = ?");
+        assert_eq!(condition.params(), &[Value::Text("acme\" or 1 = 1 -- ".to_string())]);
+        assert_eq!(col("org_id").eq(None::<i64>).cond().unwrap().sql(), "org_id is null");
+        assert!(col("org_id = 1 or 1").eq(1).cond().is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! `secret` is a module for the database password of `ConnectOptions`, which is given apart from the URL,
+//! so that the URL can be logged and kept in configuration files without the credentials.
+
+use std::fmt::{self, Debug};
+use std::path::PathBuf;
+use std::sync::Arc;
+use crate::ORMError;
+
+/// `SecretProvider` is a trait for sources of the database password, e.g. a secret manager client,
+/// registered with `ConnectOptions::password_provider`. `secret` is called on every connect.
+pub trait SecretProvider: Send + Sync {
+    /// Returns the password.
+    fn secret(&self) -> Result<String, ORMError>;
+}
+
+impl Debug for dyn SecretProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretProvider")
+    }
+}
+
+/// `Secret` is the source of the database password. Its `Debug` output never contains the password.
+#[derive(Clone)]
+pub enum Secret {
+    /// The password itself.
+    Value(String),
+
+    /// The environment variable that contains the password.
+    Env(String),
+
+    /// The file that contains the password, e.g. a Docker or Kubernetes secret. A trailing line break is removed.
+    File(PathBuf),
+
+    /// A provider of the password.
+    Provider(Arc<dyn SecretProvider>),
+}
+
+impl Secret {
+    /// Returns the password.
+    pub fn resolve(&self) -> Result<String, ORMError> {
+        match self {
+            Secret::Value(password) => Ok(password.clone()),
+            Secret::Env(name) => std::env::var(name)
+                .map_err(|e| ORMError::Config(format!("password variable {}: {}", name, e))),
+            Secret::File(path) => std::fs::read_to_string(path)
+                .map(|password| password.trim_end_matches(['\r', '\n']).to_string())
+                .map_err(|e| ORMError::Config(format!("password file {}: {}", path.display(), e))),
+            Secret::Provider(provider) => provider.secret(),
+        }
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Secret::Value(_) => f.write_str("Value(***)"),
+            Secret::Env(name) => f.debug_tuple("Env").field(name).finish(),
+            Secret::File(path) => f.debug_tuple("File").field(path).finish(),
+            Secret::Provider(provider) => f.debug_tuple("Provider").field(provider).finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::ORMError;
+    use super::{Secret, SecretProvider};
+
+    struct Vault;
+
+    impl SecretProvider for Vault {
+        fn secret(&self) -> Result<String, ORMError> {
+            Ok("from-vault".to_string())
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let secret = Secret::Value("hunter2".to_string());
+        assert_eq!(secret.resolve().unwrap(), "hunter2");
+        assert_eq!(format!("{:?}", secret), "Value(***)");
+
+        std::env::set_var("PARVATI_TEST_PASSWORD", "from-env");
+        assert_eq!(Secret::Env("PARVATI_TEST_PASSWORD".to_string()).resolve().unwrap(), "from-env");
+        assert!(matches!(Secret::Env("PARVATI_TEST_MISSING".to_string()).resolve(), Err(ORMError::Config(_))));
+
+        let path = std::env::temp_dir().join("parvati_test_password");
+        std::fs::write(&path, "from-file\n").unwrap();
+        assert_eq!(Secret::File(path.clone()).resolve().unwrap(), "from-file");
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(Secret::File(path).resolve(), Err(ORMError::Config(_))));
+
+        let secret = Secret::Provider(Arc::new(Vault));
+        assert_eq!(secret.resolve().unwrap(), "from-vault");
+        assert_eq!(format!("{:?}", secret), "Provider(SecretProvider)");
+    }
+}
@@ -31,6 +31,8 @@ pub enum Error {
     Syntax,
     ExpectedBoolean,
     ExpectedInteger,
+    ExpectedFloat,
+    NonFiniteFloat(f64),
     ExpectedString,
     ExpectedNull,
     ExpectedArray,
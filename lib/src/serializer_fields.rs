@@ -0,0 +1,245 @@
+//! `serializer_fields` is a module that serializes an entity into its field names and `Value`s,
+//! so that single fields can be read without knowing the type of the entity.
+
+use serde::ser::{self, Impossible, Serialize};
+use crate::serializer_error::{Error, Result};
+use crate::{datetime, ColumnDef, DateTimePolicy, FloatPolicy, Value};
+
+/// Serializes a struct into a list of `(field name, value)` pairs in declaration order.
+/// Nested structs, sequences and maps are not supported.
+pub fn to_values<T>(value: &T) -> Result<Vec<(String, Value)>>
+    where
+        T: ?Sized + Serialize,
+{
+    value.serialize(StructSerializer)
+}
+
+/// Returns the value of the field `name` of a struct, or `None` if there is no such field.
+pub fn field_value<T>(value: &T, name: &str) -> Result<Option<Value>>
+    where
+        T: ?Sized + Serialize,
+{
+    Ok(to_values(value)?.into_iter().find(|(field, _)| field == name).map(|(_, value)| value))
+}
+
+/// Serializes a struct into the `(field name, value)` pairs bound as the params of a statement, without the ID.
+/// The strings of the datetime columns among `columns` are converted according to `datetime_policy`
+/// and NaN and infinite floats according to `float_policy`.
+pub fn to_params<T>(value: &T, columns: &[ColumnDef], datetime_policy: DateTimePolicy, float_policy: FloatPolicy) -> Result<Vec<(String, Value)>>
+    where
+        T: ?Sized + Serialize,
+{
+    Ok(to_params_with_id(value, columns, datetime_policy, float_policy)?.into_iter().filter(|(field, _)| field != "id").collect())
+}
+
+/// Like `to_params`, but includes the ID, e.g. for upserts that match existing rows by their ID.
+pub fn to_params_with_id<T>(value: &T, columns: &[ColumnDef], datetime_policy: DateTimePolicy, float_policy: FloatPolicy) -> Result<Vec<(String, Value)>>
+    where
+        T: ?Sized + Serialize,
+{
+    to_values(value)?.into_iter()
+        .map(|(field, value)| {
+            let value = match value {
+                Value::Text(v) => {
+                    let column = columns.iter().find(|column| column.name == field);
+                    Value::Text(datetime::to_storage(&v, column, datetime_policy))
+                }
+                Value::Float(v) if !v.is_finite() => match float_policy {
+                    FloatPolicy::Error => return Err(Error::NonFiniteFloat(v)),
+                    FloatPolicy::Null => Value::Null,
+                },
+                value => value,
+            };
+            Ok((field, value))
+        })
+        .collect()
+}
+
+fn unsupported<T>(what: &str) -> Result<T> {
+    Err(Error::Message(format!("{} is not supported", what)))
+}
+
+struct StructSerializer;
+
+pub struct FieldsSerializer {
+    fields: Vec<(String, Value)>,
+}
+
+impl ser::SerializeStruct for FieldsSerializer {
+    type Ok = Vec<(String, Value)>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+        where
+            T: ?Sized + Serialize,
+    {
+        self.fields.push((key.to_string(), value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Vec<(String, Value)>> {
+        Ok(self.fields)
+    }
+}
+
+impl ser::Serializer for StructSerializer {
+    type Ok = Vec<(String, Value)>;
+    type Error = Error;
+    type SerializeSeq = Impossible<Self::Ok, Error>;
+    type SerializeTuple = Impossible<Self::Ok, Error>;
+    type SerializeTupleStruct = Impossible<Self::Ok, Error>;
+    type SerializeTupleVariant = Impossible<Self::Ok, Error>;
+    type SerializeMap = Impossible<Self::Ok, Error>;
+    type SerializeStruct = FieldsSerializer;
+    type SerializeStructVariant = Impossible<Self::Ok, Error>;
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<FieldsSerializer> {
+        Ok(FieldsSerializer { fields: Vec::with_capacity(len) })
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<Self::Ok> { unsupported("bool entity") }
+    fn serialize_i8(self, _v: i8) -> Result<Self::Ok> { unsupported("i8 entity") }
+    fn serialize_i16(self, _v: i16) -> Result<Self::Ok> { unsupported("i16 entity") }
+    fn serialize_i32(self, _v: i32) -> Result<Self::Ok> { unsupported("i32 entity") }
+    fn serialize_i64(self, _v: i64) -> Result<Self::Ok> { unsupported("i64 entity") }
+    fn serialize_u8(self, _v: u8) -> Result<Self::Ok> { unsupported("u8 entity") }
+    fn serialize_u16(self, _v: u16) -> Result<Self::Ok> { unsupported("u16 entity") }
+    fn serialize_u32(self, _v: u32) -> Result<Self::Ok> { unsupported("u32 entity") }
+    fn serialize_u64(self, _v: u64) -> Result<Self::Ok> { unsupported("u64 entity") }
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok> { unsupported("f32 entity") }
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok> { unsupported("f64 entity") }
+    fn serialize_char(self, _v: char) -> Result<Self::Ok> { unsupported("char entity") }
+    fn serialize_str(self, _v: &str) -> Result<Self::Ok> { unsupported("str entity") }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> { unsupported("bytes entity") }
+    fn serialize_none(self) -> Result<Self::Ok> { unsupported("None entity") }
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok> where T: ?Sized + Serialize { value.serialize(self) }
+    fn serialize_unit(self) -> Result<Self::Ok> { unsupported("unit entity") }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> { Ok(Vec::new()) }
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, _variant: &'static str) -> Result<Self::Ok> { unsupported("enum entity") }
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok> where T: ?Sized + Serialize { value.serialize(self) }
+    fn serialize_newtype_variant<T>(self, _name: &'static str, _index: u32, _variant: &'static str, _value: &T) -> Result<Self::Ok> where T: ?Sized + Serialize { unsupported("enum entity") }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> { unsupported("sequence entity") }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> { unsupported("tuple entity") }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct> { unsupported("tuple struct entity") }
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant> { unsupported("enum entity") }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> { unsupported("map entity") }
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant> { unsupported("enum entity") }
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = Impossible<Value, Error>;
+    type SerializeTuple = Impossible<Value, Error>;
+    type SerializeTupleStruct = Impossible<Value, Error>;
+    type SerializeTupleVariant = Impossible<Value, Error>;
+    type SerializeMap = Impossible<Value, Error>;
+    type SerializeStruct = Impossible<Value, Error>;
+    type SerializeStructVariant = Impossible<Value, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> { Ok(Value::Int(v as i64)) }
+    fn serialize_i8(self, v: i8) -> Result<Value> { Ok(Value::Int(v as i64)) }
+    fn serialize_i16(self, v: i16) -> Result<Value> { Ok(Value::Int(v as i64)) }
+    fn serialize_i32(self, v: i32) -> Result<Value> { Ok(Value::Int(v as i64)) }
+    fn serialize_i64(self, v: i64) -> Result<Value> { Ok(Value::Int(v)) }
+    fn serialize_i128(self, v: i128) -> Result<Value> {
+        Ok(i64::try_from(v).map(Value::Int).unwrap_or_else(|_| Value::Text(v.to_string())))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Value> { Ok(Value::Int(v as i64)) }
+    fn serialize_u16(self, v: u16) -> Result<Value> { Ok(Value::Int(v as i64)) }
+    fn serialize_u32(self, v: u32) -> Result<Value> { Ok(Value::Int(v as i64)) }
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        Ok(i64::try_from(v).map(Value::Int).unwrap_or_else(|_| Value::Text(v.to_string())))
+    }
+    fn serialize_u128(self, v: u128) -> Result<Value> {
+        Ok(i64::try_from(v).map(Value::Int).unwrap_or_else(|_| Value::Text(v.to_string())))
+    }
+    fn serialize_f32(self, v: f32) -> Result<Value> { Ok(Value::Float(v as f64)) }
+    fn serialize_f64(self, v: f64) -> Result<Value> { Ok(Value::Float(v)) }
+    fn serialize_char(self, v: char) -> Result<Value> { Ok(Value::Text(v.to_string())) }
+    fn serialize_str(self, v: &str) -> Result<Value> { Ok(Value::Text(v.to_string())) }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> { Ok(Value::Bytes(v.to_vec())) }
+    fn serialize_none(self) -> Result<Value> { Ok(Value::Null) }
+    fn serialize_some<T>(self, value: &T) -> Result<Value> where T: ?Sized + Serialize { value.serialize(self) }
+    fn serialize_unit(self) -> Result<Value> { Ok(Value::Null) }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> { Ok(Value::Null) }
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Value> { Ok(Value::Text(variant.to_string())) }
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Value> where T: ?Sized + Serialize { value.serialize(self) }
+    fn serialize_newtype_variant<T>(self, _name: &'static str, _index: u32, _variant: &'static str, _value: &T) -> Result<Value> where T: ?Sized + Serialize { unsupported("enum field with data") }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> { unsupported("sequence field") }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> { unsupported("tuple field") }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct> { unsupported("tuple struct field") }
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant> { unsupported("enum field with data") }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> { unsupported("map field") }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> { unsupported("struct field") }
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant> { unsupported("enum field with data") }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::Serialize;
+    use crate::{ColumnDef, ColumnType, DateTimePolicy, FloatPolicy, Value};
+    use super::{field_value, to_params, to_params_with_id, to_values};
+
+    #[test]
+    fn test_to_values() {
+        #[derive(Serialize)]
+        pub struct User {
+            id: u32,
+            name: Option<String>,
+            age: Option<i32>,
+            score: f64,
+        }
+
+        let user = User { id: 1, name: Some("test".to_string()), age: None, score: 1.5 };
+        assert_eq!(to_values(&user).unwrap(), vec![
+            ("id".to_string(), Value::Int(1)),
+            ("name".to_string(), Value::Text("test".to_string())),
+            ("age".to_string(), Value::Null),
+            ("score".to_string(), Value::Float(1.5)),
+        ]);
+        assert_eq!(field_value(&user, "name").unwrap(), Some(Value::Text("test".to_string())));
+        assert_eq!(field_value(&user, "missing").unwrap(), None);
+        assert!(to_values(&1).is_err());
+    }
+
+    #[test]
+    fn test_to_params() {
+        #[derive(Serialize)]
+        pub struct Point {
+            id: u32,
+            x: f64,
+            y: f32,
+        }
+        let point = Point { id: 1, x: f64::NAN, y: f32::INFINITY };
+        assert!(to_params(&point, &[], DateTimePolicy::AsIs, FloatPolicy::Error).is_err());
+        assert_eq!(to_params(&point, &[], DateTimePolicy::AsIs, FloatPolicy::Null).unwrap(), vec![
+            ("x".to_string(), Value::Null),
+            ("y".to_string(), Value::Null),
+        ]);
+        let point = Point { id: 1, x: -1.5, y: 2.0 };
+        assert_eq!(to_params_with_id(&point, &[], DateTimePolicy::AsIs, FloatPolicy::Error).unwrap(), vec![
+            ("id".to_string(), Value::Int(1)),
+            ("x".to_string(), Value::Float(-1.5)),
+            ("y".to_string(), Value::Float(2.0)),
+        ]);
+    }
+
+    #[test]
+    fn test_datetime_params() {
+        #[derive(Serialize)]
+        pub struct Event {
+            id: u32,
+            created: String,
+            note: String,
+        }
+        let event = Event { id: 1, created: "2023-09-08T15:33:22+02:00".to_string(), note: "2023-09-08T15:33:22+02:00".to_string() };
+        let columns = [ColumnDef::new("created", ColumnType::DateTime), ColumnDef::new("note", ColumnType::Text)];
+        assert_eq!(to_params(&event, &columns, DateTimePolicy::Utc, FloatPolicy::Error).unwrap(), vec![
+            ("created".to_string(), Value::Text("2023-09-08 13:33:22".to_string())),
+            ("note".to_string(), Value::Text("2023-09-08T15:33:22+02:00".to_string())),
+        ]);
+    }
+}
@@ -1,17 +1,71 @@
 //! `sqlite` is a module that contains the `ORM` struct that represents an Object-Relational Mapping (ORM) for a SQLite database.
+//!
+//! rusqlite is synchronous, so the statements are executed on the blocking thread pool of Tokio and the tasks of
+//! the executor keep running while SQLite works.
 
 use std::fmt::Debug;
-use std::sync::Arc;
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::HashMap;
 use async_trait::async_trait;
-use futures::lock::Mutex;
-use rusqlite::Connection;
+use futures::lock::{Mutex, MutexGuard, OwnedMutexGuard};
+use futures::{SinkExt, StreamExt};
+use futures::stream::LocalBoxStream;
+use rusqlite::{params_from_iter, Connection, DatabaseName, ToSql};
+use rusqlite::types::{ToSqlOutput, ValueRef};
 use serde::{Deserialize, Serialize};
-use crate::{deserializer_key_values, ORMError, ORMTrait, QueryBuilder, Row, serializer_error, serializer_key_values, serializer_types, serializer_values, TableDeserialize, TableSerialize};
+use crate::cassette::statement_key;
+use crate::dynamic;
+use crate::queue::{self, Job};
+use crate::schema::{self, Dialect};
+use crate::health::{self, HealthReport};
+use crate::migrator::{self, Migration, MigrationPlan};
+use crate::n_plus_one::NPlusOneDetector;
+use crate::write_hooks::WriteHooks;
+use crate::entity_cache::{self, CachedLookup};
+use crate::stats::{self, IndexUsage, QueryStats, QueryStatsCollector, RowCount, TableSize};
+use crate::{AddMany, AddOrUpdate, AlterTable, Batch, BlobReader, Column, Cond, ConnectOptions, counter_statements, datetime, DbManager, DecodeError, DdlStatement, deserializer_key_values, DynamicEntity, end_transaction_statements, exec_all_statements, FromRow, id_param, ImportAll, in_transaction, ImportReport, InsertMeta, insert_rows_statement, insert_statement, is_table_name, is_unset_id, IsolationLevel, MergeAction, MergeResult, MergeStrategy, Order, ORMError, ORMTrait, Page, Persisted, Policy, PoolMetrics, PoolStatus, plan_merge, QueryBuilder, Queue, Recorded, ResultSet, Row, row_stream, savepoint_statement, Scope, Scoped, serializer_error, spawn_pool_status_logger, Statement, TableDeserialize, TableSerialize, TableWrite, ToSqlLiteral, transaction_owner, TransactionOwner, tree_statement, update_fields_statement, update_statement, UpsertAll, Value};
+
+/// The LIMIT SQLite uses for an OFFSET without a LIMIT.
+const UNLIMITED: &str = "-1";
+
+/// The interval at which a low priority write checks whether the connection is free.
+const LOW_PRIORITY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(1);
+
+/// The table of the advisory locks of `ORM::with_advisory_lock`.
+const ADVISORY_LOCK_TABLE: &str = "parvati_lock";
+
+/// `Shared` is a connection that is used by the statements on the blocking thread pool, see `ORM::blocking`.
+type Shared = Arc<std::sync::Mutex<Connection>>;
+
+/// `Executed` is the result and the duration of a statement that was executed on the blocking thread pool.
+type Executed = (rusqlite::Result<usize>, Duration);
+
+/// `Transaction` is the transaction begun with `begin`: the task that owns it, its nesting depth and the gate
+/// that keeps the statements of other tasks waiting until it ends, see `transaction_owner`.
+#[derive(Debug)]
+struct Transaction {
+    owner: TransactionOwner,
+    depth: usize,
+    _gate: OwnedMutexGuard<()>,
+}
 
 #[derive(Debug)]
 pub struct ORM {
-    conn: Mutex<Option<Connection>>,
+    conn: Mutex<Option<Shared>>,
+    readers: Vec<Mutex<Option<Shared>>>,
+    next_reader: AtomicUsize,
+    metrics: PoolMetrics,
+    waiting: AtomicUsize,
+    query_stats: QueryStatsCollector,
+    n_plus_one: NPlusOneDetector,
+    write_hooks: WriteHooks,
+    policy: std::sync::RwLock<Policy>,
+    transaction: std::sync::Mutex<Option<Transaction>>,
+    transaction_gate: Arc<Mutex<()>>,
     change_count: Mutex<u32>,
+    options: ConnectOptions,
 }
 
 impl ORM {
@@ -19,145 +73,1134 @@ impl ORM {
     pub fn connect(url: String) -> Result<Arc<ORM>, ORMError>
         where Arc<ORM>: Send + Sync + 'static
     {
-        let conn = Connection::open(url)?;
-        Ok(Arc::new(ORM {
-            conn: Mutex::new(Some(conn)),
+        ORM::connect_with(ConnectOptions::new(url.as_str()))
+    }
+
+    /// Opens the database described by `options` and executes its `on_connect` statements.
+    /// If `options` replay a cassette, no database is opened.
+    pub fn connect_with(options: ConnectOptions) -> Result<Arc<ORM>, ORMError>
+        where Arc<ORM>: Send + Sync + 'static
+    {
+        let (conn, readers) = if options.replaying().is_some() {
+            (None, Vec::new())
+        } else {
+            let conn = ORM::open(&options)?;
+            let mut readers = Vec::new();
+            if options.readers > 0 && ORM::is_in_memory(&options.url) {
+                log::warn!("readers are ignored for the in-memory database {:?}", options.url);
+            } else if options.readers > 0 {
+                let journal_mode: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
+                if !journal_mode.eq_ignore_ascii_case("wal") {
+                    log::warn!("the database {:?} uses journal mode {}, in which readers block the writer", options.url, journal_mode);
+                }
+                for _ in 0..options.readers {
+                    let reader = ORM::open(&options)?;
+                    reader.execute_batch("PRAGMA query_only = ON")?;
+                    readers.push(Mutex::new(Some(Arc::new(std::sync::Mutex::new(reader)))));
+                }
+            }
+            (Some(Arc::new(std::sync::Mutex::new(conn))), readers)
+        };
+        let orm = Arc::new(ORM {
+            conn: Mutex::new(conn),
+            readers,
+            next_reader: AtomicUsize::new(0),
+            metrics: PoolMetrics::default(),
+            waiting: AtomicUsize::new(0),
+            query_stats: QueryStatsCollector::default(),
+            n_plus_one: NPlusOneDetector::new(options.n_plus_one_threshold),
+            write_hooks: WriteHooks::default(),
+            policy: Default::default(),
+            transaction: std::sync::Mutex::new(None),
+            transaction_gate: Arc::new(Mutex::new(())),
             change_count: 0.into(),
+            options,
+        });
+        if let Some(cache) = orm.options.entity_cache.clone() {
+            orm.write_hooks.add_all_tables(Arc::new(move |write| cache.invalidate(&write.table.to_lowercase())));
+        }
+        if let Some(interval) = orm.options.pool_status_interval {
+            spawn_pool_status_logger(&orm, interval);
+        }
+        Ok(orm)
+    }
+
+    // Opens a connection to the database of `options` and executes the `on_connect` statements.
+    fn open(options: &ConnectOptions) -> Result<Connection, ORMError> {
+        let conn = Connection::open(options.url.as_str())?;
+        for statement in options.on_connect.iter() {
+            log::debug!("{:?}", statement);
+            conn.execute_batch(statement.as_str())?;
+        }
+        Ok(conn)
+    }
+
+    // Returns `true` if `url` opens an in-memory database, which is private to its connection.
+    fn is_in_memory(url: &str) -> bool {
+        url.is_empty() || url == ":memory:" || url.contains("mode=memory")
+    }
+
+    /// Creates the database file `path` and its missing parent directories, if it does not exist,
+    /// e.g. in the setup of integration tests.
+    pub fn create_database(path: &str) -> Result<(), ORMError> {
+        if let Some(parent) = std::path::Path::new(path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        Connection::open(path)?.close().map_err(|(_, e)| e)?;
+        Ok(())
+    }
+
+    /// Removes the database file `path` with its journal and WAL files, if they exist.
+    /// The database must not be open.
+    pub fn drop_database(path: &str) -> Result<(), ORMError> {
+        for suffix in ["", "-journal", "-wal", "-shm"] {
+            match std::fs::remove_file(format!("{}{}", path, suffix)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens the database configured by the `DATABASE_*` environment variables, see `ConnectOptions::from_env`.
+    pub fn from_env() -> Result<Arc<ORM>, ORMError>
+        where Arc<ORM>: Send + Sync + 'static
+    {
+        ORM::connect_with(ConnectOptions::from_env()?)
+    }
+
+    /// Opens the database configured in the `[database]` section of the TOML file `path`, see `ConnectOptions::from_config`.
+    pub fn from_config<P: AsRef<std::path::Path>>(path: P) -> Result<Arc<ORM>, ORMError>
+        where Arc<ORM>: Send + Sync + 'static
+    {
+        ORM::connect_with(ConnectOptions::from_config(path)?)
+    }
+
+    /// Executes `f` in a transaction: `f` receives the connection, whose queries run inside the transaction,
+    /// and the transaction is committed if the returned future succeeds and rolled back if it fails.
+    /// Inside another transaction a savepoint is used, so transactions can be nested. The transaction belongs
+    /// to the current task: the statements of other tasks wait until it ends, so `f` must not wait for them.
+    ///
+    /// ```ignore
+    /// let order = conn.transaction(|tx| async move {
+    ///     let order = tx.add(order).apply().await?;
+    ///     tx.modify(stock).run().await?;
+    ///     Ok::<_, ORMError>(order)
+    /// }).await?;
+    /// ```
+    pub async fn transaction<F, Fut, T, E>(self: &Arc<Self>, f: F) -> Result<T, E>
+        where F: FnOnce(Arc<ORM>) -> Fut, Fut: std::future::Future<Output = Result<T, E>>, E: From<ORMError>
+    {
+        in_transaction(self.as_ref(), IsolationLevel::Default, f(self.clone())).await
+    }
+
+    /// Executes `f` while holding the advisory lock `name`, e.g. so that only one instance of a cron job runs
+    /// against the shared database. SQLite has no advisory locks, so the lock is a row of the `parvati_lock` table,
+    /// which is created if it does not exist; call it outside of a transaction, so that other connections see the row.
+    /// Returns `ORMError::LockNotAcquired` without calling `f` if the lock is held, also by this connection.
+    /// The lock is released when the future of `f` completes. The lock of a process that exited while holding it
+    /// stays until its row is deleted.
+    ///
+    /// ```ignore
+    /// conn.with_advisory_lock("nightly-job", |c| async move {
+    ///     c.query_update("delete from session where expires_at < strftime('%s')").exec().await?;
+    ///     Ok::<_, ORMError>(())
+    /// }).await?;
+    /// ```
+    pub async fn with_advisory_lock<F, Fut, T, E>(self: &Arc<Self>, name: &str, f: F) -> Result<T, E>
+        where F: FnOnce(Arc<ORM>) -> Fut, Fut: std::future::Future<Output = Result<T, E>>, E: From<ORMError>
+    {
+        let create = format!("create table if not exists {ADVISORY_LOCK_TABLE} \
+            (name TEXT PRIMARY KEY, owner INTEGER NOT NULL, acquired_at INTEGER NOT NULL)");
+        let _: usize = self.query_update(&create).exec().await?;
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|now| now.as_secs() as i64).unwrap_or_default();
+        let acquired = self.query_update(&format!("insert or ignore into {ADVISORY_LOCK_TABLE} (name, owner, acquired_at) values (?, ?, ?)"))
+            .bind(vec![Value::Text(name.to_string()), Value::Int(std::process::id() as i64), Value::Int(now)])
+            .exec().await?;
+        if acquired == 0 {
+            return Err(ORMError::LockNotAcquired(name.to_string()).into());
+        }
+        let result = f(self.clone()).await;
+        let released = self.query_update(&format!("delete from {ADVISORY_LOCK_TABLE} where name = ?"))
+            .bind(vec![Value::Text(name.to_string())])
+            .exec().await;
+        let value = result?;
+        released?;
+        Ok(value)
+    }
+
+    // Locks the connection. Outside of its own transaction a statement also waits until the transaction
+    // of another task ends, so that it is not executed as part of it.
+    async fn lock(&self) -> MutexGuard<'_, Option<Shared>> {
+        self.lock_connection(self.transaction_depth() == 0).await
+    }
+
+    // Locks the connection, after the transaction of another task ended if `wait_for_transaction` is set.
+    async fn lock_connection(&self, wait_for_transaction: bool) -> MutexGuard<'_, Option<Shared>> {
+        // counts the waiting statement until the connection is locked, also if the statement is cancelled
+        struct Waiting<'a>(&'a AtomicUsize);
+        impl Drop for Waiting<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::AcqRel);
+            }
+        }
+        self.waiting.fetch_add(1, Ordering::AcqRel);
+        let _waiting = Waiting(&self.waiting);
+        let start = Instant::now();
+        let _gate = if wait_for_transaction { Some(self.transaction_gate.lock().await) } else { None };
+        let conn = self.conn.lock().await;
+        self.metrics.record_wait(start.elapsed());
+        conn
+    }
+
+    // Returns the nesting depth of the transaction of the current task, or 0 if it has none.
+    // The transaction of another task does not count, see `transaction_owner`.
+    fn transaction_depth(&self) -> usize {
+        match &*self.transaction.lock().unwrap_or_else(PoisonError::into_inner) {
+            Some(transaction) if transaction.owner == transaction_owner() => transaction.depth,
+            _ => 0,
+        }
+    }
+
+    // Locks a reader for the SELECT `query`, or the connection if there are no readers, a transaction is active,
+    // whose writes the SELECT must see, or `query` is not a SELECT. An idle reader is taken if there is one,
+    // otherwise the statement waits for the readers in turn.
+    async fn lock_reader(&self, query: &str) -> MutexGuard<'_, Option<Shared>> {
+        let is_select = query.trim_start().get(..6).is_some_and(|keyword| keyword.eq_ignore_ascii_case("select"));
+        if self.readers.is_empty() || !is_select || self.transaction_depth() > 0 {
+            return self.lock().await;
+        }
+        let start = Instant::now();
+        let reader = match self.readers.iter().find_map(|reader| reader.try_lock()) {
+            Some(reader) => reader,
+            None => self.readers[self.next_reader.fetch_add(1, Ordering::Relaxed) % self.readers.len()].lock().await,
+        };
+        self.metrics.record_wait(start.elapsed());
+        reader
+    }
+
+    // Locks the connection once no other statement is waiting for it, for low priority writes.
+    async fn lock_low_priority(&self) -> MutexGuard<'_, Option<Shared>> {
+        let start = Instant::now();
+        loop {
+            if self.waiting.load(Ordering::Acquire) == 0 {
+                // the transaction of another task holds the gate until it ends
+                let gate = self.transaction_gate.try_lock();
+                if gate.is_some() || self.transaction_depth() > 0 {
+                    if let Some(conn) = self.conn.try_lock() {
+                        self.metrics.record_wait(start.elapsed());
+                        return conn;
+                    }
+                }
+            }
+            tokio::time::sleep(LOW_PRIORITY_POLL_INTERVAL).await;
+        }
+    }
+
+    // Executes `f` with the locked connection `conn` on the blocking thread pool of Tokio, so that the executor is not
+    // blocked while SQLite works. A cancelled statement still completes on its thread, and the next statement waits
+    // for it before it uses the connection.
+    async fn blocking<T, F>(conn: &Option<Shared>, f: F) -> Result<T, ORMError>
+        where F: FnOnce(&Connection) -> T + Send + 'static, T: Send + 'static
+    {
+        let conn = conn.clone().ok_or(ORMError::NoConnection)?;
+        let task = tokio::task::spawn_blocking(move || f(&conn.lock().unwrap_or_else(PoisonError::into_inner)));
+        match task.await {
+            Ok(value) => Ok(value),
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            // the runtime is shutting down
+            Err(_) => Err(ORMError::NoConnection),
+        }
+    }
+
+    // Executes the statement `query` with `params` on the locked connection `conn`, see `blocking`.
+    async fn execute(conn: &Option<Shared>, query: &str, params: &[Value]) -> Result<rusqlite::Result<usize>, ORMError> {
+        let (query, params) = (query.to_string(), params.to_vec());
+        ORM::blocking(conn, move |conn| conn.execute(query.as_str(), params_from_iter(&params))).await
+    }
+
+    // Closes `conn`. A connection that is still used by a cancelled statement is closed when the statement completes.
+    fn close_shared(conn: Shared) -> Result<(), ORMError> {
+        match Arc::try_unwrap(conn) {
+            Ok(conn) => conn.into_inner().unwrap_or_else(PoisonError::into_inner).close().map_err(|e| ORMError::RusqliteError(e.1)),
+            Err(_) => Ok(()),
+        }
+    }
+
+    // Executes the statements of `exec_all` in a transaction, or in a savepoint inside `begin`, until one fails.
+    // Returns the result and the duration of each executed statement, and the result of the end of the transaction.
+    fn execute_all(conn: &Connection, statements: &[Statement], in_transaction: bool)
+        -> (Vec<Executed>, rusqlite::Result<()>)
+    {
+        let (begin, commit, rollback) = if in_transaction {
+            // inside `begin` the queries are wrapped into a savepoint instead of a new transaction
+            ("SAVEPOINT parvati_exec_all", "RELEASE SAVEPOINT parvati_exec_all",
+                "ROLLBACK TO SAVEPOINT parvati_exec_all; RELEASE SAVEPOINT parvati_exec_all")
+        } else {
+            ("BEGIN DEFERRED", "COMMIT", "ROLLBACK")
+        };
+        let mut executed = Vec::new();
+        if let Err(e) = conn.execute_batch(begin) {
+            return (executed, Err(e));
+        }
+        for (query, params) in statements {
+            log::debug!("{:?} {:?}", query, params);
+            let start = Instant::now();
+            let r = conn.execute(query.as_str(), params_from_iter(params));
+            let failed = r.is_err();
+            executed.push((r, start.elapsed()));
+            if failed {
+                return (executed, conn.execute_batch(rollback));
+            }
+        }
+        let end = conn.execute_batch(commit);
+        if end.is_err() {
+            let _ = conn.execute_batch(rollback);
+        }
+        (executed, end)
+    }
+
+    async fn end_transaction(&self, commit: bool) -> Result<(), ORMError> {
+        // the transaction of another task cannot be ended
+        let depth = self.transaction_depth();
+        if depth == 0 {
+            return Err(ORMError::TransactionError("no active transaction".to_string()));
+        }
+        let conn = self.lock_connection(false).await;
+        if conn.is_none() && self.options.replaying().is_none() {
+            return Err(ORMError::NoConnection);
+        }
+        {
+            // the statements of other tasks still wait for the lock of the connection until the transaction ended
+            let mut transaction = self.transaction.lock().unwrap_or_else(PoisonError::into_inner);
+            match transaction.as_mut() {
+                Some(transaction) if depth > 1 => transaction.depth -= 1,
+                _ => *transaction = None,
+            }
+        }
+        if depth == 1 {
+            self.n_plus_one.reset_task();
+        }
+        if self.options.replaying().is_some() {
+            return Ok(());
+        }
+        for statement in end_transaction_statements(depth, commit) {
+            log::debug!("{:?}", statement);
+            ORM::blocking(&conn, move |conn| conn.execute_batch(statement.as_str())).await??;
+        }
+        if depth == 1 {
+            self.write_hooks.end_transaction(commit);
+        }
+        Ok(())
+    }
+
+    // Executes an INSERT and returns the generated row ID and the number of inserted rows.
+    pub(crate) async fn insert(&self, query: &str, params: &[Value]) -> Result<(u64, usize), ORMError> {
+        log::debug!("{:?} {:?}", query, params);
+        self.check_policy(query)?;
+        let key = statement_key(query, params, Dialect::Sqlite);
+        if let Some(cassette) = self.options.replaying() {
+            let (affected_rows, r) = cassette.replay_affected(&key)?;
+            return Ok((r.ok_or(ORMError::InsertError)?, affected_rows as usize));
+        }
+        let conn = self.lock().await;
+        let start = Instant::now();
+        let (statement, values) = (query.to_string(), params.to_vec());
+        let affected_rows = ORM::blocking(&conn, move |conn| {
+            conn.execute(statement.as_str(), params_from_iter(&values)).map(|r| (conn.last_insert_rowid(), r))
+        }).await?;
+        self.log_statement(query, start, &affected_rows, |(_, r)| *r);
+        self.options.record(&key, &affected_rows, |(id, r)| {
+            Recorded::Affected { rows: *r as u64, last_insert_id: Some(*id as u64) }
+        });
+        let (r, affected_rows) = affected_rows?;
+        Ok((r as u64, affected_rows))
+    }
+
+    // Reports a statement to the statement logger and counts it in `query_stats`.
+    fn log_statement<T, E: Debug>(&self, statement: &str, start: Instant, result: &Result<T, E>, rows: impl FnOnce(&T) -> usize) {
+        self.query_stats.record(statement, start.elapsed(), result.is_err());
+        self.n_plus_one.record(statement);
+        if result.is_ok() {
+            self.write_hooks.record(statement, self.transaction_depth() > 0);
+        }
+        self.options.log_statement(statement, start, result, rows);
+    }
+
+    // Returns `ORMError::PolicyViolation` if the policy set with `set_policy` denies `statement`.
+    fn check_policy(&self, statement: &str) -> Result<(), ORMError> {
+        self.policy.read().unwrap().check(statement, self.transaction_depth() > 0)
+    }
+
+    // Executes the select `query` with `params` bound to its placeholders and returns every row as a `HashMap`, see `query_map`.
+    async fn read_values(&self, query: &str, params: &[Value]) -> Result<Vec<HashMap<String, Value>>, ORMError> {
+        log::debug!("{:?} {:?}", query, params);
+        self.check_policy(query)?;
+        let key = statement_key(query, params, Dialect::Sqlite);
+        if let Some(cassette) = self.options.replaying() {
+            return cassette.replay_values(&key);
+        }
+        let conn = self.lock_reader(query).await;
+        let start = Instant::now();
+        let (statement, values) = (query.to_string(), params.to_vec());
+        let result = ORM::blocking(&conn, move |conn| ORM::read_map(conn, statement.as_str(), &values)).await?;
+        self.log_statement(query, start, &result, |rows| rows.len());
+        self.options.record(&key, &result, |rows| Recorded::values(rows));
+        Ok(result?)
+    }
+
+    // Opens the value of `column` for incremental reads. The ID of an entity is the rowid of its table.
+    fn open_blob<'c>(conn: &'c Connection, table_name: &str, column: &str, id: u64) -> rusqlite::Result<rusqlite::blob::Blob<'c>> {
+        let (database, table) = match table_name.split_once('.') {
+            Some((schema, table)) => (DatabaseName::Attached(schema), table),
+            None => (DatabaseName::Main, table_name),
+        };
+        conn.blob_open(database, table, column, id as i64, true)
+    }
+
+    fn read_map(conn: &Connection, query: &str, params: &[Value]) -> rusqlite::Result<Vec<HashMap<String, Value>>> {
+        let mut stmt = conn.prepare(query)?;
+        let names: Vec<String> = stmt.column_names().iter().map(|name| name.to_string()).collect();
+        let rows = stmt.query_map(params_from_iter(params), |row| {
+            let mut map: HashMap<String, Value> = HashMap::new();
+            for (i, name) in names.iter().enumerate() {
+                let value = match row.get_ref(i)? {
+                    ValueRef::Null => Value::Null,
+                    ValueRef::Integer(v) => Value::Int(v),
+                    ValueRef::Real(v) => Value::Float(v),
+                    ValueRef::Text(v) => Value::Text(String::from_utf8_lossy(v).to_string()),
+                    ValueRef::Blob(v) => Value::Bytes(v.to_vec()),
+                };
+                map.insert(name.clone(), value);
+            }
+            Ok(map)
+        })?;
+        let mut result: Vec<HashMap<String, Value>> = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
+    fn read_result_set(conn: &Connection, query: &str, params: &[Value], strict: bool) -> Result<ResultSet, ORMError>
+    {
+        let stmt_result = conn.prepare(query);
+        if stmt_result.is_err() {
+            let e = stmt_result.err().unwrap();
+            log::error!("{:?}", e);
+            return Err(ORMError::RusqliteError(e));
+        }
+        let mut stmt = stmt_result.unwrap();
+        let columns: Vec<Column> = stmt.columns().iter().map(|column| {
+            Column {
+                name: column.name().to_string(),
+                declared_type: column.decl_type().map(|t| t.to_string()),
+                nullable: None,
+            }
+        }).collect();
+        let names = Arc::new(columns.iter().map(|column| column.name.clone()).collect());
+        let mut result: Vec<Row> = Vec::new();
+        let mut decode_error: Option<DecodeError> = None;
+        let person_iter = stmt.query_map(params_from_iter(params), |row| {
+            let r = ORM::read_row(row, &names, strict, &mut decode_error)?;
+            result.push(r);
+            Ok(())
+        })?;
+        for _x in person_iter {
+        }
+        // log::debug!("{:?}", result);
+        if let Some(e) = decode_error {
+            log::error!("{}", e);
+            return Err(ORMError::DecodeError(e));
+        }
+
+        Ok(ResultSet { columns, rows: result })
+    }
+
+    fn read_row(row: &rusqlite::Row, names: &Arc<Vec<String>>, strict: bool, decode_error: &mut Option<DecodeError>) -> rusqlite::Result<Row> {
+        let mut i = 0;
+        let mut r: Row = Row::with_names(names.clone());
+        loop {
+            let res: rusqlite::Result<i64>= row.get(i);
+
+            match  res{
+                Ok(v) => {
+                    r.set(i.try_into().unwrap(), Some(v));
+
+                },
+                Err(e) => {
+                    if e ==  rusqlite::Error::InvalidColumnIndex(i) {
+                        break;
+                    }
+                }
+            }
+
+            let res: rusqlite::Result<String>= row.get(i);
+            match  res{
+
+                Ok(v) => {
+                    r.set(i.try_into().unwrap(), Some(v));
+                }
+                Err(_e) => {
+                }
+            }
+
+            if let Ok(ValueRef::Real(v)) = row.get_ref(i) {
+                r.set(i as i32, Some(v));
+            }
+
+            if strict && decode_error.is_none() && !r.columns.contains_key(&(i as i32)) {
+                let value = row.get_ref(i)?;
+                if value == ValueRef::Null {
+                    r.set(i as i32, None::<String>);
+                } else {
+                    *decode_error = Some(DecodeError::InvalidValue {
+                        index: i as i32,
+                        value: format!("{:?}", value),
+                        type_name: "i64, f64 or String",
+                    });
+                }
+            }
+
+            i = i + 1;
+        }
+        Ok(r)
+    }
+}
+
+impl DbManager<ORM> {
+    /// Opens the databases configured in the `[databases.<name>]` sections of the TOML file `path`,
+    /// see `ConnectOptions::databases_from_config`.
+    pub fn from_config<P: AsRef<std::path::Path>>(path: P) -> Result<DbManager<ORM>, ORMError> {
+        let mut manager = DbManager::new();
+        for (name, options) in ConnectOptions::databases_from_config(path)? {
+            manager.insert(&name, ORM::connect_with(options)?);
+        }
+        Ok(manager)
+    }
+}
+
+impl ToSql for Value {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::Borrowed(match self {
+            Value::Null => ValueRef::Null,
+            Value::Int(v) => ValueRef::Integer(*v),
+            Value::Float(v) => ValueRef::Real(*v),
+            Value::Text(v) => ValueRef::Text(v.as_bytes()),
+            Value::Bytes(v) => ValueRef::Blob(v),
         }))
     }
 }
+
 #[async_trait]
 impl ORMTrait<ORM> for ORM {
 
     fn add<T>(&self, data: T) -> QueryBuilder<T, T, ORM>
         where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static
     {
-        let table_name = data.name();
-        let types = serializer_types::to_string(&data).unwrap();
-        let values = serializer_values::to_string(&data).unwrap();
-        let query: String = format!("insert into {table_name} {types} values {values}");
+        let table_name = self.options.table_name::<T>();
+        let (query, params, error) = match insert_statement(&table_name, &data, &self.options) {
+            Ok((query, params)) => (query, params, None),
+            Err(e) => (format!("insert into {table_name}"), Vec::new(), Some(e)),
+        };
         let qb = QueryBuilder::<T,T, ORM> {
-            query: query,
+            query,
+            params,
+            counters: counter_statements(&table_name, &data, &data.get_id(), true),
             entity: Default::default(),
             orm: self,
             result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: data.validation().err(),
+            table: Some(table_name),
         };
         qb
     }
 
-    async fn last_insert_rowid(&self)  -> Result<i64, ORMError>{
-        let conn = self.conn.lock().await;
-        if conn.is_none() {
-            return Err(ORMError::NoConnection);
+    fn add_or_update<T>(&self, data: T) -> AddOrUpdate<'_, T, ORM>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static
+    {
+        AddOrUpdate::new(self, data)
+    }
+
+    fn add_many<T>(&self, data: Vec<T>) -> AddMany<'_, T, ORM>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + 'static
+    {
+        AddMany::new(self, data)
+    }
+
+    async fn add_all<T>(&self, data: Vec<T>) -> Result<Vec<u64>, ORMError>
+        where T: TableDeserialize + TableSerialize + Serialize + Send + 'static
+    {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        for d in data.iter() {
+            d.validation().map_err(ORMError::Validation)?;
         }
-        Ok(conn.as_ref().unwrap().last_insert_rowid())
+        let table_name = self.options.table_name::<T>();
+        let (query, _, params) = insert_rows_statement(&table_name, &data, false, &self.options)?;
+        let query = format!("{query} returning id");
+        log::debug!("{:?} {:?}", query, params);
+        self.check_policy(&query)?;
+        let key = statement_key(&query, &params, Dialect::Sqlite);
+        let mut ids = if let Some(cassette) = self.options.replaying() {
+            cassette.replay_values(&key)?.iter()
+                .map(|row| row.get("id").and_then(Value::as_i64).map(|id| id as u64).ok_or(ORMError::InsertError))
+                .collect::<Result<Vec<u64>, ORMError>>()?
+        } else {
+            let conn = self.lock().await;
+            let start = Instant::now();
+            let (statement, values) = (query.clone(), params.clone());
+            let ids = ORM::blocking(&conn, move |conn| conn.prepare(statement.as_str()).and_then(|mut stmt| {
+                stmt.query_map(params_from_iter(&values), |row| row.get::<_, i64>(0))?
+                    .map(|id| id.map(|id| id as u64))
+                    .collect::<Result<Vec<u64>, rusqlite::Error>>()
+            })).await?;
+            self.log_statement(&query, start, &ids, |ids| ids.len());
+            self.options.record(&key, &ids, |ids| {
+                Recorded::values(&ids.iter().map(|id| HashMap::from([("id".to_string(), Value::Int(*id as i64))])).collect::<Vec<_>>())
+            });
+            ids?
+        };
+        // the order of RETURNING rows is unspecified, but rowids are assigned in the order of the VALUES rows
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    async fn last_insert_rowid(&self)  -> Result<i64, ORMError>{
+        let conn = self.lock().await;
+        ORM::blocking(&conn, |conn| conn.last_insert_rowid()).await
+    }
+
+    async fn last_insert_id(&self) -> Result<u64, ORMError> {
+        Ok(self.last_insert_rowid().await? as u64)
     }
 
     async fn close(&self)  -> Result<(), ORMError>{
-        let mut conn_lock = self.conn.lock().await;
+        if self.options.replaying().is_some() {
+            return Ok(());
+        }
+        let mut conn_lock = self.lock().await;
         if conn_lock.is_none() {
             return Err(ORMError::NoConnection);
         }
-        let conn = conn_lock.take();
-        let r = conn.unwrap().close();
-        match r {
-            Ok(_) => {
-                Ok(())
-            }
-            Err(e) => {
-                Err(ORMError::RusqliteError(e.1))
+        for reader in self.readers.iter() {
+            if let Some(reader) = reader.lock().await.take() {
+                ORM::close_shared(reader)?;
             }
         }
+        ORM::close_shared(conn_lock.take().unwrap())
     }
 
     fn find_one<T: TableDeserialize>(&self, id: u64) -> QueryBuilder<Option<T>, T, ORM>
         where T: TableDeserialize + TableSerialize + for<'a> Deserialize<'a> + 'static
     {
-        let table_name = T::same_name();
+        let table_name = self.options.table_name::<T>();
 
         let query: String = format!("select * from {table_name} where id = {id}");
 
         let qb = QueryBuilder::<Option<T>, T, ORM> {
             query,
+            params: Vec::new(),
+            counters: Vec::new(),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
+            error: None,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
         };
         qb
     }
 
-    fn find_many<T>(&self, query_where: &str) -> QueryBuilder<Vec<T>, T, ORM>
+    async fn exists_by_id<T>(&self, id: u64) -> Result<bool, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let rows: Vec<Row> = self.query(format!("select 1 from {table_name} where id = {id} limit 1").as_str()).exec().await?;
+        Ok(!rows.is_empty())
+    }
+
+    async fn find_by_ids<T>(&self, ids: &[u64]) -> Result<Vec<Option<T>>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + Clone + Send + Sync + 'static
+    {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let found: Vec<T> = self.find_many(Cond::field("id").is_in(ids.iter().copied())).run().await?;
+        let by_id: HashMap<String, T> = found.into_iter().map(|data| (data.get_id(), data)).collect();
+        Ok(ids.iter().map(|id| by_id.get(&id.to_string()).cloned()).collect())
+    }
+
+    async fn descendants_of<T>(&self, id: u64) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + Send + Sync + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        self.query(tree_statement(&table_name, id, true).as_str()).run().await
+    }
+
+    async fn ancestors_of<T>(&self, id: u64) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + Send + Sync + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        self.query(tree_statement(&table_name, id, false).as_str()).run().await
+    }
+
+    async fn refresh<T>(&self, data: &mut T) -> Result<(), ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + Send + Sync + 'static
+    {
+        let id = data.get_id();
+        let stored: Option<T> = self.find_one_where(Cond::field("id").eq(id_param(&id))).run().await?;
+        match stored {
+            Some(stored) => {
+                *data = stored;
+                Ok(())
+            }
+            None => Err(ORMError::NotFound(format!("{} with id {}", T::same_name(), id))),
+        }
+    }
+
+    async fn save<T>(&self, data: T) -> Result<T, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Send + Sync + 'static
+    {
+        let id = data.get_id();
+        if is_unset_id(&id) {
+            return self.add(data).apply().await;
+        }
+        self.modify(data).run().await?;
+        let stored: Option<T> = self.find_one_where(Cond::field("id").eq(id_param(&id))).run().await?;
+        stored.ok_or_else(|| ORMError::NotFound(format!("{} with id {}", T::same_name(), id)))
+    }
+
+    async fn persist<T>(&self, entity: &mut Persisted<T>) -> Result<(), ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + Send + Sync + 'static
+    {
+        let stored = match entity.original_id() {
+            None => self.add(T::clone(entity)).apply().await?,
+            Some(id) if id != entity.get_id() => {
+                return Err(ORMError::InvalidValue(format!("the id of a persisted entity changed from {} to {}", id, entity.get_id())));
+            }
+            Some(_) => self.save(T::clone(entity)).await?,
+        };
+        *entity = Persisted::loaded(stored);
+        Ok(())
+    }
+
+    fn find_one_where<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, Option<T>, T, ORM>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+
+        let (query_where, params, error) = query_where.into().into_parts();
+        let query: String = format!("select * from {table_name} where {query_where}");
+
+        let qb = QueryBuilder::<Option<T>, T, ORM> {
+            query,
+            params,
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        };
+        qb.limit(1)
+    }
+
+    fn find_many<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<Vec<T>, T, ORM>
         where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
 
     {
 
-        let table_name = T::same_name();
+        let table_name = self.options.table_name::<T>();
 
+        let (query_where, params, error) = query_where.into().into_parts();
         let query: String = format!("select * from {table_name} where {query_where}");
 
         let qb = QueryBuilder::<Vec<T>, T, ORM> {
             query,
+            params,
+            counters: Vec::new(),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
         };
         qb
     }
 
     fn find_all<T>(&self) -> QueryBuilder<Vec<T>, T, ORM>
         where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static {
-        let table_name = T::same_name();
+        let table_name = self.options.table_name::<T>();
 
         let query: String = format!("select * from {table_name}");
 
-        let qb = QueryBuilder::<Vec<T>, T, ORM> {
-            query,
-            entity: std::marker::PhantomData,
-            orm: self,
-            result: std::marker::PhantomData,
-        };
-        qb
+        let qb = QueryBuilder::<Vec<T>, T, ORM> {
+            query,
+            params: Vec::new(),
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error: None,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        };
+        qb
+    }
+
+    fn count<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, u64, T, ORM>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let (query_where, params, error) = query_where.into().into_parts();
+        QueryBuilder::<u64, T, ORM> {
+            query: format!("select count(*) from {table_name} where {query_where}"),
+            params,
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        }
+    }
+
+    fn exists<T>(&self, query_where: impl Into<Cond>) -> QueryBuilder<'_, bool, T, ORM>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let (query_where, params, error) = query_where.into().into_parts();
+        QueryBuilder::<bool, T, ORM> {
+            query: format!("select exists(select 1 from {table_name} where {query_where})"),
+            params,
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        }
+    }
+
+    fn find_as<P>(&self, table: &str, query_where: &str) -> QueryBuilder<'_, Vec<P>, P, ORM>
+        where P: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+    {
+        let table_name = self.options.qualify(table);
+        let columns = P::fields().join(", ");
+        let query: String = format!("select {columns} from {table_name} where {query_where}");
+        QueryBuilder::<Vec<P>, P, ORM> {
+            query,
+            params: Vec::new(),
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error: (!is_table_name(table)).then(|| format!("invalid table name {:?}", table)),
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        }
+    }
+
+    async fn fetch<T>(&self, query: QueryBuilder<'_, Vec<T>, T, ORM>) -> Result<Vec<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + Send + Sync + 'static
+    {
+        query.run().await
+    }
+
+    fn modify<T>(&self, data: T) -> QueryBuilder<usize, (), ORM>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let (query, params, error) = match update_statement(&table_name, &data, &data.get_id(), &self.options) {
+            Ok((query, params)) => (query, params, None),
+            Err(e) => (format!("update {table_name}"), Vec::new(), Some(e)),
+        };
+        let qb = QueryBuilder::<usize, (), ORM> {
+            query,
+            params,
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: data.validation().err(),
+            table: Some(table_name),
+        };
+        qb
+    }
+
+    fn modify_fields<T>(&self, data: &T, fields: &[&str]) -> QueryBuilder<'_, usize, (), ORM>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let columns = T::fields();
+        let ((query, params), error) = if fields.is_empty() {
+            ((String::new(), Vec::new()), Some("no fields to modify".to_string()))
+        } else if let Some(field) = fields.iter().find(|field| **field == "id" || !columns.iter().any(|column| column == *field)) {
+            ((String::new(), Vec::new()), Some(format!("cannot modify field {}", field)))
+        } else {
+            match update_fields_statement(&table_name, data, fields, &data.get_id(), &self.options) {
+                Ok(statement) => (statement, None),
+                Err(e) => ((String::new(), Vec::new()), Some(e)),
+            }
+        };
+        QueryBuilder::<usize, (), ORM> {
+            query,
+            params,
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: data.validation().err(),
+            table: Some(table_name),
+        }
+    }
+
+    fn increment<T>(&self, id: u64, column: &str, by: i64) -> QueryBuilder<'_, usize, (), ORM>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let error = if column == "id" || !T::fields().iter().any(|field| field == column) {
+            Some(format!("cannot increment field {}", column))
+        } else {
+            None
+        };
+        let query: String = format!("update {table_name} set {column} = {column} + ({by}) where id = {id}");
+        QueryBuilder::<usize, (), ORM> {
+            query,
+            params: Vec::new(),
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        }
+    }
+
+    fn upsert_all<T>(&self, rows: Vec<T>) -> UpsertAll<'_, T, ORM>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        UpsertAll {
+            rows,
+            conflict_columns: Vec::new(),
+            chunk_size: UpsertAll::<T, ORM>::DEFAULT_CHUNK_SIZE,
+            orm: self,
+        }
+    }
+
+    fn import_all<T>(&self, rows: Vec<T>) -> ImportAll<'_, T, ORM>
+        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    {
+        ImportAll {
+            rows,
+            chunk_size: ImportAll::<T, ORM>::DEFAULT_CHUNK_SIZE,
+            orm: self,
+        }
+    }
+
+    fn queue(&self, name: &str) -> Queue<'_, ORM> {
+        Queue::new(self, name)
+    }
+
+    fn create_table<T>(&self) -> QueryBuilder<'_, usize, (), ORM>
+        where T: TableDeserialize + 'static
+    {
+        let columns = T::columns();
+        let error = if columns.is_empty() { Some(format!("{} has no column definitions", T::same_name())) } else { None };
+        QueryBuilder::<usize, (), ORM> {
+            query: schema::create_table_sql(&self.options.table_name::<T>(), T::table_comment().as_deref(), &columns, Dialect::Sqlite),
+            params: Vec::new(),
+            counters: Vec::new(),
+            entity: std::marker::PhantomData,
+            orm: self,
+            result: std::marker::PhantomData,
+            error,
+            modifiers: Default::default(),
+            validation: None,
+            table: None,
+        }
+    }
+
+    fn ddl<D: DdlStatement>(&self, ddl: &D) -> QueryBuilder<'_, usize, (), ORM> {
+        self.query_update(ddl.to_sql(Dialect::Sqlite).as_str())
+    }
+
+    fn alter_table<T>(&self) -> AlterTable<'_, T, ORM>
+        where T: TableDeserialize + 'static
+    {
+        AlterTable::new(self, self.options.table_name::<T>())
+    }
+
+    async fn store_comments<T>(&self) -> Result<usize, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        let columns = T::columns();
+        let comment = T::table_comment();
+        let statements = schema::comment_statements(&self.options.table_name::<T>(), comment.as_deref(), &columns, Dialect::Sqlite);
+        let queries: Vec<_> = statements.iter().map(|query| self.query_update(query)).collect();
+        self.exec_all(&queries).await?;
+        Ok(comment.iter().count() + columns.iter().filter(|column| column.comment.is_some()).count())
     }
 
-    fn modify<T>(&self, data: T) -> QueryBuilder<usize, (), ORM>
+    fn remove<T>(&self, data: T) -> QueryBuilder<usize, (), ORM>
         where T: TableDeserialize + TableSerialize + Serialize + 'static
     {
-        let table_name = data.name();
-        let key_value_str = serializer_key_values::to_string(&data).unwrap();
-        // remove first and last char
-        let key_value = &key_value_str[1..key_value_str.len()-1];
+        let table_name = self.options.table_name::<T>();
         let id = data.get_id();
-        let query: String = format!("update {table_name} set {key_value} where id = {id}");
+        let query: String = format!("delete from {table_name} where id = ?");
         let qb = QueryBuilder::<usize, (), ORM> {
             query,
+            params: vec![id_param(&id)],
+            counters: counter_statements(&table_name, &data, &id, false),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
+            error: None,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
         };
         qb
     }
 
-    fn remove<T>(&self, data: T) -> QueryBuilder<usize, (), ORM>
-        where T: TableDeserialize + TableSerialize + Serialize + 'static
+    fn remove_by_id<T>(&self, id: u64) -> QueryBuilder<'_, usize, (), ORM>
+        where T: TableDeserialize + 'static
     {
-        let table_name = data.name();
-        let id = data.get_id();
+        let table_name = self.options.table_name::<T>();
         let query: String = format!("delete from {table_name} where id = {id}");
-        let qb = QueryBuilder::<usize, (), ORM> {
+        QueryBuilder::<usize, (), ORM> {
             query,
+            params: Vec::new(),
+            counters: Vec::new(),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
-        };
-        qb
+            error: None,
+            modifiers: Default::default(),
+            validation: None,
+            table: Some(table_name),
+        }
+    }
+
+    async fn remove_by_ids<T>(&self, ids: &[u64]) -> Result<usize, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let table_name = self.options.table_name::<T>();
+        let cond = Cond::field("id").is_in(ids.iter().copied());
+        self.query_update(format!("delete from {table_name} where {}", cond.sql()).as_str()).bind(cond.params().to_vec()).run().await
+    }
+
+    fn read_blob<T>(&self, id: u64, column: &str) -> BlobReader<'_, T, ORM>
+        where T: TableDeserialize + 'static
+    {
+        BlobReader::new(self, id, column)
+    }
+
+    async fn blob_length<T>(&self, id: u64, column: &str) -> Result<u64, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let column = column.to_string();
+        let conn = self.lock().await;
+        let length = ORM::blocking(&conn, move |conn| {
+            let e = match ORM::open_blob(conn, &table_name, &column, id) {
+                Ok(blob) => return Ok(Some(blob.len() as u64)),
+                Err(e) => e,
+            };
+            // NULL values cannot be opened
+            let query = format!("select {column} is null from {table_name} where id = {id}");
+            match conn.query_row(query.as_str(), [], |row| row.get::<_, bool>(0)) {
+                Ok(true) => Ok(Some(0)),
+                Ok(false) => Err(e),
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(e),
+            }
+        }).await??;
+        length.ok_or_else(|| ORMError::NotFound(format!("{} with id {}", T::same_name(), id)))
+    }
+
+    async fn read_blob_chunk<T>(&self, id: u64, column: &str, offset: u64, len: usize) -> Result<Vec<u8>, ORMError>
+        where T: TableDeserialize + 'static
+    {
+        let table_name = self.options.table_name::<T>();
+        let column = column.to_string();
+        let conn = self.lock().await;
+        ORM::blocking(&conn, move |conn| -> Result<Vec<u8>, ORMError> {
+            let blob = ORM::open_blob(conn, &table_name, &column, id)?;
+            let mut chunk = vec![0; len];
+            let read = blob.read_at(&mut chunk, offset as usize)?;
+            chunk.truncate(read);
+            Ok(chunk)
+        }).await?
+    }
+
+    fn scoped(&self, scope: Scope) -> Scoped<'_, ORM> {
+        Scoped::new(self, scope)
+    }
+
+    async fn add_dynamic(&self, entity: &DynamicEntity) -> Result<DynamicEntity, ORMError> {
+        let (query, params) = entity.insert_statement()?;
+        let (id, _) = self.insert(&query, &params).await?;
+        self.find_dynamic(entity.table(), id).await?.ok_or(ORMError::InsertError)
+    }
+
+    async fn find_dynamic(&self, table: &str, id: u64) -> Result<Option<DynamicEntity>, ORMError> {
+        Ok(self.find_many_dynamic(table, Cond::field("id").eq(id)).await?.into_iter().next())
+    }
+
+    async fn find_many_dynamic<W: Into<Cond> + Send>(&self, table: &str, query_where: W) -> Result<Vec<DynamicEntity>, ORMError> {
+        let query_where = query_where.into();
+        let query = format!("select * from {} where {}", dynamic::checked_table(table)?, query_where.sql());
+        let rows = self.read_values(&query, query_where.params()).await?;
+        Ok(rows.into_iter().map(|row| DynamicEntity::from_map(table, row)).collect())
+    }
+
+    async fn modify_dynamic(&self, entity: &DynamicEntity) -> Result<usize, ORMError> {
+        let (query, params) = entity.update_statement()?;
+        self.query_update(&query).bind(params).run().await
+    }
+
+    async fn remove_dynamic(&self, entity: &DynamicEntity) -> Result<usize, ORMError> {
+        let (query, params) = entity.delete_statement()?;
+        self.query_update(&query).bind(params).run().await
     }
 
     fn query<T>(&self, query: &str) -> QueryBuilder<Vec<T>, T, ORM> {
         let qb = QueryBuilder::<Vec<T>, T, ORM> {
             query: query.to_string(),
+            params: Vec::new(),
+            counters: Vec::new(),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
+            error: None,
+            modifiers: Default::default(),
+            validation: None,
+            table: None,
         };
         qb
     }
@@ -165,18 +1208,223 @@ impl ORMTrait<ORM> for ORM {
     fn query_update(&self, query: &str) -> QueryBuilder<usize, (), ORM> {
         let qb = QueryBuilder::<usize, (), ORM> {
             query: query.to_string(),
+            params: Vec::new(),
+            counters: Vec::new(),
             entity: std::marker::PhantomData,
             orm: self,
             result: std::marker::PhantomData,
+            error: None,
+            modifiers: Default::default(),
+            validation: None,
+            table: None,
         };
         qb
     }
 
+    fn batch(&self) -> Batch<'_, ORM> {
+        Batch {
+            queries: Vec::new(),
+            orm: self,
+        }
+    }
+
+    async fn query_as<T: FromRow + Send>(&self, query: &str) -> Result<Vec<T>, ORMError> {
+        let result_set = self.query(query).exec_result_set().await?;
+        Ok(result_set.decode()?)
+    }
+
+    async fn query_map(&self, query: &str) -> Result<Vec<HashMap<String, Value>>, ORMError> {
+        self.read_values(query, &[]).await
+    }
+
+    fn pool_status(&self) -> PoolStatus {
+        let (mut size, mut in_use) = (0, 0);
+        for conn in std::iter::once(&self.conn).chain(self.readers.iter()) {
+            match conn.try_lock() {
+                Some(conn) => size += usize::from(conn.is_some()),
+                None => {
+                    size += 1;
+                    in_use += 1;
+                }
+            }
+        }
+        self.metrics.status(size, in_use)
+    }
+
+    fn query_stats(&self) -> Vec<QueryStats> {
+        self.query_stats.snapshot()
+    }
+
+    fn reset_query_stats(&self) {
+        self.query_stats.reset()
+    }
+
+    fn on_table_write(&self, table: &str, hook: impl Fn(&TableWrite) + Send + Sync + 'static) {
+        self.write_hooks.add(table, Arc::new(hook));
+    }
+
+    fn set_policy(&self, policy: Policy) {
+        *self.policy.write().unwrap() = policy;
+    }
+
+    async fn health(&self) -> HealthReport {
+        health::check(self).await
+    }
+
+    async fn plan_migrations(&self, migrations: &[Migration]) -> Result<MigrationPlan, ORMError> {
+        let tracked = self.query_map(format!("SELECT name FROM pragma_table_list WHERE name = {} AND type = 'table'",
+            schema::quote(migrator::TABLE)).as_str()).await?;
+        migrator::plan(self, migrations, !tracked.is_empty()).await
+    }
+
+    async fn table_sizes(&self) -> Result<Vec<TableSize>, ORMError> {
+        let schema = self.options.schema.as_deref().unwrap_or("main");
+        let rows = self.query_map(format!("SELECT m.tbl_name AS table_name, \
+            SUM(CASE WHEN m.type = 'table' THEN s.pgsize ELSE 0 END) AS data_bytes, \
+            SUM(CASE WHEN m.type = 'index' THEN s.pgsize ELSE 0 END) AS index_bytes \
+            FROM {schema}.sqlite_master m JOIN dbstat({}) s ON s.name = m.name \
+            WHERE m.type IN ('table', 'index') AND m.tbl_name NOT LIKE 'sqlite_%' GROUP BY m.tbl_name ORDER BY m.tbl_name",
+            schema::quote(schema)).as_str()).await?;
+        Ok(stats::table_sizes(&rows))
+    }
+
+    async fn row_counts(&self) -> Result<Vec<RowCount>, ORMError> {
+        let schema = self.options.schema.as_deref().unwrap_or("main");
+        let tables = self.query_map(format!("SELECT name FROM {schema}.sqlite_master \
+            WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name").as_str()).await?;
+        let counts: Vec<String> = tables.iter().filter_map(|table| table.get("name").and_then(Value::as_str))
+            .map(|table| format!("SELECT {} AS table_name, COUNT(*) AS row_count FROM {schema}.\"{}\"", schema::quote(table), table.replace('"', "\"\"")))
+            .collect();
+        if counts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let rows = self.query_map(counts.join(" UNION ALL ").as_str()).await?;
+        Ok(stats::row_counts(&rows, true))
+    }
+
+    async fn index_usage(&self) -> Result<Vec<IndexUsage>, ORMError> {
+        let schema = self.options.schema.as_deref().unwrap_or("main");
+        let rows = self.query_map(format!("SELECT tbl_name AS table_name, name AS index_name FROM {schema}.sqlite_master \
+            WHERE type = 'index' ORDER BY tbl_name, name").as_str()).await?;
+        Ok(stats::index_usage(&rows))
+    }
+
+    async fn merge<T>(&self, entities: Vec<T>, strategy: MergeStrategy<T>) -> Result<MergeResult, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + Send + Sync + 'static
+    {
+        let mut result = MergeResult::default();
+        if entities.is_empty() {
+            return Ok(result);
+        }
+        let existing = self.find_many::<T>(Cond::field("id").is_in(entities.iter().map(|e| id_param(&e.get_id())))).run().await?;
+        let mut queries = Vec::new();
+        for action in plan_merge(entities, existing, &strategy)? {
+            match action {
+                MergeAction::Insert(e) => {
+                    result.inserted += 1;
+                    queries.push(self.add(e).into_update()?);
+                }
+                MergeAction::Update(e) => {
+                    result.updated += 1;
+                    queries.push(self.modify(e).into_update()?);
+                }
+                MergeAction::Skip => result.skipped += 1,
+            }
+        }
+        self.exec_all(&queries).await?;
+        Ok(result)
+    }
+
+    async fn join_all(&self, queries: Vec<QueryBuilder<'_, Vec<Row>, Row, ORM>>) -> Result<Vec<Vec<Row>>, ORMError> {
+        futures::future::try_join_all(queries.iter().map(|query| query.exec())).await
+    }
+
+    async fn begin(&self, isolation: IsolationLevel) -> Result<(), ORMError> {
+        let statement = match isolation {
+            IsolationLevel::Default | IsolationLevel::Deferred | IsolationLevel::ReadUncommitted
+            | IsolationLevel::ReadCommitted | IsolationLevel::RepeatableRead => "BEGIN DEFERRED",
+            IsolationLevel::Serializable | IsolationLevel::Immediate => "BEGIN IMMEDIATE",
+            IsolationLevel::Exclusive => "BEGIN EXCLUSIVE",
+        };
+        let depth = self.transaction_depth();
+        // a new transaction waits until the transaction of another task ends and keeps the gate until it ends itself
+        let gate = if depth == 0 { Some(self.transaction_gate.clone().lock_owned().await) } else { None };
+        let conn = self.lock_connection(false).await;
+        if conn.is_none() && self.options.replaying().is_none() {
+            return Err(ORMError::NoConnection);
+        }
+        if depth == 0 {
+            self.n_plus_one.reset_task();
+        }
+        let statement = if depth == 0 { statement.to_string() } else { savepoint_statement(depth) };
+        log::debug!("{:?}", statement);
+        if self.options.replaying().is_none() {
+            ORM::blocking(&conn, move |conn| conn.execute_batch(statement.as_str())).await??;
+        }
+        let mut transaction = self.transaction.lock().unwrap_or_else(PoisonError::into_inner);
+        match (transaction.as_mut(), gate) {
+            (_, Some(gate)) => *transaction = Some(Transaction { owner: transaction_owner(), depth: 1, _gate: gate }),
+            (Some(transaction), None) => transaction.depth += 1,
+            (None, None) => {}
+        }
+        Ok(())
+    }
+
+    async fn commit(&self) -> Result<(), ORMError> {
+        self.end_transaction(true).await
+    }
+
+    async fn rollback(&self) -> Result<(), ORMError> {
+        self.end_transaction(false).await
+    }
+
+    async fn exec_all(&self, queries: &[QueryBuilder<'_, usize, (), ORM>]) -> Result<usize, ORMError> {
+        for qb in queries.iter() {
+            qb.check()?;
+        }
+        let statements = exec_all_statements(queries);
+        // the statements are executed in a transaction
+        let policy = self.policy.read().unwrap().clone();
+        for (query, _, _) in statements.iter() {
+            policy.check(query, true)?;
+        }
+        if let Some(cassette) = self.options.replaying() {
+            let mut updated_rows: usize = 0;
+            for (query, params, counted) in statements {
+                log::debug!("{:?} {:?}", query, params);
+                let r = cassette.replay_affected(&statement_key(query, params, Dialect::Sqlite))?.0 as usize;
+                updated_rows += if counted { r } else { 0 };
+            }
+            return Ok(updated_rows);
+        }
+        let owned: Vec<Statement> = statements.iter().map(|(query, params, _)| (query.to_string(), params.to_vec())).collect();
+        let in_transaction = self.transaction_depth() > 0;
+        let conn = self.lock().await;
+        let (executed, end) = ORM::blocking(&conn, move |conn| ORM::execute_all(conn, &owned, in_transaction)).await?;
+        let mut updated_rows: usize = 0;
+        for ((query, params, counted), (r, duration)) in statements.into_iter().zip(executed) {
+            // the statement was timed on the blocking thread
+            self.log_statement(query, Instant::now() - duration, &r, |r| *r);
+            self.options.record(&statement_key(query, params, Dialect::Sqlite), &r, |r| Recorded::Affected { rows: *r as u64, last_insert_id: None });
+            match r {
+                Ok(r) => updated_rows += if counted { r } else { 0 },
+                // the transaction was rolled back
+                Err(e) => return Err(e.into()),
+            }
+        }
+        end?;
+        Ok(updated_rows)
+    }
+
     fn protect(&self, value: &str) -> String {
         let protected: String = format!("\"{}\"", ORM::escape(value));
         protected
 
     }
+    fn protect_value<V: ToSqlLiteral>(&self, value: V) -> Result<String, ORMError> {
+        value.to_sql_literal(Dialect::Sqlite, self.options.float_policy)
+    }
+
     fn escape(str: &str) -> String {
         let mut escaped = String::new();
 
@@ -251,14 +1499,158 @@ impl ORMTrait<ORM> for ORM {
 
 impl<T> QueryBuilder<'_, usize, T, ORM>{
     pub async fn exec(&self) -> Result<usize, ORMError> {
-        log::debug!("{:?}", self.query);
-        let conn = self.orm.conn.lock().await;
-        if conn.is_none() {
-            return Err(ORMError::NoConnection);
+        self.check()?;
+        log::debug!("{:?} {:?}", self.query, self.params);
+        self.orm.check_policy(&self.query)?;
+        if let Some(cassette) = self.orm.options.replaying() {
+            return Ok(cassette.replay_affected(&self.statement_key(Dialect::Sqlite))?.0 as usize);
         }
-        let conn = conn.as_ref().unwrap();
-        let r = conn.execute(self.query.as_str(),(),)?;
-        Ok(r)
+        let conn = if self.modifiers.low_priority { self.orm.lock_low_priority().await } else { self.orm.lock().await };
+        let start = Instant::now();
+        let r = ORM::execute(&conn, &self.query, &self.params).await?;
+        self.orm.log_statement(&self.query, start, &r, |r| *r);
+        self.orm.options.record(&self.statement_key(Dialect::Sqlite), &r, |r| Recorded::Affected { rows: *r as u64, last_insert_id: None });
+        Ok(r?)
+    }
+}
+
+impl Batch<'_, ORM> {
+    pub async fn execute(&self) -> Result<Vec<usize>, ORMError> {
+        for query in self.queries.iter() {
+            self.orm.check_policy(query)?;
+        }
+        if let Some(cassette) = self.orm.options.replaying() {
+            return self.queries.iter().map(|query| Ok(cassette.replay_affected(query)?.0 as usize)).collect();
+        }
+        let conn = self.orm.lock().await;
+        let queries = self.queries.clone();
+        // the queries are executed until the first one fails
+        let executed = ORM::blocking(&conn, move |conn| {
+            let mut executed: Vec<Executed> = Vec::new();
+            for query in queries.iter() {
+                log::debug!("{:?}", query);
+                let start = Instant::now();
+                let r = conn.execute(query.as_str(), ());
+                let failed = r.is_err();
+                executed.push((r, start.elapsed()));
+                if failed {
+                    break;
+                }
+            }
+            executed
+        }).await?;
+        let mut result: Vec<usize> = Vec::new();
+        for (query, (r, duration)) in self.queries.iter().zip(executed) {
+            // the statement was timed on the blocking thread
+            self.orm.log_statement(query, Instant::now() - duration, &r, |r| *r);
+            self.orm.options.record(query, &r, |r| Recorded::Affected { rows: *r as u64, last_insert_id: None });
+            result.push(r?);
+        }
+        Ok(result)
+    }
+}
+
+impl<T: TableDeserialize + TableSerialize + Serialize> ImportAll<'_, T, ORM> {
+    pub async fn run(&self) -> Result<ImportReport, ORMError> {
+        self.execute(self.statements(&self.orm.options)).await
+    }
+}
+
+impl Queue<'_, ORM> {
+    pub async fn create_table(&self) -> Result<(), ORMError> {
+        let statements = [
+            format!("create table if not exists {} (id INTEGER PRIMARY KEY AUTOINCREMENT, queue TEXT NOT NULL, \
+                payload TEXT NOT NULL, attempts INTEGER NOT NULL, visible_at INTEGER NOT NULL)", queue::TABLE),
+            format!("create index if not exists {0}_visible on {0} (queue, visible_at)", queue::TABLE),
+        ];
+        let queries: Vec<_> = statements.iter().map(|query| self.orm.query_update(query)).collect();
+        self.orm.exec_all(&queries).await?;
+        Ok(())
+    }
+
+    pub async fn push(&self, payload: &str) -> Result<u64, ORMError> {
+        let (query, params) = self.push_statement(payload);
+        Ok(self.orm.insert(&query, &params).await?.0)
+    }
+
+    // BEGIN IMMEDIATE takes the write lock of the database before the jobs are selected,
+    // so other connections cannot claim the same jobs in between
+    pub async fn claim(&self, n: usize, visibility_timeout: Duration) -> Result<Vec<Job>, ORMError> {
+        self.claim_locked(n, visibility_timeout, IsolationLevel::Immediate, "").await
+    }
+}
+
+impl<T: TableDeserialize + TableSerialize + Serialize> UpsertAll<'_, T, ORM> {
+    pub async fn run(&self) -> Result<usize, ORMError> {
+        let statements = self.statements(&self.orm.options, |conflict_columns, update_columns| {
+            if update_columns.is_empty() {
+                format!("on conflict ({}) do nothing", conflict_columns.join(", "))
+            } else {
+                let set: Vec<String> = update_columns.iter().map(|column| format!("{column} = excluded.{column}")).collect();
+                format!("on conflict ({}) do update set {}", conflict_columns.join(", "), set.join(", "))
+            }
+        })?;
+        let queries: Vec<_> = statements.into_iter().map(|(query, params)| self.orm.query_update(&query).bind(params)).collect();
+        self.orm.exec_all(&queries).await
+    }
+}
+
+impl<T> AddOrUpdate<'_, T, ORM>
+    where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Serialize + Debug + Clone + 'static
+{
+    pub async fn apply(&self) -> Result<T, ORMError> {
+        let Some(lookup) = self.lookup(&self.upsert.orm.options)? else {
+            return self.upsert.orm.add(self.upsert.rows[0].clone()).apply().await;
+        };
+        in_transaction(self.upsert.orm, IsolationLevel::Default, async {
+            self.upsert.run().await?;
+            let stored: Option<T> = self.upsert.orm.find_one_where(lookup).run().await?;
+            stored.ok_or(ORMError::InsertError)
+        }).await
+    }
+}
+
+impl<T: TableDeserialize> AlterTable<'_, T, ORM> {
+    pub async fn run(&self) -> Result<(), ORMError> {
+        if !self.needs_rebuild() {
+            let statements = self.alter_statements(Dialect::Sqlite)?;
+            let queries: Vec<_> = statements.iter().map(|query| self.orm.query_update(query)).collect();
+            self.orm.exec_all(&queries).await?;
+            return Ok(());
+        }
+        let table = self.orm.options.table_name::<T>();
+        let (schema, name) = self.schema_and_name();
+        let existing: Vec<String> = self.orm.query_map(format!("PRAGMA {}table_info({})", schema, name).as_str()).await?.iter()
+            .filter_map(|column| column.get("name").and_then(Value::as_str).map(str::to_string))
+            .collect();
+        if existing.is_empty() {
+            return Err(ORMError::InvalidValue(format!("table {} does not exist", table)));
+        }
+        let indexes: Vec<String> = self.orm.query_map(format!("SELECT sql FROM {}sqlite_master WHERE tbl_name = {} \
+            AND type IN ('index', 'trigger') AND sql IS NOT NULL", schema, schema::quote(name)).as_str()).await?.iter()
+            .filter_map(|index| index.get("sql").and_then(Value::as_str).map(str::to_string))
+            .collect();
+        if !schema.is_empty() && !indexes.is_empty() {
+            // the statements of the indexes name neither the schema of the index nor of the table
+            return Err(ORMError::InvalidValue(format!("cannot recreate the indexes and triggers of {} in a rebuild", table)));
+        }
+        let statements = self.rebuild_statements(&existing, &indexes)?;
+        // dropping the old table must not delete the rows that reference it, but SQLite ignores
+        // `PRAGMA foreign_keys` inside a transaction
+        let foreign_keys = self.orm.query_map("PRAGMA foreign_keys").await?.first()
+            .and_then(|row| row.get("foreign_keys").and_then(Value::as_i64)) == Some(1);
+        if foreign_keys && self.orm.transaction_depth() > 0 {
+            return Err(ORMError::TransactionError(format!("cannot rebuild the table {} inside a transaction with foreign keys enabled", table)));
+        }
+        if foreign_keys {
+            self.orm.query_update("PRAGMA foreign_keys = OFF").run().await?;
+        }
+        let queries: Vec<_> = statements.iter().map(|query| self.orm.query_update(query)).collect();
+        let r = self.orm.exec_all(&queries).await;
+        if foreign_keys {
+            self.orm.query_update("PRAGMA foreign_keys = ON").run().await?;
+        }
+        r.map(|_| ())
     }
 }
 
@@ -266,51 +1658,71 @@ impl<T> QueryBuilder<'_, T,T, ORM>{
     pub async fn apply(&self) -> Result<T, ORMError>
         where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + 'static
     {
-        log::debug!("{:?}", self.query);
-        let r = {
-            let conn = self.orm.conn.lock().await;
-            if conn.is_none() {
-                return Err(ORMError::NoConnection);
-            }
-            let conn = conn.as_ref().unwrap();
-            let _r = conn.execute(self.query.as_str(),(),)?;
-            let r = conn.last_insert_rowid();
-            r
-        };
-        let rows: Vec<T> = self.orm.find_many(format!("rowid = {}", r).as_str()).run().await?;
-        if rows.len() == 0 {
-            return Err(ORMError::InsertError);
+        self.apply_with_meta().await.map(|(t, _)| t)
+    }
+
+    pub async fn apply_with_meta(&self) -> Result<(T, InsertMeta), ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + TableSerialize + Debug + 'static
+    {
+        self.check()?;
+        let (r, affected_rows) = self.with_counters(self.orm.insert(&self.query, &self.params)).await?;
+        let mut read_back = self.orm.find_many(format!("rowid = {}", r).as_str());
+        if let Some(table) = &self.table {
+            read_back = read_back.into_table(table);
         }
-        let t_opt = rows.into_iter().next();
-        match t_opt {
-            Some(t) => Ok(t),
+        let rows: Vec<T> = read_back.run().await?;
+        match rows.into_iter().next() {
+            Some(t) => Ok((t, InsertMeta { id: r, affected_rows })),
             None => Err(ORMError::InsertError),
         }
-
     }
 }
 
 impl<T> QueryBuilder<'_, usize,T, ORM> {
     pub async fn run(&self) -> Result<usize, ORMError> {
-        log::debug!("{:?}", self.query);
-        let conn = self.orm.conn.lock().await;
-        if conn.is_none() {
-            return Err(ORMError::NoConnection);
-        }
-        let conn = conn.as_ref().unwrap();
-        let r = conn.execute(self.query.as_str(),(),)?;
-        Ok(r)
+        self.check()?;
+        self.with_counters(async {
+            log::debug!("{:?} {:?}", self.query, self.params);
+            self.orm.check_policy(&self.query)?;
+            if let Some(cassette) = self.orm.options.replaying() {
+                return Ok(cassette.replay_affected(&self.statement_key(Dialect::Sqlite))?.0 as usize);
+            }
+            let conn = if self.modifiers.low_priority { self.orm.lock_low_priority().await } else { self.orm.lock().await };
+            let start = Instant::now();
+            let r = ORM::execute(&conn, &self.query, &self.params).await?;
+            self.orm.log_statement(&self.query, start, &r, |r| *r);
+            self.orm.options.record(&self.statement_key(Dialect::Sqlite), &r, |r| Recorded::Affected { rows: *r as u64, last_insert_id: None });
+            Ok(r?)
+        }).await
+    }
+}
+
+
+impl<T> QueryBuilder<'_, u64,T, ORM> {
+    pub async fn run(&self) -> Result<u64, ORMError> {
+        self.check()?;
+        let rows = self.orm.query(self.query.as_str()).bind(self.params.clone()).exec().await?;
+        Ok(rows.first().and_then(|row| row.get::<u64>(0)).unwrap_or(0))
     }
 }
 
+impl<T> QueryBuilder<'_, bool,T, ORM> {
+    pub async fn run(&self) -> Result<bool, ORMError> {
+        self.check()?;
+        let rows = self.orm.query(self.query.as_str()).bind(self.params.clone()).exec().await?;
+        Ok(rows.first().and_then(|row| row.get::<i64>(0)) == Some(1))
+    }
+}
 
 impl<T> QueryBuilder<'_, Option<T>,T, ORM>
     where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
 {
     pub async fn run(&self) -> Result<Option<T>, ORMError> {
-
-        let rows  = self.orm.query(self.query.clone().as_str()).exec().await?;
+        self.check()?;
+        let query = self.orm.query(self.query.clone().as_str()).bind(self.params.clone());
+        let rows = entity_cache::read_through(self.cached_lookup(), query.exec()).await?;
         let columns: Vec<String> =T::fields();
+        let definitions = T::columns();
         if rows.len() == 0 {
             return Ok(None);
         } else {
@@ -318,9 +1730,11 @@ impl<T> QueryBuilder<'_, Option<T>,T, ORM>
             for row in rows {
                 let mut i = 0;
                 for column in columns.iter() {
-                    let value_opt:Option<String> = row.get(i);
+                    let definition = definitions.iter().find(|definition| definition.name == *column);
+                    let value_opt:Option<String> = row.field_value(i, column, definition);
                     let value = match value_opt {
                         Some(v) => {
+                            let v = datetime::from_storage(v.as_str(), definition, self.orm.options.datetime_policy);
                             format!("\"{}\"", ORM::escape_json(v.as_str()))
                         }
                         None => {
@@ -333,90 +1747,114 @@ impl<T> QueryBuilder<'_, Option<T>,T, ORM>
             }
             let user_str = format!("{{{}}}", column_str.join(","));
             // log::debug!("zzz{}", user_str);
-            let user: T = deserializer_key_values::from_str(&user_str).unwrap();
-            Ok(Some(user))
+            let user_result: std::result::Result<T, serializer_error::Error> = deserializer_key_values::from_str(&user_str);
+            match user_result {
+                Ok(user) => Ok(Some(user)),
+                Err(e) => {
+                    log::error!("{:?}", e);
+                    log::error!("{}", user_str);
+                    Err(self.orm.options.decode_error::<T>(e))
+                }
+            }
 
         }
 
     }
+
+    // Returns the lookup of the query in the entity cache, if it is a `find_one` of an entity with a `cache_ttl`
+    // outside a transaction.
+    fn cached_lookup(&self) -> Option<CachedLookup<'_>> {
+        let cache = self.orm.options.entity_cache.as_ref()?;
+        let ttl = T::cache_ttl()?;
+        if !self.params.is_empty() || self.orm.transaction_depth() > 0 {
+            return None;
+        }
+        let table = self.table.as_deref()?;
+        let id = entity_cache::looked_up_id(&self.query, table)?;
+        Some(CachedLookup { cache, ttl, table, id })
+    }
 }
 
 impl<R> QueryBuilder<'_, Vec<Row>,R, ORM> {
     pub async fn exec(&self) -> Result<Vec<Row>, ORMError>
     {
-        log::debug!("{:?}", self.query);
-        let conn = self.orm.conn.lock().await;
-        if conn.is_none() {
-            return Err(ORMError::NoConnection);
-        }
-        let conn = conn.as_ref().unwrap();
-        let stmt_result = conn.prepare( self.query.as_str());
-        if stmt_result.is_err() {
-            let e = stmt_result.err().unwrap();
-            log::error!("{:?}", e);
-            return Err(ORMError::RusqliteError(e));
-        }
-        let mut stmt = stmt_result.unwrap();
-        let mut result: Vec<Row> = Vec::new();
-        let person_iter = stmt.query_map([], |row| {
-            let mut i = 0;
-            let mut r: Row = Row::new();
-            loop {
-                let res: rusqlite::Result<i32>= row.get(i);
-
-                match  res{
-                    Ok(v) => {
-                        r.set(i.try_into().unwrap(), Some(v));
-
-                    },
-                    Err(e) => {
-                        if e ==  rusqlite::Error::InvalidColumnIndex(i) {
-                            break;
-                        }
-                    }
-                }
-
-                let res: rusqlite::Result<String>= row.get(i);
-                match  res{
+        Ok(self.exec_result_set().await?.rows)
+    }
 
-                    Ok(v) => {
-                        r.set(i.try_into().unwrap(), Some(v));
-                    }
-                    Err(_e) => {
+    pub fn stream(&self) -> LocalBoxStream<'_, Result<Row, ORMError>> {
+        row_stream(move |mut tx| async move {
+            if self.orm.options.cassette.is_some() {
+                // recorded and replayed selects are read at once
+                for row in self.exec_result_set().await?.rows {
+                    if tx.send(Ok(row)).await.is_err() {
+                        break;
                     }
                 }
-
-                i = i + 1;
+                return Ok(());
             }
+            log::debug!("{:?} {:?}", self.query, self.params);
+            self.orm.check_policy(&self.query)?;
+            let conn = self.orm.lock_reader(&self.query).await;
+            let (query, params, strict) = (self.query.clone(), self.params.clone(), self.orm.options.strict_decode);
+            // the rows are sent from the blocking thread, which waits while the buffer of the stream is full
+            ORM::blocking(&conn, move |conn| -> Result<(), ORMError> {
+                let mut stmt = conn.prepare(query.as_str())?;
+                let names = Arc::new(stmt.column_names().iter().map(|name| name.to_string()).collect());
+                let mut rows = stmt.query(params_from_iter(&params))?;
+                while let Some(row) = rows.next()? {
+                    let mut decode_error: Option<DecodeError> = None;
+                    let r = ORM::read_row(row, &names, strict, &mut decode_error)?;
+                    let item = match decode_error {
+                        Some(e) => Err(ORMError::DecodeError(e)),
+                        None => Ok(r),
+                    };
+                    if futures::executor::block_on(tx.send(item)).is_err() {
+                        break;
+                    }
+                }
+                Ok(())
+            }).await?
+        }).boxed_local()
+    }
 
-            result.push(r);
-            Ok(())
-        })?;
-        for _x in person_iter {
+    pub async fn exec_result_set(&self) -> Result<ResultSet, ORMError>
+    {
+        log::debug!("{:?} {:?}", self.query, self.params);
+        self.orm.check_policy(&self.query)?;
+        if let Some(cassette) = self.orm.options.replaying() {
+            return cassette.replay_result_set(&self.statement_key(Dialect::Sqlite));
         }
-        // log::debug!("{:?}", result);
-
-        Ok(result)
+        let conn = self.orm.lock_reader(&self.query).await;
+        let start = Instant::now();
+        let (query, params, strict) = (self.query.clone(), self.params.clone(), self.orm.options.strict_decode);
+        let result = ORM::blocking(&conn, move |conn| ORM::read_result_set(conn, &query, &params, strict)).await?;
+        self.orm.log_statement(&self.query, start, &result, |result_set| result_set.rows.len());
+        self.orm.options.record(&self.statement_key(Dialect::Sqlite), &result, Recorded::result_set);
+        result
     }
 
 
+
 }
 
 impl<T> QueryBuilder<'_, Vec<T>,T, ORM> {
     pub async fn run(&self) -> Result<Vec<T>, ORMError>
         where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
     {
-
+        self.check()?;
         let mut result: Vec<T> = Vec::new();
-        let rows  = self.orm.query(self.query.clone().as_str()).exec().await?;
+        let rows  = self.orm.query(self.query.clone().as_str()).bind(self.params.clone()).exec().await?;
         let columns: Vec<String> =T::fields();
+        let definitions = T::columns();
         for row in rows {
             let mut column_str: Vec<String> = Vec::new();
             let mut i = 0;
             for column in columns.iter() {
-                let value_opt:Option<String> = row.get(i);
+                let definition = definitions.iter().find(|definition| definition.name == *column);
+                let value_opt:Option<String> = row.field_value(i, column, definition);
                 let value = match value_opt {
                     Some(v) => {
+                        let v = datetime::from_storage(v.as_str(), definition, self.orm.options.datetime_policy);
                         format!("\"{}\"", ORM::escape_json(v.as_str()))
                     }
                     None => {
@@ -436,7 +1874,7 @@ impl<T> QueryBuilder<'_, Vec<T>,T, ORM> {
                 Err(e) => {
                     log::error!("{:?}", e);
                     log::error!("{}", user_str);
-                    return Err(ORMError::Unknown);
+                    return Err(self.orm.options.decode_error::<T>(e));
                 }
             }
 
@@ -445,15 +1883,106 @@ impl<T> QueryBuilder<'_, Vec<T>,T, ORM> {
         Ok(result)
     }
 
-    pub fn limit(&self, limit: i32) -> QueryBuilder<Vec<T>, T, ORM> {
+    pub async fn paginate(&self, page: u64, per_page: u64) -> Result<Page<T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static
+    {
+        if page == 0 || per_page == 0 {
+            return Err(ORMError::InvalidValue(format!("invalid page {} of {} records", page, per_page)));
+        }
+        self.check()?;
+        let counted = self.orm.query(self.count_statement().as_str()).bind(self.params.clone()).exec().await?;
+        let total = counted.first().and_then(|row| row.get::<u64>(0)).unwrap_or(0);
+        let items = self.with_modifiers(UNLIMITED, |m| {
+            m.limit = Some(per_page as i64);
+            m.offset = Some((page - 1) * per_page);
+        }).run().await?;
+        Ok(Page { items, total, page, per_page })
+    }
+
+    pub async fn run_into_map<K, F>(&self, key: F) -> Result<HashMap<K, T>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static, K: Eq + std::hash::Hash, F: Fn(&T) -> K
+    {
+        Ok(self.run().await?.into_iter().map(|data| (key(&data), data)).collect())
+    }
 
-        let qb =  QueryBuilder::<Vec<T>,T, ORM> {
-            query: format!("{} limit {}", self.query, limit),
-            entity: std::marker::PhantomData,
-            orm: self.orm,
-            result: std::marker::PhantomData,
+    pub async fn run_grouped<K, F>(&self, key: F) -> Result<HashMap<K, Vec<T>>, ORMError>
+        where T: for<'a> Deserialize<'a> + TableDeserialize + Debug + 'static, K: Eq + std::hash::Hash, F: Fn(&T) -> K
+    {
+        let mut groups: HashMap<K, Vec<T>> = HashMap::new();
+        for data in self.run().await? {
+            groups.entry(key(&data)).or_default().push(data);
+        }
+        Ok(groups)
+    }
+}
+
+impl<'a, R, E> QueryBuilder<'a, R, E, ORM> {
+    pub fn limit(&self, limit: i32) -> QueryBuilder<'a, R, E, ORM> {
+        self.with_modifiers(UNLIMITED, |m| m.limit = Some(limit as i64))
+    }
+
+    pub fn offset(&self, offset: u64) -> QueryBuilder<'a, R, E, ORM> {
+        self.with_modifiers(UNLIMITED, |m| m.offset = Some(offset))
+    }
+
+    pub fn order_by(&self, column: &str, order: Order) -> QueryBuilder<'a, R, E, ORM> {
+        let clause = match order {
+            Order::Asc => format!("{} asc", column),
+            Order::Desc => format!("{} desc", column),
         };
-        qb
+        let ordered = self.with_modifiers(UNLIMITED, |m| m.order_by.push(clause));
+        if is_table_name(column) {
+            return ordered;
+        }
+        QueryBuilder { error: ordered.error.or_else(|| Some(format!("invalid column name {:?}", column))), ..ordered }
+    }
+}
+
+
+/// Creates SQLite connections for the deadpool and bb8 pools.
+#[cfg(any(feature = "deadpool", feature = "bb8"))]
+#[derive(Debug, Clone)]
+pub struct Manager {
+    options: ConnectOptions,
+}
+
+#[cfg(any(feature = "deadpool", feature = "bb8"))]
+impl Manager {
+    pub fn new(options: ConnectOptions) -> Manager {
+        Manager { options }
+    }
+}
+
+#[cfg(feature = "deadpool")]
+#[async_trait]
+impl deadpool::managed::Manager for Manager {
+    type Type = Arc<ORM>;
+    type Error = ORMError;
+
+    async fn create(&self) -> Result<Arc<ORM>, ORMError> {
+        ORM::connect_with(self.options.clone())
+    }
+
+    async fn recycle(&self, orm: &mut Arc<ORM>, _metrics: &deadpool::managed::Metrics) -> deadpool::managed::RecycleResult<ORMError> {
+        orm.query::<Row>("select 1").exec().await.map(|_| ()).map_err(deadpool::managed::RecycleError::Backend)
     }
 }
 
+#[cfg(feature = "bb8")]
+#[async_trait]
+impl bb8::ManageConnection for Manager {
+    type Connection = Arc<ORM>;
+    type Error = ORMError;
+
+    async fn connect(&self) -> Result<Arc<ORM>, ORMError> {
+        ORM::connect_with(self.options.clone())
+    }
+
+    async fn is_valid(&self, orm: &mut Arc<ORM>) -> Result<(), ORMError> {
+        orm.query::<Row>("select 1").exec().await.map(|_| ())
+    }
+
+    fn has_broken(&self, orm: &mut Arc<ORM>) -> bool {
+        orm.pool_status().size == 0
+    }
+}
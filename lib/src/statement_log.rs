@@ -0,0 +1,185 @@
+//! `statement_log` is a module that reports every executed statement to a pluggable `StatementLogger`,
+//! independently of the `log` crate, e.g. as JSON lines that are shipped to a log pipeline.
+
+use std::fmt::{self, Debug, Write as _};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// `StatementRecord` describes one executed statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementRecord {
+    /// `timestamp` is the time the statement was started.
+    pub timestamp: SystemTime,
+
+    /// `duration` is the time spent executing the statement and reading its rows.
+    pub duration: Duration,
+
+    /// `rows` is the number of affected rows of an update or the number of returned rows of a select.
+    /// It is `None` if the statement failed.
+    pub rows: Option<usize>,
+
+    /// `statement` is the executed SQL.
+    pub statement: String,
+
+    /// `error` is the error of a failed statement.
+    pub error: Option<String>,
+}
+
+impl StatementRecord {
+    /// Returns the fingerprint of the statement, see `fingerprint`.
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.statement)
+    }
+
+    /// Returns the record as a single line JSON object (without the line break):
+    /// `{"timestamp_ms":...,"duration_us":...,"rows":...,"fingerprint":"...","statement":"...","error":...}`.
+    pub fn to_json(&self) -> String {
+        let timestamp = self.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let rows = self.rows.map(|rows| rows.to_string()).unwrap_or_else(|| "null".to_string());
+        let error = self.error.as_deref().map(json_string).unwrap_or_else(|| "null".to_string());
+        format!("{{\"timestamp_ms\":{},\"duration_us\":{},\"rows\":{},\"fingerprint\":{},\"statement\":{},\"error\":{}}}",
+                timestamp, self.duration.as_micros(), rows, json_string(&self.fingerprint()), json_string(&self.statement), error)
+    }
+}
+
+/// `StatementLogger` is a trait for sinks of executed statements, registered with `ConnectOptions::statement_logger`.
+/// `log` is called after every statement, on the task that executed it, so it should not block for long.
+pub trait StatementLogger: Send + Sync {
+    /// Receives an executed statement.
+    fn log(&self, record: &StatementRecord);
+}
+
+impl Debug for dyn StatementLogger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StatementLogger")
+    }
+}
+
+/// `JsonLinesLogger` is a `StatementLogger` that writes every record as one JSON line (see `StatementRecord::to_json`),
+/// e.g. to a file or to stdout. Write errors are reported with `log::warn!` and do not fail the statement.
+pub struct JsonLinesLogger<W: Write + Send> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonLinesLogger<W> {
+    /// Constructs a `JsonLinesLogger` that writes to `writer`.
+    pub fn new(writer: W) -> Self {
+        JsonLinesLogger { writer: Mutex::new(writer) }
+    }
+
+    /// Returns the writer.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap()
+    }
+}
+
+impl<W: Write + Send> StatementLogger for JsonLinesLogger<W> {
+    fn log(&self, record: &StatementRecord) {
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(e) = writeln!(writer, "{}", record.to_json()).and_then(|_| writer.flush()) {
+            log::warn!("statement log write failed: {:?}", e);
+        }
+    }
+}
+
+/// Returns the statement with its string and number literals replaced by `?` and its whitespace collapsed,
+/// so that statements that differ only in their values are equal, e.g. `select * from user where id = ?`.
+pub fn normalize_statement(statement: &str) -> String {
+    let mut normalized = String::with_capacity(statement.len());
+    let mut chars = statement.chars().peekable();
+    let mut previous = ' ';
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                // a doubled quote inside a literal is an escaped quote
+                while let Some(next) = chars.next() {
+                    if next == c && chars.next_if_eq(&c).is_none() {
+                        break;
+                    }
+                }
+                normalized.push('?');
+                previous = '?';
+            }
+            c if c.is_ascii_digit() && !(previous.is_alphanumeric() || previous == '_') => {
+                while chars.next_if(|next| next.is_ascii_alphanumeric() || *next == '.').is_some() {}
+                normalized.push('?');
+                previous = '?';
+            }
+            c if c.is_whitespace() => {
+                if previous != ' ' {
+                    normalized.push(' ');
+                    previous = ' ';
+                }
+            }
+            c => {
+                normalized.push(c);
+                previous = c;
+            }
+        }
+    }
+    normalized.trim_end().to_string()
+}
+
+/// Returns the fingerprint of a statement: a 64-bit FNV-1a hash of `normalize_statement` as 16 hex digits.
+/// Statements that differ only in their values have the same fingerprint.
+pub fn fingerprint(statement: &str) -> String {
+    let hash = normalize_statement(statement).bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    format!("{:016x}", hash)
+}
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+    use super::{fingerprint, normalize_statement, JsonLinesLogger, StatementLogger, StatementRecord};
+
+    #[test]
+    fn test_normalize_statement() {
+        assert_eq!(normalize_statement("select * from user  where id = 42\n and name = 'it''s' or name = \"x\""),
+                   "select * from user where id = ? and name = ? or name = ?");
+        assert_eq!(normalize_statement("insert into t2 (a_1) values (-1.5, 3e10)"), "insert into t2 (a_1) values (-?, ?)");
+        assert_eq!(fingerprint("select * from user where id = 1"), fingerprint("select * from user where id = 2"));
+        assert_ne!(fingerprint("select * from user where id = 1"), fingerprint("select * from team where id = 1"));
+        assert_eq!(fingerprint("").len(), 16);
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let record = StatementRecord {
+            timestamp: UNIX_EPOCH + Duration::from_millis(1500),
+            duration: Duration::from_micros(250),
+            rows: None,
+            statement: "select \"a\"\n".to_string(),
+            error: Some("no such table".to_string()),
+        };
+        let logger = JsonLinesLogger::new(Vec::new());
+        logger.log(&record);
+        logger.log(&StatementRecord { rows: Some(3), error: None, ..record.clone() });
+        let output = String::from_utf8(logger.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], format!("{{\"timestamp_ms\":1500,\"duration_us\":250,\"rows\":null,\"fingerprint\":\"{}\",\
+            \"statement\":\"select \\\"a\\\"\\n\",\"error\":\"no such table\"}}", record.fingerprint()));
+        assert!(lines[1].contains("\"rows\":3,") && lines[1].ends_with("\"error\":null}"));
+    }
+}
@@ -0,0 +1,200 @@
+//! `stats` is a module with the results of the metadata queries of `ORMTrait`, e.g. for ops dashboards:
+//! `table_sizes`, `row_counts` and `index_usage` read `information_schema` and `performance_schema` on MySQL
+//! and `sqlite_master` and `dbstat` on SQLite. `query_stats` returns the counters the ORM keeps for the executed statements.
+#![cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::{fingerprint, normalize_statement, Value};
+
+/// The maximum number of fingerprints counted by `QueryStatsCollector`; statements with new fingerprints are not counted
+/// once it is reached, so that statements with generated SQL, e.g. IN lists of varying length, cannot exhaust the memory.
+const MAX_FINGERPRINTS: usize = 1000;
+
+/// `TableSize` is the storage used by a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSize {
+    /// `table` is the name of the table.
+    pub table: String,
+
+    /// `data_bytes` is the size of the rows.
+    pub data_bytes: u64,
+
+    /// `index_bytes` is the size of the indexes of the table.
+    pub index_bytes: u64,
+}
+
+impl TableSize {
+    /// Returns the size of the rows and the indexes.
+    pub fn total_bytes(&self) -> u64 {
+        self.data_bytes + self.index_bytes
+    }
+}
+
+/// `RowCount` is the number of rows of a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowCount {
+    /// `table` is the name of the table.
+    pub table: String,
+
+    /// `rows` is the number of rows.
+    pub rows: u64,
+
+    /// `exact` is `false` if `rows` is the estimate of the storage engine, as for InnoDB tables.
+    pub exact: bool,
+}
+
+/// `IndexUsage` describes an index and how often it was read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexUsage {
+    /// `table` is the name of the indexed table.
+    pub table: String,
+
+    /// `index` is the name of the index, `PRIMARY` for the primary key on MySQL.
+    pub index: String,
+
+    /// `reads` is the number of rows read with the index since the server started,
+    /// or `None` if the backend does not count them, as SQLite.
+    pub reads: Option<u64>,
+}
+
+/// `QueryStats` are the counters of the executed statements with the same fingerprint (see `fingerprint`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryStats {
+    /// `fingerprint` is the fingerprint of the statements.
+    pub fingerprint: String,
+
+    /// `statement` is the normalized statement, see `normalize_statement`.
+    pub statement: String,
+
+    /// `count` is the number of executions.
+    pub count: u64,
+
+    /// `errors` is the number of failed executions.
+    pub errors: u64,
+
+    /// `total_duration` is the time spent in all executions.
+    pub total_duration: Duration,
+
+    /// `max_duration` is the time spent in the slowest execution.
+    pub max_duration: Duration,
+}
+
+impl QueryStats {
+    /// Returns the average time spent in an execution.
+    pub fn mean_duration(&self) -> Duration {
+        self.total_duration.checked_div(self.count as u32).unwrap_or_default()
+    }
+
+    /// Returns the share of failed executions, between 0 and 1.
+    pub fn error_rate(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { self.errors as f64 / self.count as f64 }
+    }
+}
+
+/// `QueryStatsCollector` counts the executed statements of an ORM by fingerprint.
+#[derive(Debug, Default)]
+pub(crate) struct QueryStatsCollector {
+    stats: Mutex<HashMap<String, QueryStats>>,
+}
+
+impl QueryStatsCollector {
+    pub(crate) fn record(&self, statement: &str, duration: Duration, failed: bool) {
+        let normalized = normalize_statement(statement);
+        let fingerprint = fingerprint(&normalized);
+        let mut stats = self.stats.lock().unwrap();
+        if !stats.contains_key(&fingerprint) && stats.len() >= MAX_FINGERPRINTS {
+            return;
+        }
+        let entry = stats.entry(fingerprint.clone()).or_insert_with(|| QueryStats {
+            fingerprint,
+            statement: normalized,
+            count: 0,
+            errors: 0,
+            total_duration: Duration::ZERO,
+            max_duration: Duration::ZERO,
+        });
+        entry.count += 1;
+        entry.errors += failed as u64;
+        entry.total_duration += duration;
+        entry.max_duration = entry.max_duration.max(duration);
+    }
+
+    /// Returns the counters ordered by the total duration, the most expensive statements first.
+    pub(crate) fn snapshot(&self) -> Vec<QueryStats> {
+        let mut stats: Vec<QueryStats> = self.stats.lock().unwrap().values().cloned().collect();
+        stats.sort_by(|a, b| b.total_duration.cmp(&a.total_duration).then_with(|| a.statement.cmp(&b.statement)));
+        stats
+    }
+
+    pub(crate) fn reset(&self) {
+        self.stats.lock().unwrap().clear();
+    }
+}
+
+// Reads a counter, which MySQL returns as text for DECIMAL sums.
+fn count(row: &HashMap<String, Value>, column: &str) -> Option<u64> {
+    match row.get(column)? {
+        Value::Int(v) => u64::try_from(*v).ok(),
+        Value::Float(v) => Some(*v as u64),
+        Value::Text(v) => v.parse::<f64>().ok().map(|v| v as u64),
+        _ => None,
+    }
+}
+
+fn text(row: &HashMap<String, Value>, column: &str) -> String {
+    row.get(column).and_then(Value::as_str).unwrap_or_default().to_string()
+}
+
+/// Reads the rows of a query with the columns `table_name`, `data_bytes` and `index_bytes`.
+pub(crate) fn table_sizes(rows: &[HashMap<String, Value>]) -> Vec<TableSize> {
+    rows.iter().map(|row| TableSize {
+        table: text(row, "table_name"),
+        data_bytes: count(row, "data_bytes").unwrap_or(0),
+        index_bytes: count(row, "index_bytes").unwrap_or(0),
+    }).collect()
+}
+
+/// Reads the rows of a query with the columns `table_name` and `row_count`.
+pub(crate) fn row_counts(rows: &[HashMap<String, Value>], exact: bool) -> Vec<RowCount> {
+    rows.iter().map(|row| RowCount {
+        table: text(row, "table_name"),
+        rows: count(row, "row_count").unwrap_or(0),
+        exact,
+    }).collect()
+}
+
+/// Reads the rows of a query with the columns `table_name`, `index_name` and `reads`.
+pub(crate) fn index_usage(rows: &[HashMap<String, Value>]) -> Vec<IndexUsage> {
+    rows.iter().map(|row| IndexUsage {
+        table: text(row, "table_name"),
+        index: text(row, "index_name"),
+        reads: count(row, "reads"),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::QueryStatsCollector;
+
+    #[test]
+    fn test_query_stats() {
+        let collector = QueryStatsCollector::default();
+        collector.record("select * from user where id = 1", Duration::from_millis(2), false);
+        collector.record("select * from user  where id = 2", Duration::from_millis(6), true);
+        collector.record("delete from user", Duration::from_millis(1), false);
+        let stats = collector.snapshot();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].statement, "select * from user where id = ?");
+        assert_eq!(stats[0].fingerprint, crate::fingerprint("select * from user where id = 3"));
+        assert_eq!((stats[0].count, stats[0].errors), (2, 1));
+        assert_eq!(stats[0].max_duration, Duration::from_millis(6));
+        assert_eq!(stats[0].mean_duration(), Duration::from_millis(4));
+        assert_eq!(stats[0].error_rate(), 0.5);
+        assert_eq!(stats[1].statement, "delete from user");
+        collector.reset();
+        assert!(collector.snapshot().is_empty());
+    }
+}
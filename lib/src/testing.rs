@@ -0,0 +1,39 @@
+//! `testing` is a module with helpers for tests against a real database. `rollback_test` runs the body of a test
+//! in a transaction that is always rolled back, so that tests can share one schema without cleaning up:
+//!
+//! ```ignore
+//! #[tokio::test]
+//! async fn test_signup() -> Result<(), ORMError> {
+//!     let conn = ORM::connect(url)?;
+//!     rollback_test(&conn, |tx| async move {
+//!         tx.add(User { id: 0, name: Some("Mary".to_string()), age: 30 }).apply().await?;
+//!         assert_eq!(tx.find_many::<User>("name = 'Mary'").run().await?.len(), 1);
+//!         Ok(())
+//!     }).await
+//! }
+//! ```
+//!
+//! Inside a transaction that is already active, the body runs in a savepoint, which is rolled back as well.
+
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use futures::FutureExt;
+use crate::{IsolationLevel, ORMError, ORMTrait};
+
+/// Executes `f` in a transaction of `conn` and rolls the transaction back afterwards, whether `f` succeeds,
+/// fails or panics, e.g. on a failed assertion. Returns the result of `f`.
+pub async fn rollback_test<O, F, Fut, T, E>(conn: &Arc<O>, f: F) -> Result<T, E>
+    where O: ORMTrait<O> + Sync, F: FnOnce(Arc<O>) -> Fut, Fut: Future<Output = Result<T, E>>, E: From<ORMError>
+{
+    conn.begin(IsolationLevel::Default).await?;
+    let result = AssertUnwindSafe(f(conn.clone())).catch_unwind().await;
+    let rolled_back = conn.rollback().await;
+    match result {
+        Ok(result) => {
+            rolled_back?;
+            result
+        }
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
+}
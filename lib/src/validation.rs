@@ -0,0 +1,180 @@
+//! `validation` is a module that checks entities before they are written, so that invalid data is rejected
+//! with per-field messages instead of being stored or failing with a database error.
+
+use std::fmt;
+
+/// `Validate` is a trait for entities that check their fields before they are written.
+/// Derive `TableSerialize` with `#[table(validate)]` to call it automatically from `add`, `add_all`, `modify`
+/// and `modify_fields`; a single call opts out with `QueryBuilder::skip_validation`.
+/// With the `validator` feature, `#[table(validator)]` calls `validator::Validate` the same way.
+/// The `#[column(max_len = ...)]`, `#[column(min = ...)]` and `#[column(max = ...)]` constraints are checked
+/// in the same place, also without `#[table(validate)]`.
+pub trait Validate {
+    /// Returns the invalid fields of the entity, if any.
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+/// `FieldError` is the message of a field that failed validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// `field` is the name of the field.
+    pub field: String,
+
+    /// `message` describes why the value is invalid, e.g. `must not be empty`.
+    pub message: String,
+}
+
+/// `ValidationErrors` is the list of the invalid fields of an entity, in the order they were added.
+/// It is returned as `ORMError::Validation`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationErrors {
+    errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    /// Constructs an empty list.
+    pub fn new() -> Self {
+        ValidationErrors::default()
+    }
+
+    /// Adds a message for `field`.
+    pub fn add(&mut self, field: &str, message: &str) {
+        self.errors.push(FieldError { field: field.to_string(), message: message.to_string() });
+    }
+
+    /// Returns `true` if no field is invalid.
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns the messages of all fields.
+    pub fn errors(&self) -> &[FieldError] {
+        &self.errors
+    }
+
+    /// Returns the messages of `field`.
+    pub fn messages(&self, field: &str) -> Vec<&str> {
+        self.errors.iter().filter(|error| error.field == field).map(|error| error.message.as_str()).collect()
+    }
+
+    /// Appends the messages of `other`, e.g. of a nested check.
+    pub fn merge(&mut self, other: ValidationErrors) {
+        self.errors.extend(other.errors);
+    }
+
+    /// Adds a message for `field` if `value` has more than `max_len` characters.
+    /// It is called for `#[column(max_len = 255)]`, so that MySQL does not truncate the value silently.
+    pub fn check_max_len(&mut self, field: &str, value: &str, max_len: usize) {
+        if value.chars().count() > max_len {
+            self.add(field, &format!("must have at most {} characters", max_len));
+        }
+    }
+
+    /// Adds a message for `field` if `value` is less than `min` or greater than `max`.
+    /// It is called for `#[column(min = 0, max = 150)]`, so that SQLite, which stores any value in a typed column,
+    /// rejects out of range values like MySQL in strict mode.
+    pub fn check_range(&mut self, field: &str, value: f64, min: Option<f64>, max: Option<f64>) {
+        if let Some(min) = min.filter(|min| value < *min) {
+            self.add(field, &format!("must be at least {}", min));
+        }
+        if let Some(max) = max.filter(|max| value > *max) {
+            self.add(field, &format!("must be at most {}", max));
+        }
+    }
+
+    /// Returns `Ok` if no field is invalid, or the list as error, so that `validate` can end with it.
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() { Ok(()) } else { Err(self) }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}: {}", error.field, error.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+/// Converts the errors of the `validator` crate. Nested fields are named by their path, e.g. `address.city`
+/// or `items[0].name`, and a message defaults to the validation code, e.g. `length`.
+#[cfg(feature = "validator")]
+impl From<validator::ValidationErrors> for ValidationErrors {
+    fn from(errors: validator::ValidationErrors) -> Self {
+        let mut converted = ValidationErrors::new();
+        add_validator_errors(&mut converted, "", &errors);
+        converted
+    }
+}
+
+#[cfg(feature = "validator")]
+fn add_validator_errors(converted: &mut ValidationErrors, prefix: &str, errors: &validator::ValidationErrors) {
+    use validator::ValidationErrorsKind;
+    // the errors are kept in a hash map, sort them for a stable order
+    let mut fields: Vec<_> = errors.errors().iter().collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    for (field, kind) in fields {
+        let path = if prefix.is_empty() { field.to_string() } else { format!("{}.{}", prefix, field) };
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                for error in errors {
+                    converted.add(&path, error.message.as_deref().unwrap_or(&error.code));
+                }
+            }
+            ValidationErrorsKind::Struct(errors) => add_validator_errors(converted, &path, errors),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    add_validator_errors(converted, &format!("{}[{}]", path, index), errors);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Validate, ValidationErrors};
+
+    struct User {
+        name: String,
+        age: i32,
+    }
+
+    impl Validate for User {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            if self.name.is_empty() {
+                errors.add("name", "must not be empty");
+            }
+            if !(0..=150).contains(&self.age) {
+                errors.add("age", "must be between 0 and 150");
+            }
+            errors.into_result()
+        }
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        assert!(User { name: "John".to_string(), age: 30 }.validate().is_ok());
+        let errors = User { name: String::new(), age: 200 }.validate().unwrap_err();
+        assert_eq!(errors.errors().len(), 2);
+        assert_eq!(errors.messages("age"), vec!["must be between 0 and 150"]);
+        assert!(errors.messages("id").is_empty());
+        assert_eq!(errors.to_string(), "name: must not be empty, age: must be between 0 and 150");
+
+        let mut errors = ValidationErrors::new();
+        errors.check_max_len("name", "Zoë", 3);
+        errors.check_range("age", 150.0, Some(0.0), Some(150.0));
+        assert!(errors.is_empty());
+        errors.check_max_len("name", "Zoë!", 3);
+        errors.check_range("age", -0.5, Some(0.0), None);
+        errors.check_range("score", 2.5, None, Some(2.0));
+        assert_eq!(errors.to_string(), "name: must have at most 3 characters, age: must be at least 0, score: must be at most 2");
+    }
+}
@@ -0,0 +1,174 @@
+//! `write_hooks` is a module for the callbacks registered with `ORMTrait::on_table_write`, e.g. to invalidate the
+//! entries of an external cache precisely when a table changes:
+//!
+//! ```ignore
+//! let cache = redis.clone();
+//! conn.on_table_write("user", move |write| cache.invalidate_prefix(&format!("{}:", write.table)));
+//! ```
+//!
+//! The written table is parsed from the executed INSERT, REPLACE, UPDATE and DELETE statements. A hook is called
+//! after the statement succeeded; inside a transaction started with `begin` it is called when the transaction is
+//! committed and not at all if it is rolled back. Writes of a rolled back savepoint are still reported on commit,
+//! which only invalidates more than necessary.
+#![cfg_attr(not(any(feature = "sqlite", feature = "mysql")), allow(dead_code))]
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// `WriteKind` is an enumeration of the kinds of statements reported to the hooks of `on_table_write`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WriteKind {
+    /// An `INSERT` or `REPLACE` statement.
+    Insert,
+
+    /// An `UPDATE` statement.
+    Update,
+
+    /// A `DELETE` statement.
+    Delete,
+}
+
+/// `TableWrite` is a successful write of a table, see `ORMTrait::on_table_write`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableWrite {
+    /// `table` is the written table as it appears in the statement, e.g. `user` or `billing.invoice`.
+    pub table: String,
+
+    /// `kind` is the kind of the statement.
+    pub kind: WriteKind,
+}
+
+/// `WriteHook` is a callback registered with `ORMTrait::on_table_write`.
+pub type WriteHook = Arc<dyn Fn(&TableWrite) + Send + Sync>;
+
+/// `WriteHooks` holds the hooks of an ORM by table and the writes of the active transaction.
+#[derive(Default)]
+pub(crate) struct WriteHooks {
+    hooks: RwLock<HashMap<String, Vec<WriteHook>>>,
+    all_tables: RwLock<Vec<WriteHook>>,
+    pending: Mutex<Vec<TableWrite>>,
+}
+
+impl WriteHooks {
+    /// Registers `hook` for the writes of `table`.
+    pub(crate) fn add(&self, table: &str, hook: WriteHook) {
+        self.hooks.write().unwrap().entry(table.to_lowercase()).or_default().push(hook);
+    }
+
+    /// Registers `hook` for the writes of every table, e.g. to invalidate the `EntityCache`.
+    pub(crate) fn add_all_tables(&self, hook: WriteHook) {
+        self.all_tables.write().unwrap().push(hook);
+    }
+
+    /// Reports the successfully executed `statement`. Inside a transaction the write is kept until it ends.
+    pub(crate) fn record(&self, statement: &str, in_transaction: bool) {
+        let Some(write) = written_table(statement) else {
+            return;
+        };
+        if self.hooks_of(&write.table).is_empty() {
+            return;
+        }
+        if in_transaction {
+            self.pending.lock().unwrap().push(write);
+        } else {
+            self.call(&write);
+        }
+    }
+
+    /// Calls the hooks of the writes of the transaction that was committed, or discards them on rollback.
+    pub(crate) fn end_transaction(&self, commit: bool) {
+        let writes = std::mem::take(&mut *self.pending.lock().unwrap());
+        if commit {
+            for write in writes.iter() {
+                self.call(write);
+            }
+        }
+    }
+
+    fn call(&self, write: &TableWrite) {
+        for hook in self.hooks_of(&write.table) {
+            hook(write);
+        }
+    }
+
+    // Returns the hooks registered for every table and for `table` or its name without the schema.
+    fn hooks_of(&self, table: &str) -> Vec<WriteHook> {
+        let mut found: Vec<WriteHook> = self.all_tables.read().unwrap().clone();
+        let hooks = self.hooks.read().unwrap();
+        if hooks.is_empty() {
+            return found;
+        }
+        let table = table.to_lowercase();
+        let unqualified = table.rsplit('.').next().unwrap_or_default();
+        found.extend(hooks.get(&table).cloned().unwrap_or_default());
+        if unqualified != table {
+            found.extend(hooks.get(unqualified).cloned().unwrap_or_default());
+        }
+        found
+    }
+}
+
+impl std::fmt::Debug for WriteHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tables: Vec<String> = self.hooks.read().unwrap().keys().cloned().collect();
+        f.debug_struct("WriteHooks").field("tables", &tables).finish()
+    }
+}
+
+/// Returns the table written by `statement` and the kind of the write, or `None` if it is not a write.
+pub(crate) fn written_table(statement: &str) -> Option<TableWrite> {
+    let mut words = statement.split_whitespace().map(|word| word.to_lowercase());
+    let kind = match words.next()?.as_str() {
+        "insert" | "replace" => WriteKind::Insert,
+        "update" => WriteKind::Update,
+        "delete" => WriteKind::Delete,
+        _ => return None,
+    };
+    // skips the modifiers, e.g. `insert ignore into`, `insert or replace into` or `delete low_priority from`
+    let table = loop {
+        let word = words.next()?;
+        match word.as_str() {
+            "low_priority" | "delayed" | "high_priority" | "ignore" | "quick" | "or" | "rollback" | "abort"
+            | "replace" | "fail" | "into" | "from" => continue,
+            _ => break word,
+        }
+    };
+    let table: String = table.split('(').next().unwrap_or_default()
+        .chars()
+        .filter(|c| !matches!(c, '`' | '"' | '[' | ']'))
+        .collect();
+    if table.is_empty() {
+        return None;
+    }
+    Some(TableWrite { table, kind })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use super::{written_table, TableWrite, WriteHooks, WriteKind};
+
+    #[test]
+    fn test_written_table() {
+        let write = |table: &str, kind| Some(TableWrite { table: table.to_string(), kind });
+        assert_eq!(written_table("insert into user (name,age) values (?,?)"), write("user", WriteKind::Insert));
+        assert_eq!(written_table("INSERT OR REPLACE INTO `user`(id) VALUES (1)"), write("user", WriteKind::Insert));
+        assert_eq!(written_table("update LOW_PRIORITY billing.invoice set total = 1"), write("billing.invoice", WriteKind::Update));
+        assert_eq!(written_table("delete from user where id = 1"), write("user", WriteKind::Delete));
+        assert_eq!(written_table("select * from user"), None);
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let hooks = WriteHooks::default();
+        let counted = calls.clone();
+        hooks.add("user", Arc::new(move |_| { counted.fetch_add(1, Ordering::SeqCst); }));
+        hooks.record("delete from main.user where id = 1", false);
+        hooks.record("update team set name = 'a'", false);
+        hooks.record("insert into user (id) values (2)", true);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        hooks.end_transaction(false);
+        hooks.record("insert into user (id) values (3)", true);
+        hooks.end_transaction(true);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,4 +1,4 @@
-use darling::FromDeriveInput;
+use darling::{FromDeriveInput, FromField};
 use proc_macro::{self, TokenStream};
 use quote::quote;
 use syn::{parse_macro_input, DeriveInput};
@@ -7,15 +7,203 @@ use syn::{parse_macro_input, DeriveInput};
 #[darling(default, attributes(table), forward_attrs(allow, doc, cfg))]
 struct Opts {
     name: Option<String>,
+    comment: Option<String>,
+    schema: Option<String>,
+    cache_ttl: Option<String>,
+    validate: bool,
+    validator: bool,
 }
 
-#[proc_macro_derive(TableSerialize, attributes(table))]
+#[derive(FromField, Default)]
+#[darling(default, attributes(column))]
+struct ColumnOpts {
+    references: Option<String>,
+    on_delete: Option<String>,
+    on_update: Option<String>,
+    enumeration: bool,
+    datetime: bool,
+    comment: Option<String>,
+    max_len: Option<usize>,
+    min: Option<i64>,
+    max: Option<i64>,
+    #[darling(multiple)]
+    alias: Vec<String>,
+    default: Option<String>,
+}
+
+#[derive(FromField)]
+#[darling(attributes(counter_cache))]
+struct CounterCacheOpts {
+    column: String,
+}
+
+// Returns the milliseconds of a duration like `500ms`, `60s`, `5m`, `1h` or `1d`.
+fn duration_millis(duration: &str) -> Option<u64> {
+    let duration = duration.trim();
+    let split = duration.find(|c: char| !c.is_ascii_digit())?;
+    let (value, unit) = duration.split_at(split);
+    let millis = match unit.trim() {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        _ => return None,
+    };
+    value.parse::<u64>().ok()?.checked_mul(millis)
+}
+
+// Returns the last path segment of a type, e.g. `Option` for `std::option::Option<i32>`.
+fn last_segment(ty: &syn::Type) -> Option<&syn::PathSegment> {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last(),
+        _ => None,
+    }
+}
+
+// Returns the first generic argument of a type, e.g. `i32` for `Option<i32>`.
+fn generic_argument(segment: &syn::PathSegment) -> Option<&syn::Type> {
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(arguments) => arguments.args.iter().find_map(|argument| match argument {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+// Returns the type inside an `Option`, or the type itself.
+fn value_type(ty: &syn::Type) -> &syn::Type {
+    match last_segment(ty) {
+        Some(segment) if segment.ident == "Option" => generic_argument(segment).unwrap_or(ty),
+        _ => ty,
+    }
+}
+
+// Maps a field type to a `parvati::ColumnType` and whether the column is nullable.
+fn column_type(ty: &syn::Type) -> (proc_macro2::TokenStream, bool) {
+    let Some(segment) = last_segment(ty) else {
+        return (quote! { parvati::ColumnType::Text }, false);
+    };
+    let name = segment.ident.to_string();
+    if name == "Option" {
+        if let Some(inner) = generic_argument(segment) {
+            return (column_type(inner).0, true);
+        }
+    }
+    let column_type = match name.as_str() {
+        "i8" | "i16" | "i32" | "u8" | "u16" => quote! { parvati::ColumnType::Integer },
+        "i64" | "i128" | "isize" | "u32" | "u64" | "u128" | "usize" => quote! { parvati::ColumnType::BigInt },
+        "f32" | "f64" => quote! { parvati::ColumnType::Real },
+        "bool" => quote! { parvati::ColumnType::Boolean },
+        "NaiveDate" => quote! { parvati::ColumnType::Date },
+        "NaiveDateTime" | "DateTime" => quote! { parvati::ColumnType::DateTime },
+        "Vec" if generic_argument(segment).and_then(last_segment).is_some_and(|s| s.ident == "u8") => quote! { parvati::ColumnType::Blob },
+        _ => quote! { parvati::ColumnType::Text },
+    };
+    (column_type, false)
+}
+
+// Parses `cascade`, `set null`, `set_default`, `restrict` or `no action`.
+fn referential_action(action: &str) -> proc_macro2::TokenStream {
+    match action.to_lowercase().replace('_', " ").as_str() {
+        "cascade" => quote! { parvati::ReferentialAction::Cascade },
+        "set null" => quote! { parvati::ReferentialAction::SetNull },
+        "set default" => quote! { parvati::ReferentialAction::SetDefault },
+        "restrict" => quote! { parvati::ReferentialAction::Restrict },
+        "no action" => quote! { parvati::ReferentialAction::NoAction },
+        _ => panic!("Wrong referential action: {}", action),
+    }
+}
+
+// Generates the `ColumnDef` of a field. `references` is `table(column)` or just `table` for `table(id)`.
+// `#[counter_cache(column = "orders_count")]` on a field with `references` maintains a counter of the referenced row.
+fn column_def(field: &syn::Field) -> proc_macro2::TokenStream {
+    let opts = ColumnOpts::from_field(field).expect("Wrong column options");
+    let name = field.ident.as_ref().unwrap().to_string();
+    let (mut column_type, nullable) = column_type(&field.ty);
+    if opts.datetime {
+        column_type = quote! { parvati::ColumnType::DateTime };
+    }
+    let mut column = quote! { parvati::ColumnDef::new(#name, #column_type).nullable(#nullable) };
+    let has_references = opts.references.is_some();
+    if let Some(references) = opts.references {
+        let (table, referenced) = match references.split_once('(') {
+            Some((table, referenced)) => (table.trim().to_string(), referenced.trim_end_matches(')').trim().to_string()),
+            None => (references.trim().to_string(), "id".to_string()),
+        };
+        let mut foreign_key = quote! { parvati::ForeignKey::new(#table, #referenced) };
+        if let Some(action) = opts.on_delete {
+            let action = referential_action(&action);
+            foreign_key = quote! { #foreign_key.on_delete(#action) };
+        }
+        if let Some(action) = opts.on_update {
+            let action = referential_action(&action);
+            foreign_key = quote! { #foreign_key.on_update(#action) };
+        }
+        column = quote! { #column.references(#foreign_key) };
+    } else if opts.on_delete.is_some() || opts.on_update.is_some() {
+        panic!("on_delete and on_update of {} require references", name);
+    }
+    if opts.enumeration {
+        let ty = value_type(&field.ty);
+        column = quote! { #column.values(parvati::enum_variants::<#ty>()) };
+    }
+    if let Some(comment) = opts.comment {
+        column = quote! { #column.comment(#comment) };
+    }
+    for alias in opts.alias {
+        column = quote! { #column.alias(#alias) };
+    }
+    if let Some(default) = opts.default {
+        column = quote! { #column.read_default(#default) };
+    }
+    if field.attrs.iter().any(|attr| attr.path.is_ident("counter_cache")) {
+        let counter = CounterCacheOpts::from_field(field).expect("Wrong counter_cache options").column;
+        if !has_references {
+            panic!("counter_cache of {} requires references", name);
+        }
+        column = quote! { #column.counter_cache(#counter) };
+    }
+    column
+}
+
+// Generates the checks of `max_len`, `min` and `max` of a field, which add their messages to `errors`.
+// `min` and `max` are integers; negative bounds are given as strings, e.g. `min = "-10"`.
+fn column_checks(field: &syn::Field) -> Option<proc_macro2::TokenStream> {
+    let opts = ColumnOpts::from_field(field).expect("Wrong column options");
+    let ident = field.ident.as_ref().unwrap();
+    let name = ident.to_string();
+    let mut checks = Vec::new();
+    if let Some(max_len) = opts.max_len {
+        checks.push(quote! { errors.check_max_len(#name, value.as_ref(), #max_len); });
+    }
+    if opts.min.is_some() || opts.max.is_some() {
+        let min = match opts.min { Some(min) => quote! { Some(#min as f64) }, None => quote! { None } };
+        let max = match opts.max { Some(max) => quote! { Some(#max as f64) }, None => quote! { None } };
+        checks.push(quote! { errors.check_range(#name, *value as f64, #min, #max); });
+    }
+    if checks.is_empty() {
+        return None;
+    }
+    let is_option = last_segment(&field.ty).is_some_and(|segment| segment.ident == "Option");
+    Some(if is_option {
+        quote! { if let Some(value) = &self.#ident { #(#checks)* } }
+    } else {
+        quote! { let value = &self.#ident; #(#checks)* }
+    })
+}
+
+#[proc_macro_derive(TableSerialize, attributes(table, column, counter_cache))]
 pub fn derive(input: TokenStream) -> TokenStream {
     // println!("!!!!!!!!!!!!!");
 
     let input = parse_macro_input!(input);
     let opts = Opts::from_derive_input(&input).expect("Wrong options");
     let DeriveInput { ident, .. } = input;
+    let syn::Data::Struct(data) = input.data else {
+        unimplemented!()
+    };
     let answer = match opts.name {
         Some(x) => quote! {
             fn name(&self) -> String {
@@ -33,9 +221,37 @@ pub fn derive(input: TokenStream) -> TokenStream {
         },
     };
 
+    let mut checks: Vec<proc_macro2::TokenStream> = data.fields.iter().filter_map(column_checks).collect();
+    if opts.validate {
+        checks.push(quote! {
+            if let Err(e) = parvati::Validate::validate(self) {
+                errors.merge(e);
+            }
+        });
+    }
+    if opts.validator {
+        checks.push(quote! {
+            if let Err(e) = validator::Validate::validate(self) {
+                errors.merge(parvati::ValidationErrors::from(e));
+            }
+        });
+    }
+    let validation = if checks.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            fn validation(&self) -> Result<(), parvati::ValidationErrors> {
+                let mut errors = parvati::ValidationErrors::new();
+                #(#checks)*
+                errors.into_result()
+            }
+        }
+    };
+
     let output = quote! {
-        impl ormlib::TableSerialize for #ident {
+        impl parvati::TableSerialize for #ident {
             #answer
+            #validation
         }
     };
     // println!("++++++++++++++++");
@@ -43,7 +259,7 @@ pub fn derive(input: TokenStream) -> TokenStream {
     output.into()
 }
 
-#[proc_macro_derive(TableDeserialize, attributes(table))]
+#[proc_macro_derive(TableDeserialize, attributes(table, column, counter_cache))]
 pub fn derive_de(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input);
     let opts = Opts::from_derive_input(&input).expect("Wrong options");
@@ -53,6 +269,8 @@ pub fn derive_de(input: TokenStream) -> TokenStream {
         unimplemented!()
     };
 
+    let columns: Vec<proc_macro2::TokenStream> = data.fields.iter().map(column_def).collect();
+
     let mut fields: Vec<String> = Vec::new();
     for f in data.fields.iter() {
         fields.push(f.ident.as_ref().unwrap().to_string());
@@ -93,11 +311,54 @@ pub fn derive_de(input: TokenStream) -> TokenStream {
         },
     };
 
+    let table_comment = match opts.comment {
+        Some(comment) => quote! {
+            fn table_comment() -> Option<String> {
+                Some(#comment.to_string())
+            }
+        },
+        None => quote! {
+        },
+    };
+
+    let schema = match opts.schema {
+        Some(schema) => quote! {
+            fn schema() -> Option<String> {
+                Some(#schema.to_string())
+            }
+        },
+        None => quote! {
+        },
+    };
+
+    let cache_ttl = match opts.cache_ttl {
+        Some(ttl) => {
+            let millis = duration_millis(&ttl).unwrap_or_else(|| panic!("invalid cache_ttl {:?}, expected e.g. \"60s\"", ttl));
+            quote! {
+                fn cache_ttl() -> Option<std::time::Duration> {
+                    Some(std::time::Duration::from_millis(#millis))
+                }
+            }
+        }
+        None => quote! {
+        },
+    };
+
     let output = quote! {
-        impl ormlib::TableDeserialize for #ident {
+        impl parvati::TableDeserialize for #ident {
             #answer
 
+            #table_comment
+
+            #schema
+
+            #cache_ttl
+
             #code_token
+
+            fn columns() -> Vec<parvati::ColumnDef> {
+                vec![#(#columns),*]
+            }
         }
     };
 
@@ -0,0 +1,4 @@
+select * from user order by age desc limit 10;
+update user set name = ?,age = ? where id = ? /* 'O''Brien', 30, 7 */;
+delete from user where id = ? /* 7 */;
+select * from user where id = 7;